@@ -0,0 +1,357 @@
+//! Admin control socket (`--admin-socket`)
+//! `mcp-proxy status|stop|reload` send one JSON line (`{"command": "status"}`) to a running proxy
+//! and print its one-line reply; every session of the proxy answers `status` and applies `reload`
+//! A TCP admin socket (the default on Windows) only takes commands carrying the token the
+//! proxy writes to a file only its owner can read.
+
+use crate::config::Config;
+use crate::queue::{self, InflightLimiter};
+use crate::transport::{BackendAddress, Listener};
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot, watch};
+use tracing::{debug, info, warn};
+
+/// How long a session gets to answer, and a client to get its reply
+const ADMIN_TIMEOUT: Duration = Duration::from_secs(30);
+/// Default admin port on Windows (no unix sockets)
+#[cfg(not(unix))]
+const DEFAULT_ADMIN_PORT: u16 = 7899;
+
+/// Request from the admin socket to one session
+pub enum AdminCommand {
+    Status(oneshot::Sender<Value>),
+    /// Apply a freshly parsed configuration, with the global inflight limiter built for it
+    Reload(Box<Config>, Option<Arc<InflightLimiter>>, oneshot::Sender<Value>),
+}
+
+/// Sessions of this process reachable from the admin socket, and its stop switch
+pub struct AdminHub {
+    sessions: Mutex<HashMap<u64, mpsc::UnboundedSender<AdminCommand>>>,
+    next_session: AtomicU64,
+    /// Configuration for sessions started from now on (replaced by `reload`)
+    config: Mutex<Config>,
+    /// Global inflight limiter every session counts against (replaced by `reload`)
+    inflight: Mutex<Option<Arc<InflightLimiter>>>,
+    stop: watch::Sender<bool>,
+}
+
+impl AdminHub {
+    pub fn new(config: Config) -> Arc<Self> {
+        Arc::new(Self {
+            sessions: Mutex::new(HashMap::new()),
+            next_session: AtomicU64::new(1),
+            inflight: Mutex::new(queue::global_limiter(&config)),
+            config: Mutex::new(config),
+            stop: watch::channel(false).0,
+        })
+    }
+
+    /// Add a session; it should serve the returned channel until it ends, then `unregister`
+    pub fn register(&self) -> (u64, mpsc::UnboundedReceiver<AdminCommand>) {
+        let id = self.next_session.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner()).insert(id, tx);
+        (id, rx)
+    }
+
+    pub fn unregister(&self, id: u64) {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+    }
+
+    /// Configuration for a new session
+    pub fn config(&self) -> Config {
+        self.config.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Global inflight limiter for a new session
+    pub fn inflight_limiter(&self) -> Option<Arc<InflightLimiter>> {
+        self.inflight.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Completes once `stop` was requested
+    pub async fn stopped(&self) {
+        let mut stop = self.stop.subscribe();
+        let _ = stop.wait_for(|stopped| *stopped).await;
+    }
+
+    async fn handle(&self, line: &str) -> Value {
+        let request: Value = serde_json::from_str(line).unwrap_or(Value::Null);
+        match request.get("command").and_then(Value::as_str) {
+            Some("status") => json!({
+                "pid": std::process::id(),
+                "version": env!("CARGO_PKG_VERSION"),
+                "sessions": self.broadcast(AdminCommand::Status).await,
+            }),
+            Some("stop") => {
+                info!("Stop requested over the admin socket");
+                self.stop.send_replace(true);
+                json!({ "stopping": true })
+            }
+            Some("reload") => {
+                // The same command line and environment, with the config file read again
+                let config = match Config::try_parse() {
+                    Ok(config) => config,
+                    Err(e) => return json!({ "error": format!("Invalid configuration: {}", e) }),
+                };
+                info!("Reloading configuration over the admin socket");
+                let limiter = queue::global_limiter(&config);
+                *self.inflight.lock().unwrap_or_else(|e| e.into_inner()) = limiter.clone();
+                *self.config.lock().unwrap_or_else(|e| e.into_inner()) = config.clone();
+                json!({
+                    "sessions": self
+                        .broadcast(|reply| AdminCommand::Reload(Box::new(config.clone()), limiter.clone(), reply))
+                        .await
+                })
+            }
+            _ => json!({ "error": "Unknown command (expected status, stop or reload)" }),
+        }
+    }
+
+    /// Send a command to every session and collect the replies, tagged with the session id
+    async fn broadcast(&self, command: impl Fn(oneshot::Sender<Value>) -> AdminCommand) -> Vec<Value> {
+        let sessions: Vec<(u64, mpsc::UnboundedSender<AdminCommand>)> =
+            self.sessions.lock().unwrap_or_else(|e| e.into_inner()).iter().map(|(id, tx)| (*id, tx.clone())).collect();
+        let mut replies = Vec::new();
+        for (id, tx) in sessions {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            if tx.send(command(reply_tx)).is_err() {
+                continue;
+            }
+            let mut reply = match tokio::time::timeout(ADMIN_TIMEOUT, reply_rx).await {
+                Ok(Ok(reply)) => reply,
+                _ => json!({ "error": "Session did not answer" }),
+            };
+            reply["session"] = id.into();
+            replies.push(reply);
+        }
+        replies
+    }
+}
+
+/// Default `--admin-socket`: `.mcp-proxy.admin.sock` in `$XDG_RUNTIME_DIR` or `$HOME` (a
+/// localhost port on Windows), scoped to the instance
+pub fn default_address(config: &Config) -> String {
+    #[cfg(unix)]
+    return format!(
+        "unix:{}",
+        crate::dirs::runtime_file(&crate::instance::file_name(config, "admin.sock")).display()
+    );
+    #[cfg(not(unix))]
+    return format!("127.0.0.1:{}", DEFAULT_ADMIN_PORT + crate::instance::port_offset(config));
+}
+
+/// Where a TCP admin socket's token is kept: `.mcp-proxy.admin.token` next to the lock and
+/// PID files, scoped to the instance
+fn token_path(config: &Config) -> PathBuf {
+    crate::dirs::runtime_file(&crate::instance::file_name(config, "admin.token"))
+}
+
+/// Write a fresh random token to `path`, readable by the owner only (on Windows the file sits
+/// in the user's profile)
+fn write_token(path: &Path) -> std::io::Result<String> {
+    // RandomState is keyed from the OS's random source
+    let mut hasher = Sha256::new();
+    for _ in 0..2 {
+        hasher.update(RandomState::new().hash_one((std::process::id(), SystemTime::now())).to_le_bytes());
+    }
+    let token: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+    // Created anew, so it never keeps a looser mode from an old file
+    let _ = std::fs::remove_file(path);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(token.as_bytes())?;
+    Ok(token)
+}
+
+/// Whether a command line carries `token` (compared in constant time)
+fn authorized(line: &str, token: &str) -> bool {
+    let request: Value = serde_json::from_str(line).unwrap_or(Value::Null);
+    let given = request.get("token").and_then(Value::as_str).unwrap_or_default();
+    given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// The admin socket address from `--admin-socket` (`None` when disabled with `none`)
+pub fn address(config: &Config) -> Option<String> {
+    match config.admin_socket.as_deref() {
+        Some("none") => None,
+        Some(address) => Some(address.to_string()),
+        None => Some(default_address(config)),
+    }
+}
+
+/// Bind the admin socket and answer commands on it until the process exits; the proxy runs
+/// without one when the address is taken (e.g. by another instance)
+pub async fn spawn(hub: Arc<AdminHub>, address: &str) {
+    let listener = match bind(address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Admin socket {} unavailable, status/stop/reload disabled: {}", address, e);
+            return;
+        }
+    };
+    // Any local user can reach a TCP port, so commands on one must carry the token
+    let token = match BackendAddress::parse(address) {
+        Ok(BackendAddress::Tcp(_)) => {
+            let path = token_path(&hub.config());
+            match write_token(&path) {
+                Ok(token) => Some(token),
+                Err(e) => {
+                    warn!("Failed to write admin token {}, status/stop/reload disabled: {}", path.display(), e);
+                    return;
+                }
+            }
+        }
+        _ => None,
+    };
+    info!("Admin socket listening on {}", address);
+    tokio::spawn(async move {
+        loop {
+            let (reader, mut writer, peer) = match listener.accept().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    warn!("Failed to accept admin connection: {}", e);
+                    continue;
+                }
+            };
+            let hub = hub.clone();
+            let token = token.clone();
+            tokio::spawn(async move {
+                let mut line = String::new();
+                if BufReader::new(reader).read_line(&mut line).await.is_err() {
+                    return;
+                }
+                let reply = match token {
+                    Some(ref token) if !authorized(line.trim(), token) => {
+                        warn!("Rejected admin command from {} without a valid token", peer);
+                        json!({ "error": "Missing or wrong admin token" })
+                    }
+                    _ => {
+                        debug!("Admin command from {}: {}", peer, line.trim());
+                        hub.handle(line.trim()).await
+                    }
+                };
+                let mut reply = reply.to_string();
+                reply.push('\n');
+                let _ = writer.write_all(reply.as_bytes()).await;
+                let _ = writer.flush().await;
+            });
+        }
+    });
+}
+
+async fn bind(address: &str) -> Result<Listener> {
+    let parsed = BackendAddress::parse(address).map_err(|e| anyhow::anyhow!("Invalid --admin-socket address: {}", e))?;
+    let listener = Listener::bind(&parsed).await?;
+    // Only the owner may stop or reconfigure the proxy
+    #[cfg(unix)]
+    if let BackendAddress::Unix(path) = &parsed {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(listener)
+}
+
+/// Send `command` (with the token a TCP admin socket requires) to the proxy listening on
+/// `address` and return its reply
+pub async fn request(address: &str, command: &str, token: Option<&str>) -> Result<Value> {
+    let parsed = BackendAddress::parse(address).map_err(|e| anyhow::anyhow!("Invalid --admin-socket address: {}", e))?;
+    let mut message = json!({ "command": command });
+    if let Some(token) = token {
+        message["token"] = token.into();
+    }
+    let exchange = async {
+        let (reader, mut writer) = parsed.connect().await?;
+        writer.write_all(format!("{}\n", message).as_bytes()).await?;
+        writer.flush().await?;
+        let mut line = String::new();
+        BufReader::new(reader).read_line(&mut line).await?;
+        Ok::<_, std::io::Error>(line)
+    };
+    let line = tokio::time::timeout(ADMIN_TIMEOUT, exchange)
+        .await
+        .context("The proxy did not answer")?
+        .with_context(|| format!("No proxy is running on admin socket {}", address))?;
+    let reply: Value = serde_json::from_str(&line).context("Invalid reply from the proxy")?;
+    if let Some(error) = reply.get("error").and_then(Value::as_str) {
+        bail!("{}", error);
+    }
+    Ok(reply)
+}
+
+/// Run a client subcommand (`status`, `stop`, `reload`) and print the reply
+pub async fn run_command(config: &Config, command: &str) -> Result<()> {
+    let Some(address) = address(config) else {
+        bail!("The admin socket is disabled (--admin-socket none)");
+    };
+    let token = match BackendAddress::parse(&address) {
+        Ok(BackendAddress::Tcp(_)) => std::fs::read_to_string(token_path(config)).ok(),
+        _ => None,
+    };
+    let reply = request(&address, command, token.as_deref().map(str::trim)).await?;
+    println!("{}", serde_json::to_string_pretty(&reply)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_broadcast_and_stop() {
+        let hub = AdminHub::new(Config::parse_from(["mcp-proxy"]));
+        let (id, mut rx) = hub.register();
+        let session = tokio::spawn(async move {
+            while let Some(command) = rx.recv().await {
+                if let AdminCommand::Status(reply) = command {
+                    let _ = reply.send(json!({ "backends": [] }));
+                }
+            }
+        });
+
+        let status = hub.handle(r#"{"command":"status"}"#).await;
+        assert_eq!(status["pid"], std::process::id());
+        assert_eq!(status["sessions"], json!([{ "backends": [], "session": id }]));
+        assert!(hub.handle(r#"{"command":"restart"}"#).await["error"].is_string());
+
+        hub.unregister(id);
+        session.await.unwrap();
+        assert_eq!(hub.handle(r#"{"command":"status"}"#).await["sessions"], json!([]));
+
+        assert_eq!(hub.handle(r#"{"command":"stop"}"#).await["stopping"], true);
+        tokio::time::timeout(Duration::from_secs(1), hub.stopped()).await.unwrap();
+    }
+
+    #[test]
+    fn test_admin_token() {
+        let path = std::env::temp_dir().join(format!("mcp-proxy-admin-token-{}", std::process::id()));
+        let token = write_token(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), token);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        assert_ne!(write_token(&path).unwrap(), token);
+
+        assert!(authorized(&json!({ "command": "stop", "token": token }).to_string(), &token));
+        assert!(!authorized(r#"{"command":"stop"}"#, &token));
+        assert!(!authorized(&json!({ "command": "stop", "token": &token[1..] }).to_string(), &token));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,203 @@
+//! Audit log of tool invocations
+//! Append-only JSONL (separate from debug logs) with size-based rotation
+
+use crate::error::{ProxyError, ERROR_CONFIRMATION_REJECTED, ERROR_METHOD_NOT_FOUND, ERROR_POLICY_DENIED};
+use crate::jsonrpc::{JsonRpcId, JsonRpcResponse};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use crate::logfile::RotatingFile;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::warn;
+
+/// `audit` section of the config file
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// Audit log file (disabled when unset)
+    pub path: Option<PathBuf>,
+    /// Rotate once the file would exceed this size
+    pub max_bytes: u64,
+    /// Number of rotated files kept (`audit.jsonl.1` .. `audit.jsonl.N`)
+    pub max_files: usize,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_bytes: 10 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
+/// One `tools/call` record
+#[derive(Serialize, Debug, Clone)]
+pub struct AuditEntry {
+    pub ts: String,
+    pub id: Option<JsonRpcId>,
+    pub session: Option<String>,
+    pub root: Option<String>,
+    pub tool: String,
+    /// SHA-256 of the arguments (serialized with sorted keys), never the arguments themselves
+    pub args_sha256: String,
+    /// `ok`, `tool_error` (result with `isError`), `denied` or `error`
+    pub outcome: &'static str,
+    pub error_code: Option<i32>,
+    pub duration_ms: u64,
+}
+
+impl AuditEntry {
+    /// Start a record when the call arrives
+    pub fn start(
+        id: Option<JsonRpcId>,
+        session: Option<String>,
+        root: Option<&Path>,
+        tool: &str,
+        arguments: Option<&Value>,
+    ) -> Self {
+        Self {
+            ts: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            id,
+            session,
+            root: root.map(|r| r.display().to_string()),
+            tool: tool.to_string(),
+            args_sha256: digest(arguments),
+            outcome: "ok",
+            error_code: None,
+            duration_ms: 0,
+        }
+    }
+
+    /// Fill in the outcome once the call has been handled
+    pub fn finish(mut self, result: &Result<Option<JsonRpcResponse>, ProxyError>, elapsed: Duration) -> Self {
+        self.duration_ms = elapsed.as_millis() as u64;
+        match result {
+            Ok(Some(response)) => match response.error {
+                Some(ref error) => {
+                    self.error_code = Some(error.code);
+                    self.outcome = match error.code {
+                        ERROR_POLICY_DENIED | ERROR_METHOD_NOT_FOUND | ERROR_CONFIRMATION_REJECTED => "denied",
+                        _ => "error",
+                    };
+                }
+                None => {
+                    let is_error = response
+                        .result
+                        .as_ref()
+                        .and_then(|r| r.get("isError"))
+                        .and_then(|v| v.as_bool())
+                        .unwrap_or(false);
+                    if is_error {
+                        self.outcome = "tool_error";
+                    }
+                }
+            },
+            Ok(None) => {}
+            Err(_) => self.outcome = "error",
+        }
+        self
+    }
+}
+
+/// SHA-256 hex digest of a JSON value (`serde_json` maps serialize with sorted keys)
+fn digest(value: Option<&Value>) -> String {
+    let bytes = value.map(|v| v.to_string()).unwrap_or_default();
+    Sha256::digest(bytes.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Append-only JSONL writer with rotation
+pub struct AuditLog {
+    file: RotatingFile,
+}
+
+impl AuditLog {
+    pub fn open(path: &Path, max_bytes: u64, max_files: usize) -> std::io::Result<Self> {
+        Ok(Self {
+            file: RotatingFile::open(path, max_bytes, max_files)?,
+        })
+    }
+
+    /// Append an entry (errors are logged, never fatal)
+    pub fn record(&mut self, entry: &AuditEntry) {
+        let line = match serde_json::to_string(entry) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Failed to serialize audit entry: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = self.file.write_line(&line) {
+            warn!("Failed to write audit log {}: {}", self.file.path().display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jsonrpc::JsonRpcError;
+    use serde_json::json;
+
+    fn entry() -> AuditEntry {
+        AuditEntry::start(
+            Some(JsonRpcId::Number(1)),
+            Some("s1".to_string()),
+            Some(Path::new("/repo")),
+            "codebase-retrieval",
+            Some(&json!({"query": "auth"})),
+        )
+    }
+
+    #[test]
+    fn test_args_digest_ignores_key_order() {
+        let a: Value = serde_json::from_str(r#"{"a": 1, "b": 2}"#).unwrap();
+        let b: Value = serde_json::from_str(r#"{"b": 2, "a": 1}"#).unwrap();
+        assert_eq!(digest(Some(&a)), digest(Some(&b)));
+        assert_eq!(digest(Some(&a)).len(), 64);
+    }
+
+    #[test]
+    fn test_outcomes() {
+        let elapsed = Duration::from_millis(42);
+        let ok = Ok(Some(JsonRpcResponse::success(Some(JsonRpcId::Number(1)), json!({"content": []}))));
+        let finished = entry().finish(&ok, elapsed);
+        assert_eq!((finished.outcome, finished.duration_ms), ("ok", 42));
+
+        let tool_error = Ok(Some(JsonRpcResponse::success(None, json!({"isError": true}))));
+        assert_eq!(entry().finish(&tool_error, elapsed).outcome, "tool_error");
+
+        let denied = Ok(Some(JsonRpcResponse::error(None, JsonRpcError::new(ERROR_POLICY_DENIED, "no"))));
+        let finished = entry().finish(&denied, elapsed);
+        assert_eq!((finished.outcome, finished.error_code), ("denied", Some(ERROR_POLICY_DENIED)));
+
+        let failed = Err(ProxyError::BackendTimeout("slow".to_string()));
+        assert_eq!(entry().finish(&failed, elapsed).outcome, "error");
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-audit-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("audit.jsonl");
+
+        let line_len = serde_json::to_string(&entry()).unwrap().len() as u64 + 1;
+        let mut log = AuditLog::open(&path, line_len * 2, 2).unwrap();
+        for _ in 0..7 {
+            log.record(&entry());
+        }
+
+        let lines = |p: &Path| std::fs::read_to_string(p).map(|s| s.lines().count()).unwrap_or(0);
+        assert_eq!(lines(&path), 1);
+        assert_eq!(lines(&dir.join("audit.jsonl.1")), 2);
+        assert_eq!(lines(&dir.join("audit.jsonl.2")), 2);
+        assert!(!dir.join("audit.jsonl.3").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
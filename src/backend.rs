@@ -1,17 +1,27 @@
 //! Backend process management for auggie instances
 
+use crate::backend_log::{self, StderrSink, StderrTail};
 use crate::config::Config;
+use crate::container::ContainerHandle;
 use crate::error::ProxyError;
+use crate::eviction::BackendCost;
 use crate::jsonrpc::{JsonRpcId, JsonRpcRequest, JsonRpcResponse};
-use std::collections::HashMap;
-use std::path::PathBuf;
-use std::process::Stdio;
-use std::sync::atomic::{AtomicU64, Ordering};
+use crate::servers::ServerConfig;
+use crate::transport::{BackendAddress, BackendReader, BackendWriter};
+use crate::version::Versions;
+#[cfg(windows)]
+use crate::wsl::WslPath;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, Command};
-use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::sync::{mpsc, oneshot, watch, Mutex};
 use tracing::{debug, error, info, warn};
 
 /// Global counter for generating unique proxy IDs
@@ -22,18 +32,532 @@ fn next_proxy_id() -> u64 {
     PROXY_ID_COUNTER.fetch_add(1, Ordering::Relaxed)
 }
 
+/// Global counter numbering the backend processes started
+static PROCESS_SERIAL_COUNTER: AtomicU64 = AtomicU64::new(1);
+
 /// Backend instance state
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BackendState {
     Ready,
+    /// Evicted while busy: serves what it has, then is shut down
+    Draining,
     Stopping,
     Dead,
 }
 
+/// Per-method timeout override compiled from the `timeouts` config map
+struct MethodTimeout {
+    pattern: String,
+    matcher: globset::GlobMatcher,
+    timeout: Duration,
+}
+
+/// Compile `timeouts` config entries (method pattern -> seconds), skipping invalid patterns
+fn compile_method_timeouts(timeouts: &HashMap<String, u64>) -> Vec<MethodTimeout> {
+    timeouts
+        .iter()
+        .filter_map(|(pattern, secs)| match globset::Glob::new(pattern) {
+            Ok(glob) => Some(MethodTimeout {
+                pattern: pattern.clone(),
+                matcher: glob.compile_matcher(),
+                timeout: Duration::from_secs(*secs),
+            }),
+            Err(e) => {
+                warn!("Ignoring invalid timeout pattern {:?}: {}", pattern, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Pick the timeout for a method: exact match first, then the longest matching pattern
+fn resolve_method_timeout(overrides: &[MethodTimeout], default: Duration, method: &str) -> Duration {
+    if let Some(exact) = overrides.iter().find(|t| t.pattern == method) {
+        return exact.timeout;
+    }
+    overrides
+        .iter()
+        .filter(|t| t.matcher.is_match(method))
+        .max_by_key(|t| t.pattern.len())
+        .map(|t| t.timeout)
+        .unwrap_or(default)
+}
+
+/// Mode resolved when a backend starts: `minimal` for small repositories, else `default`
+pub const AUTO_MODE: &str = "auto";
+
+/// Default `auto_mode_max_files`
+pub const DEFAULT_AUTO_MODE_MAX_FILES: usize = 2000;
+
+/// The auggie mode to start a root's backend in (`mode_rules`, then `--mode`), with `auto`
+/// decided by the number of files the git filter lists for the root
+pub async fn resolve_mode(config: &Config, root: &Path) -> String {
+    let mode = config.mode_for(root);
+    if mode != AUTO_MODE {
+        return mode.to_string();
+    }
+    let files = crate::git_filter::list_files(root, config.filter_source).await.map(|files| files.len());
+    let mode = auto_mode(files, config.auto_mode_max_files);
+    match files {
+        Some(count) => info!("Auto mode: {} files in {}, using {}", count, root.display(), mode),
+        None => info!("Auto mode: cannot list the files of {}, using {}", root.display(), mode),
+    }
+    mode.to_string()
+}
+
+/// `auto` for a repository of `files` files (`None` when they can't be listed)
+fn auto_mode(files: Option<usize>, max_files: usize) -> &'static str {
+    match files {
+        Some(count) if count <= max_files => "minimal",
+        _ => "default",
+    }
+}
+
+/// How long a crashed backend's last stderr output gets to arrive before pending requests fail
+const STDERR_SETTLE: Duration = Duration::from_millis(250);
+
+/// Client capabilities that would make a backend send requests the proxy doesn't relay
+const UNRELAYED_CAPABILITIES: &[&str] = &["roots", "sampling", "elicitation"];
+
+/// Params for a backend's `initialize`: the client's own initialize params (or defaults
+/// before the client has initialized) with the proxy added to `clientInfo`
+pub fn handshake_params(client_params: Option<&serde_json::Value>) -> serde_json::Value {
+    let mut params = client_params.cloned().unwrap_or_else(|| {
+        serde_json::json!({
+            "protocolVersion": "2024-11-05",
+            "capabilities": {},
+        })
+    });
+    if let Some(capabilities) = params.get_mut("capabilities").and_then(|c| c.as_object_mut()) {
+        for name in UNRELAYED_CAPABILITIES {
+            capabilities.remove(*name);
+        }
+    }
+    let proxy = format!("mcp-proxy/{}", env!("CARGO_PKG_VERSION"));
+    let name = match params.pointer("/clientInfo/name").and_then(|n| n.as_str()) {
+        Some(client) => format!("{} via {}", client, proxy),
+        None => proxy,
+    };
+    match params.get_mut("clientInfo").and_then(|c| c.as_object_mut()) {
+        Some(info) => {
+            info.insert("name".to_string(), name.into());
+        }
+        None => {
+            params["clientInfo"] = serde_json::json!({
+                "name": name,
+                "version": env!("CARGO_PKG_VERSION"),
+            });
+        }
+    }
+    params
+}
+
+/// Recently flushed file change batches, replayed to a restarted backend
+struct ChangeReplay {
+    batches: VecDeque<JsonRpcRequest>,
+    capacity: usize,
+}
+
+impl ChangeReplay {
+    fn new(capacity: usize) -> Self {
+        Self {
+            batches: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Remember a batch, dropping the oldest once full
+    fn record(&mut self, batch: &JsonRpcRequest) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.batches.len() == self.capacity {
+            self.batches.pop_front();
+        }
+        self.batches.push_back(batch.clone());
+    }
+}
+
+/// Something a backend did on its own, outside any request
+pub enum BackendEvent {
+    /// A notification the backend sent
+    Notification(JsonRpcRequest),
+    /// The backend process exited without being asked to
+    Exited(ExitStatus),
+}
+
+/// Receives `(root, event)` for every backend
+pub type EventSink = mpsc::UnboundedSender<(PathBuf, BackendEvent)>;
+
+/// A request sent to every backend right after its handshake (`warmup_requests` in the config
+/// file), so indexing starts before the first real query
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+pub struct WarmupRequest {
+    /// JSON-RPC method (e.g. `tools/list` or `tools/call`)
+    pub method: String,
+    pub params: Option<serde_json::Value>,
+}
+
+/// How far a backend got through its warm-up requests
+#[derive(Default)]
+struct WarmupProgress {
+    total: usize,
+    /// Answered, failed or timed out
+    done: AtomicUsize,
+    failed: AtomicUsize,
+}
+
 /// Pending request info for ID mapping
 struct PendingRequest {
     client_id: Option<JsonRpcId>,
-    response_tx: oneshot::Sender<JsonRpcResponse>,
+    /// The client's `_meta.progressToken`; the backend is given the proxy id instead, which
+    /// no other request on any backend uses
+    progress_token: Option<serde_json::Value>,
+    response_tx: oneshot::Sender<Result<JsonRpcResponse, ProxyError>>,
+}
+
+/// Requests in flight on one backend, shared with its reader task
+/// Maps the id sent to the backend to the pending request (and so the client's original id and
+/// progress token), and the client's id back to the backend's for `notifications/cancelled`
+#[derive(Default)]
+struct PendingRequests {
+    by_proxy_id: HashMap<JsonRpcId, PendingRequest>,
+    proxy_ids: HashMap<JsonRpcId, JsonRpcId>,
+}
+
+/// Our numeric id as a string (or the reverse), for backends that echo it back changed
+fn alternate_id(id: &JsonRpcId) -> Option<JsonRpcId> {
+    match id {
+        JsonRpcId::Number(n) => Some(JsonRpcId::String(n.to_string())),
+        JsonRpcId::String(s) => Some(JsonRpcId::Number(s.parse().ok()?)),
+        JsonRpcId::Null => None,
+    }
+}
+
+impl PendingRequests {
+    fn insert(&mut self, proxy_id: JsonRpcId, request: PendingRequest) {
+        if let Some(ref client_id) = request.client_id {
+            self.proxy_ids.insert(client_id.clone(), proxy_id.clone());
+        }
+        self.by_proxy_id.insert(proxy_id, request);
+    }
+
+    /// Take the request a backend response answers
+    /// A backend echoing our numeric id back as a string (or the reverse) still matches.
+    fn remove(&mut self, proxy_id: &JsonRpcId) -> Option<PendingRequest> {
+        let request = match self.by_proxy_id.remove(proxy_id) {
+            Some(request) => request,
+            None => self.by_proxy_id.remove(&alternate_id(proxy_id)?)?,
+        };
+        if let Some(ref client_id) = request.client_id {
+            self.proxy_ids.remove(client_id);
+        }
+        Some(request)
+    }
+
+    /// The client's progress token for one the backend reports progress under
+    fn client_progress_token(&self, backend_token: &serde_json::Value) -> Option<serde_json::Value> {
+        let proxy_id: JsonRpcId = serde_json::from_value(backend_token.clone()).ok()?;
+        let request = match self.by_proxy_id.get(&proxy_id) {
+            Some(request) => request,
+            None => self.by_proxy_id.get(&alternate_id(&proxy_id)?)?,
+        };
+        request.progress_token.clone()
+    }
+
+    /// Id the backend knows a client request by
+    fn proxy_id(&self, client_id: &JsonRpcId) -> Option<&JsonRpcId> {
+        self.proxy_ids.get(client_id)
+    }
+
+    fn len(&self) -> usize {
+        self.by_proxy_id.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.by_proxy_id.is_empty()
+    }
+
+    /// Fail every pending request with the backend being unavailable for `reason`, so crash
+    /// recovery can retry them
+    fn fail_all(&mut self, reason: &str) {
+        for (_, request) in self.by_proxy_id.drain() {
+            let _ = request.response_tx.send(Err(ProxyError::BackendUnavailable(reason.to_string())));
+        }
+        self.proxy_ids.clear();
+    }
+
+    fn clear(&mut self) {
+        self.by_proxy_id.clear();
+        self.proxy_ids.clear();
+    }
+}
+
+/// Spawned backend process, owned by a task that waits for it to exit
+struct ChildProcess {
+    pid: Option<u32>,
+    /// Set before an intentional stop so the exit isn't treated as a crash
+    stopping: Arc<AtomicBool>,
+    /// Sending (or dropping it) kills the process
+    kill_tx: Option<oneshot::Sender<()>>,
+    exit_rx: watch::Receiver<Option<ExitStatus>>,
+}
+
+impl ChildProcess {
+    /// Take over `child` and watch for its exit
+    /// An unexpected exit fails the pending requests right away (once the stderr tail has
+    /// settled, for the error data the proxy adds) and is reported to the event sink.
+    fn monitor(
+        mut child: Child,
+        root: PathBuf,
+        stderr_tail: Option<StderrTail>,
+        pending: Arc<Mutex<PendingRequests>>,
+        event_sink: Arc<std::sync::Mutex<Option<EventSink>>>,
+    ) -> Self {
+        let pid = child.id();
+        let stopping = Arc::new(AtomicBool::new(false));
+        let stopping_clone = stopping.clone();
+        let (kill_tx, kill_rx) = oneshot::channel::<()>();
+        let (exit_tx, exit_rx) = watch::channel(None);
+
+        tokio::spawn(async move {
+            let status = tokio::select! {
+                status = child.wait() => status,
+                _ = kill_rx => {
+                    stopping_clone.store(true, Ordering::Relaxed);
+                    if let Err(e) = child.start_kill() {
+                        warn!("Failed to kill backend process for {}: {}", root.display(), e);
+                    }
+                    child.wait().await
+                }
+            };
+            let status = match status {
+                Ok(status) => status,
+                Err(e) => {
+                    warn!("Failed to wait for backend process for {}: {}", root.display(), e);
+                    return;
+                }
+            };
+            if stopping_clone.load(Ordering::Relaxed) {
+                debug!("Backend process for {} exited: {}", root.display(), status);
+                let _ = exit_tx.send(Some(status));
+                return;
+            }
+
+            warn!("Backend process for {} exited unexpectedly: {}", root.display(), status);
+            // Before publishing the status, so the reader task doesn't drop them unanswered first
+            if let Some(ref tail) = stderr_tail {
+                tail.wait_closed(STDERR_SETTLE).await;
+            }
+            pending.lock().await.fail_all(&describe_exit(&status));
+            let _ = exit_tx.send(Some(status));
+            if let Some(ref sink) = *event_sink.lock().unwrap_or_else(|e| e.into_inner()) {
+                let _ = sink.send((root.clone(), BackendEvent::Exited(status)));
+            }
+        });
+
+        Self {
+            pid,
+            stopping,
+            kill_tx: Some(kill_tx),
+            exit_rx,
+        }
+    }
+
+    fn exit_status(&self) -> Option<ExitStatus> {
+        *self.exit_rx.borrow()
+    }
+
+    /// Wait for the process to exit (`None` if its status couldn't be read)
+    async fn wait(&mut self) -> Option<ExitStatus> {
+        self.exit_rx.wait_for(Option::is_some).await.ok().and_then(|status| *status)
+    }
+
+    /// Ask the process to stop (SIGTERM, or CTRL_BREAK on Windows) without forcing it
+    fn terminate(&self) -> bool {
+        let pid = match self.pid {
+            Some(pid) => pid,
+            None => return false,
+        };
+        #[cfg(unix)]
+        {
+            unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) == 0 }
+        }
+        #[cfg(windows)]
+        {
+            // Only reaches a backend attached to a console; others fall through to the kill
+            use windows::Win32::System::Console::{GenerateConsoleCtrlEvent, CTRL_BREAK_EVENT};
+            unsafe { GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, pid).is_ok() }
+        }
+    }
+
+    fn kill(&mut self) {
+        if let Some(kill_tx) = self.kill_tx.take() {
+            let _ = kill_tx.send(());
+        }
+    }
+}
+
+/// Message for a backend process that exited
+fn describe_exit(status: &ExitStatus) -> String {
+    format!("Backend process exited ({})", status)
+}
+
+/// `error.data` for requests failed by a backend process exiting
+fn exit_data(root: &Path, status: &ExitStatus) -> serde_json::Value {
+    #[allow(unused_mut)]
+    let mut data = serde_json::json!({
+        "root": root.display().to_string(),
+        "exitCode": status.code(),
+    });
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        data["signal"] = status.signal().into();
+    }
+    data
+}
+
+/// Add the last stderr lines (if any) to `error.data`
+fn add_stderr(data: &mut serde_json::Value, lines: Vec<String>) {
+    if !lines.is_empty() {
+        data["stderr"] = lines.into();
+    }
+}
+
+/// What sending a request to a backend process and waiting for its answer takes, cloned out of
+/// the instance so several requests can be in flight to one process at once
+/// A handle taken before a restart keeps talking to the old process, failing once it's gone.
+#[derive(Clone)]
+pub struct BackendHandle {
+    /// Which process it talks to
+    serial: u64,
+    stdin_tx: mpsc::Sender<String>,
+    pending: Arc<Mutex<PendingRequests>>,
+    exit_rx: Option<watch::Receiver<Option<ExitStatus>>>,
+    consecutive_timeouts: Arc<AtomicU32>,
+    max_consecutive_timeouts: u32,
+    /// The process restarted in place of this one for a retry, shared by every handle to it
+    successor: Arc<Mutex<Option<BackendHandle>>>,
+}
+
+impl BackendHandle {
+    /// Send a request and wait at most `request_timeout` for the response
+    pub async fn send_request_within(
+        &self,
+        request: JsonRpcRequest,
+        request_timeout: Duration,
+    ) -> Result<JsonRpcResponse, ProxyError> {
+        if request.is_notification() {
+            return Err(ProxyError::RoutingFailed(
+                "send_request called with notification (id is None)".to_string(),
+            ));
+        }
+
+        // Generate proxy ID and setup response channel
+        let proxy_id = JsonRpcId::Number(next_proxy_id() as i64);
+        let (response_tx, response_rx) = oneshot::channel();
+
+        // Replace ID with proxy ID, and the progress token with it too
+        let mut backend_request = request.clone();
+        backend_request.id = Some(proxy_id.clone());
+        let progress_token = backend_request.take_meta("progressToken");
+        if progress_token.is_some() {
+            backend_request.set_meta("progressToken", serde_json::to_value(&proxy_id)?);
+        }
+
+        // Register pending request
+        {
+            let mut pending = self.pending.lock().await;
+            pending.insert(
+                proxy_id.clone(),
+                PendingRequest {
+                    client_id: request.id.clone(),
+                    progress_token,
+                    response_tx,
+                },
+            );
+        }
+
+        let json = serde_json::to_string(&backend_request)?;
+        debug!(
+            "Sending request to backend: {} (proxy_id: {})",
+            request.method, proxy_id.as_string()
+        );
+
+        self.stdin_tx.send(json).await.map_err(|e| {
+            ProxyError::BackendUnavailable(format!("Failed to send to backend: {}", e))
+        })?;
+
+        // Wait for response with timeout
+        match tokio::time::timeout(request_timeout, response_rx).await {
+            Ok(Ok(Ok(response))) => {
+                self.consecutive_timeouts.store(0, Ordering::Relaxed);
+                Ok(response)
+            }
+            // Failed by the process exiting or being recycled
+            Ok(Ok(Err(e))) => Err(e),
+            Ok(Err(_)) => {
+                // Channel closed - backend probably died
+                let mut pending = self.pending.lock().await;
+                pending.remove(&proxy_id);
+                let reason = match self.exit_rx.as_ref().and_then(|exit_rx| *exit_rx.borrow()) {
+                    Some(status) => describe_exit(&status),
+                    None => "Backend response channel closed".to_string(),
+                };
+                Err(ProxyError::BackendUnavailable(reason))
+            }
+            Err(_) => {
+                // Timeout - remove pending and mark backend as potentially unhealthy
+                warn!("Request {} timed out after {:?}", request.method, request_timeout);
+                self.consecutive_timeouts.fetch_add(1, Ordering::Relaxed);
+                let mut pending = self.pending.lock().await;
+                pending.remove(&proxy_id);
+                Err(ProxyError::BackendTimeout(format!(
+                    "Request timed out after {} seconds",
+                    request_timeout.as_secs()
+                )))
+            }
+        }
+    }
+
+    /// Which backend process the handle talks to
+    pub fn serial(&self) -> u64 {
+        self.serial
+    }
+
+    /// Whether the process timed out `max_consecutive_timeouts` times in a row
+    pub fn is_hung(&self) -> bool {
+        self.max_consecutive_timeouts > 0
+            && self.consecutive_timeouts.load(Ordering::Relaxed) >= self.max_consecutive_timeouts
+    }
+
+    /// Give up on a hung process: its pending requests fail now instead of each of them
+    /// waiting out its own timeout (restarting it is up to the caller)
+    pub async fn fail_hung(&self) {
+        let timeouts = self.consecutive_timeouts.swap(0, Ordering::Relaxed);
+        warn!("Backend timed out {} times in a row, restarting it", timeouts);
+        self.pending
+            .lock()
+            .await
+            .fail_all(&format!("Backend restarted after {} consecutive timeouts", timeouts));
+    }
+
+    /// Move on to a process restarted in place of this one, started by `restart` unless a
+    /// request that failed on the same process already did
+    pub async fn replace_with<F, Fut>(&self, restart: F) -> Result<BackendHandle, ProxyError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<BackendHandle, ProxyError>>,
+    {
+        let mut successor = self.successor.lock().await;
+        if let Some(ref handle) = *successor {
+            return Ok(handle.clone());
+        }
+        let handle = restart().await?;
+        *successor = Some(handle.clone());
+        Ok(handle)
+    }
 }
 
 /// A single backend instance (auggie process)
@@ -41,13 +565,49 @@ pub struct BackendInstance {
     pub root: PathBuf,
     pub state: BackendState,
     pub last_used: Instant,
-    child: Option<Child>,
+    /// Number of the current process among those the proxy started
+    serial: u64,
+    /// The process restarted in place of the current one by a call task, if any
+    successor: Arc<Mutex<Option<BackendHandle>>>,
+    /// When the current process started
+    started_at: Instant,
+    /// Requests sent to the current process
+    requests_served: u64,
+    /// Time the current process took to complete its handshake
+    warmup: Duration,
+    /// Auggie mode the current process runs in (`None` for connected backends)
+    mode: Option<String>,
+    /// Warm-up requests of the current process (`None` when none are configured)
+    warmup_progress: Option<Arc<WarmupProgress>>,
+    process: Option<ChildProcess>,
     stdin_tx: Option<mpsc::Sender<String>>,
-    pending: Arc<Mutex<HashMap<u64, PendingRequest>>>,
+    pending: Arc<Mutex<PendingRequests>>,
+    /// Set once the backend's output stream has closed
+    disconnected: Arc<AtomicBool>,
+    /// Where the backend's notifications and unexpected exit are reported
+    event_sink: Arc<std::sync::Mutex<Option<EventSink>>>,
+    /// Container running this backend, if any
+    container: Option<ContainerHandle>,
     /// Request timeout duration
     request_timeout: Duration,
+    /// Per-method timeout overrides
+    method_timeouts: Vec<MethodTimeout>,
+    /// Requests timed out in a row (`max_consecutive_timeouts`), across retry restarts
+    consecutive_timeouts: Arc<AtomicU32>,
     /// Config for restart
     config: Config,
+    /// Change batches to replay if the process restarts
+    recent_changes: ChangeReplay,
+    /// `initialize` params, repeated when the process restarts
+    init_params: Option<serde_json::Value>,
+    /// Whether the client has sent `notifications/initialized`; the backend's own waits for it
+    client_ready: bool,
+    /// `initialize` answered, `notifications/initialized` held until the client's
+    initialized_held: bool,
+    /// Captured stderr (`backend_log_dir` / `stderr_notifications`)
+    stderr_tail: Option<StderrTail>,
+    /// Where captured stderr lines are forwarded
+    stderr_sink: Option<StderrSink>,
     /// Job object reference for Windows (Arc for safe sharing)
     #[cfg(windows)]
     job_object: Option<Arc<crate::job_object::JobObject>>,
@@ -58,12 +618,16 @@ pub struct BackendInstance {
 
 impl BackendInstance {
     /// Spawn a new backend instance for the given workspace root
+    /// (or attach to a running one when the root has a `connect` address)
     #[cfg(windows)]
     pub async fn spawn(
         config: &Config,
         root: PathBuf,
         job_object: Option<Arc<crate::job_object::JobObject>>,
     ) -> Result<Self, ProxyError> {
+        if let Some(address) = config.root_config(&root).and_then(|rc| rc.connect.as_deref()) {
+            return Self::connect(config, root, address).await;
+        }
         Self::spawn_internal(config, root, job_object).await
     }
 
@@ -73,58 +637,114 @@ impl BackendInstance {
         root: PathBuf,
         process_group: Option<Arc<crate::process_group::ProcessGroup>>,
     ) -> Result<Self, ProxyError> {
+        if let Some(address) = config.root_config(&root).and_then(|rc| rc.connect.as_deref()) {
+            return Self::connect(config, root, address).await;
+        }
         Self::spawn_internal(config, root, process_group).await
     }
 
-    /// Internal spawn implementation
+    /// Attach to an already-running MCP server over TCP or a Unix socket
+    async fn connect(config: &Config, root: PathBuf, address: &str) -> Result<Self, ProxyError> {
+        let address = BackendAddress::parse(address)
+            .map_err(|e| ProxyError::ConfigError(format!("Invalid connect address for {}: {}", root.display(), e)))?;
+
+        info!("Connecting to backend for root: {} at {}", root.display(), address);
+        let (reader, writer) = address.connect().await.map_err(|e| {
+            ProxyError::BackendSpawnFailed(format!("Failed to connect to backend at {}: {}", address, e))
+        })?;
+
+        Ok(Self::attach(config, root, None, None, reader, writer))
+    }
+
+    /// Spawn one of the extra MCP servers of the `servers` section (its name stands in for the root)
     #[cfg(windows)]
-    async fn spawn_internal(
+    pub fn spawn_server(
         config: &Config,
-        root: PathBuf,
+        name: &str,
+        server: &ServerConfig,
         job_object: Option<Arc<crate::job_object::JobObject>>,
     ) -> Result<Self, ProxyError> {
-        let node_path = config
-            .node
-            .as_ref()
-            .ok_or_else(|| ProxyError::ConfigError("Node path not configured".to_string()))?;
+        let mut backend = Self::launch_server(config, name, server)?;
+        if let (Some(pid), Some(job)) = (backend.pid(), job_object.as_ref()) {
+            if let Err(e) = job.assign_process_by_pid(pid) {
+                warn!("Failed to assign MCP server {} to Job Object: {}", name, e);
+            }
+        }
+        backend.job_object = job_object;
+        Ok(backend)
+    }
 
-        let auggie_entry = config
-            .auggie_entry
-            .as_ref()
-            .ok_or_else(|| ProxyError::ConfigError("Auggie entry path not configured".to_string()))?;
+    #[cfg(unix)]
+    pub fn spawn_server(
+        config: &Config,
+        name: &str,
+        server: &ServerConfig,
+        process_group: Option<Arc<crate::process_group::ProcessGroup>>,
+    ) -> Result<Self, ProxyError> {
+        let mut backend = Self::launch_server(config, name, server)?;
+        if let (Some(pid), Some(pg)) = (backend.pid(), process_group.as_ref()) {
+            if let Err(e) = pg.add_process(pid, &backend.root) {
+                warn!("Failed to add MCP server {} to ProcessGroup: {}", name, e);
+            }
+        }
+        backend.process_group = process_group;
+        Ok(backend)
+    }
 
-        info!(
-            "Spawning backend for root: {} with node: {:?}, entry: {:?}",
-            root.display(),
-            node_path,
-            auggie_entry
-        );
+    fn launch_server(config: &Config, name: &str, server: &ServerConfig) -> Result<Self, ProxyError> {
+        if server.command.is_empty() {
+            return Err(ProxyError::ConfigError(format!("No command configured for MCP server {}", name)));
+        }
+        // Warm-up requests are meant for auggie
+        let mut config = config.clone();
+        config.warmup_requests.clear();
+        let root = PathBuf::from(name);
+        let mut cmd = server.command();
+        cmd.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Self::stderr_stdio(&config));
+        #[cfg(windows)]
+        {
+            const CREATE_NO_WINDOW: u32 = 0x08000000;
+            cmd.creation_flags(CREATE_NO_WINDOW);
+        }
+        info!("Spawning MCP server {}: {:?}", name, cmd.as_std());
+        let mut child = cmd.spawn().map_err(|e| {
+            ProxyError::BackendSpawnFailed(format!("Failed to spawn MCP server {}: {}. Command: {:?}", name, e, cmd.as_std()))
+        })?;
+        let stderr_tail = Self::capture_stderr(&mut child, &config, &root);
+        let (stdout, stdin) = Self::take_stdio(&mut child)?;
+        Ok(Self::attach(&config, root, Some(child), stderr_tail, stdout, stdin))
+    }
 
-        // Build command - bypass .cmd to avoid cmd.exe shell issues
-        let mut cmd = Command::new(node_path);
-        cmd.arg(auggie_entry)
-            .arg("--mcp")
-            .arg("-m")
-            .arg(&config.mode)
-            .arg("--workspace-root")
-            .arg(&root)
-            .stdin(Stdio::piped())
+    /// Internal spawn implementation
+    #[cfg(windows)]
+    async fn spawn_internal(
+        config: &Config,
+        root: PathBuf,
+        job_object: Option<Arc<crate::job_object::JobObject>>,
+    ) -> Result<Self, ProxyError> {
+        let mode = resolve_mode(config, &root).await;
+        let (mut cmd, container) = Self::build_command(config, &root, &mode)?;
+        cmd.stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::inherit()) // Let backend stderr pass through for debugging
+            .stderr(Self::stderr_stdio(config)) // Passes through for debugging unless captured
             .env("AUGMENT_DISABLE_AUTO_UPDATE", "1");
 
         // On Windows, don't create a window
         #[cfg(windows)]
         {
             const CREATE_NO_WINDOW: u32 = 0x08000000;
+            // Its own process group, so CTRL_BREAK on shutdown reaches only the backend
+            const CREATE_NEW_PROCESS_GROUP: u32 = 0x00000200;
             // creation_flags is available on tokio::process::Command on Windows
-            cmd.creation_flags(CREATE_NO_WINDOW);
+            cmd.creation_flags(CREATE_NO_WINDOW | CREATE_NEW_PROCESS_GROUP);
         }
 
         let mut child = cmd.spawn().map_err(|e| {
             ProxyError::BackendSpawnFailed(format!(
-                "Failed to spawn backend: {}. Node: {:?}, Entry: {:?}",
-                e, node_path, auggie_entry
+                "Failed to spawn backend: {}. Command: {:?}",
+                e, cmd.as_std()
             ))
         })?;
 
@@ -145,111 +765,13 @@ impl BackendInstance {
             Self::configure_process_resources(pid, config);
         }
 
-        let stdin = child.stdin.take().ok_or_else(|| {
-            ProxyError::BackendSpawnFailed("Failed to get stdin handle".to_string())
-        })?;
-        let stdout = child.stdout.take().ok_or_else(|| {
-            ProxyError::BackendSpawnFailed("Failed to get stdout handle".to_string())
-        })?;
-
-        // Create channel for sending requests to backend
-        let (stdin_tx, mut stdin_rx) = mpsc::channel::<String>(100);
-
-        // Pending requests map
-        let pending: Arc<Mutex<HashMap<u64, PendingRequest>>> = Arc::new(Mutex::new(HashMap::new()));
-        let pending_clone = pending.clone();
-
-        // Spawn task to write to backend stdin
-        let mut stdin_writer = stdin;
-        tokio::spawn(async move {
-            while let Some(line) = stdin_rx.recv().await {
-                if let Err(e) = stdin_writer.write_all(line.as_bytes()).await {
-                    error!("Failed to write to backend stdin: {}", e);
-                    break;
-                }
-                if let Err(e) = stdin_writer.write_all(b"\n").await {
-                    error!("Failed to write newline to backend stdin: {}", e);
-                    break;
-                }
-                if let Err(e) = stdin_writer.flush().await {
-                    error!("Failed to flush backend stdin: {}", e);
-                    break;
-                }
-            }
-            debug!("Stdin writer task ended");
-        });
-
-        // Spawn task to read backend stdout and dispatch responses
-        let mut reader = BufReader::new(stdout);
-        tokio::spawn(async move {
-            let mut line = String::new();
-            loop {
-                line.clear();
-                match reader.read_line(&mut line).await {
-                    Ok(0) => {
-                        debug!("Backend stdout closed (EOF)");
-                        break;
-                    }
-                    Ok(_) => {
-                        let trimmed = line.trim();
-                        if trimmed.is_empty() {
-                            continue;
-                        }
-                        
-                        debug!("Backend response: {}", trimmed);
-                        
-                        match serde_json::from_str::<JsonRpcResponse>(trimmed) {
-                            Ok(response) => {
-                                // Extract proxy_id from response
-                                if let Some(ref id) = response.id {
-                                    let proxy_id = match id {
-                                        JsonRpcId::Number(n) => *n as u64,
-                                        JsonRpcId::String(s) => {
-                                            s.parse().unwrap_or(0)
-                                        }
-                                    };
-                                    
-                                    let mut pending_guard = pending_clone.lock().await;
-                                    if let Some(req) = pending_guard.remove(&proxy_id) {
-                                        // Restore original client ID
-                                        let mut final_response = response;
-                                        final_response.id = req.client_id;
-                                        
-                                        if req.response_tx.send(final_response).is_err() {
-                                            warn!("Failed to send response - receiver dropped");
-                                        }
-                                    } else {
-                                        warn!("Received response for unknown proxy_id: {}", proxy_id);
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                // Might be a notification or malformed
-                                debug!("Failed to parse backend response: {} - {}", e, trimmed);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        error!("Error reading backend stdout: {}", e);
-                        break;
-                    }
-                }
-            }
-            debug!("Stdout reader task ended");
-        });
-
-        Ok(Self {
-            root,
-            state: BackendState::Ready,
-            last_used: Instant::now(),
-            child: Some(child),
-            stdin_tx: Some(stdin_tx),
-            pending,
-            request_timeout: Duration::from_secs(config.request_timeout_seconds),
-            config: config.clone(),
-            #[cfg(windows)]
-            job_object,
-        })
+        let stderr_tail = Self::capture_stderr(&mut child, config, &root);
+        let (stdout, stdin) = Self::take_stdio(&mut child)?;
+        let mut backend = Self::attach(config, root, Some(child), stderr_tail, stdout, stdin);
+        backend.container = container;
+        backend.mode = Some(mode);
+        backend.job_object = job_object;
+        Ok(backend)
     }
 
     /// Internal spawn implementation for Unix (macOS/Linux)
@@ -259,40 +781,17 @@ impl BackendInstance {
         root: PathBuf,
         process_group: Option<Arc<crate::process_group::ProcessGroup>>,
     ) -> Result<Self, ProxyError> {
-        let node_path = config
-            .node
-            .as_ref()
-            .ok_or_else(|| ProxyError::ConfigError("Node path not configured".to_string()))?;
-
-        let auggie_entry = config
-            .auggie_entry
-            .as_ref()
-            .ok_or_else(|| ProxyError::ConfigError("Auggie entry path not configured".to_string()))?;
-
-        info!(
-            "Spawning backend for root: {} with node: {:?}, entry: {:?}",
-            root.display(),
-            node_path,
-            auggie_entry
-        );
-
-        // Build command
-        let mut cmd = Command::new(node_path);
-        cmd.arg(auggie_entry)
-            .arg("--mcp")
-            .arg("-m")
-            .arg(&config.mode)
-            .arg("--workspace-root")
-            .arg(&root)
-            .stdin(Stdio::piped())
+        let mode = resolve_mode(config, &root).await;
+        let (mut cmd, container) = Self::build_command(config, &root, &mode)?;
+        cmd.stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::inherit())
+            .stderr(Self::stderr_stdio(config))
             .env("AUGMENT_DISABLE_AUTO_UPDATE", "1");
 
         let mut child = cmd.spawn().map_err(|e| {
             ProxyError::BackendSpawnFailed(format!(
-                "Failed to spawn backend: {}. Node: {:?}, Entry: {:?}",
-                e, node_path, auggie_entry
+                "Failed to spawn backend: {}. Command: {:?}",
+                e, cmd.as_std()
             ))
         })?;
 
@@ -302,7 +801,7 @@ impl BackendInstance {
             
             // Add to process group
             if let Some(ref pg) = process_group {
-                match pg.add_process(pid) {
+                match pg.add_process(pid, &root) {
                     Ok(_) => info!("Process {} added to ProcessGroup", pid),
                     Err(e) => warn!("Failed to add process to ProcessGroup: {} - process cleanup may not work correctly", e),
                 }
@@ -312,22 +811,110 @@ impl BackendInstance {
             Self::configure_process_resources_unix(pid, config);
         }
 
+        let stderr_tail = Self::capture_stderr(&mut child, config, &root);
+        let (stdout, stdin) = Self::take_stdio(&mut child)?;
+        let mut backend = Self::attach(config, root, Some(child), stderr_tail, stdout, stdin);
+        backend.container = container;
+        backend.mode = Some(mode);
+        backend.process_group = process_group;
+        Ok(backend)
+    }
+
+    /// Whether stderr is captured (`backend_log_dir` or `stderr_notifications`)
+    fn captures_stderr(config: &Config) -> bool {
+        config.backend_log_dir.is_some() || config.stderr_notifications
+    }
+
+    /// Pipe stderr when it is captured, otherwise inherit the proxy's
+    fn stderr_stdio(config: &Config) -> Stdio {
+        match Self::captures_stderr(config) {
+            true => Stdio::piped(),
+            false => Stdio::inherit(),
+        }
+    }
+
+    /// Read the child's stderr into its log file and tail when captured
+    fn capture_stderr(child: &mut Child, config: &Config, root: &Path) -> Option<StderrTail> {
+        if !Self::captures_stderr(config) {
+            return None;
+        }
+        let stderr = child.stderr.take()?;
+        let file = config
+            .backend_log_dir
+            .as_deref()
+            .and_then(|dir| backend_log::open_log(dir, root, config.backend_log_max_bytes, config.backend_log_files));
+        Some(backend_log::capture(stderr, root, file))
+    }
+
+    /// Last lines of captured stderr (empty unless stderr is captured)
+    pub fn stderr_tail(&self) -> Vec<String> {
+        self.stderr_tail.as_ref().map(|t| t.lines()).unwrap_or_default()
+    }
+
+    /// `error.data` for a failed request: root, state, exit status if the process is gone,
+    /// and the stderr tail
+    pub fn error_data(&self) -> serde_json::Value {
+        let mut data = match self.exit_status() {
+            Some(status) => exit_data(&self.root, &status),
+            None => serde_json::json!({ "root": self.root.display().to_string() }),
+        };
+        data["backendState"] = format!("{:?}", self.state).into();
+        add_stderr(&mut data, self.stderr_tail());
+        data
+    }
+
+    /// Forward captured stderr lines to `sink` (kept across restarts)
+    pub fn forward_stderr(&mut self, sink: StderrSink) {
+        if let Some(ref tail) = self.stderr_tail {
+            tail.forward_to(sink.clone());
+        }
+        self.stderr_sink = Some(sink);
+    }
+
+    /// Report the backend's notifications and unexpected exit to `sink` (kept across restarts)
+    pub fn forward_events(&mut self, sink: EventSink) {
+        *self.event_sink.lock().unwrap_or_else(|e| e.into_inner()) = Some(sink);
+    }
+
+    /// Take the child's stdio handles as a backend read/write pair
+    fn take_stdio(child: &mut Child) -> Result<(BackendReader, BackendWriter), ProxyError> {
         let stdin = child.stdin.take().ok_or_else(|| {
             ProxyError::BackendSpawnFailed("Failed to get stdin handle".to_string())
         })?;
         let stdout = child.stdout.take().ok_or_else(|| {
             ProxyError::BackendSpawnFailed("Failed to get stdout handle".to_string())
         })?;
+        Ok((Box::new(stdout), Box::new(stdin)))
+    }
 
+    /// Build an instance around a backend read/write pair
+    /// Starts the writer task (requests out) and the reader task (responses in)
+    fn attach(
+        config: &Config,
+        root: PathBuf,
+        child: Option<Child>,
+        stderr_tail: Option<StderrTail>,
+        reader: BackendReader,
+        writer: BackendWriter,
+    ) -> Self {
         // Create channel for sending requests to backend
         let (stdin_tx, mut stdin_rx) = mpsc::channel::<String>(100);
 
         // Pending requests map
-        let pending: Arc<Mutex<HashMap<u64, PendingRequest>>> = Arc::new(Mutex::new(HashMap::new()));
+        let pending: Arc<Mutex<PendingRequests>> = Arc::default();
         let pending_clone = pending.clone();
+        let disconnected = Arc::new(AtomicBool::new(false));
+        let disconnected_clone = disconnected.clone();
+        let event_sink: Arc<std::sync::Mutex<Option<EventSink>>> = Arc::default();
+        let event_sink_clone = event_sink.clone();
+        let reader_root = root.clone();
+        let process = child.map(|child| {
+            ChildProcess::monitor(child, root.clone(), stderr_tail.clone(), pending.clone(), event_sink.clone())
+        });
+        let exit_rx = process.as_ref().map(|p| p.exit_rx.clone());
 
         // Spawn task to write to backend stdin
-        let mut stdin_writer = stdin;
+        let mut stdin_writer = writer;
         tokio::spawn(async move {
             while let Some(line) = stdin_rx.recv().await {
                 if let Err(e) = stdin_writer.write_all(line.as_bytes()).await {
@@ -347,7 +934,7 @@ impl BackendInstance {
         });
 
         // Spawn task to read backend stdout and dispatch responses
-        let mut reader = BufReader::new(stdout);
+        let mut reader = BufReader::new(reader);
         tokio::spawn(async move {
             let mut line = String::new();
             loop {
@@ -363,33 +950,54 @@ impl BackendInstance {
                             continue;
                         }
                         
-                        debug!("Backend response: {}", trimmed);
+                        debug!("Backend response: {}", crate::redact::global().redact_str(trimmed));
+
+                        // Messages with a method are the backend's own notifications or requests
+                        if let Ok(mut message) = serde_json::from_str::<JsonRpcRequest>(trimmed) {
+                            // Progress goes out under the token the client chose
+                            if message.method == "notifications/progress" {
+                                let token = message.get_param_path("progressToken").cloned().unwrap_or_default();
+                                match pending_clone.lock().await.client_progress_token(&token) {
+                                    Some(client_token) => {
+                                        if let Some(params) = message.params.as_mut() {
+                                            params["progressToken"] = client_token;
+                                        }
+                                    }
+                                    None => {
+                                        debug!("Dropping progress for unknown token {}", token);
+                                        continue;
+                                    }
+                                }
+                            }
+                            if !message.is_notification() {
+                                debug!("Ignoring request from backend: {}", message.method);
+                            } else if let Some(ref sink) = *event_sink_clone.lock().unwrap_or_else(|e| e.into_inner()) {
+                                let _ = sink.send((reader_root.clone(), BackendEvent::Notification(message)));
+                            }
+                            continue;
+                        }
                         
                         match serde_json::from_str::<JsonRpcResponse>(trimmed) {
                             Ok(response) => {
-                                if let Some(ref id) = response.id {
-                                    let proxy_id = match id {
-                                        JsonRpcId::Number(n) => *n as u64,
-                                        JsonRpcId::String(s) => {
-                                            s.parse().unwrap_or(0)
-                                        }
-                                    };
-                                    
+                                // Match the response to its request by the id we sent
+                                if let Some(ref proxy_id) = response.id {
                                     let mut pending_guard = pending_clone.lock().await;
-                                    if let Some(req) = pending_guard.remove(&proxy_id) {
+                                    if let Some(req) = pending_guard.remove(proxy_id) {
+                                        // Restore original client ID
                                         let mut final_response = response;
                                         final_response.id = req.client_id;
                                         
-                                        if req.response_tx.send(final_response).is_err() {
+                                        if req.response_tx.send(Ok(final_response)).is_err() {
                                             warn!("Failed to send response - receiver dropped");
                                         }
                                     } else {
-                                        warn!("Received response for unknown proxy_id: {}", proxy_id);
+                                        warn!("Received response for unknown proxy_id: {:?}", proxy_id);
                                     }
                                 }
                             }
                             Err(e) => {
-                                debug!("Failed to parse backend response: {} - {}", e, trimmed);
+                                // Might be a notification or malformed
+                                debug!("Failed to parse backend response: {} - {}", e, crate::redact::global().redact_str(trimmed));
                             }
                         }
                     }
@@ -399,20 +1007,164 @@ impl BackendInstance {
                     }
                 }
             }
+            // Fail outstanding requests now rather than letting them time out; a process
+            // that exited gets a moment to do so with its exit status
+            disconnected_clone.store(true, Ordering::Relaxed);
+            if let Some(mut exit_rx) = exit_rx {
+                let _ = tokio::time::timeout(Duration::from_secs(1), exit_rx.wait_for(Option::is_some)).await;
+            }
+            pending_clone.lock().await.clear();
             debug!("Stdout reader task ended");
         });
 
-        Ok(Self {
-            root,
-            state: BackendState::Ready,
-            last_used: Instant::now(),
-            child: Some(child),
-            stdin_tx: Some(stdin_tx),
-            pending,
-            request_timeout: Duration::from_secs(config.request_timeout_seconds),
-            config: config.clone(),
-            process_group,
-        })
+        Self {
+            root,
+            state: BackendState::Ready,
+            last_used: Instant::now(),
+            serial: PROCESS_SERIAL_COUNTER.fetch_add(1, Ordering::Relaxed),
+            successor: Arc::default(),
+            started_at: Instant::now(),
+            requests_served: 0,
+            warmup: Duration::ZERO,
+            mode: None,
+            warmup_progress: None,
+            process,
+            stdin_tx: Some(stdin_tx),
+            pending,
+            disconnected,
+            event_sink,
+            container: None,
+            request_timeout: Duration::from_secs(config.request_timeout_seconds),
+            method_timeouts: compile_method_timeouts(&config.timeouts),
+            consecutive_timeouts: Arc::new(AtomicU32::new(0)),
+            config: config.clone(),
+            recent_changes: ChangeReplay::new(config.replay_batches),
+            init_params: None,
+            client_ready: true,
+            initialized_held: false,
+            stderr_tail,
+            stderr_sink: None,
+            #[cfg(windows)]
+            job_object: None,
+            #[cfg(unix)]
+            process_group: None,
+        }
+    }
+
+    /// What a backend for `root` would run, for `--dry-run`: the program and its arguments, or
+    /// the address a connected root attaches to
+    pub async fn command_line(config: &Config, root: &Path) -> Result<Vec<String>, ProxyError> {
+        if let Some(address) = config.root_config(root).and_then(|rc| rc.connect.as_deref()) {
+            return Ok(vec![format!("connect {}", address)]);
+        }
+        let mode = resolve_mode(config, root).await;
+        let (cmd, _) = Self::build_command(config, root, &mode)?;
+        let cmd = cmd.as_std();
+        Ok(std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect())
+    }
+
+    /// Build the backend command line for a root
+    /// (node + auggie entry, ssh or a container runtime per root overrides, or wsl.exe
+    /// for WSL roots when enabled), along with the container to remove on shutdown
+    fn build_command(config: &Config, root: &Path, mode: &str) -> Result<(Command, Option<ContainerHandle>), ProxyError> {
+        let root_config = config.root_config(root);
+        let env = match root_config {
+            Some(rc) => crate::env::load_backend_env(root, rc.env_file.as_deref(), &rc.env)
+                .map_err(|e| ProxyError::ConfigError(format!("Invalid environment for {}: {}", root.display(), e)))?,
+            None => Vec::new(),
+        };
+        if !env.is_empty() {
+            debug!("Injecting {} environment variable(s) for root: {}", env.len(), root.display());
+        }
+
+        if config.mock_backend {
+            let exe = std::env::current_exe()
+                .map_err(|e| ProxyError::ConfigError(format!("Cannot locate mcp-proxy for --mock-backend: {}", e)))?;
+            info!("Spawning mock backend for root: {}", root.display());
+            let mut cmd = Command::new(exe);
+            cmd.arg("mock-backend").arg("--workspace-root").arg(root).envs(env);
+            return Ok((cmd, None));
+        }
+
+        if let Some(ssh) = root_config.and_then(|rc| rc.ssh.as_ref()) {
+            info!(
+                "Spawning backend for root: {} on remote host {} ({})",
+                root.display(),
+                ssh.host,
+                ssh.remote_root_for(root)
+            );
+            return Ok((ssh.backend_command(root, mode, &env), None));
+        }
+
+        if let Some(container) = root_config.and_then(|rc| rc.container.as_ref()) {
+            if container.image.is_empty() {
+                return Err(ProxyError::ConfigError(format!(
+                    "No container image configured for root: {}",
+                    root.display()
+                )));
+            }
+            let (mut cmd, handle) = container.backend_command(root, mode, &env);
+            cmd.envs(env);
+            info!(
+                "Spawning backend for root: {} in {} container {} ({})",
+                root.display(),
+                container.runtime,
+                handle.name,
+                container.image
+            );
+            return Ok((cmd, Some(handle)));
+        }
+
+        #[cfg(windows)]
+        if config.wsl.enabled {
+            if let Some(wsl_path) = WslPath::from_path(root) {
+                info!(
+                    "Spawning backend for root: {} inside WSL distro {} ({})",
+                    root.display(),
+                    wsl_path.distro,
+                    wsl_path.linux_path
+                );
+                let mut cmd = crate::wsl::backend_command(&config.wsl, &wsl_path, mode, &env);
+                cmd.envs(env);
+                return Ok((cmd, None));
+            }
+        }
+
+        let node_path = config
+            .node
+            .as_ref()
+            .ok_or_else(|| ProxyError::ConfigError("Node path not configured".to_string()))?;
+
+        let auggie_entry = config
+            .auggie_entry
+            .as_ref()
+            .ok_or_else(|| ProxyError::ConfigError("Auggie entry path not configured".to_string()))?;
+
+        if config.min_node_version.is_some() || config.min_auggie_version.is_some() {
+            Versions::cached(config.runtime, Some(node_path), Some(auggie_entry))
+                .check(config.min_node_version.as_deref(), config.min_auggie_version.as_deref())?;
+        }
+
+        info!(
+            "Spawning backend for root: {} with {}: {:?}, entry: {:?}",
+            root.display(),
+            config.runtime,
+            node_path,
+            auggie_entry
+        );
+
+        // Bypass .cmd wrappers to avoid cmd.exe shell issues on Windows
+        let mut cmd = config.runtime.command(node_path, auggie_entry);
+        cmd.arg("--mcp")
+            .arg("-m")
+            .arg(mode)
+            .arg("--workspace-root")
+            .arg(root)
+            .envs(env);
+        Ok((cmd, None))
     }
 
     /// Configure process resources (priority) on Unix
@@ -449,74 +1201,56 @@ impl BackendInstance {
         &mut self,
         request: JsonRpcRequest,
     ) -> Result<JsonRpcResponse, ProxyError> {
-        self.last_used = Instant::now();
-
-        let stdin_tx = self.stdin_tx.as_ref().ok_or_else(|| {
-            ProxyError::BackendUnavailable("Backend stdin not available".to_string())
-        })?;
-
-        if request.is_notification() {
-            return Err(ProxyError::RoutingFailed(
-                "send_request called with notification (id is None)".to_string(),
-            ));
-        }
-
-        // Generate proxy ID and setup response channel
-        let proxy_id = next_proxy_id();
-        let (response_tx, response_rx) = oneshot::channel();
+        let request_timeout = resolve_method_timeout(&self.method_timeouts, self.request_timeout, &request.method);
+        self.requests_served += 1;
+        self.send_request_within(request, request_timeout).await
+    }
 
-        // Register pending request
-        {
-            let mut pending = self.pending.lock().await;
-            pending.insert(
-                proxy_id,
-                PendingRequest {
-                    client_id: request.id.clone(),
-                    response_tx,
-                },
-            );
+    /// Send a request and wait at most `request_timeout` for the response
+    pub async fn send_request_within(
+        &mut self,
+        request: JsonRpcRequest,
+        request_timeout: Duration,
+    ) -> Result<JsonRpcResponse, ProxyError> {
+        self.last_used = Instant::now();
+        let result = self.handle()?.send_request_within(request, request_timeout).await;
+        if let Err(ProxyError::BackendUnavailable(_)) = result {
+            // Backend probably died
+            self.state = BackendState::Dead;
         }
+        result
+    }
 
-        // Replace ID with proxy ID
-        let mut backend_request = request.clone();
-        backend_request.id = Some(JsonRpcId::Number(proxy_id as i64));
-
-        let json = serde_json::to_string(&backend_request)?;
-        debug!(
-            "Sending request to backend: {} (proxy_id: {})",
-            request.method, proxy_id
-        );
+    /// Count a request as sent and hand out what it takes to wait for its answer without
+    /// borrowing the instance, along with its timeout
+    pub fn begin_request(&mut self, method: &str) -> Result<(BackendHandle, Duration), ProxyError> {
+        self.last_used = Instant::now();
+        self.requests_served += 1;
+        let timeout = resolve_method_timeout(&self.method_timeouts, self.request_timeout, method);
+        Ok((self.handle()?, timeout))
+    }
 
-        stdin_tx.send(json).await.map_err(|e| {
-            ProxyError::BackendUnavailable(format!("Failed to send to backend: {}", e))
+    /// What it takes to send requests to the current process without borrowing the instance
+    pub fn handle(&mut self) -> Result<BackendHandle, ProxyError> {
+        let stdin_tx = self.stdin_tx.clone().ok_or_else(|| {
+            ProxyError::BackendUnavailable("Backend stdin not available".to_string())
         })?;
-
-        // Wait for response with timeout
-        match tokio::time::timeout(self.request_timeout, response_rx).await {
-            Ok(Ok(response)) => Ok(response),
-            Ok(Err(_)) => {
-                // Channel closed - backend probably died
-                let mut pending = self.pending.lock().await;
-                pending.remove(&proxy_id);
-                self.state = BackendState::Dead;
-                Err(ProxyError::BackendUnavailable(
-                    "Backend response channel closed".to_string(),
-                ))
-            }
-            Err(_) => {
-                // Timeout - remove pending and mark backend as potentially unhealthy
-                warn!("Request {} timed out after {:?}", request.method, self.request_timeout);
-                let mut pending = self.pending.lock().await;
-                pending.remove(&proxy_id);
-                Err(ProxyError::BackendTimeout(format!(
-                    "Request timed out after {} seconds",
-                    self.request_timeout.as_secs()
-                )))
-            }
+        if let Some(status) = self.exit_status() {
+            self.state = BackendState::Dead;
+            return Err(ProxyError::BackendUnavailable(describe_exit(&status)));
         }
+        Ok(BackendHandle {
+            serial: self.serial,
+            stdin_tx,
+            pending: self.pending.clone(),
+            exit_rx: self.process.as_ref().map(|process| process.exit_rx.clone()),
+            consecutive_timeouts: self.consecutive_timeouts.clone(),
+            max_consecutive_timeouts: self.config.max_consecutive_timeouts,
+            successor: self.successor.clone(),
+        })
     }
 
-    pub async fn send_notification(&mut self, notification: JsonRpcRequest) -> Result<(), ProxyError> {
+    pub async fn send_notification(&mut self, mut notification: JsonRpcRequest) -> Result<(), ProxyError> {
         self.last_used = Instant::now();
 
         if !notification.is_notification() {
@@ -525,6 +1259,17 @@ impl BackendInstance {
             ));
         }
 
+        // The backend knows the cancelled request by the id the proxy gave it
+        if notification.method == "notifications/cancelled" {
+            if let Some(params) = notification.params.as_mut() {
+                if let Ok(client_id) = serde_json::from_value::<JsonRpcId>(params["requestId"].clone()) {
+                    if let Some(proxy_id) = self.pending.lock().await.proxy_id(&client_id) {
+                        params["requestId"] = serde_json::to_value(proxy_id)?;
+                    }
+                }
+            }
+        }
+
         let stdin_tx = self.stdin_tx.as_ref().ok_or_else(|| {
             ProxyError::BackendUnavailable("Backend stdin not available".to_string())
         })?;
@@ -538,36 +1283,282 @@ impl BackendInstance {
         Ok(())
     }
 
+    /// Run the MCP handshake (`initialize` + `notifications/initialized`) with these params;
+    /// `notifications/initialized` and the warm-up wait for [`Self::client_initialized`] unless
+    /// the client is already initialized
+    pub async fn initialize(&mut self, params: serde_json::Value, client_ready: bool) {
+        self.init_params = Some(params);
+        self.client_ready = client_ready;
+        self.handshake().await;
+    }
+
+    /// The client has sent `notifications/initialized`: pass it on if it was held
+    pub async fn client_initialized(&mut self) {
+        self.client_ready = true;
+        if std::mem::take(&mut self.initialized_held) {
+            self.send_initialized().await;
+        }
+    }
+
+    /// Failures are only logged: backends that skip the handshake still serve requests
+    async fn handshake(&mut self) {
+        self.initialized_held = false;
+        let params = match self.init_params.clone() {
+            Some(params) => params,
+            None => return,
+        };
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "initialize".to_string(),
+            id: Some(JsonRpcId::String("mcp-proxy-initialize".to_string())),
+            params: Some(params),
+        };
+        let timeout = Duration::from_secs(self.config.spawn_timeout_seconds);
+        match self.send_request_within(request, timeout).await {
+            Ok(response) => {
+                if let Some(error) = response.error {
+                    warn!("Backend for {} rejected initialize: {}", self.root.display(), error.message);
+                    return;
+                }
+                self.warmup = self.started_at.elapsed();
+                debug!("Backend for {} initialized in {:?}", self.root.display(), self.warmup);
+            }
+            Err(e) => {
+                warn!("Backend for {} did not complete initialize: {}", self.root.display(), e);
+                return;
+            }
+        }
+        if self.client_ready {
+            self.send_initialized().await;
+        } else {
+            debug!("Holding initialized for {} until the client's", self.root.display());
+            self.initialized_held = true;
+        }
+    }
+
+    async fn send_initialized(&mut self) {
+        let initialized = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "notifications/initialized".to_string(),
+            id: None,
+            params: None,
+        };
+        if let Err(e) = self.send_notification(initialized).await {
+            warn!("Failed to send initialized to backend for {}: {}", self.root.display(), e);
+        }
+        self.start_warmup();
+    }
+
+    /// Send the `warmup_requests` one after another on a task of their own, so they don't hold
+    /// up real requests; their answers are dropped
+    fn start_warmup(&mut self) {
+        self.warmup_progress = None;
+        let requests = self.config.warmup_requests.clone();
+        let Some(stdin_tx) = self.stdin_tx.clone() else { return };
+        if requests.is_empty() {
+            return;
+        }
+        let progress = Arc::new(WarmupProgress { total: requests.len(), ..Default::default() });
+        self.warmup_progress = Some(progress.clone());
+        let timeouts: Vec<Duration> = requests
+            .iter()
+            .map(|request| resolve_method_timeout(&self.method_timeouts, self.request_timeout, &request.method))
+            .collect();
+        let pending = self.pending.clone();
+        let root = self.root.clone();
+        tokio::spawn(async move {
+            let started = Instant::now();
+            for (request, timeout) in requests.into_iter().zip(timeouts) {
+                let proxy_id = JsonRpcId::Number(next_proxy_id() as i64);
+                let (response_tx, response_rx) = oneshot::channel();
+                pending.lock().await.insert(proxy_id.clone(), PendingRequest { client_id: None, progress_token: None, response_tx });
+                let message = JsonRpcRequest {
+                    jsonrpc: "2.0".to_string(),
+                    method: request.method.clone(),
+                    id: Some(proxy_id.clone()),
+                    params: request.params,
+                };
+                let sent = match serde_json::to_string(&message) {
+                    Ok(json) => stdin_tx.send(json).await.is_ok(),
+                    Err(_) => false,
+                };
+                let error = match sent {
+                    false => Some("backend gone".to_string()),
+                    true => match tokio::time::timeout(timeout, response_rx).await {
+                        Ok(Ok(Ok(response))) => response.error.map(|e| e.message),
+                        Ok(Ok(Err(_)) | Err(_)) => Some("backend gone".to_string()),
+                        Err(_) => Some(format!("timed out after {}s", timeout.as_secs())),
+                    },
+                };
+                if let Some(error) = error {
+                    pending.lock().await.remove(&proxy_id);
+                    progress.failed.fetch_add(1, Ordering::Relaxed);
+                    warn!("Warm-up request {} failed for {}: {}", request.method, root.display(), error);
+                }
+                progress.done.fetch_add(1, Ordering::Relaxed);
+                if !sent {
+                    return;
+                }
+            }
+            info!("Backend for {} warmed up in {:?}", root.display(), started.elapsed());
+        });
+    }
+
+    /// Send MCP `shutdown` (waiting at most `timeout` for its answer) and `exit`
+    /// Skipped when the backend is already gone.
+    async fn request_exit(&mut self, timeout: Duration) {
+        if self.stdin_tx.is_none() || self.exit_status().is_some() || self.disconnected.load(Ordering::Relaxed) {
+            return;
+        }
+        let message = |method: &str, id: Option<JsonRpcId>| JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method.to_string(),
+            id,
+            params: None,
+        };
+        let shutdown = message("shutdown", Some(JsonRpcId::String("mcp-proxy-shutdown".to_string())));
+        if let Err(e) = self.send_request_within(shutdown, timeout).await {
+            debug!("Backend for {} did not answer shutdown: {}", self.root.display(), e);
+        }
+        if let Err(e) = self.send_notification(message("exit", None)).await {
+            debug!("Failed to send exit to backend for {}: {}", self.root.display(), e);
+        }
+    }
+
+    /// Send a batch of file changes, keeping it for replay after a restart
+    pub async fn send_file_changes(&mut self, notification: JsonRpcRequest) -> Result<(), ProxyError> {
+        self.recent_changes.record(&notification);
+        self.send_notification(notification).await
+    }
+
+    /// The change batches kept for replay, oldest first
+    pub fn recent_changes(&self) -> Vec<JsonRpcRequest> {
+        self.recent_changes.batches.iter().cloned().collect()
+    }
+
+    /// Take over the change batches kept by the instance this one replaces and replay them
+    pub async fn adopt_changes(&mut self, old: &mut BackendInstance) {
+        let capacity = old.recent_changes.capacity;
+        self.recent_changes = std::mem::replace(&mut old.recent_changes, ChangeReplay::new(capacity));
+        self.replay_changes().await;
+    }
+
+    /// Re-send the recent change batches the dead process may never have indexed
+    async fn replay_changes(&mut self) {
+        let batches: Vec<JsonRpcRequest> = self.recent_changes.batches.iter().cloned().collect();
+        if batches.is_empty() {
+            return;
+        }
+        info!("Replaying {} change batches to restarted backend for {}", batches.len(), self.root.display());
+        for batch in batches {
+            if let Err(e) = self.send_notification(batch).await {
+                warn!("Failed to replay change batch: {}", e);
+                break;
+            }
+        }
+    }
+
+    /// Requests waiting for the backend's answer
+    pub async fn pending_count(&self) -> usize {
+        self.pending.lock().await.len()
+    }
+
+    /// Client ids of the requests waiting for the backend's answer (empty while the table is busy)
+    pub fn pending_client_ids(&self) -> Vec<JsonRpcId> {
+        match self.pending.try_lock() {
+            Ok(pending) => pending.by_proxy_id.values().filter_map(|request| request.client_id.clone()).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
     /// Check if backend has pending requests
     pub async fn has_pending(&self) -> bool {
         let pending = self.pending.lock().await;
         !pending.is_empty()
     }
 
+    /// Number of the current process among those the proxy started (see [`BackendHandle::serial`])
+    pub fn serial(&self) -> u64 {
+        self.serial
+    }
+
+    /// Process id of the spawned backend (`None` for connected backends)
+    pub fn pid(&self) -> Option<u32> {
+        self.process.as_ref().and_then(|p| p.pid)
+    }
+
+    /// Auggie mode of the spawned backend (`None` for connected backends)
+    pub fn mode(&self) -> Option<&str> {
+        self.mode.as_deref()
+    }
+
+    /// Progress of the warm-up requests for `proxy_status` (`None` when none are configured)
+    pub fn warmup_status(&self) -> Option<serde_json::Value> {
+        let progress = self.warmup_progress.as_ref()?;
+        let done = progress.done.load(Ordering::Relaxed);
+        Some(serde_json::json!({
+            "done": done,
+            "total": progress.total,
+            "failed": progress.failed.load(Ordering::Relaxed),
+            "complete": done == progress.total,
+        }))
+    }
+
+    /// How the backend process exited, once it has
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        self.process.as_ref().and_then(ChildProcess::exit_status)
+    }
+
+    /// What evicting this backend would cost (`eviction_policy = cost`)
+    pub fn eviction_cost(&self) -> BackendCost {
+        BackendCost {
+            warmup: self.warmup,
+            memory_bytes: self.pid().and_then(crate::eviction::process_memory),
+            requests: self.requests_served,
+            idle: self.last_used.elapsed(),
+        }
+    }
+
+    /// Why the process is due for a restart (`max_backend_lifetime_seconds`, `max_requests_per_backend`)
+    pub fn recycle_reason(&self) -> Option<String> {
+        let lifetime = self.config.max_backend_lifetime_seconds;
+        if lifetime > 0 && self.started_at.elapsed() >= Duration::from_secs(lifetime) {
+            return Some(format!("running for over {} seconds", lifetime));
+        }
+        let max_requests = self.config.max_requests_per_backend;
+        if max_requests > 0 && self.requests_served >= max_requests {
+            return Some(format!("served {} requests", self.requests_served));
+        }
+        None
+    }
+
+    /// Stop counting this backend against `max_backends` once its requests finish
+    pub fn start_draining(&mut self) {
+        if self.state == BackendState::Ready {
+            self.state = BackendState::Draining;
+        }
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.state == BackendState::Draining
+    }
+
     /// Check if backend is dead/crashed
     pub fn is_dead(&self) -> bool {
-        self.state == BackendState::Dead
+        self.state == BackendState::Dead || self.exit_status().is_some()
     }
 
     /// Check if the backend process is still alive
-    #[allow(dead_code)]
     pub fn is_process_alive(&mut self) -> bool {
-        if let Some(ref mut child) = self.child {
-            // try_wait returns Ok(Some(status)) if exited, Ok(None) if still running
-            match child.try_wait() {
-                Ok(Some(status)) => {
-                    warn!("Backend process exited with status: {:?}", status);
-                    self.state = BackendState::Dead;
-                    false
-                }
-                Ok(None) => true, // Still running
-                Err(e) => {
-                    warn!("Failed to check backend process status: {}", e);
-                    false
-                }
+        if self.process.is_some() {
+            if self.exit_status().is_some() {
+                self.state = BackendState::Dead;
+                return false;
             }
+            true
         } else {
-            false
+            // Connected backends have no process; alive while the connection is open
+            self.stdin_tx.is_some() && !self.disconnected.load(Ordering::Relaxed)
         }
     }
 
@@ -635,153 +1626,81 @@ impl BackendInstance {
         }
     }
 
-    /// Restart the backend process
-    #[cfg(windows)]
-    pub async fn restart(&mut self) -> Result<(), ProxyError> {
-        info!("Restarting backend for root: {}", self.root.display());
-        
-        // Shutdown existing process
-        self.shutdown().await;
-        
-        // Clone the Arc to pass to spawn (safe shared ownership)
-        let job_object = self.job_object.clone();
-        
-        // Respawn
-        let mut new_instance = Self::spawn(&self.config, self.root.clone(), job_object).await?;
-        
-        // Take ownership of fields from new instance using std::mem::take
-        self.state = new_instance.state;
-        self.child = std::mem::take(&mut new_instance.child);
-        self.stdin_tx = std::mem::take(&mut new_instance.stdin_tx);
-        self.pending = std::mem::take(&mut new_instance.pending);
-        self.last_used = Instant::now();
-        
-        // Prevent new_instance Drop from killing the process we just took
-        new_instance.state = BackendState::Dead;
-        
-        info!("Backend restarted successfully for root: {}", self.root.display());
-        Ok(())
-    }
-
-    #[cfg(unix)]
-    pub async fn restart(&mut self) -> Result<(), ProxyError> {
-        info!("Restarting backend for root: {}", self.root.display());
-        
-        // Shutdown existing process
-        self.shutdown().await;
-        
-        // Clone the Arc to pass to spawn (safe shared ownership)
-        let process_group = self.process_group.clone();
-        
-        // Respawn
-        let mut new_instance = Self::spawn(&self.config, self.root.clone(), process_group).await?;
-        
-        // Take ownership of fields from new instance using std::mem::take
-        self.state = new_instance.state;
-        self.child = std::mem::take(&mut new_instance.child);
-        self.stdin_tx = std::mem::take(&mut new_instance.stdin_tx);
-        self.pending = std::mem::take(&mut new_instance.pending);
-        self.last_used = Instant::now();
-        
-        // Prevent new_instance Drop from killing the process we just took
-        new_instance.state = BackendState::Dead;
-        
-        info!("Backend restarted successfully for root: {}", self.root.display());
-        Ok(())
-    }
-
-    /// Send request with automatic retry on failure (crash recovery)
-    pub async fn send_request_with_retry(
-        &mut self,
-        request: JsonRpcRequest,
-        max_retries: u32,
-    ) -> Result<JsonRpcResponse, ProxyError> {
-        let mut last_error = None;
-        
-        for attempt in 0..=max_retries {
-            // Check if backend is dead and needs restart
-            if self.is_dead() && attempt > 0 {
-                warn!("Backend is dead, attempting restart (attempt {}/{})", attempt, max_retries);
-                if let Err(e) = self.restart().await {
-                    error!("Failed to restart backend: {}", e);
-                    last_error = Some(e);
-                    continue;
-                }
-            }
-            
-            match self.send_request(request.clone()).await {
-                Ok(response) => return Ok(response),
-                Err(e) => {
-                    if attempt < max_retries {
-                        warn!(
-                            "Request failed (attempt {}/{}): {}, will retry",
-                            attempt + 1,
-                            max_retries + 1,
-                            e
-                        );
-                        last_error = Some(e);
-                        // Mark as dead to trigger restart on next attempt
-                        if self.state != BackendState::Dead {
-                            self.state = BackendState::Dead;
-                        }
-                    } else {
-                        return Err(e);
-                    }
-                }
-            }
-        }
-        
-        Err(last_error.unwrap_or_else(|| ProxyError::BackendUnavailable("All retries exhausted".to_string())))
-    }
-
     /// Shutdown the backend gracefully
-    /// Waits for graceful_timeout before force killing
+    /// Waits `shutdown_grace_seconds` after asking it to exit, then `shutdown_term_seconds`
+    /// after SIGTERM, before force killing
     pub async fn shutdown(&mut self) {
-        self.shutdown_with_timeout(Duration::from_secs(5)).await;
+        self.shutdown_with_timeout(Duration::from_secs(self.config.shutdown_grace_seconds)).await;
     }
 
     /// Shutdown the backend with a custom graceful timeout
+    /// Stages: MCP `shutdown` + `exit` and closed stdin, SIGTERM (CTRL_BREAK on Windows), kill
     pub async fn shutdown_with_timeout(&mut self, graceful_timeout: Duration) {
         info!("Shutting down backend for root: {}", self.root.display());
         self.state = BackendState::Stopping;
+        let pid = self.pid();
+        if let Some(ref process) = self.process {
+            process.stopping.store(true, Ordering::Relaxed);
+        }
+        let deadline = Instant::now() + graceful_timeout;
+
+        // Let the backend flush its state before it goes
+        self.request_exit(graceful_timeout).await;
         
         // Close stdin channel to signal shutdown (this tells the backend to exit gracefully)
         self.stdin_tx.take();
         
-        if let Some(mut child) = self.child.take() {
-            // Wait for graceful shutdown
-            match tokio::time::timeout(graceful_timeout, child.wait()).await {
-                Ok(Ok(status)) => {
-                    info!("Backend exited gracefully with status: {:?}", status);
+        if let Some(mut process) = self.process.take() {
+            let grace = deadline.saturating_duration_since(Instant::now());
+            let mut exited = tokio::time::timeout(grace, process.wait()).await.ok().flatten();
+            if exited.is_none() {
+                warn!("Backend did not exit within {:?}, terminating", graceful_timeout);
+                if process.terminate() {
+                    let term_timeout = Duration::from_secs(self.config.shutdown_term_seconds);
+                    exited = tokio::time::timeout(term_timeout, process.wait()).await.ok().flatten();
                 }
-                Ok(Err(e)) => {
-                    warn!("Error waiting for backend to exit: {}", e);
-                    // Force kill
-                    let _ = child.kill().await;
+            }
+            match exited {
+                Some(status) => {
+                    info!("Backend exited gracefully with status: {:?}", status);
                 }
-                Err(_) => {
-                    // Timeout - force kill
-                    warn!(
-                        "Backend did not exit within {:?}, force killing",
-                        graceful_timeout
-                    );
-                    if let Err(e) = child.kill().await {
-                        warn!("Failed to kill backend process: {}", e);
-                    }
+                None => {
+                    warn!("Backend for {} did not stop, force killing", self.root.display());
+                    process.kill();
+                    let _ = tokio::time::timeout(Duration::from_secs(5), process.wait()).await;
                 }
             }
         }
+
+        // The runtime client exiting doesn't guarantee the container is gone
+        if let Some(container) = self.container.take() {
+            container.remove().await;
+        }
+        self.forget_process(pid);
         
         self.state = BackendState::Dead;
     }
+
+    /// Stop tracking a process that is gone (it is no longer an orphan candidate)
+    fn forget_process(&self, pid: Option<u32>) {
+        #[cfg(unix)]
+        if let (Some(pid), Some(ref pg)) = (pid, &self.process_group) {
+            pg.remove_process(pid);
+        }
+        #[cfg(not(unix))]
+        let _ = pid;
+    }
 }
 
 impl Drop for BackendInstance {
     fn drop(&mut self) {
         // Ensure process is killed on drop
-        if let Some(ref mut child) = self.child {
-            // Use start_kill for sync drop context
-            let _ = child.start_kill();
+        if let Some(ref mut process) = self.process {
+            process.kill();
+        }
+        self.forget_process(self.pid());
+        if let Some(ref container) = self.container {
+            container.remove_detached();
         }
     }
 }
@@ -790,6 +1709,44 @@ impl Drop for BackendInstance {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_handshake_params() {
+        let client = serde_json::json!({
+            "protocolVersion": "2025-03-26",
+            "capabilities": {"roots": {"listChanged": true}, "experimental": {"x": {}}},
+            "clientInfo": {"name": "windsurf", "version": "1.2.0"},
+        });
+        let params = handshake_params(Some(&client));
+        assert_eq!(params["protocolVersion"], "2025-03-26");
+        assert_eq!(params["clientInfo"]["version"], "1.2.0");
+        assert!(params["clientInfo"]["name"].as_str().unwrap().starts_with("windsurf via mcp-proxy/"));
+        assert!(params["capabilities"].get("roots").is_none());
+        assert!(params["capabilities"].get("experimental").is_some());
+
+        let params = handshake_params(None);
+        assert!(params["clientInfo"]["name"].as_str().unwrap().starts_with("mcp-proxy/"));
+    }
+
+    #[test]
+    fn test_change_replay_ring() {
+        let batch = |n: u64| JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "notifications/files/didChange".to_string(),
+            id: None,
+            params: Some(serde_json::json!({ "uris": [format!("file:///repo/{}.rs", n)] })),
+        };
+        let mut replay = ChangeReplay::new(2);
+        for n in 0..3 {
+            replay.record(&batch(n));
+        }
+        let kept: Vec<_> = replay.batches.iter().map(|b| b.params.clone().unwrap()["uris"][0].clone()).collect();
+        assert_eq!(kept, vec!["file:///repo/1.rs", "file:///repo/2.rs"]);
+
+        let mut disabled = ChangeReplay::new(0);
+        disabled.record(&batch(0));
+        assert!(disabled.batches.is_empty());
+    }
+
     #[test]
     fn test_backend_state_transitions() {
         assert_eq!(BackendState::Ready, BackendState::Ready);
@@ -797,6 +1754,33 @@ mod tests {
         assert_ne!(BackendState::Stopping, BackendState::Dead);
     }
 
+    #[test]
+    fn test_pending_id_mapping() {
+        let mut pending = PendingRequests::default();
+        let mut receivers = Vec::new();
+        for (proxy_id, client_id) in [(7, JsonRpcId::String("abc".to_string())), (8, JsonRpcId::Number(1))] {
+            let (response_tx, response_rx) = oneshot::channel();
+            receivers.push(response_rx);
+            let progress_token = Some(serde_json::json!(format!("progress-{}", proxy_id)));
+            pending.insert(JsonRpcId::Number(proxy_id), PendingRequest { client_id: Some(client_id), progress_token, response_tx });
+        }
+        assert_eq!(pending.proxy_id(&JsonRpcId::String("abc".to_string())), Some(&JsonRpcId::Number(7)));
+        assert_eq!(pending.client_progress_token(&serde_json::json!(7)), Some(serde_json::json!("progress-7")));
+        assert_eq!(pending.client_progress_token(&serde_json::json!("8")), Some(serde_json::json!("progress-8")));
+        assert_eq!(pending.client_progress_token(&serde_json::json!(9)), None);
+
+        // A string that isn't one of our ids no longer collapses onto some other request
+        assert!(pending.remove(&JsonRpcId::String("abc".to_string())).is_none());
+        assert!(pending.remove(&JsonRpcId::String("0".to_string())).is_none());
+
+        let request = pending.remove(&JsonRpcId::String("7".to_string())).unwrap();
+        assert_eq!(request.client_id, Some(JsonRpcId::String("abc".to_string())));
+        assert!(pending.proxy_id(&JsonRpcId::String("abc".to_string())).is_none());
+        let request = pending.remove(&JsonRpcId::Number(8)).unwrap();
+        assert_eq!(request.client_id, Some(JsonRpcId::Number(1)));
+        assert!(pending.is_empty());
+    }
+
     #[test]
     fn test_proxy_id_generation() {
         let id1 = next_proxy_id();
@@ -804,10 +1788,162 @@ mod tests {
         assert!(id2 > id1, "Proxy IDs should be monotonically increasing");
     }
 
+    #[test]
+    fn test_method_timeout_resolution() {
+        let mut timeouts = HashMap::new();
+        timeouts.insert("tools/*".to_string(), 10);
+        timeouts.insert("tools/call".to_string(), 600);
+        timeouts.insert("[invalid".to_string(), 1);
+        let overrides = compile_method_timeouts(&timeouts);
+        assert_eq!(overrides.len(), 2);
+
+        let default = Duration::from_secs(120);
+        assert_eq!(resolve_method_timeout(&overrides, default, "tools/call"), Duration::from_secs(600));
+        assert_eq!(resolve_method_timeout(&overrides, default, "tools/list"), Duration::from_secs(10));
+        assert_eq!(resolve_method_timeout(&overrides, default, "resources/list"), default);
+    }
+
+    #[tokio::test]
+    async fn test_connect_to_running_backend() {
+        use tokio::io::AsyncBufReadExt;
+
+        // Minimal MCP server answering every request with its own id
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let request: serde_json::Value = serde_json::from_str(&line).unwrap();
+                let response = serde_json::json!({"jsonrpc": "2.0", "id": request["id"], "result": {}});
+                writer.write_all(format!("{}\n", response).as_bytes()).await.unwrap();
+            }
+        });
+
+        let config = <Config as clap::Parser>::parse_from(["mcp-proxy"]);
+        let mut backend = BackendInstance::connect(&config, PathBuf::from("/repo"), &address).await.unwrap();
+        assert!(backend.is_process_alive());
+
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "tools/list".to_string(),
+            id: Some(JsonRpcId::String("client-1".to_string())),
+            params: None,
+        };
+        let response = backend.send_request(request).await.unwrap();
+        assert_eq!(response.id, Some(JsonRpcId::String("client-1".to_string())));
+
+        backend.shutdown().await;
+        assert!(!backend.is_process_alive());
+    }
+
+    #[tokio::test]
+    async fn test_warmup_requests() {
+        // Answers every request, refusing `nope`; notifications get no answer
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let request: serde_json::Value = serde_json::from_str(&line).unwrap();
+                let response = match request["method"].as_str() {
+                    _ if request.get("id").is_none() => continue,
+                    Some("nope") => serde_json::json!({"jsonrpc": "2.0", "id": request["id"], "error": {"code": -32601, "message": "nope"}}),
+                    _ => serde_json::json!({"jsonrpc": "2.0", "id": request["id"], "result": {}}),
+                };
+                writer.write_all(format!("{}\n", response).as_bytes()).await.unwrap();
+            }
+        });
+
+        let mut config = <Config as clap::Parser>::parse_from(["mcp-proxy"]);
+        config.warmup_requests = vec![
+            WarmupRequest { method: "tools/list".to_string(), params: None },
+            WarmupRequest { method: "nope".to_string(), params: Some(serde_json::json!({})) },
+        ];
+        let mut backend = BackendInstance::connect(&config, PathBuf::from("/repo"), &address).await.unwrap();
+        assert!(backend.warmup_status().is_none());
+        // The warm-up waits for the client's initialized
+        backend.initialize(handshake_params(None), false).await;
+        assert!(backend.warmup_status().is_none());
+        backend.client_initialized().await;
+        for _ in 0..100 {
+            if backend.warmup_status().unwrap()["complete"] == true {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(
+            backend.warmup_status(),
+            Some(serde_json::json!({ "done": 2, "total": 2, "failed": 1, "complete": true }))
+        );
+        backend.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_progress_token_mapping() {
+        // Reports progress under the token it was given (and under one nobody gave it) before answering
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let request: serde_json::Value = serde_json::from_str(&line).unwrap();
+                let token = request["params"]["_meta"]["progressToken"].clone();
+                assert_eq!(token, request["id"]);
+                for token in [serde_json::json!("stray"), token] {
+                    let progress = serde_json::json!({"jsonrpc": "2.0", "method": "notifications/progress", "params": {"progressToken": token, "progress": 1}});
+                    writer.write_all(format!("{}\n", progress).as_bytes()).await.unwrap();
+                }
+                let response = serde_json::json!({"jsonrpc": "2.0", "id": request["id"], "result": {}});
+                writer.write_all(format!("{}\n", response).as_bytes()).await.unwrap();
+            }
+        });
+
+        let config = <Config as clap::Parser>::parse_from(["mcp-proxy"]);
+        let mut backend = BackendInstance::connect(&config, PathBuf::from("/repo"), &address).await.unwrap();
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        backend.forward_events(event_tx);
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "tools/call".to_string(),
+            id: Some(JsonRpcId::Number(1)),
+            params: Some(serde_json::json!({ "_meta": { "progressToken": "client-token" } })),
+        };
+        backend.send_request(request).await.unwrap();
+        let Some((_, BackendEvent::Notification(progress))) = event_rx.recv().await else { panic!("no progress") };
+        assert_eq!(progress.get_param_path("progressToken"), Some(&serde_json::json!("client-token")));
+        // The stray token was dropped
+        assert!(event_rx.try_recv().is_err());
+        backend.shutdown().await;
+    }
+
     #[tokio::test]
     async fn test_graceful_shutdown_timeout() {
         // Test that Duration::from_secs works correctly for shutdown
         let timeout = Duration::from_secs(5);
         assert_eq!(timeout.as_secs(), 5);
     }
+
+    #[tokio::test]
+    async fn test_resolve_mode() {
+        let mut config = <Config as clap::Parser>::parse_from(["mcp-proxy", "--mode", "minimal"]);
+        config.mode_rules = vec![
+            crate::config::ModeRule { root: "/scratch/**".to_string(), mode: "auto".to_string() },
+            crate::config::ModeRule { root: "/work/*".to_string(), mode: "default".to_string() },
+        ];
+        assert_eq!(resolve_mode(&config, Path::new("/work/repo")).await, "default");
+        assert_eq!(resolve_mode(&config, Path::new("/other/repo")).await, "minimal");
+        // Files that can't be listed count as a large repository
+        assert_eq!(resolve_mode(&config, Path::new("/scratch/missing")).await, "default");
+
+        assert_eq!(auto_mode(Some(10), 2000), "minimal");
+        assert_eq!(auto_mode(Some(2000), 2000), "minimal");
+        assert_eq!(auto_mode(Some(2001), 2000), "default");
+        assert_eq!(auto_mode(None, 2000), "default");
+    }
 }
@@ -0,0 +1,187 @@
+//! Backend stderr capture
+//! Writes each backend's stderr to its own rotating file instead of the proxy's stderr,
+//! keeping the last lines around for error reports, and optionally forwards the lines
+//! to the client as MCP logging notifications
+
+use crate::logfile::RotatingFile;
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::sync::{mpsc, watch};
+use tracing::{debug, warn};
+
+/// Lines of stderr kept in memory for error data
+pub const STDERR_TAIL_LINES: usize = 50;
+
+/// Receives `(root, line)` for every stderr line of every backend
+pub type StderrSink = mpsc::UnboundedSender<(PathBuf, String)>;
+
+/// Last lines a backend wrote to stderr
+#[derive(Clone, Default)]
+pub struct StderrTail {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    sink: Arc<Mutex<Option<StderrSink>>>,
+    /// Set once stderr reached EOF
+    closed: Arc<watch::Sender<bool>>,
+}
+
+impl StderrTail {
+    fn push(&self, line: String) {
+        let mut lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        if lines.len() == STDERR_TAIL_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+    }
+
+    /// Wait (at most `timeout`) for the rest of the output of a process that exited
+    pub async fn wait_closed(&self, timeout: Duration) {
+        let mut closed = self.closed.subscribe();
+        let _ = tokio::time::timeout(timeout, closed.wait_for(|closed| *closed)).await;
+    }
+
+    /// Forward lines written from now on to `sink`
+    pub fn forward_to(&self, sink: StderrSink) {
+        *self.sink.lock().unwrap_or_else(|e| e.into_inner()) = Some(sink);
+    }
+
+    fn forward(&self, root: &Path, line: &str) {
+        if let Some(ref sink) = *self.sink.lock().unwrap_or_else(|e| e.into_inner()) {
+            let _ = sink.send((root.to_path_buf(), line.to_string()));
+        }
+    }
+}
+
+/// MCP logging level for a stderr line, guessed from its wording
+pub fn infer_level(line: &str) -> &'static str {
+    let lower = line.to_ascii_lowercase();
+    if lower.contains("fatal") || lower.contains("panic") {
+        "critical"
+    } else if lower.contains("error") || lower.contains("exception") {
+        "error"
+    } else if lower.contains("warn") {
+        "warning"
+    } else if lower.contains("debug") || lower.contains("trace") {
+        "debug"
+    } else {
+        "info"
+    }
+}
+
+/// Log file for a root: `<dir>/<root name>-<hash of the full path>.log`
+pub fn log_path(dir: &Path, root: &Path) -> PathBuf {
+    let name: String = root
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "root".to_string())
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    let hash: String = Sha256::digest(root.to_string_lossy().as_bytes())
+        .iter()
+        .take(4)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    dir.join(format!("{}-{}.log", name, hash))
+}
+
+/// Open a root's stderr log in `dir` (failures are logged, the lines then only go to the tail)
+pub fn open_log(dir: &Path, root: &Path, max_bytes: u64, max_files: usize) -> Option<RotatingFile> {
+    let path = log_path(dir, root);
+    match RotatingFile::open(&path, max_bytes, max_files) {
+        Ok(file) => Some(file),
+        Err(e) => {
+            warn!("Failed to open backend log {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Read a backend's stderr until it closes, into the log file (if any) and the returned tail
+pub fn capture(
+    stderr: impl AsyncRead + Unpin + Send + 'static,
+    root: &Path,
+    mut file: Option<RotatingFile>,
+) -> StderrTail {
+    let tail = StderrTail::default();
+    let root = root.to_path_buf();
+
+    let tail_writer = tail.clone();
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(stderr).lines();
+        loop {
+            match lines.next_line().await {
+                Ok(Some(line)) => {
+                    if let Some(ref mut f) = file {
+                        if let Err(e) = f.write_line(&line) {
+                            warn!("Failed to write backend log {}: {}", f.path().display(), e);
+                            file = None;
+                        }
+                    }
+                    tail_writer.forward(&root, &line);
+                    tail_writer.push(line);
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    debug!("Error reading backend stderr: {}", e);
+                    break;
+                }
+            }
+        }
+        tail_writer.closed.send_replace(true);
+        debug!("Backend stderr closed");
+    });
+    tail
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_path() {
+        let dir = Path::new("/logs");
+        let a = log_path(dir, Path::new("/work/my repo"));
+        let b = log_path(dir, Path::new("/other/my repo"));
+        assert!(a.file_name().unwrap().to_str().unwrap().starts_with("my_repo-"));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_infer_level() {
+        assert_eq!(infer_level("Error: ENOENT: no such file"), "error");
+        assert_eq!(infer_level("[WARN] slow indexing"), "warning");
+        assert_eq!(infer_level("FATAL out of memory"), "critical");
+        assert_eq!(infer_level("Indexed 1200 files"), "info");
+    }
+
+    #[tokio::test]
+    async fn test_capture_keeps_tail_and_file() {
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-stderr-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let output: String = (0..60).map(|n| format!("line {}\n", n)).collect();
+        let root = Path::new("/work/repo");
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let file = open_log(&dir, root, 1024 * 1024, 2);
+        let tail = capture(std::io::Cursor::new(output.into_bytes()), root, file);
+        tail.forward_to(tx);
+        tail.wait_closed(Duration::from_secs(5)).await;
+        let lines = tail.lines();
+        assert_eq!((lines.len(), lines[0].as_str()), (STDERR_TAIL_LINES, "line 10"));
+        let logged = std::fs::read_to_string(log_path(&dir, root)).unwrap();
+        assert_eq!(logged.lines().count(), 60);
+        // The reader task first ran at the wait above, after forwarding was set up
+        let forwarded: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+        assert_eq!(forwarded.len(), 60);
+        assert_eq!(forwarded[59], (root.to_path_buf(), "line 59".to_string()));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,61 @@
+//! Dispatch across several backend instances of one root (`instances_per_root`)
+
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// How requests are spread over a root's instances
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum LoadBalancing {
+    /// Each instance in turn
+    #[default]
+    RoundRobin,
+    /// The instance with the fewest requests in flight (ties go round-robin)
+    LeastPending,
+}
+
+/// Pick the instance for the next request; `pending` holds each instance's requests in
+/// flight and `counter` advances on every pick
+pub fn pick(policy: LoadBalancing, counter: &mut usize, pending: &[usize]) -> usize {
+    if pending.is_empty() {
+        return 0;
+    }
+    let start = *counter % pending.len();
+    *counter = counter.wrapping_add(1);
+    match policy {
+        LoadBalancing::RoundRobin => start,
+        LoadBalancing::LeastPending => (0..pending.len())
+            .map(|i| (start + i) % pending.len())
+            .min_by_key(|&i| pending[i])
+            .unwrap_or(start),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_robin() {
+        let mut counter = 0;
+        let picks: Vec<usize> = (0..5).map(|_| pick(LoadBalancing::RoundRobin, &mut counter, &[4, 0, 0])).collect();
+        assert_eq!(picks, vec![0, 1, 2, 0, 1]);
+    }
+
+    #[test]
+    fn test_least_pending() {
+        let mut counter = 0;
+        assert_eq!(pick(LoadBalancing::LeastPending, &mut counter, &[2, 0, 1]), 1);
+        // Ties rotate instead of piling onto the first instance
+        let picks: Vec<usize> = (0..3).map(|_| pick(LoadBalancing::LeastPending, &mut counter, &[0, 0, 0])).collect();
+        assert_eq!(picks, vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn test_single_instance() {
+        let mut counter = 7;
+        assert_eq!(pick(LoadBalancing::LeastPending, &mut counter, &[3]), 0);
+        assert_eq!(pick(LoadBalancing::RoundRobin, &mut counter, &[]), 0);
+    }
+}
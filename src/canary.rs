@@ -0,0 +1,156 @@
+//! Canary routing
+//! Serves a share of real requests from a second backend version per root, and rolls the
+//! canary back to 0% as soon as its error rate over recent requests crosses a threshold
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+/// `canary` section of the config file
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(default)]
+pub struct CanaryConfig {
+    /// auggie entry of the canary backend (canary routing is off without it)
+    pub auggie_entry: Option<PathBuf>,
+    /// Node.js for the canary backend (defaults to the primary's)
+    pub node: Option<PathBuf>,
+    /// Share of requests served by the canary, 0-100
+    pub percent: u32,
+    /// Error rate (0-1) over the last `window` canary requests that triggers the rollback
+    pub max_error_rate: f64,
+    /// Canary requests considered for the error rate
+    pub window: usize,
+    /// Requests needed in the window before the error rate counts
+    pub min_requests: usize,
+}
+
+impl Default for CanaryConfig {
+    fn default() -> Self {
+        Self {
+            auggie_entry: None,
+            node: None,
+            percent: 5,
+            max_error_rate: 0.2,
+            window: 50,
+            min_requests: 10,
+        }
+    }
+}
+
+/// Decides which requests go to the canary and tracks how it does
+pub struct CanaryGate {
+    percent: u32,
+    max_error_rate: f64,
+    window: usize,
+    min_requests: usize,
+    /// Accumulated share; a request goes to the canary each time it passes 100
+    credit: u32,
+    /// Outcomes of recent canary requests (true = failed)
+    outcomes: VecDeque<bool>,
+    rolled_back: bool,
+}
+
+impl CanaryGate {
+    pub fn new(config: &CanaryConfig) -> Self {
+        Self {
+            percent: config.percent.min(100),
+            max_error_rate: config.max_error_rate,
+            window: config.window.max(1),
+            min_requests: config.min_requests.max(1),
+            credit: 0,
+            outcomes: VecDeque::new(),
+            rolled_back: false,
+        }
+    }
+
+    /// Whether the canary still takes traffic
+    pub fn is_active(&self) -> bool {
+        self.percent > 0 && !self.rolled_back
+    }
+
+    /// Whether the next request goes to the canary; spreads canary requests evenly
+    pub fn sample(&mut self) -> bool {
+        if !self.is_active() {
+            return false;
+        }
+        self.credit += self.percent;
+        if self.credit >= 100 {
+            self.credit -= 100;
+            return true;
+        }
+        false
+    }
+
+    /// Record a canary request's outcome; returns true when it triggers the rollback
+    pub fn record(&mut self, failed: bool) -> bool {
+        if self.rolled_back {
+            return false;
+        }
+        self.outcomes.push_back(failed);
+        if self.outcomes.len() > self.window {
+            self.outcomes.pop_front();
+        }
+        if self.outcomes.len() >= self.min_requests && self.error_rate() > self.max_error_rate {
+            self.rolled_back = true;
+            return true;
+        }
+        false
+    }
+
+    /// Share of failed requests in the window
+    pub fn error_rate(&self) -> f64 {
+        if self.outcomes.is_empty() {
+            return 0.0;
+        }
+        self.outcomes.iter().filter(|&&failed| failed).count() as f64 / self.outcomes.len() as f64
+    }
+
+    /// Canary share and health for `proxy_status`
+    pub fn status(&self) -> serde_json::Value {
+        serde_json::json!({
+            "percent": if self.rolled_back { 0 } else { self.percent },
+            "rolled_back": self.rolled_back,
+            "error_rate": self.error_rate(),
+            "requests": self.outcomes.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampling() {
+        let mut gate = CanaryGate::new(&CanaryConfig { percent: 20, ..CanaryConfig::default() });
+        assert_eq!((0..10).filter(|_| gate.sample()).count(), 2);
+        let mut off = CanaryGate::new(&CanaryConfig { percent: 0, ..CanaryConfig::default() });
+        assert!(!off.sample());
+    }
+
+    #[test]
+    fn test_rollback() {
+        let config = CanaryConfig { percent: 100, max_error_rate: 0.25, window: 8, min_requests: 4, ..CanaryConfig::default() };
+        let mut gate = CanaryGate::new(&config);
+        // Too few requests to judge
+        assert!(!gate.record(true));
+        assert!(!gate.record(true));
+        assert!(!gate.record(false));
+        assert!(gate.record(false));
+        assert!(!gate.sample() && !gate.is_active());
+        assert!(!gate.record(true));
+        assert_eq!(gate.status()["percent"], 0);
+    }
+
+    #[test]
+    fn test_healthy_canary_stays() {
+        let config = CanaryConfig { percent: 100, max_error_rate: 0.25, window: 4, min_requests: 4, ..CanaryConfig::default() };
+        let mut gate = CanaryGate::new(&config);
+        for failed in [true, false, false, false, false, true, false, false] {
+            assert!(!gate.record(failed));
+        }
+        assert!(gate.is_active());
+        assert_eq!(gate.error_rate(), 0.25);
+    }
+}
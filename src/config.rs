@@ -1,52 +1,393 @@
-use clap::Parser;
+use crate::audit::AuditConfig;
+use crate::backend::{WarmupRequest, DEFAULT_AUTO_MODE_MAX_FILES};
+use crate::balance::LoadBalancing;
+use crate::canary::CanaryConfig;
+use crate::config_file;
+use crate::confirm::ConfirmConfig;
+use crate::container::ContainerConfig;
+use crate::error::ProxyError;
+use crate::eviction::EvictionPolicy;
+use crate::git_filter::{FilterSource, DEFAULT_GIT_CACHE_MAX_ENTRIES, DEFAULT_GIT_CACHE_TTL_SECS};
+use crate::lifecycle::PreInitPolicy;
+use crate::limit::OversizeMode;
+use crate::loadtest::LoadtestArgs;
+use crate::mock::MockBackendArgs;
+use crate::paths;
+use crate::policy::PolicyConfig;
+use crate::queue::OverflowPolicy;
+use crate::redact::RedactionConfig;
+use crate::repl::ReplArgs;
+use crate::retry::RetryConfig;
+use crate::routing::{RoutingRule, DEFAULT_WORKSPACE_MARKERS};
+use crate::runtime::Runtime;
+use crate::servers::ServerConfig;
+use crate::shadow::ShadowConfig;
+use crate::ssh::SshConfig;
+use crate::tools::{ToolNamespacing, ToolOverride};
+use crate::wsl::WslConfig;
+use clap::{Parser, Subcommand};
+use globset::Glob;
+use schemars::JsonSchema;
 use serde::Deserialize;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use tracing::{info, warn};
 
 /// JSON config file structure
-#[derive(Deserialize, Default, Debug)]
+#[derive(Deserialize, JsonSchema, Default, Debug)]
 struct FileConfig {
     node: Option<PathBuf>,
+    runtime: Option<Runtime>,
     auggie_entry: Option<PathBuf>,
+    min_node_version: Option<String>,
+    min_auggie_version: Option<String>,
     mode: Option<String>,
     max_backends: Option<usize>,
+    max_backends_burst: Option<usize>,
+    eviction_policy: Option<EvictionPolicy>,
+    instances_per_root: Option<usize>,
+    load_balancing: Option<LoadBalancing>,
+    pinned_roots: Option<Vec<PathBuf>>,
+    max_backend_lifetime_seconds: Option<u64>,
+    max_requests_per_backend: Option<u64>,
     idle_ttl_seconds: Option<u64>,
+    exit_after_idle: Option<u64>,
     log_level: Option<String>,
+    inject_trace_id: Option<bool>,
+    strict_config: Option<bool>,
+    slow_request_ms: Option<u64>,
     default_root: Option<PathBuf>,
     debounce_ms: Option<u64>,
+    max_batch_size: Option<usize>,
+    storm_threshold: Option<u64>,
+    replay_batches: Option<usize>,
+    spawn_on_change: Option<bool>,
+    backend_log_dir: Option<PathBuf>,
+    stderr_notifications: Option<bool>,
+    ping_backend: Option<bool>,
+    restart_crashed_backends: Option<bool>,
+    max_consecutive_timeouts: Option<u32>,
+    shutdown_grace_seconds: Option<u64>,
+    shutdown_term_seconds: Option<u64>,
+    shutdown_deadline_seconds: Option<u64>,
+    backend_log_max_bytes: Option<u64>,
+    backend_log_files: Option<usize>,
+    max_debounce_ms: Option<u64>,
     cpu_affinity: Option<u64>,
     low_priority: Option<bool>,
     git_filter: Option<bool>,
+    filter_source: Option<FilterSource>,
+    git_cache_ttl_secs: Option<u64>,
+    git_cache_max_entries: Option<usize>,
+    /// Globs (relative to the root) forced through the git filter, e.g. generated docs
+    filter_include: Option<Vec<String>>,
+    /// Globs (relative to the root) dropped from change notifications even when tracked
+    filter_exclude: Option<Vec<String>>,
+    filter_max_file_size: Option<u64>,
+    filter_binary: Option<bool>,
+    /// Extra extensions treated as binary by `filter_binary`
+    binary_extensions: Option<Vec<String>>,
+    watch: Option<bool>,
+    max_inflight_global: Option<usize>,
+    max_queue_depth: Option<usize>,
+    queue_overflow: Option<OverflowPolicy>,
+    pre_init: Option<PreInitPolicy>,
+    strict_lifecycle: Option<bool>,
+    preserve_order: Option<bool>,
+    /// Per-method request timeouts in seconds (method glob pattern -> seconds)
+    timeouts: Option<HashMap<String, u64>>,
+    /// Ordered routing rules evaluated before prefix matching
+    routing: Option<Vec<RoutingRule>>,
+    /// Auggie mode per root, the first rule whose glob matches the root wins
+    mode_rules: Option<Vec<ModeRule>>,
+    auto_mode_max_files: Option<usize>,
+    /// Requests sent to every backend right after its handshake, to start indexing early
+    warmup_requests: Option<Vec<WarmupRequest>>,
+    session_id_field: Option<String>,
+    max_sessions: Option<usize>,
+    persist_session: Option<bool>,
+    workspace_markers: Option<Vec<String>>,
+    canonicalize_paths: Option<bool>,
+    wsl: Option<WslConfig>,
+    /// Per-root overrides keyed by root path
+    root_overrides: Option<HashMap<PathBuf, RootConfig>>,
+    redaction: Option<RedactionConfig>,
+    policy: Option<PolicyConfig>,
+    audit: Option<AuditConfig>,
+    /// Per-tool overrides keyed by the backend's tool name: hide, rename, redescribe
+    tools: Option<HashMap<String, ToolOverride>>,
+    /// Extra stdio MCP servers keyed by the name their tools and prompts are listed under
+    servers: Option<BTreeMap<String, ServerConfig>>,
+    confirm: Option<ConfirmConfig>,
+    retry: Option<RetryConfig>,
+    shadow: Option<ShadowConfig>,
+    canary: Option<CanaryConfig>,
+    /// WASM plugins run as middleware layers, in order (`wasm-plugins` feature)
+    plugins: Option<Vec<PathBuf>>,
+    scripts: Option<ScriptsConfig>,
+    tool_namespacing: Option<ToolNamespacing>,
+    virtual_tools: Option<bool>,
+    diagnostic_resources: Option<bool>,
+    max_response_bytes: Option<usize>,
+    oversize_mode: Option<OversizeMode>,
+    spill_dir: Option<PathBuf>,
+    server_name: Option<String>,
+    server_version: Option<String>,
+    instructions: Option<String>,
+    listen: Option<String>,
+    log_file: Option<PathBuf>,
+    pid_file: Option<PathBuf>,
+}
+
+impl FileConfig {
+    /// Expand `~` and environment variables in the local paths (`paths::expand`)
+    fn expand_paths(&mut self) {
+        let expand = |path: &mut PathBuf| *path = paths::expand(path);
+        let expand_all = |paths: &mut Vec<PathBuf>| paths.iter_mut().for_each(expand);
+
+        [
+            &mut self.node,
+            &mut self.auggie_entry,
+            &mut self.default_root,
+            &mut self.backend_log_dir,
+            &mut self.spill_dir,
+            &mut self.log_file,
+            &mut self.pid_file,
+        ]
+        .into_iter()
+            .flatten()
+            .for_each(expand);
+        self.pinned_roots.iter_mut().for_each(expand_all);
+        self.plugins.iter_mut().for_each(expand_all);
+        if let Some(ref mut scripts) = self.scripts {
+            [&mut scripts.pre_route, &mut scripts.pre_forward, &mut scripts.post_response]
+                .into_iter()
+                .flatten()
+                .for_each(expand);
+        }
+        if let Some(path) = self.audit.as_mut().and_then(|audit| audit.path.as_mut()) {
+            expand(path);
+        }
+        if let Some(policy) = self.policy.as_mut() {
+            expand_all(&mut policy.allowed_paths);
+        }
+        if let Some(path) = self.wsl.as_mut().and_then(|wsl| wsl.wsl_exe.as_mut()) {
+            expand(path);
+        }
+        for rule in self.mode_rules.iter_mut().flatten() {
+            rule.root = paths::expand(Path::new(&rule.root)).to_string_lossy().into_owned();
+        }
+        for server in self.servers.iter_mut().flat_map(|servers| servers.values_mut()) {
+            server.command = paths::expand(Path::new(&server.command)).to_string_lossy().into_owned();
+            server.cwd.iter_mut().for_each(expand);
+        }
+        if let Some(shadow) = self.shadow.as_mut() {
+            [&mut shadow.node, &mut shadow.auggie_entry].into_iter().flatten().for_each(expand);
+        }
+        if let Some(canary) = self.canary.as_mut() {
+            [&mut canary.node, &mut canary.auggie_entry].into_iter().flatten().for_each(expand);
+        }
+        if let Some(overrides) = self.root_overrides.take() {
+            let overrides = overrides
+                .into_iter()
+                .map(|(mut root, mut rc)| {
+                    expand(&mut root);
+                    rc.env_file.iter_mut().for_each(expand);
+                    rc.shards.iter_mut().for_each(expand);
+                    if let Some(path) = rc.ssh.as_mut().and_then(|ssh| ssh.ssh_exe.as_mut()) {
+                        expand(path);
+                    }
+                    (root, rc)
+                })
+                .collect();
+            self.root_overrides = Some(overrides);
+        }
+    }
+}
+
+/// A config file for messages, with the files it extends
+fn files_description(files: &[PathBuf]) -> String {
+    match files.len() {
+        1 => files[0].display().to_string(),
+        _ => format!("{} (with the files it extends)", files[0].display()),
+    }
+}
+
+/// Rhai hook scripts (`scripts` in the config file, `scripting` feature)
+#[derive(Deserialize, JsonSchema, Default, Debug, Clone)]
+#[serde(default)]
+pub struct ScriptsConfig {
+    /// Runs before the backend is chosen; may rewrite params, change the root or answer directly
+    pub pre_route: Option<PathBuf>,
+    /// Runs right before the request is forwarded; may rewrite params or answer directly
+    pub pre_forward: Option<PathBuf>,
+    /// Runs on the backend's response; may rewrite the result or error
+    pub post_response: Option<PathBuf>,
+}
+
+/// Per-root settings overriding the global ones (`root_overrides` in the config file)
+#[derive(Deserialize, JsonSchema, Default, Debug, Clone)]
+#[serde(default)]
+pub struct RootConfig {
+    /// Run this root's backend on a remote host over SSH
+    pub ssh: Option<SshConfig>,
+    /// Attach to an already-running MCP server instead of spawning one
+    /// (`host:port`, `tcp://host:port` or `unix:/path/to/socket`)
+    pub connect: Option<String>,
+    /// Run this root's backend inside a Docker/Podman container
+    pub container: Option<ContainerConfig>,
+    /// Extra environment variables for this root's backend (override `env_file`)
+    pub env: HashMap<String, String>,
+    /// Dotenv file loaded into this root's backend environment (relative to the root)
+    pub env_file: Option<PathBuf>,
+    /// Debounce window for this root's change events (ms)
+    pub debounce_ms: Option<u64>,
+    /// Enable or disable the git filter for this root
+    pub git_filter: Option<bool>,
+    /// Refresh interval of this root's git filter cache when its ignore files can't be watched
+    pub git_cache_ttl_secs: Option<u64>,
+    /// Prefix for this root's tool names when tools are namespaced (defaults to the directory name)
+    pub namespace: Option<String>,
+    /// Idle timeout of this root's backend in seconds
+    pub idle_ttl_seconds: Option<u64>,
+    /// Backend processes for this root (`instances_per_root`)
+    pub instances: Option<usize>,
+    /// Subdirectories (relative to the root) split off into backends of their own; requests
+    /// and changes under one go to its shard, everything else to the root's backend
+    pub shards: Vec<PathBuf>,
+}
+
+/// Auggie mode for roots matching a glob (`mode_rules` in the config file)
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+pub struct ModeRule {
+    /// Glob matched against the root path (e.g. `/tmp/**` or `~/scratch/*`)
+    pub root: String,
+    /// Mode for matching roots (`default`, `minimal`, or `auto`)
+    pub mode: String,
+}
+
+impl RootConfig {
+    /// A path as seen by this root's backend, when it runs in another namespace
+    /// (remote host or container)
+    pub fn backend_path(&self, root: &Path, path: &Path) -> Option<String> {
+        if let Some(ref ssh) = self.ssh {
+            return Some(ssh.remote_path(root, path));
+        }
+        self.container.as_ref().map(|c| c.container_path(root, path))
+    }
 }
 
 /// Rust MCP Proxy for Augment Context Engine
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 pub struct Config {
-    /// Path to node.exe
+    /// Path to node.exe (or to the bun/deno executable with `--runtime`)
     #[arg(long, env = "MCP_PROXY_NODE_PATH")]
     pub node: Option<PathBuf>,
 
+    /// JavaScript runtime for the auggie entry: node, bun or deno
+    #[arg(long, value_enum, default_value = "node")]
+    pub runtime: Runtime,
+
     /// Path to auggie entry.js
     #[arg(long, env = "MCP_PROXY_AUGGIE_ENTRY")]
     pub auggie_entry: Option<PathBuf>,
 
-    /// Auggie mode (default, minimal, etc.)
+    /// Run `mcp-proxy mock-backend` (this executable) as every backend instead of auggie, for
+    /// integration tests and benchmarks
+    #[arg(long, default_value_t = false)]
+    pub mock_backend: bool,
+
+    /// Refuse to start when the config file has keys nothing reads (misspelled settings)
+    /// instead of warning about them
+    #[arg(long, default_value_t = false)]
+    pub strict_config: bool,
+
+    /// Route requests without starting backends, answering each with the root, the reason it
+    /// was chosen and the command line its backend would run
+    #[arg(long, default_value_t = false)]
+    pub dry_run: bool,
+
+    /// Refuse to spawn backends with a Node.js older than this (e.g. 20.11.0)
+    #[arg(long)]
+    pub min_node_version: Option<String>,
+
+    /// Refuse to spawn backends with an auggie package older than this
+    #[arg(long)]
+    pub min_auggie_version: Option<String>,
+
+    /// Auggie mode (default, minimal, etc.; `auto` picks minimal for repositories with at most
+    /// `--auto-mode-max-files` files)
     #[arg(long, default_value = "default")]
     pub mode: String,
 
+    /// Auggie mode per root pattern, before `--mode` (config file only)
+    #[arg(skip)]
+    pub mode_rules: Vec<ModeRule>,
+
+    /// Largest repository (files passing the git filter) that `auto` mode runs as minimal
+    #[arg(long, default_value_t = DEFAULT_AUTO_MODE_MAX_FILES)]
+    pub auto_mode_max_files: usize,
+
+    /// Requests sent to every backend right after its handshake (config file only)
+    #[arg(skip)]
+    pub warmup_requests: Vec<WarmupRequest>,
+
     /// Maximum number of backend instances
     #[arg(long, default_value = "3")]
     pub max_backends: usize,
 
+    /// Extra backends allowed while every backend has requests in flight (0 = refuse new roots)
+    #[arg(long, default_value = "1")]
+    pub max_backends_burst: usize,
+
+    /// Backend evicted at `--max-backends`: the least recently used (lru), or the cheapest to bring back for its idle time (cost)
+    #[arg(long, value_enum, default_value = "lru")]
+    pub eviction_policy: EvictionPolicy,
+
+    /// Backend processes per root, sharing the root's slot in `--max-backends`
+    #[arg(long, default_value = "1")]
+    pub instances_per_root: usize,
+
+    /// How requests are spread over a root's instances: round-robin or least-pending
+    #[arg(long, value_enum, default_value = "round-robin")]
+    pub load_balancing: LoadBalancing,
+
+    /// Restart a backend once it has run this long, the next time it is idle (0 = never)
+    #[arg(long, default_value = "0")]
+    pub max_backend_lifetime_seconds: u64,
+
+    /// Restart a backend once it has served this many requests, the next time it is idle (0 = never)
+    #[arg(long, default_value = "0")]
+    pub max_requests_per_backend: u64,
+
+    /// Roots whose backends are never evicted or shut down for idling (comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    pub pinned_roots: Vec<PathBuf>,
+
     /// Idle timeout in seconds before backend is shut down
     #[arg(long, default_value = "600")]
     pub idle_ttl_seconds: u64,
 
+    /// Shut down (backends included) after this many minutes without a message from the
+    /// client (stdio mode; 0 to never)
+    #[arg(long, default_value_t = 0)]
+    pub exit_after_idle: u64,
+
     /// Log level (trace, debug, info, warn, error)
     #[arg(long, default_value = "info", env = "MCP_PROXY_LOG")]
     pub log_level: String,
 
+    /// Pass each request's trace id to the backend as `params._meta.traceId`, so backend logs
+    /// can be matched with the proxy's
+    #[arg(long, default_value_t = false)]
+    pub inject_trace_id: bool,
+
+    /// Log a warning with a timing breakdown for requests routed to a backend that take longer
+    /// than this many milliseconds (0 to disable)
+    #[arg(long, default_value_t = 0)]
+    pub slow_request_ms: u64,
+
     /// Spawn timeout in seconds
     #[arg(long, default_value = "30")]
     pub spawn_timeout_seconds: u64,
@@ -55,9 +396,22 @@ pub struct Config {
     #[arg(long, default_value = "120")]
     pub request_timeout_seconds: u64,
 
+    /// Per-method request timeouts in seconds (method glob pattern -> seconds, config file only)
+    #[arg(skip)]
+    pub timeouts: HashMap<String, u64>,
+
+    /// Maximum number of requests in flight across all backends (0 = unlimited)
     #[arg(long, default_value = "0")]
     pub max_inflight_global: usize,
 
+    /// Maximum number of requests waiting for an inflight slot
+    #[arg(long, default_value = "64")]
+    pub max_queue_depth: usize,
+
+    /// What to do when the wait queue is full (reject-newest, reject-oldest, block)
+    #[arg(long, value_enum, default_value = "reject-newest")]
+    pub queue_overflow: OverflowPolicy,
+
     /// Default workspace root (used when no root is provided)
     #[arg(long, env = "MCP_PROXY_DEFAULT_ROOT")]
     pub default_root: Option<PathBuf>,
@@ -66,10 +420,84 @@ pub struct Config {
     #[arg(long, default_value_t = false)]
     pub prewarm_default_root: bool,
 
+    /// Requests arriving before initialize has been handled: hold them until it has (at most
+    /// 10s) or answer them with an error (queue, reject)
+    #[arg(long, value_enum, default_value = "queue")]
+    pub pre_init: PreInitPolicy,
+
+    /// Reject messages out of lifecycle order (before initialize, before
+    /// notifications/initialized, initialize twice) with Invalid Request, to validate clients
+    #[arg(long, default_value_t = false)]
+    pub strict_lifecycle: bool,
+
+    /// Send responses in the order the requests arrived, holding ones that finish early
+    #[arg(long, default_value_t = false)]
+    pub preserve_order: bool,
+
+    /// Answer `ping` only after the relevant root's running backend answers it too
+    #[arg(long, default_value_t = false)]
+    pub ping_backend: bool,
+
+    /// Restart a backend as soon as its process exits unexpectedly (instead of on the next request)
+    #[arg(long, default_value_t = false)]
+    pub restart_crashed_backends: bool,
+
+    /// Restart a backend after this many requests to it time out in a row (0 to disable)
+    #[arg(long, default_value = "3")]
+    pub max_consecutive_timeouts: u32,
+
+    /// Seconds a backend gets to exit after MCP `shutdown`/`exit` before it is sent SIGTERM
+    #[arg(long, default_value = "5")]
+    pub shutdown_grace_seconds: u64,
+
+    /// Seconds a backend gets to exit after SIGTERM before it is killed
+    #[arg(long, default_value = "3")]
+    pub shutdown_term_seconds: u64,
+
+    /// Seconds all backends together get to stop when the proxy exits before the rest are killed
+    #[arg(long, default_value = "10")]
+    pub shutdown_deadline_seconds: u64,
+
     /// Event debounce window in milliseconds (0 to disable)
     #[arg(long, default_value = "500")]
     pub debounce_ms: u64,
 
+    /// Flush a root's pending changes early once this many paths accumulate (0 = unlimited)
+    #[arg(long, default_value = "1000")]
+    pub max_batch_size: usize,
+
+    /// Change events per second on one root that widen its debounce window (0 = fixed window)
+    #[arg(long, default_value = "1000")]
+    pub storm_threshold: u64,
+
+    /// Upper bound for a debounce window widened during an event storm (ms)
+    #[arg(long, default_value = "10000")]
+    pub max_debounce_ms: u64,
+
+    /// Recent change batches per root replayed to a backend after it restarts (0 to disable)
+    #[arg(long, default_value = "16")]
+    pub replay_batches: usize,
+
+    /// Spawn a root's backend when changes for it are flushed (otherwise they are held until it spawns)
+    #[arg(long, default_value_t = false)]
+    pub spawn_on_change: bool,
+
+    /// Capture each backend's stderr into rotating files in this directory (inherited when unset)
+    #[arg(long)]
+    pub backend_log_dir: Option<PathBuf>,
+
+    /// Relay backend stderr lines to the client as MCP logging notifications
+    #[arg(long, default_value_t = false)]
+    pub stderr_notifications: bool,
+
+    /// Rotate a backend's stderr log once it reaches this size
+    #[arg(long, default_value = "10485760")]
+    pub backend_log_max_bytes: u64,
+
+    /// Rotated stderr logs kept per backend
+    #[arg(long, default_value = "3")]
+    pub backend_log_files: usize,
+
     /// CPU affinity mask for backend processes (e.g., 0x03 = cores 0,1). 0 means no affinity.
     #[arg(long, default_value = "0")]
     pub cpu_affinity: u64,
@@ -82,26 +510,279 @@ pub struct Config {
     #[arg(long, default_value_t = true)]
     pub git_filter: bool,
 
+    /// How the git filter lists files: `ignore` (walk honoring .gitignore, no git needed) or `git` (git ls-files)
+    #[arg(long, value_enum, default_value_t = FilterSource::Ignore)]
+    pub filter_source: FilterSource,
+
+    /// Seconds before a root's git filter list is refreshed when its ignore files can't be watched
+    #[arg(long, default_value_t = DEFAULT_GIT_CACHE_TTL_SECS)]
+    pub git_cache_ttl_secs: u64,
+
+    /// Maximum number of roots with a cached git filter list
+    #[arg(long, default_value_t = DEFAULT_GIT_CACHE_MAX_ENTRIES)]
+    pub git_cache_max_entries: usize,
+
+    /// Globs (relative to the root) forced through the git filter, e.g. generated docs (config file only)
+    #[arg(skip)]
+    pub filter_include: Vec<String>,
+
+    /// Globs (relative to the root) dropped from change notifications even when tracked (config file only)
+    #[arg(skip)]
+    pub filter_exclude: Vec<String>,
+
+    /// Drop change events for files larger than this many bytes (0 = no limit)
+    #[arg(long, default_value = "0")]
+    pub filter_max_file_size: u64,
+
+    /// Drop change events for binary files (images, archives, executables, ...)
+    #[arg(long, default_value_t = false)]
+    pub filter_binary: bool,
+
+    /// Extra extensions treated as binary by `filter_binary` (config file only)
+    #[arg(skip)]
+    pub binary_extensions: Vec<String>,
+
+    /// Watch roots with an active backend for file changes (for clients that send no change notifications)
+    #[arg(long, default_value_t = false)]
+    pub watch: bool,
+
+    /// Ordered routing rules evaluated before prefix matching (config file only)
+    #[arg(skip)]
+    pub routing: Vec<RoutingRule>,
+
+    /// Dotted path in request params identifying the agent session for sticky routing
+    #[arg(long, default_value = "_meta.sessionId")]
+    pub session_id_field: String,
+
+    /// Maximum number of remembered session-to-root bindings
+    #[arg(long, default_value = "256")]
+    pub max_sessions: usize,
+
+    /// Save known roots, session bindings and recent change batches on exit and restore them on
+    /// the next start (stdio mode), so a restart doesn't lose routing context
+    #[arg(long, default_value_t = false)]
+    pub persist_session: bool,
+
+    /// Project markers used to auto-detect a workspace root, in priority order
+    #[arg(long, value_delimiter = ',', default_value = DEFAULT_WORKSPACE_MARKERS)]
+    pub workspace_markers: Vec<String>,
+
+    /// Resolve symlinks in roots and request paths before routing (`--canonicalize-paths false`
+    /// to disable)
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    pub canonicalize_paths: bool,
+
+    /// WSL path translation and in-distro backend spawning (config file only)
+    #[arg(skip)]
+    pub wsl: WslConfig,
+
+    /// Per-root overrides keyed by root path (config file only)
+    #[arg(skip)]
+    pub root_overrides: HashMap<PathBuf, RootConfig>,
+
+    /// Secrets redaction for logged messages and error data (config file only)
+    #[arg(skip)]
+    pub redaction: RedactionConfig,
+
+    /// Method and tool allow/deny lists (config file only)
+    #[arg(skip)]
+    pub policy: PolicyConfig,
+
+    /// Per-tool overrides keyed by the backend's tool name: hide, rename, redescribe (config file only)
+    #[arg(skip)]
+    pub tools: HashMap<String, ToolOverride>,
+
+    /// Extra stdio MCP servers run alongside auggie, keyed by the name their tools and prompts
+    /// are listed under (config file only)
+    #[arg(skip)]
+    pub servers: BTreeMap<String, ServerConfig>,
+
+    /// Audit log of tool invocations (config file only)
+    #[arg(skip)]
+    pub audit: AuditConfig,
+
+    /// Human confirmation gate for dangerous tools (config file only)
+    #[arg(skip)]
+    pub confirm: ConfirmConfig,
+
+    /// Retry count, retryable failures and backoff for backend requests (config file only)
+    #[arg(skip)]
+    pub retry: RetryConfig,
+
+    /// Second backend per root that a share of requests is mirrored to for comparison (config file only)
+    #[arg(skip)]
+    pub shadow: ShadowConfig,
+
+    /// Second backend version per root serving a share of real requests (config file only)
+    #[arg(skip)]
+    pub canary: CanaryConfig,
+
+    /// WASM plugins run as middleware layers, in order (config file only, `wasm-plugins` feature)
+    #[arg(skip)]
+    pub plugins: Vec<PathBuf>,
+
+    /// Rhai hook scripts (config file only, `scripting` feature)
+    #[arg(skip)]
+    pub scripts: ScriptsConfig,
+
+    /// Merge tools from all running backends in tools/list, prefixing names with the root's namespace
+    #[arg(long, value_enum, default_value = "off")]
+    pub tool_namespacing: ToolNamespacing,
+
+    /// Truncate results larger than this many bytes, keeping the head and tail of each text block (0 = unlimited)
+    #[arg(long, default_value = "0")]
+    pub max_response_bytes: usize,
+
+    /// What to do with results above `--max-response-bytes`: truncate, or spill to a file served as a resource
+    #[arg(long, value_enum, default_value = "truncate")]
+    pub oversize_mode: OversizeMode,
+
+    /// Directory for spilled results (defaults to `mcp-proxy-spill` in the temp directory)
+    #[arg(long)]
+    pub spill_dir: Option<PathBuf>,
+
+    /// List the proxy's own tools (proxy_status, proxy_restart_backend, proxy_switch_root) in tools/list
+    #[arg(long, default_value_t = false)]
+    pub virtual_tools: bool,
+
+    /// Serve the proxy's recent logs, metrics and backends as resources (`proxy://logs/recent`,
+    /// `proxy://metrics`, `proxy://backends`)
+    #[arg(long, default_value_t = false)]
+    pub diagnostic_resources: bool,
+
+    /// `serverInfo.name` reported to the client
+    #[arg(long, default_value = "mcp-proxy")]
+    pub server_name: String,
+
+    /// `serverInfo.version` reported to the client (defaults to the proxy version)
+    #[arg(long)]
+    pub server_version: Option<String>,
+
+    /// `instructions` returned from initialize, telling the model how to use the tools
+    #[arg(long)]
+    pub instructions: Option<String>,
+
     /// Enable single instance lock (prevents multiple proxy instances)
     #[arg(long, default_value_t = false)]
     pub single_instance: bool,
+
+    /// Name of this instance: scopes the single-instance lock, the default admin socket and
+    /// the daemon's default files, so proxies for separate IDE profiles can run side by side
+    #[arg(long, env = "MCP_PROXY_INSTANCE_NAME")]
+    pub instance_name: Option<String>,
+
+    /// Scope the single-instance lock (and the other per-instance files) to the default root
+    /// (the current directory without one), allowing one proxy per workspace
+    #[arg(long, default_value_t = false)]
+    pub lock_per_root: bool,
+
+    /// Serve clients on a socket instead of stdio (`host:port` or `unix:/path/to/socket`);
+    /// each connection gets its own session and backends
+    #[arg(long, env = "MCP_PROXY_LISTEN")]
+    pub listen: Option<String>,
+
+    /// Write the proxy's own log to this file (rotated at 10 MiB, 5 kept) instead of stderr
+    #[arg(long)]
+    pub log_file: Option<PathBuf>,
+
+    /// Detach into the background (Unix, needs `--listen`); logs go to `--log-file`
+    /// (default `~/.mcp-proxy.log`) and the PID to `--pid-file` (default `~/.mcp-proxy.pid`)
+    #[arg(long, default_value_t = false)]
+    pub daemonize: bool,
+
+    /// Shut down when the process that started the proxy exits, even if stdin stays open
+    /// (stdio mode; `--exit-with-parent false` to disable)
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    pub exit_with_parent: bool,
+
+    /// Write the proxy's PID to this file while it runs (Unix)
+    #[arg(long)]
+    pub pid_file: Option<PathBuf>,
+
+    /// Control socket for `status`, `stop` and `reload` (`host:port` or `unix:/path`; default
+    /// `~/.mcp-proxy.admin.sock`, `127.0.0.1:7899` on Windows; `none` disables it); commands on
+    /// a TCP socket must carry the token from `.mcp-proxy.admin.token`
+    #[arg(long, env = "MCP_PROXY_ADMIN_SOCKET")]
+    pub admin_socket: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Subcommands (without one, the proxy serves a client on stdio, `--listen`, or a socket
+/// passed by systemd socket activation)
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Manage the Windows service running the proxy with `--listen`
+    Service {
+        #[command(subcommand)]
+        action: ServiceAction,
+    },
+    /// Write and enable a systemd user unit (and socket unit) running the proxy with the
+    /// options given before `systemd-install`
+    SystemdInstall,
+    /// Print the sessions, backends and metrics of the running proxy
+    Status,
+    /// Ask the running proxy to shut down gracefully
+    Stop,
+    /// Make the running proxy re-read its configuration
+    Reload,
+    /// Serve as a minimal scriptable MCP server on stdio (what `--mock-backend` runs)
+    MockBackend(MockBackendArgs),
+    /// Send synthetic load to a proxy and report throughput and latency percentiles
+    Loadtest(LoadtestArgs),
+    /// Print a JSON Schema of the config file, for editor completion and validation
+    ConfigSchema,
+    /// Show the config file in use, the files it extends and where each setting comes from
+    CheckConfig,
+    /// Type requests to a proxy interactively and see its responses pretty-printed
+    Repl(ReplArgs),
+}
+
+/// `service` actions; options given before `service install` are passed to the service
+#[derive(Subcommand, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceAction {
+    /// Register the service (started automatically at boot) and start it
+    Install,
+    /// Stop and remove the service
+    Uninstall,
+    /// Run as the service (invoked by the service control manager)
+    Run,
 }
 
 impl Config {
     /// Load config from file and merge with CLI args
     /// Priority: CLI args > env vars > config file > auto-detect
-    pub fn with_auto_detect(mut self) -> Self {
+    pub fn with_auto_detect(mut self) -> Result<Self, ProxyError> {
         // Try to load config file
         let file_config = Self::load_config_file();
         
         // Merge file config (lower priority than CLI/env)
-        if let Some(fc) = file_config {
+        if let Some((fc, unknown)) = file_config {
+            if let Some(message) = Self::unknown_settings_message(&unknown) {
+                if self.strict_config || fc.strict_config == Some(true) {
+                    return Err(ProxyError::ConfigError(format!("{} (strict_config)", message)));
+                }
+                warn!("{}; they are ignored", message);
+            }
+            if let Some(v) = fc.strict_config {
+                if !self.strict_config { self.strict_config = v; }
+            }
             if self.node.is_none() {
                 self.node = fc.node;
             }
+            if let Some(v) = fc.runtime {
+                if self.runtime == Runtime::default() { self.runtime = v; }
+            }
             if self.auggie_entry.is_none() {
                 self.auggie_entry = fc.auggie_entry;
             }
+            if self.min_node_version.is_none() {
+                self.min_node_version = fc.min_node_version;
+            }
+            if self.min_auggie_version.is_none() {
+                self.min_auggie_version = fc.min_auggie_version;
+            }
             if self.default_root.is_none() {
                 self.default_root = fc.default_root;
             }
@@ -110,18 +791,102 @@ impl Config {
                     self.mode = mode;
                 }
             }
+            if let Some(v) = fc.mode_rules {
+                self.mode_rules = v;
+            }
+            if let Some(v) = fc.warmup_requests {
+                self.warmup_requests = v;
+            }
+            if let Some(v) = fc.auto_mode_max_files {
+                if self.auto_mode_max_files == DEFAULT_AUTO_MODE_MAX_FILES { self.auto_mode_max_files = v; }
+            }
             if let Some(v) = fc.max_backends {
                 if self.max_backends == 3 { self.max_backends = v; }
             }
+            if let Some(v) = fc.max_backends_burst {
+                if self.max_backends_burst == 1 { self.max_backends_burst = v; }
+            }
+            if let Some(v) = fc.eviction_policy {
+                if self.eviction_policy == EvictionPolicy::default() { self.eviction_policy = v; }
+            }
+            if let Some(v) = fc.instances_per_root {
+                if self.instances_per_root == 1 { self.instances_per_root = v; }
+            }
+            if let Some(v) = fc.load_balancing {
+                if self.load_balancing == LoadBalancing::default() { self.load_balancing = v; }
+            }
+            if let Some(v) = fc.pinned_roots {
+                if self.pinned_roots.is_empty() { self.pinned_roots = v; }
+            }
+            if let Some(v) = fc.max_backend_lifetime_seconds {
+                if self.max_backend_lifetime_seconds == 0 { self.max_backend_lifetime_seconds = v; }
+            }
+            if let Some(v) = fc.max_requests_per_backend {
+                if self.max_requests_per_backend == 0 { self.max_requests_per_backend = v; }
+            }
             if let Some(v) = fc.idle_ttl_seconds {
                 if self.idle_ttl_seconds == 600 { self.idle_ttl_seconds = v; }
             }
+            if let Some(v) = fc.exit_after_idle {
+                if self.exit_after_idle == 0 { self.exit_after_idle = v; }
+            }
             if let Some(v) = fc.log_level {
                 if self.log_level == "info" { self.log_level = v; }
             }
+            if let Some(v) = fc.inject_trace_id {
+                if !self.inject_trace_id { self.inject_trace_id = v; }
+            }
+            if let Some(v) = fc.slow_request_ms {
+                if self.slow_request_ms == 0 { self.slow_request_ms = v; }
+            }
             if let Some(v) = fc.debounce_ms {
                 if self.debounce_ms == 500 { self.debounce_ms = v; }
             }
+            if let Some(v) = fc.max_batch_size {
+                if self.max_batch_size == 1000 { self.max_batch_size = v; }
+            }
+            if let Some(v) = fc.storm_threshold {
+                if self.storm_threshold == 1000 { self.storm_threshold = v; }
+            }
+            if let Some(v) = fc.max_debounce_ms {
+                if self.max_debounce_ms == 10000 { self.max_debounce_ms = v; }
+            }
+            if let Some(v) = fc.replay_batches {
+                if self.replay_batches == 16 { self.replay_batches = v; }
+            }
+            if let Some(v) = fc.spawn_on_change {
+                if !self.spawn_on_change { self.spawn_on_change = v; }
+            }
+            if let Some(v) = fc.backend_log_dir {
+                if self.backend_log_dir.is_none() { self.backend_log_dir = Some(v); }
+            }
+            if let Some(v) = fc.stderr_notifications {
+                if !self.stderr_notifications { self.stderr_notifications = v; }
+            }
+            if let Some(v) = fc.ping_backend {
+                if !self.ping_backend { self.ping_backend = v; }
+            }
+            if let Some(v) = fc.restart_crashed_backends {
+                if !self.restart_crashed_backends { self.restart_crashed_backends = v; }
+            }
+            if let Some(v) = fc.max_consecutive_timeouts {
+                if self.max_consecutive_timeouts == 3 { self.max_consecutive_timeouts = v; }
+            }
+            if let Some(v) = fc.shutdown_grace_seconds {
+                if self.shutdown_grace_seconds == 5 { self.shutdown_grace_seconds = v; }
+            }
+            if let Some(v) = fc.shutdown_term_seconds {
+                if self.shutdown_term_seconds == 3 { self.shutdown_term_seconds = v; }
+            }
+            if let Some(v) = fc.shutdown_deadline_seconds {
+                if self.shutdown_deadline_seconds == 10 { self.shutdown_deadline_seconds = v; }
+            }
+            if let Some(v) = fc.backend_log_max_bytes {
+                if self.backend_log_max_bytes == 10 * 1024 * 1024 { self.backend_log_max_bytes = v; }
+            }
+            if let Some(v) = fc.backend_log_files {
+                if self.backend_log_files == 3 { self.backend_log_files = v; }
+            }
             if let Some(v) = fc.cpu_affinity {
                 if self.cpu_affinity == 0 { self.cpu_affinity = v; }
             }
@@ -131,12 +896,144 @@ impl Config {
             if let Some(v) = fc.git_filter {
                 self.git_filter = v;
             }
+            if let Some(v) = fc.filter_source {
+                if self.filter_source == FilterSource::default() { self.filter_source = v; }
+            }
+            if let Some(v) = fc.git_cache_ttl_secs {
+                if self.git_cache_ttl_secs == DEFAULT_GIT_CACHE_TTL_SECS { self.git_cache_ttl_secs = v; }
+            }
+            if let Some(v) = fc.git_cache_max_entries {
+                if self.git_cache_max_entries == DEFAULT_GIT_CACHE_MAX_ENTRIES { self.git_cache_max_entries = v; }
+            }
+            if let Some(v) = fc.filter_include {
+                self.filter_include = v;
+            }
+            if let Some(v) = fc.filter_exclude {
+                self.filter_exclude = v;
+            }
+            if let Some(v) = fc.filter_max_file_size {
+                if self.filter_max_file_size == 0 { self.filter_max_file_size = v; }
+            }
+            if let Some(v) = fc.filter_binary {
+                if !self.filter_binary { self.filter_binary = v; }
+            }
+            if let Some(v) = fc.binary_extensions {
+                self.binary_extensions = v;
+            }
+            if let Some(v) = fc.watch {
+                if !self.watch { self.watch = v; }
+            }
+            if let Some(v) = fc.max_inflight_global {
+                if self.max_inflight_global == 0 { self.max_inflight_global = v; }
+            }
+            if let Some(v) = fc.max_queue_depth {
+                if self.max_queue_depth == 64 { self.max_queue_depth = v; }
+            }
+            if let Some(v) = fc.queue_overflow {
+                if self.queue_overflow == OverflowPolicy::default() { self.queue_overflow = v; }
+            }
+            if let Some(v) = fc.pre_init {
+                if self.pre_init == PreInitPolicy::default() { self.pre_init = v; }
+            }
+            if let Some(v) = fc.strict_lifecycle {
+                if !self.strict_lifecycle { self.strict_lifecycle = v; }
+            }
+            if let Some(v) = fc.preserve_order {
+                if !self.preserve_order { self.preserve_order = v; }
+            }
+            if let Some(v) = fc.timeouts {
+                self.timeouts = v;
+            }
+            if let Some(v) = fc.routing {
+                self.routing = v;
+            }
+            if let Some(v) = fc.session_id_field {
+                if self.session_id_field == "_meta.sessionId" { self.session_id_field = v; }
+            }
+            if let Some(v) = fc.max_sessions {
+                if self.max_sessions == 256 { self.max_sessions = v; }
+            }
+            if let Some(v) = fc.persist_session {
+                if !self.persist_session { self.persist_session = v; }
+            }
+            if let Some(v) = fc.canonicalize_paths {
+                self.canonicalize_paths = v;
+            }
+            if let Some(v) = fc.wsl {
+                self.wsl = v;
+            }
+            if let Some(v) = fc.root_overrides {
+                self.root_overrides = v;
+            }
+            if let Some(v) = fc.redaction {
+                self.redaction = v;
+            }
+            if let Some(v) = fc.policy {
+                self.policy = v;
+            }
+            if let Some(v) = fc.audit {
+                self.audit = v;
+            }
+            if let Some(v) = fc.tools {
+                self.tools = v;
+            }
+            if let Some(v) = fc.servers {
+                self.servers = v;
+            }
+            if let Some(v) = fc.confirm {
+                self.confirm = v;
+            }
+            if let Some(v) = fc.retry {
+                self.retry = v;
+            }
+            if let Some(v) = fc.shadow {
+                self.shadow = v;
+            }
+            if let Some(v) = fc.canary {
+                self.canary = v;
+            }
+            if let Some(v) = fc.plugins {
+                self.plugins = v;
+            }
+            if let Some(v) = fc.scripts {
+                self.scripts = v;
+            }
+            if let Some(v) = fc.tool_namespacing {
+                if self.tool_namespacing == ToolNamespacing::default() { self.tool_namespacing = v; }
+            }
+            if let Some(v) = fc.max_response_bytes {
+                if self.max_response_bytes == 0 { self.max_response_bytes = v; }
+            }
+            if let Some(v) = fc.oversize_mode {
+                if self.oversize_mode == OversizeMode::default() { self.oversize_mode = v; }
+            }
+            if let Some(v) = fc.spill_dir {
+                if self.spill_dir.is_none() { self.spill_dir = Some(v); }
+            }
+            if let Some(v) = fc.virtual_tools {
+                if !self.virtual_tools { self.virtual_tools = v; }
+            }
+            if let Some(v) = fc.diagnostic_resources {
+                if !self.diagnostic_resources { self.diagnostic_resources = v; }
+            }
+            if let Some(v) = fc.server_name {
+                if self.server_name == "mcp-proxy" { self.server_name = v; }
+            }
+            if let Some(v) = fc.server_version {
+                if self.server_version.is_none() { self.server_version = Some(v); }
+            }
+            if let Some(v) = fc.instructions {
+                if self.instructions.is_none() { self.instructions = Some(v); }
+            }
+            if let Some(v) = fc.workspace_markers {
+                if self.workspace_markers.join(",") == DEFAULT_WORKSPACE_MARKERS { self.workspace_markers = v; }
+            }
         }
         
         // Validate configured paths exist, fallback to auto-detect if not
         if let Some(ref path) = self.node {
             if !path.exists() {
-                warn!("Configured node path does not exist: {}, falling back to auto-detect", path.display());
+                warn!("Configured {} path does not exist: {}, falling back to auto-detect", self.runtime, path.display());
                 self.node = None;
             }
         }
@@ -149,7 +1046,7 @@ impl Config {
         
         // Auto-detect remaining missing values
         if self.node.is_none() {
-            self.node = Self::detect_node_path();
+            self.node = Self::detect_node_path(self.runtime, self.min_node_version.as_deref());
         }
         if self.auggie_entry.is_none() {
             self.auggie_entry = Self::detect_auggie_entry();
@@ -157,9 +1054,11 @@ impl Config {
         
         // Log detection results
         if let Some(ref node) = self.node {
-            info!("Node.js: {}", node.display());
-        } else {
+            info!("Runtime ({}): {}", self.runtime, node.display());
+        } else if self.runtime == Runtime::Node {
             info!("⚠️ Node.js not found - please install Node.js or set --node");
+        } else {
+            info!("⚠️ {} not found - please install it or set --node", self.runtime);
         }
         if let Some(ref entry) = self.auggie_entry {
             info!("Auggie: {}", entry.display());
@@ -167,29 +1066,90 @@ impl Config {
             info!("⚠️ Auggie not found - please run: npm install -g @augmentcode/auggie");
         }
         
-        self
+        Ok(self)
+    }
+
+    /// Warning about config file keys nothing reads, with the closest known key for each
+    fn unknown_settings_message(unknown: &[String]) -> Option<String> {
+        if unknown.is_empty() {
+            return None;
+        }
+        let known = config_file::schema_keys(&Self::config_schema());
+        let described: Vec<String> = unknown
+            .iter()
+            .map(|path| {
+                let key = path.rsplit('.').next().unwrap_or(path);
+                match config_file::suggest(key, &known) {
+                    Some(suggestion) => format!("{} (did you mean {}?)", path, suggestion),
+                    None => path.clone(),
+                }
+            })
+            .collect();
+        Some(format!("Unknown settings in the config file: {}", described.join(", ")))
+    }
+
+    /// Fail on unknown config file keys under `strict_config`, for `--listen`, where sessions
+    /// would otherwise each fail to start
+    pub fn check_strict_config(&self) -> Result<(), ProxyError> {
+        let Some((fc, unknown)) = Self::load_config_file() else { return Ok(()) };
+        match Self::unknown_settings_message(&unknown) {
+            Some(message) if self.strict_config || fc.strict_config == Some(true) => {
+                Err(ProxyError::ConfigError(format!("{} (strict_config)", message)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Overrides configured for a root (keys are resolved by the proxy at startup)
+    pub fn root_config(&self, root: &Path) -> Option<&RootConfig> {
+        self.root_overrides.get(root)
+    }
+
+    /// Auggie mode for a root: the first matching `mode_rules` entry, else `--mode`
+    /// (possibly `auto`, which is resolved when the backend starts)
+    pub fn mode_for(&self, root: &Path) -> &str {
+        for rule in &self.mode_rules {
+            match Glob::new(&rule.root) {
+                Ok(glob) if glob.compile_matcher().is_match(root) => return &rule.mode,
+                Ok(_) => {}
+                Err(e) => warn!("Ignoring mode rule with invalid glob {:?}: {}", rule.root, e),
+            }
+        }
+        &self.mode
+    }
+
+    /// Backend processes to run for a root (always one for `connect` roots)
+    pub fn instances_for(&self, root: &Path) -> usize {
+        match self.root_config(root) {
+            Some(rc) if rc.connect.is_some() => 1,
+            Some(rc) => rc.instances.unwrap_or(self.instances_per_root).max(1),
+            None => self.instances_per_root.max(1),
+        }
     }
 
-    /// Load config from file (searches multiple locations)
-    fn load_config_file() -> Option<FileConfig> {
+    /// Load config from file (searches multiple locations), merged with the files it extends
+    /// along with the keys nothing reads
+    fn load_config_file() -> Option<(FileConfig, Vec<String>)> {
         let candidates = Self::get_config_file_candidates();
         
         for path in candidates {
             if path.exists() {
-                match std::fs::read_to_string(&path) {
-                    Ok(content) => {
-                        match serde_json::from_str::<FileConfig>(&content) {
-                            Ok(config) => {
-                                info!("Loaded config from: {}", path.display());
-                                return Some(config);
-                            }
-                            Err(e) => {
-                                eprintln!("Warning: Failed to parse {}: {}", path.display(), e);
-                            }
-                        }
+                let layers = match config_file::read(&path) {
+                    Ok(layers) => layers,
+                    Err(e) => {
+                        eprintln!("Warning: {}", e);
+                        continue;
+                    }
+                };
+                match config_file::parse::<FileConfig>(layers.settings) {
+                    Ok((mut config, unknown)) => {
+                        config.expand_paths();
+                        let files: Vec<String> = layers.files.iter().map(|f| f.display().to_string()).collect();
+                        info!("Loaded config from: {}", files.join(" (extends) "));
+                        return Some((config, unknown));
                     }
                     Err(e) => {
-                        eprintln!("Warning: Failed to read {}: {}", path.display(), e);
+                        eprintln!("Warning: Failed to parse {}: {}", files_description(&layers.files), e);
                     }
                 }
             }
@@ -197,6 +1157,90 @@ impl Config {
         None
     }
 
+    /// JSON Schema of the config file (`config-schema`); settings without doc comments of their
+    /// own are described by the matching command-line option
+    pub fn config_schema() -> serde_json::Value {
+        let mut schema = serde_json::to_value(schemars::schema_for!(FileConfig)).unwrap_or_default();
+        schema["title"] = "mcp-proxy.json".into();
+        // Unknown keys are ignored when loading, so flag them in the editor instead
+        schema["additionalProperties"] = false.into();
+        if let Some(properties) = schema.get_mut("properties").and_then(|p| p.as_object_mut()) {
+            properties.insert("$schema".to_string(), serde_json::json!({ "type": "string" }));
+            properties.insert(
+                "extends".to_string(),
+                serde_json::json!({
+                    "type": "string",
+                    "description": "Config file whose settings this one inherits and overrides (relative to this file)",
+                }),
+            );
+            for arg in <Config as clap::CommandFactory>::command().get_arguments() {
+                let (Some(property), Some(help)) = (properties.get_mut(arg.get_id().as_str()), arg.get_help()) else {
+                    continue;
+                };
+                if property.get("description").is_none() {
+                    property["description"] = help.to_string().into();
+                }
+            }
+        }
+        schema
+    }
+
+    /// Report which config file is used, the files it extends and where each setting comes
+    /// from (`check-config`); fails when the file would be ignored or rejected
+    pub fn check_config_file(&self) -> anyhow::Result<()> {
+        let candidates = Self::get_config_file_candidates();
+        let Some(path) = candidates.iter().find(|path| path.exists()) else {
+            println!("No config file found; searched:");
+            for candidate in &candidates {
+                println!("  {}", candidate.display());
+            }
+            return Ok(());
+        };
+        let layers = config_file::read(path).map_err(|e| anyhow::anyhow!(e))?;
+        println!("Config file: {}", path.display());
+        for extended in &layers.files[1..] {
+            println!("  extends {}", extended.display());
+        }
+        let width = layers.sources.keys().map(String::len).max().unwrap_or(0);
+        if !layers.sources.is_empty() {
+            println!("Settings:");
+        }
+        for (key, source) in &layers.sources {
+            println!("  {:width$}  {}", key, source.display(), width = width);
+        }
+        let (fc, unknown) = match config_file::parse::<FileConfig>(layers.settings) {
+            Ok(parsed) => parsed,
+            Err(e) => anyhow::bail!("Invalid config in {}: {}", files_description(&layers.files), e),
+        };
+        if let Some(message) = Self::unknown_settings_message(&unknown) {
+            if self.strict_config || fc.strict_config == Some(true) {
+                anyhow::bail!("{} (strict_config)", message);
+            }
+            println!("Warning: {}", message);
+        }
+        println!("OK");
+        Ok(())
+    }
+
+    /// Config file settings needed before the full merge: `default_root` for scoping the lock
+    /// (under `lock_per_root`), and where a daemon listens, logs and writes its PID, which are
+    /// settled before forking
+    pub fn merge_early_file_settings(&mut self) {
+        let Some((fc, _)) = Self::load_config_file() else { return };
+        if self.lock_per_root && self.default_root.is_none() {
+            self.default_root = fc.default_root;
+        }
+        if self.listen.is_none() {
+            self.listen = fc.listen;
+        }
+        if self.log_file.is_none() {
+            self.log_file = fc.log_file;
+        }
+        if self.pid_file.is_none() {
+            self.pid_file = fc.pid_file;
+        }
+    }
+
     /// Get list of config file candidates in priority order
     fn get_config_file_candidates() -> Vec<PathBuf> {
         let mut candidates = Vec::new();
@@ -220,6 +1264,10 @@ impl Config {
             candidates.push(PathBuf::from(&userprofile).join("mcp-proxy.json"));
         }
         
+        #[cfg(not(windows))]
+        if let Some(config_home) = crate::dirs::config_home() {
+            candidates.push(config_home.join("mcp-proxy").join("config.json"));
+        }
         #[cfg(not(windows))]
         if let Ok(home) = std::env::var("HOME") {
             candidates.push(PathBuf::from(&home).join(".config").join("mcp-proxy.json"));
@@ -229,22 +1277,27 @@ impl Config {
         candidates
     }
 
-    fn detect_node_path() -> Option<PathBuf> {
+    fn detect_node_path(runtime: Runtime, min_node_version: Option<&str>) -> Option<PathBuf> {
+        if let Some(path) = runtime.user_install_candidates(min_node_version).into_iter().find(|p| p.exists()) {
+            return Some(path);
+        }
         // Try common locations
         #[cfg(windows)]
         {
-            let candidates = [
-                r"C:\Program Files\nodejs\node.exe",
-                r"C:\Program Files (x86)\nodejs\node.exe",
-            ];
-            for path in candidates {
-                let p = PathBuf::from(path);
-                if p.exists() {
-                    return Some(p);
+            if runtime == Runtime::Node {
+                let candidates = [
+                    r"C:\Program Files\nodejs\node.exe",
+                    r"C:\Program Files (x86)\nodejs\node.exe",
+                ];
+                for path in candidates {
+                    let p = PathBuf::from(path);
+                    if p.exists() {
+                        return Some(p);
+                    }
                 }
             }
             // Try PATH
-            if let Ok(output) = std::process::Command::new("where").arg("node").output() {
+            if let Ok(output) = std::process::Command::new("where").arg(runtime.binary_name()).output() {
                 if output.status.success() {
                     if let Ok(s) = String::from_utf8(output.stdout) {
                         if let Some(line) = s.lines().next() {
@@ -256,7 +1309,7 @@ impl Config {
         }
         #[cfg(not(windows))]
         {
-            if let Ok(output) = std::process::Command::new("which").arg("node").output() {
+            if let Ok(output) = std::process::Command::new("which").arg(runtime.binary_name()).output() {
                 if output.status.success() {
                     if let Ok(s) = String::from_utf8(output.stdout) {
                         return Some(PathBuf::from(s.trim()));
@@ -0,0 +1,189 @@
+//! Config file layering (`extends`) and unknown keys (`strict_config`)
+//! A config file may name another one to inherit from (a per-project file extending the
+//! user-level one); settings of the extending file replace those of the file it extends
+
+use crate::paths;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// A config file merged with the files it extends
+#[derive(Debug)]
+pub struct ConfigLayers {
+    /// The files read, the one asked for first
+    pub files: Vec<PathBuf>,
+    /// Merged top-level settings
+    pub settings: Map<String, Value>,
+    /// File each setting came from
+    pub sources: BTreeMap<String, PathBuf>,
+}
+
+/// Read a config file and the chain of files it extends (relative `extends` paths are relative
+/// to the extending file; `~` and environment variables are expanded)
+pub fn read(path: &Path) -> Result<ConfigLayers, String> {
+    let mut layers: Vec<(PathBuf, Map<String, Value>)> = Vec::new();
+    let mut seen = Vec::new();
+    let mut next = Some(path.to_path_buf());
+    while let Some(path) = next.take() {
+        let identity = std::fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if seen.contains(&identity) {
+            let chain: Vec<String> = layers.iter().map(|(p, _)| p.display().to_string()).collect();
+            return Err(format!("Config files extend each other in a cycle: {} -> {}", chain.join(" -> "), path.display()));
+        }
+        let content = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut settings = match serde_json::from_str(&content) {
+            Ok(Value::Object(settings)) => settings,
+            Ok(_) => return Err(format!("Failed to parse {}: expected a JSON object", path.display())),
+            Err(e) => return Err(format!("Failed to parse {}: {}", path.display(), e)),
+        };
+        if let Some(parent) = settings.remove("extends") {
+            let Some(parent) = parent.as_str() else {
+                return Err(format!("Failed to parse {}: `extends` must be a path", path.display()));
+            };
+            let parent = paths::expand(Path::new(parent));
+            next = Some(match path.parent() {
+                Some(dir) if parent.is_relative() => dir.join(parent),
+                _ => parent,
+            });
+        }
+        seen.push(identity);
+        layers.push((path, settings));
+    }
+
+    let files = layers.iter().map(|(path, _)| path.clone()).collect();
+    let mut merged = Map::new();
+    let mut sources = BTreeMap::new();
+    for (path, settings) in layers.into_iter().rev() {
+        for (key, value) in settings {
+            sources.insert(key.clone(), path.clone());
+            merged.insert(key, value);
+        }
+    }
+    Ok(ConfigLayers { files, settings: merged, sources })
+}
+
+/// Deserialize merged settings, along with the keys nothing read (`retry.max_retires`)
+pub fn parse<T: DeserializeOwned>(settings: Map<String, Value>) -> Result<(T, Vec<String>), serde_json::Error> {
+    let mut unknown = Vec::new();
+    let parsed = serde_ignored::deserialize(Value::Object(settings), |path| {
+        // `?` marks where an `Option` was unwrapped, which means nothing to whoever wrote the file
+        let path = path.to_string().split('.').filter(|segment| *segment != "?").collect::<Vec<_>>().join(".");
+        // Editors' schema reference
+        if path != "$schema" {
+            unknown.push(path);
+        }
+    })?;
+    Ok((parsed, unknown))
+}
+
+/// Every property name anywhere in a JSON Schema
+pub fn schema_keys(schema: &Value) -> BTreeSet<String> {
+    let mut keys = BTreeSet::new();
+    let mut pending = vec![schema];
+    while let Some(value) = pending.pop() {
+        match value {
+            Value::Object(object) => {
+                if let Some(Value::Object(properties)) = object.get("properties") {
+                    keys.extend(properties.keys().cloned());
+                }
+                pending.extend(object.values());
+            }
+            Value::Array(items) => pending.extend(items),
+            _ => {}
+        }
+    }
+    keys
+}
+
+/// The known key closest to a misspelled one, if any is close enough
+pub fn suggest<'a>(key: &str, known: &'a BTreeSet<String>) -> Option<&'a str> {
+    let limit = (key.chars().count() / 3).max(2);
+    known
+        .iter()
+        .map(|candidate| (edit_distance(key, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+/// Levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_layers() {
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-layers-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("project")).unwrap();
+        let user = dir.join("user.json");
+        let project = dir.join("project").join("mcp-proxy.json");
+        std::fs::write(&user, r#"{"max_backends": 5, "mode": "default"}"#).unwrap();
+        std::fs::write(&project, r#"{"extends": "../user.json", "max_backends": 2}"#).unwrap();
+
+        let layers = read(&project).unwrap();
+        assert_eq!(layers.files, vec![project.clone(), dir.join("project").join("../user.json")]);
+        assert_eq!(layers.settings["max_backends"], 2);
+        assert_eq!(layers.settings["mode"], "default");
+        assert!(!layers.settings.contains_key("extends"));
+        assert_eq!(layers.sources["max_backends"], project);
+        assert_eq!(layers.sources["mode"], dir.join("project").join("../user.json"));
+
+        // A cycle is reported rather than followed
+        std::fs::write(&user, r#"{"extends": "project/mcp-proxy.json"}"#).unwrap();
+        let error = read(&project).unwrap_err();
+        assert!(error.contains("cycle"), "{}", error);
+        std::fs::write(&user, r#"{"extends": 1}"#).unwrap();
+        assert!(read(&project).unwrap_err().contains("must be a path"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        #[derive(serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Retry {
+            max_retries: Option<u32>,
+        }
+        #[derive(serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Settings {
+            max_backends: Option<usize>,
+            retry: Option<Retry>,
+        }
+        let settings = serde_json::json!({
+            "$schema": "./schema.json",
+            "max_backend": 2,
+            "retry": { "max_retires": 1 },
+        });
+        let Value::Object(settings) = settings else { unreachable!() };
+        let (_, unknown) = parse::<Settings>(settings).unwrap();
+        assert_eq!(unknown, vec!["max_backend".to_string(), "retry.max_retires".to_string()]);
+
+        let schema = serde_json::json!({
+            "properties": { "max_backends": {}, "retry": { "$ref": "#/$defs/Retry" } },
+            "$defs": { "Retry": { "properties": { "max_retries": {} } } },
+        });
+        let known = schema_keys(&schema);
+        assert_eq!(known.len(), 3);
+        assert_eq!(suggest("max_backend", &known), Some("max_backends"));
+        assert_eq!(suggest("max_retires", &known), Some("max_retries"));
+        assert_eq!(suggest("unrelated", &known), None);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
@@ -0,0 +1,248 @@
+//! Human confirmation gate for dangerous tools
+//! Pauses matching `tools/call` requests until the user approves them, either through an
+//! MCP elicitation request to the client or an external confirm command
+
+use clap::ValueEnum;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::warn;
+
+/// How confirmation is requested
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfirmMethod {
+    /// Elicitation when the client supports it, otherwise the command
+    #[default]
+    Auto,
+    /// Always ask the client via `elicitation/create`
+    Elicitation,
+    /// Always run the confirm command
+    Command,
+}
+
+/// `confirm` section of the config file
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(default)]
+pub struct ConfirmConfig {
+    /// Tool name globs that need confirmation
+    pub tools: Vec<String>,
+    pub method: ConfirmMethod,
+    /// External command (program + args, `{tool}` placeholder) that approves by exiting 0;
+    /// receives `{"tool", "arguments", "root"}` as JSON on stdin
+    pub command: Vec<String>,
+    /// Requests not confirmed within this time are rejected
+    pub timeout_seconds: u64,
+}
+
+impl Default for ConfirmConfig {
+    fn default() -> Self {
+        Self {
+            tools: Vec::new(),
+            method: ConfirmMethod::default(),
+            command: Vec::new(),
+            timeout_seconds: 60,
+        }
+    }
+}
+
+/// Outcome of a confirmation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfirmDecision {
+    Approved,
+    Rejected(String),
+}
+
+/// Channel a confirmation goes through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfirmChannel {
+    Elicitation,
+    Command,
+}
+
+/// Compiled confirmation settings
+#[derive(Default)]
+pub struct Confirmer {
+    tools: Option<GlobSet>,
+    method: ConfirmMethod,
+    command: Vec<String>,
+    timeout: Duration,
+}
+
+impl Confirmer {
+    pub fn new(config: &ConfirmConfig) -> Self {
+        let tools = if config.tools.is_empty() {
+            None
+        } else {
+            let mut builder = GlobSetBuilder::new();
+            for pattern in &config.tools {
+                match Glob::new(pattern) {
+                    Ok(glob) => {
+                        builder.add(glob);
+                    }
+                    Err(e) => warn!("Ignoring invalid confirm pattern {:?}: {}", pattern, e),
+                }
+            }
+            builder.build().ok()
+        };
+        Self {
+            tools,
+            method: config.method,
+            command: config.command.clone(),
+            timeout: Duration::from_secs(config.timeout_seconds),
+        }
+    }
+
+    /// Whether calls to this tool need confirmation
+    pub fn requires(&self, tool: &str) -> bool {
+        self.tools.as_ref().is_some_and(|t| t.is_match(tool))
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Pick the confirmation channel given the client's capabilities
+    pub fn channel(&self, client_supports_elicitation: bool) -> Option<ConfirmChannel> {
+        let has_command = !self.command.is_empty();
+        match self.method {
+            ConfirmMethod::Elicitation if client_supports_elicitation => Some(ConfirmChannel::Elicitation),
+            ConfirmMethod::Command if has_command => Some(ConfirmChannel::Command),
+            ConfirmMethod::Auto if client_supports_elicitation => Some(ConfirmChannel::Elicitation),
+            ConfirmMethod::Auto if has_command => Some(ConfirmChannel::Command),
+            _ => None,
+        }
+    }
+
+    /// Run the confirm command; exit status 0 approves
+    pub async fn run_command(&self, tool: &str, payload: Value) -> ConfirmDecision {
+        let (program, args) = match self.command.split_first() {
+            Some(split) => split,
+            None => return ConfirmDecision::Rejected("No confirm command configured".to_string()),
+        };
+        let mut cmd = Command::new(program);
+        cmd.args(args.iter().map(|a| a.replace("{tool}", tool)))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .kill_on_drop(true);
+
+        let run = async {
+            let mut child = cmd.spawn()?;
+            // The command may not read its input at all
+            if let Some(mut stdin) = child.stdin.take() {
+                let _ = stdin.write_all(payload.to_string().as_bytes()).await;
+            }
+            child.wait().await
+        };
+        match tokio::time::timeout(self.timeout, run).await {
+            Ok(Ok(status)) if status.success() => ConfirmDecision::Approved,
+            Ok(Ok(status)) => ConfirmDecision::Rejected(format!("Rejected by confirm command ({})", status)),
+            Ok(Err(e)) => ConfirmDecision::Rejected(format!("Confirm command failed: {}", e)),
+            Err(_) => ConfirmDecision::Rejected("Confirmation timed out".to_string()),
+        }
+    }
+}
+
+/// Params for an `elicitation/create` request asking to allow a tool call
+pub fn elicitation_params(tool: &str, arguments: Option<&Value>) -> Value {
+    let mut arguments = arguments
+        .map(|a| serde_json::to_string_pretty(a).unwrap_or_default())
+        .unwrap_or_else(|| "{}".to_string());
+    if arguments.len() > 2000 {
+        let mut end = 2000;
+        while !arguments.is_char_boundary(end) {
+            end -= 1;
+        }
+        arguments.truncate(end);
+        arguments.push_str("\n…");
+    }
+    json!({
+        "message": format!("Allow the agent to run tool '{}'?\n\nArguments:\n{}", tool, arguments),
+        "requestedSchema": {
+            "type": "object",
+            "properties": {
+                "approve": {"type": "boolean", "title": "Approve", "default": false}
+            },
+            "required": ["approve"]
+        }
+    })
+}
+
+/// Interpret an elicitation result (`action: accept` with `approve: true`)
+pub fn elicitation_decision(result: &Value) -> ConfirmDecision {
+    let action = result.get("action").and_then(|a| a.as_str()).unwrap_or("cancel");
+    let approved = result
+        .get("content")
+        .and_then(|c| c.get("approve"))
+        .and_then(|a| a.as_bool())
+        .unwrap_or(false);
+    match action {
+        "accept" if approved => ConfirmDecision::Approved,
+        "accept" => ConfirmDecision::Rejected("Tool call was not approved".to_string()),
+        "decline" => ConfirmDecision::Rejected("Tool call confirmation was declined".to_string()),
+        _ => ConfirmDecision::Rejected("Tool call confirmation was canceled".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn confirmer(method: ConfirmMethod, command: &[&str]) -> Confirmer {
+        Confirmer::new(&ConfirmConfig {
+            tools: vec!["save-*".to_string(), "launch-process".to_string()],
+            method,
+            command: command.iter().map(|s| s.to_string()).collect(),
+            timeout_seconds: 5,
+        })
+    }
+
+    #[test]
+    fn test_requires_and_channel() {
+        let auto = confirmer(ConfirmMethod::Auto, &["true"]);
+        assert!(auto.requires("save-file"));
+        assert!(!auto.requires("codebase-retrieval"));
+        assert_eq!(auto.channel(true), Some(ConfirmChannel::Elicitation));
+        assert_eq!(auto.channel(false), Some(ConfirmChannel::Command));
+
+        let elicitation_only = confirmer(ConfirmMethod::Elicitation, &[]);
+        assert_eq!(elicitation_only.channel(false), None);
+        assert!(!Confirmer::default().requires("save-file"));
+    }
+
+    #[test]
+    fn test_elicitation_decision() {
+        assert_eq!(
+            elicitation_decision(&json!({"action": "accept", "content": {"approve": true}})),
+            ConfirmDecision::Approved
+        );
+        assert!(matches!(
+            elicitation_decision(&json!({"action": "accept", "content": {"approve": false}})),
+            ConfirmDecision::Rejected(_)
+        ));
+        assert_eq!(
+            elicitation_decision(&json!({"action": "decline"})),
+            ConfirmDecision::Rejected("Tool call confirmation was declined".to_string())
+        );
+        assert_eq!(
+            elicitation_decision(&json!({"action": "cancel"})),
+            ConfirmDecision::Rejected("Tool call confirmation was canceled".to_string())
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_confirm_command_exit_status() {
+        let payload = json!({"tool": "save-file"});
+        assert_eq!(confirmer(ConfirmMethod::Command, &["true"]).run_command("save-file", payload.clone()).await, ConfirmDecision::Approved);
+        assert!(matches!(
+            confirmer(ConfirmMethod::Command, &["false"]).run_command("save-file", payload).await,
+            ConfirmDecision::Rejected(_)
+        ));
+    }
+}
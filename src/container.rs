@@ -0,0 +1,182 @@
+//! Container backends (Docker/Podman)
+//! Runs auggie inside a container with the workspace bind-mounted, isolating its node environment
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::{debug, warn};
+
+/// Counter making container names unique within this proxy
+static CONTAINER_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+/// Per-root `container` section of the config file
+///
+/// `run_args` and `command` may use the placeholders `{root}` (host root),
+/// `{workspace}` (mount path inside the container), `{mode}` and `{name}`.
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(default)]
+pub struct ContainerConfig {
+    /// Container runtime executable (`docker`, `podman`, or a full path)
+    pub runtime: String,
+    /// Image to run
+    pub image: String,
+    /// Where the root is mounted inside the container
+    pub mount_path: String,
+    /// Extra `run` arguments (e.g. `["--network", "host", "-e", "AUGMENT_API_TOKEN"]`)
+    pub run_args: Vec<String>,
+    /// Command run inside the container
+    pub command: Vec<String>,
+}
+
+impl Default for ContainerConfig {
+    fn default() -> Self {
+        Self {
+            runtime: "docker".to_string(),
+            image: String::new(),
+            mount_path: "/workspace".to_string(),
+            run_args: Vec::new(),
+            command: ["auggie", "--mcp", "-m", "{mode}", "--workspace-root", "{workspace}"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl ContainerConfig {
+    /// Translate a host path under `root` into the container's namespace
+    pub fn container_path(&self, root: &Path, path: &Path) -> String {
+        match path.strip_prefix(root) {
+            Ok(relative) if relative.as_os_str().is_empty() => self.mount_path.clone(),
+            Ok(relative) => format!(
+                "{}/{}",
+                self.mount_path.trim_end_matches('/'),
+                relative.display().to_string().replace('\\', "/")
+            ),
+            Err(_) => path.display().to_string(),
+        }
+    }
+
+    /// Build the `<runtime> run` command for a root, along with a handle for removing the container
+    /// (`env` names are forwarded with `-e NAME`; values come from the runtime client's environment)
+    pub fn backend_command(&self, root: &Path, mode: &str, env: &[(String, String)]) -> (Command, ContainerHandle) {
+        let name = format!(
+            "mcp-proxy-{}-{}",
+            std::process::id(),
+            CONTAINER_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let expand = |arg: &String| {
+            arg.replace("{root}", &root.display().to_string())
+                .replace("{workspace}", &self.mount_path)
+                .replace("{mode}", mode)
+                .replace("{name}", &name)
+        };
+
+        let mut cmd = Command::new(&self.runtime);
+        cmd.arg("run")
+            .arg("-i")
+            .arg("--rm")
+            .arg("--name")
+            .arg(&name)
+            .arg("--mount")
+            .arg(format!("type=bind,source={},target={}", root.display(), self.mount_path))
+            .arg("-e")
+            .arg("AUGMENT_DISABLE_AUTO_UPDATE=1");
+        for (name, _) in env {
+            cmd.arg("-e").arg(name);
+        }
+        cmd.args(self.run_args.iter().map(expand));
+        cmd.arg(&self.image);
+        cmd.args(self.command.iter().map(expand));
+
+        let handle = ContainerHandle {
+            runtime: self.runtime.clone(),
+            name,
+        };
+        (cmd, handle)
+    }
+}
+
+/// A running backend container, removed through the runtime on shutdown
+///
+/// Killing the `run` client does not reliably stop the container, so cleanup
+/// always goes through `<runtime> rm -f`.
+#[derive(Debug)]
+pub struct ContainerHandle {
+    runtime: String,
+    pub name: String,
+}
+
+impl ContainerHandle {
+    /// Force-remove the container and wait (bounded) for the runtime to finish
+    pub async fn remove(&self) {
+        let status = Command::new(&self.runtime)
+            .arg("rm")
+            .arg("-f")
+            .arg(&self.name)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        match tokio::time::timeout(Duration::from_secs(10), status).await {
+            Ok(Ok(status)) => debug!("Removed container {} ({:?})", self.name, status),
+            Ok(Err(e)) => warn!("Failed to remove container {}: {}", self.name, e),
+            Err(_) => warn!("Timed out removing container {}", self.name),
+        }
+    }
+
+    /// Fire-and-forget removal for sync contexts (Drop)
+    pub fn remove_detached(&self) {
+        let _ = std::process::Command::new(&self.runtime)
+            .arg("rm")
+            .arg("-f")
+            .arg(&self.name)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_container_command_template() {
+        let container = ContainerConfig {
+            runtime: "podman".to_string(),
+            image: "node:20".to_string(),
+            run_args: vec!["--label".to_string(), "root={root}".to_string()],
+            ..Default::default()
+        };
+        let env = [("AUGMENT_API_TOKEN".to_string(), "secret".to_string())];
+        let (cmd, handle) = container.backend_command(Path::new("/home/me/repo"), "default", &env);
+        let args: Vec<_> = cmd.as_std().get_args().map(|a| a.to_string_lossy().to_string()).collect();
+
+        assert_eq!(cmd.as_std().get_program(), "podman");
+        assert!(handle.name.starts_with("mcp-proxy-"));
+        assert_eq!(
+            args,
+            [
+                "run", "-i", "--rm", "--name", handle.name.as_str(),
+                "--mount", "type=bind,source=/home/me/repo,target=/workspace",
+                "-e", "AUGMENT_DISABLE_AUTO_UPDATE=1",
+                "-e", "AUGMENT_API_TOKEN",
+                "--label", "root=/home/me/repo",
+                "node:20",
+                "auggie", "--mcp", "-m", "default", "--workspace-root", "/workspace",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_container_path_translation() {
+        let container = ContainerConfig::default();
+        let root = Path::new("/home/me/repo");
+        assert_eq!(container.container_path(root, Path::new("/home/me/repo/src/a.rs")), "/workspace/src/a.rs");
+        assert_eq!(container.container_path(root, root), "/workspace");
+    }
+}
@@ -0,0 +1,144 @@
+//! Crash reports
+//! A panic hook writes the panic, a backtrace, each session's backends and pending requests,
+//! and the last client messages to `crashes/` in the state directory so field crashes can be diagnosed
+
+use crate::redact;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Client messages kept for the report
+const WIRE_MESSAGES: usize = 32;
+/// Characters kept per message
+const WIRE_MESSAGE_CHARS: usize = 2048;
+
+struct WireMessage {
+    at_ms: u128,
+    direction: &'static str,
+    text: String,
+}
+
+#[derive(Default)]
+struct CrashState {
+    wire: VecDeque<WireMessage>,
+    /// Latest summary of each live session
+    sessions: HashMap<u64, serde_json::Value>,
+}
+
+static STATE: Mutex<Option<CrashState>> = Mutex::new(None);
+static NEXT_SESSION: AtomicU64 = AtomicU64::new(1);
+
+fn with_state(f: impl FnOnce(&mut CrashState)) {
+    // A poisoned lock only means an earlier panic while recording; the data is still usable
+    let mut guard = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    f(guard.get_or_insert_with(CrashState::default));
+}
+
+fn now_ms() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis())
+}
+
+/// Remember a message to or from the client (truncated and redacted)
+pub fn record_wire(direction: &'static str, message: &str) {
+    let truncated: String = message.chars().take(WIRE_MESSAGE_CHARS).collect();
+    let text = redact::global().redact_str(&truncated).into_owned();
+    with_state(|state| {
+        if state.wire.len() == WIRE_MESSAGES {
+            state.wire.pop_front();
+        }
+        state.wire.push_back(WireMessage { at_ms: now_ms(), direction, text });
+    });
+}
+
+/// A session's entry in crash reports, removed when dropped
+pub struct CrashSession {
+    id: u64,
+}
+
+impl CrashSession {
+    pub fn new() -> Self {
+        Self { id: NEXT_SESSION.fetch_add(1, Ordering::Relaxed) }
+    }
+
+    /// Replace the session's summary (backends, pending requests)
+    pub fn update(&self, summary: serde_json::Value) {
+        with_state(|state| {
+            state.sessions.insert(self.id, summary);
+        });
+    }
+}
+
+impl Drop for CrashSession {
+    fn drop(&mut self) {
+        with_state(|state| {
+            state.sessions.remove(&self.id);
+        });
+    }
+}
+
+/// Write a crash report on every panic, after the default message
+pub fn install_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        let backtrace = std::backtrace::Backtrace::force_capture();
+        match write_report(&info.to_string(), &backtrace.to_string()) {
+            Ok(path) => {
+                tracing::error!("mcp-proxy panicked, crash report written to {}", path.display());
+                eprintln!("mcp-proxy crash report: {}", path.display());
+            }
+            Err(e) => eprintln!("Failed to write crash report: {}", e),
+        }
+    }));
+}
+
+fn write_report(panic: &str, backtrace: &str) -> std::io::Result<PathBuf> {
+    let mut report = String::new();
+    let _ = writeln!(report, "mcp-proxy {} crash report", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(report, "pid: {}, time (ms since epoch): {}", std::process::id(), now_ms());
+    let _ = writeln!(report, "\n== Panic\n{}", panic);
+    let _ = writeln!(report, "\n== Backtrace\n{}", backtrace);
+    with_state(|state| {
+        let _ = writeln!(report, "\n== Sessions ({})", state.sessions.len());
+        for (id, summary) in &state.sessions {
+            let summary = serde_json::to_string_pretty(summary).unwrap_or_default();
+            let _ = writeln!(report, "session {}: {}", id, summary);
+        }
+        let _ = writeln!(report, "\n== Last {} client messages", state.wire.len());
+        for message in &state.wire {
+            let _ = writeln!(report, "[{}] {} {}", message.at_ms, message.direction, message.text);
+        }
+    });
+
+    let dir = crate::dirs::state_dir().join("crashes");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("crash-{}-{}.txt", now_ms(), std::process::id()));
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wire_ring_and_sessions() {
+        for n in 0..WIRE_MESSAGES + 3 {
+            record_wire("in", &format!("message {}", n));
+        }
+        record_wire("in", &"x".repeat(WIRE_MESSAGE_CHARS * 2));
+        let session = CrashSession::new();
+        session.update(serde_json::json!({ "backends": [] }));
+        with_state(|state| {
+            assert_eq!(state.wire.len(), WIRE_MESSAGES);
+            assert_eq!(state.wire.back().unwrap().text.len(), WIRE_MESSAGE_CHARS);
+            assert!(state.sessions.contains_key(&session.id));
+        });
+        let id = session.id;
+        drop(session);
+        with_state(|state| assert!(!state.sessions.contains_key(&id)));
+    }
+}
@@ -0,0 +1,148 @@
+//! Detaching into the background (`--daemonize`) and the PID file
+//! Runs before the async runtime starts, since forking a multi-threaded process is unsafe
+
+use crate::config::Config;
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+/// PID file, removed when the proxy exits
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Write the current PID, refusing when the file names another live process
+    pub fn create(path: &Path) -> Result<Self> {
+        ensure_not_running(path)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, format!("{}\n", std::process::id()))
+            .with_context(|| format!("Failed to write PID file {}", path.display()))?;
+        Ok(Self { path: path.to_path_buf() })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if read_pid(&self.path) == Some(std::process::id()) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Fail when the PID file names another running mcp-proxy
+pub fn ensure_not_running(path: &Path) -> Result<()> {
+    if let Some(pid) = read_pid(path) {
+        if pid != std::process::id() && is_mcp_proxy(pid) {
+            bail!("mcp-proxy is already running (PID {} in {})", pid, path.display());
+        }
+    }
+    Ok(())
+}
+
+/// PID stored in a PID file
+pub fn read_pid(path: &Path) -> Option<u32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Whether a process with this PID exists
+pub fn is_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else { return false };
+    // Signal 0 only checks that the process exists (EPERM: it does, owned by someone else)
+    unsafe { libc::kill(pid, 0) == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM) }
+}
+
+/// Whether `pid` is a live mcp-proxy (a PID reused by another program is not); when its name
+/// can't be read, any live process counts, so a running proxy is never taken over
+pub fn is_mcp_proxy(pid: u32) -> bool {
+    if !is_alive(pid) {
+        return false;
+    }
+    let Some(name) = process_name(pid) else { return true };
+    let own = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.file_name().map(|name| name.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "mcp-proxy".to_string());
+    // Linux keeps only the first 15 bytes of the name
+    name.starts_with("mcp-proxy") || name == own || (name.len() == 15 && own.starts_with(&name))
+}
+
+/// Executable name of a process
+#[cfg(target_os = "linux")]
+pub fn process_name(pid: u32) -> Option<String> {
+    let name = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+    Some(name.trim_end().to_string()).filter(|name| !name.is_empty())
+}
+
+/// Executable name of a process
+#[cfg(not(target_os = "linux"))]
+pub fn process_name(pid: u32) -> Option<String> {
+    let output = std::process::Command::new("ps").args(["-p", &pid.to_string(), "-o", "comm="]).output().ok()?;
+    let command = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Path::new(&command).file_name().map(|name| name.to_string_lossy().into_owned())
+}
+
+/// Fork into the background: the calling process exits once the daemon is detached from the
+/// terminal (new session, stdio on /dev/null); returns in the daemon
+pub fn daemonize(config: &Config) -> Result<()> {
+    if config.listen.is_none() {
+        bail!("--daemonize needs --listen (a daemon has no stdio client)");
+    }
+    unsafe {
+        match libc::fork() {
+            -1 => return Err(std::io::Error::last_os_error()).context("fork failed"),
+            0 => {}
+            _ => std::process::exit(0),
+        }
+        if libc::setsid() == -1 {
+            return Err(std::io::Error::last_os_error()).context("setsid failed");
+        }
+        // Fork again so the daemon can never reacquire a controlling terminal
+        match libc::fork() {
+            -1 => return Err(std::io::Error::last_os_error()).context("fork failed"),
+            0 => {}
+            _ => std::process::exit(0),
+        }
+        let null = libc::open(c"/dev/null".as_ptr(), libc::O_RDWR);
+        if null >= 0 {
+            for fd in [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+                libc::dup2(null, fd);
+            }
+            if null > libc::STDERR_FILENO {
+                libc::close(null);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pid_file() {
+        let path = std::env::temp_dir().join(format!("mcp-proxy-pid-{}", std::process::id()));
+        {
+            let _pid_file = PidFile::create(&path).unwrap();
+            assert_eq!(read_pid(&path), Some(std::process::id()));
+        }
+        assert!(!path.exists());
+
+        // A PID file left by a process that is gone is taken over
+        std::fs::write(&path, "999999999\n").unwrap();
+        assert!(!is_alive(999_999_999));
+        let pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(read_pid(&path), Some(std::process::id()));
+        drop(pid_file);
+
+        // So is one whose PID was reused by another program
+        let mut other = std::process::Command::new("sleep").arg("5").spawn().unwrap();
+        std::fs::write(&path, format!("{}\n", other.id())).unwrap();
+        assert!(is_alive(other.id()) && !is_mcp_proxy(other.id()));
+        drop(PidFile::create(&path).unwrap());
+        other.kill().unwrap();
+        other.wait().unwrap();
+    }
+}
@@ -0,0 +1,38 @@
+//! Diagnostic resources (`--diagnostic-resources`)
+//! The proxy's recent log lines, metrics and backends, served through resources/list and
+//! resources/read so they can be pulled over the MCP connection itself
+
+use serde_json::{json, Value};
+
+/// Latest log lines, as plain text
+pub const LOGS_URI: &str = "proxy://logs/recent";
+/// `proxy_status` metrics, as JSON
+pub const METRICS_URI: &str = "proxy://metrics";
+/// Running, starting and upgrading backends, as JSON
+pub const BACKENDS_URI: &str = "proxy://backends";
+
+/// `resources/list` entries for the diagnostic resources
+pub fn resources() -> Vec<Value> {
+    [
+        (LOGS_URI, "Proxy log (recent lines)", "text/plain"),
+        (METRICS_URI, "Proxy metrics", "application/json"),
+        (BACKENDS_URI, "Proxy backends", "application/json"),
+    ]
+    .into_iter()
+    .map(|(uri, name, mime_type)| json!({ "uri": uri, "name": name, "mimeType": mime_type }))
+    .collect()
+}
+
+/// Whether a URI names one of the proxy's own resources rather than a backend's
+pub fn is_diagnostic(uri: &str) -> bool {
+    [LOGS_URI, METRICS_URI, BACKENDS_URI].contains(&uri)
+}
+
+/// `resources/read` contents entry; JSON values are pretty-printed
+pub fn contents(uri: &str, value: Value) -> Value {
+    let (mime_type, text) = match value {
+        Value::String(text) => ("text/plain", text),
+        value => ("application/json", serde_json::to_string_pretty(&value).unwrap_or_default()),
+    };
+    json!({ "uri": uri, "mimeType": mime_type, "text": text })
+}
@@ -0,0 +1,66 @@
+//! Per-user file locations
+//! On Linux these follow the XDG base directory spec (config, state, cache and runtime
+//! directories), falling back to the old dot files in `$HOME`; elsewhere `$HOME` is used
+
+use crate::runtime::home_dir;
+use std::path::PathBuf;
+
+/// State directory before XDG support, still used when it exists
+const LEGACY_STATE_DIR: &str = ".mcp-proxy.d";
+
+/// An XDG base directory: the variable when set to an absolute path, else its default under `$HOME`
+fn xdg_dir(var: &str, default: Option<&str>) -> Option<PathBuf> {
+    std::env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| Some(home_dir()?.join(default?)))
+}
+
+/// `$XDG_CONFIG_HOME` (default `~/.config`)
+#[cfg_attr(windows, allow(dead_code))]
+pub fn config_home() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", Some(".config"))
+}
+
+/// Directory for state kept between runs (sessions, backend records, crash reports):
+/// `$XDG_STATE_HOME/mcp-proxy` on Linux unless `~/.mcp-proxy.d` is already there
+pub fn state_dir() -> PathBuf {
+    let legacy = home_dir().unwrap_or_else(std::env::temp_dir).join(LEGACY_STATE_DIR);
+    if cfg!(target_os = "linux") && !legacy.is_dir() {
+        if let Some(dir) = xdg_dir("XDG_STATE_HOME", Some(".local/state")) {
+            return dir.join("mcp-proxy");
+        }
+    }
+    legacy
+}
+
+/// Directory for files that can be recreated at will (spilled responses):
+/// `$XDG_CACHE_HOME/mcp-proxy` on Linux (elsewhere they go to the temp directory)
+pub fn cache_dir() -> Option<PathBuf> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    Some(xdg_dir("XDG_CACHE_HOME", Some(".cache"))?.join("mcp-proxy"))
+}
+
+/// Lock, PID and socket file `name` (a `.mcp-proxy.*` dot file): in `$XDG_RUNTIME_DIR` on
+/// Linux when set, else in `$HOME`
+pub fn runtime_file(name: &str) -> PathBuf {
+    if cfg!(target_os = "linux") {
+        if let Some(dir) = xdg_dir("XDG_RUNTIME_DIR", None) {
+            return dir.join(name.trim_start_matches('.'));
+        }
+    }
+    home_dir().unwrap_or_else(std::env::temp_dir).join(name)
+}
+
+/// Log file `name` (a `.mcp-proxy.*` dot file): in the state directory on Linux, else in `$HOME`
+#[cfg_attr(windows, allow(dead_code))]
+pub fn log_file(name: &str) -> PathBuf {
+    if cfg!(target_os = "linux") {
+        if let Some(dir) = xdg_dir("XDG_STATE_HOME", Some(".local/state")) {
+            return dir.join("mcp-proxy").join(name.trim_start_matches('.'));
+        }
+    }
+    home_dir().unwrap_or_else(std::env::temp_dir).join(name)
+}
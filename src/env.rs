@@ -0,0 +1,117 @@
+//! Per-root backend environment
+//! Loads dotenv files and merges them with inline `env` overrides
+
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Parse dotenv content into ordered `(name, value)` pairs
+///
+/// Supports `# comments`, an optional `export ` prefix, single-quoted (literal)
+/// and double-quoted (`\n`, `\"` escapes) values, and trailing ` #` comments on
+/// unquoted values.
+pub fn parse_dotenv(content: &str) -> Result<Vec<(String, String)>, String> {
+    let mut vars = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (name, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected NAME=value", i + 1))?;
+        let name = name.trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("line {}: invalid variable name {:?}", i + 1, name));
+        }
+        vars.push((name.to_string(), parse_value(value.trim())));
+    }
+    Ok(vars)
+}
+
+fn parse_value(value: &str) -> String {
+    if let Some(inner) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        return inner.to_string();
+    }
+    if let Some(inner) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        return inner.replace("\\n", "\n").replace("\\\"", "\"").replace("\\\\", "\\");
+    }
+    match value.find(" #") {
+        Some(pos) => value[..pos].trim_end().to_string(),
+        None => value.to_string(),
+    }
+}
+
+/// Build the environment for a root's backend: dotenv file first, inline `env` on top
+///
+/// A relative `env_file` is resolved against the root.
+pub fn load_backend_env(
+    root: &Path,
+    env_file: Option<&Path>,
+    env: &HashMap<String, String>,
+) -> Result<Vec<(String, String)>, String> {
+    let mut vars = match env_file {
+        Some(file) => {
+            let file = root.join(file);
+            let content = std::fs::read_to_string(&file)
+                .map_err(|e| format!("failed to read {}: {}", file.display(), e))?;
+            parse_dotenv(&content).map_err(|e| format!("{}: {}", file.display(), e))?
+        }
+        None => Vec::new(),
+    };
+
+    let mut overrides: Vec<_> = env.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    overrides.sort();
+    for (name, value) in overrides {
+        vars.retain(|(n, _)| *n != name);
+        vars.push((name, value));
+    }
+    Ok(vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dotenv() {
+        let content = r#"
+# comment
+AUGMENT_API_TOKEN=abc123
+export ORG = acme # trailing comment
+SINGLE='raw \n value'
+DOUBLE="line1\nline2 \"quoted\""
+EMPTY=
+"#;
+        let vars = parse_dotenv(content).unwrap();
+        assert_eq!(
+            vars,
+            [
+                ("AUGMENT_API_TOKEN".to_string(), "abc123".to_string()),
+                ("ORG".to_string(), "acme".to_string()),
+                ("SINGLE".to_string(), r"raw \n value".to_string()),
+                ("DOUBLE".to_string(), "line1\nline2 \"quoted\"".to_string()),
+                ("EMPTY".to_string(), String::new()),
+            ]
+        );
+        assert!(parse_dotenv("NOT A VAR").is_err());
+        assert!(parse_dotenv("BAD-NAME=1").is_err());
+    }
+
+    #[test]
+    fn test_inline_env_overrides_file() {
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-env-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(".env.proxy"), "TOKEN=from-file\nORG=acme\n").unwrap();
+
+        let mut env = HashMap::new();
+        env.insert("TOKEN".to_string(), "inline".to_string());
+        let vars = load_backend_env(&dir, Some(Path::new(".env.proxy")), &env).unwrap();
+        assert_eq!(
+            vars,
+            [("ORG".to_string(), "acme".to_string()), ("TOKEN".to_string(), "inline".to_string())]
+        );
+        assert!(load_backend_env(&dir, Some(Path::new("missing.env")), &env).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
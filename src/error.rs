@@ -28,9 +28,37 @@ pub enum ProxyError {
 
     #[error("Job object error: {0}")]
     JobObjectError(String),
+
+    #[error("Request queue full: {0}")]
+    QueueFull(String),
 }
 
 // JSON-RPC error codes - Only export codes that are actually used
 pub const ERROR_BACKEND_SPAWN_FAILED: i32 = -32001;
 pub const ERROR_BACKEND_UNAVAILABLE: i32 = -32002;
+pub const ERROR_QUEUE_FULL: i32 = -32003;
+pub const ERROR_POLICY_DENIED: i32 = -32004;
+pub const ERROR_CONFIRMATION_REJECTED: i32 = -32005;
+pub const ERROR_BACKEND_TIMEOUT: i32 = -32006;
+pub const ERROR_ROUTING_FAILED: i32 = -32007;
+pub const ERROR_NOT_INITIALIZED: i32 = -32008;
+pub const ERROR_PARSE_ERROR: i32 = -32700;
+pub const ERROR_INVALID_REQUEST: i32 = -32600;
+pub const ERROR_METHOD_NOT_FOUND: i32 = -32601;
+pub const ERROR_INVALID_PARAMS: i32 = -32602;
 pub const ERROR_INTERNAL_ERROR: i32 = -32603;
+
+impl ProxyError {
+    /// JSON-RPC error code reported to the client for this error
+    pub fn code(&self) -> i32 {
+        match self {
+            ProxyError::BackendSpawnFailed(_) => ERROR_BACKEND_SPAWN_FAILED,
+            ProxyError::BackendUnavailable(_) => ERROR_BACKEND_UNAVAILABLE,
+            ProxyError::BackendTimeout(_) => ERROR_BACKEND_TIMEOUT,
+            ProxyError::RoutingFailed(_) => ERROR_ROUTING_FAILED,
+            ProxyError::QueueFull(_) => ERROR_QUEUE_FULL,
+            ProxyError::JsonRpcParseError(_) => ERROR_PARSE_ERROR,
+            _ => ERROR_INTERNAL_ERROR,
+        }
+    }
+}
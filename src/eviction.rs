@@ -0,0 +1,116 @@
+//! Choice of the backend to evict when `max_backends` is reached
+//! `lru` drops the least recently used backend; `cost` weighs what each backend would cost
+//! to bring back (warm-up time, memory held by its index, request volume) against how long
+//! it has been idle, so a big monorepo backend isn't dropped to admit a trivial one
+
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// How the backend to evict is chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum EvictionPolicy {
+    /// The least recently used backend
+    #[default]
+    Lru,
+    /// The backend that is cheapest to bring back for how long it has been idle
+    Cost,
+}
+
+/// Memory counted as one second of warm-up
+const BYTES_PER_SECOND: u64 = 256 * 1024 * 1024;
+
+/// What is known about an eviction candidate
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BackendCost {
+    /// Time from spawn to a completed handshake
+    pub warmup: Duration,
+    /// Resident memory of the process, where it can be read
+    pub memory_bytes: Option<u64>,
+    /// Requests served by the current process
+    pub requests: u64,
+    /// Time since the last request
+    pub idle: Duration,
+}
+
+impl BackendCost {
+    /// How much keeping the backend is worth; the lowest is evicted
+    /// Rebuild cost (1s, plus warm-up, plus 1s per 256 MiB, scaled up logarithmically by
+    /// request volume) divided by the idle time in minutes
+    pub fn retention(&self) -> f64 {
+        let memory = self.memory_bytes.unwrap_or(0) as f64 / BYTES_PER_SECOND as f64;
+        let rebuild = 1.0 + self.warmup.as_secs_f64() + memory;
+        let volume = 1.0 + (self.requests as f64).ln_1p();
+        rebuild * volume / (1.0 + self.idle.as_secs_f64() / 60.0)
+    }
+}
+
+/// Pick the candidate to evict; `candidates` are ordered least recently used first
+pub fn pick_victim<T>(policy: EvictionPolicy, candidates: Vec<(T, BackendCost)>) -> Option<T> {
+    match policy {
+        EvictionPolicy::Lru => candidates.into_iter().next().map(|(key, _)| key),
+        EvictionPolicy::Cost => candidates
+            .into_iter()
+            .min_by(|(_, a), (_, b)| a.retention().total_cmp(&b.retention()))
+            .map(|(key, _)| key),
+    }
+}
+
+/// Resident memory of a process (Linux only)
+pub fn process_memory(pid: u32) -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+        let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+        let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+        Some(kib * 1024)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = pid;
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cost(warmup: u64, memory_mb: u64, requests: u64, idle: u64) -> BackendCost {
+        BackendCost {
+            warmup: Duration::from_secs(warmup),
+            memory_bytes: Some(memory_mb * 1024 * 1024),
+            requests,
+            idle: Duration::from_secs(idle),
+        }
+    }
+
+    #[test]
+    fn test_lru_takes_first() {
+        let candidates = vec![("old", cost(60, 2048, 500, 300)), ("new", cost(1, 50, 1, 10))];
+        assert_eq!(pick_victim(EvictionPolicy::Lru, candidates), Some("old"));
+    }
+
+    #[test]
+    fn test_cost_keeps_expensive_backend() {
+        let candidates = vec![("monorepo", cost(60, 2048, 500, 300)), ("scratch", cost(1, 50, 1, 10))];
+        assert_eq!(pick_victim(EvictionPolicy::Cost, candidates), Some("scratch"));
+
+        // Long enough idle, even an expensive backend goes
+        let candidates = vec![("monorepo", cost(60, 2048, 500, 86_400)), ("scratch", cost(1, 50, 1, 10))];
+        assert_eq!(pick_victim(EvictionPolicy::Cost, candidates), Some("monorepo"));
+    }
+
+    #[test]
+    fn test_no_candidates() {
+        assert_eq!(pick_victim::<&str>(EvictionPolicy::Cost, Vec::new()), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_process_memory() {
+        assert!(process_memory(std::process::id()).unwrap() > 0);
+    }
+}
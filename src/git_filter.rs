@@ -1,39 +1,109 @@
 //! Git-based file filtering
-//! Uses `git ls-files` to get tracked files, automatically excluding node_modules, dist, etc.
+//! Lists the files that git would consider (tracked or not ignored), automatically
+//! excluding node_modules, dist, etc.
 
-use std::collections::HashSet;
+use crate::{paths, watch};
+use clap::ValueEnum;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::{Match, WalkBuilder, WalkState};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::process::Command;
 use tracing::{debug, info, warn};
 
+/// Default refresh interval for roots whose ignore files can't be watched (`git_cache_ttl_secs`)
+pub const DEFAULT_GIT_CACHE_TTL_SECS: u64 = 60;
+/// Default maximum number of roots with a cached file list (`git_cache_max_entries`)
+pub const DEFAULT_GIT_CACHE_MAX_ENTRIES: usize = 10;
+
+/// How the git filter lists a root's files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum FilterSource {
+    /// Walk the tree honoring nested `.gitignore`, `.git/info/exclude` and global excludes
+    /// (works without git and outside repositories)
+    #[default]
+    Ignore,
+    /// Run `git ls-files` (exact index view, git repositories only)
+    Git,
+    /// Read the git index in-process with gitoxide (no git binary needed, `gix` feature)
+    #[cfg(feature = "gix")]
+    Gix,
+}
+
+impl FilterSource {
+    /// Whether the list comes from the git index (relisted when `.git/index` changes)
+    fn reads_index(self) -> bool {
+        match self {
+            FilterSource::Ignore => false,
+            FilterSource::Git => true,
+            #[cfg(feature = "gix")]
+            FilterSource::Gix => true,
+        }
+    }
+}
+
 /// Git tracked files cache with optimized lookup
 /// 
 /// Stores both the full file paths and their parent directories for O(1) lookup
 pub struct GitTrackedFiles {
     /// Full paths of tracked files
     files: HashSet<PathBuf>,
-    /// All parent directories of tracked files (for prefix matching)
-    directories: HashSet<PathBuf>,
+    /// All parent directories of tracked files, with the number of files below each
+    /// (so entries can be removed incrementally)
+    directories: HashMap<PathBuf, usize>,
 }
 
 impl GitTrackedFiles {
     /// Create from a set of tracked file paths
     pub fn new(files: HashSet<PathBuf>) -> Self {
-        let mut directories = HashSet::new();
-        
-        // Pre-compute all parent directories for O(1) lookup
-        for file in &files {
-            let mut current = file.parent();
-            while let Some(dir) = current {
-                if !directories.insert(dir.to_path_buf()) {
-                    // Already seen this directory and all its parents
-                    break;
+        let mut tracked = Self {
+            files: HashSet::with_capacity(files.len()),
+            directories: HashMap::new(),
+        };
+        for file in files {
+            tracked.insert(file);
+        }
+        tracked
+    }
+
+    /// Add a file (and count it in its parent directories)
+    pub fn insert(&mut self, file: PathBuf) {
+        let mut current = file.parent();
+        while let Some(dir) = current {
+            *self.directories.entry(dir.to_path_buf()).or_default() += 1;
+            current = dir.parent();
+        }
+        if !self.files.insert(file.clone()) {
+            // Already present: undo the counts
+            self.uncount_parents(&file);
+        }
+    }
+
+    /// Remove a file; directories left without files are forgotten
+    pub fn remove(&mut self, file: &Path) {
+        if self.files.remove(file) {
+            self.uncount_parents(file);
+        }
+    }
+
+    fn uncount_parents(&mut self, file: &Path) {
+        let mut current = file.parent();
+        while let Some(dir) = current {
+            if let Some(count) = self.directories.get_mut(dir) {
+                *count -= 1;
+                if *count == 0 {
+                    self.directories.remove(dir);
                 }
-                current = dir.parent();
             }
+            current = dir.parent();
         }
-        
-        Self { files, directories }
     }
     
     /// Check if a path is tracked (file or within tracked directory)
@@ -45,7 +115,7 @@ impl GitTrackedFiles {
         }
         
         // Check if path is a tracked directory - O(1)
-        if self.directories.contains(path) {
+        if self.directories.contains_key(path) {
             return true;
         }
         
@@ -75,6 +145,61 @@ impl GitTrackedFiles {
     }
 }
 
+/// List the files of a workspace root that pass the filter
+/// Returns None when the root can't be listed (the caller then allows every path)
+pub async fn list_files(root: &Path, source: FilterSource) -> Option<GitTrackedFiles> {
+    match source {
+        FilterSource::Ignore => get_unignored_files(root).await,
+        FilterSource::Git => get_git_tracked_files(root).await,
+        #[cfg(feature = "gix")]
+        FilterSource::Gix => get_gix_index_files(root).await,
+    }
+}
+
+/// List files not excluded by ignore rules, without spawning git
+pub async fn get_unignored_files(root: &Path) -> Option<GitTrackedFiles> {
+    let root = root.to_path_buf();
+    tokio::task::spawn_blocking(move || walk_unignored(&root)).await.ok().flatten()
+}
+
+fn walk_unignored(root: &Path) -> Option<GitTrackedFiles> {
+    if !root.is_dir() {
+        debug!("Not a directory, skipping ignore filter: {}", root.display());
+        return None;
+    }
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    WalkBuilder::new(root)
+        // Git lists dotfiles too; only its own directory is skipped
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build_parallel()
+        .run(|| {
+            let tx = tx.clone();
+            Box::new(move |entry| {
+                match entry {
+                    Ok(entry) if entry.file_type().is_some_and(|t| !t.is_dir()) => {
+                        let _ = tx.send(paths::normalize(entry.into_path()));
+                    }
+                    Ok(_) => {}
+                    Err(e) => debug!("Ignore walk error: {}", e),
+                }
+                WalkState::Continue
+            })
+        });
+    drop(tx);
+
+    let tracked = GitTrackedFiles::new(rx.into_iter().collect());
+    info!(
+        "Ignore filter: found {} files in {} (cached {} directories)",
+        tracked.len(),
+        root.display(),
+        tracked.directories.len()
+    );
+    Some(tracked)
+}
+
 /// Get list of git-tracked files for a workspace root (async version)
 pub async fn get_git_tracked_files(root: &Path) -> Option<GitTrackedFiles> {
     // Check if this is a git repository
@@ -112,7 +237,7 @@ pub async fn get_git_tracked_files(root: &Path) -> Option<GitTrackedFiles> {
     let files: HashSet<PathBuf> = stdout
         .lines()
         .filter(|line| !line.is_empty())
-        .map(|line| root.join(line))
+        .map(|line| paths::normalize(root.join(line)))
         .collect();
 
     let file_count = files.len();
@@ -128,8 +253,378 @@ pub async fn get_git_tracked_files(root: &Path) -> Option<GitTrackedFiles> {
     Some(tracked)
 }
 
+/// List the files in the git index without spawning git (`gix` feature)
+///
+/// Untracked files aren't in the index; they are picked up through the ignore rules when
+/// their first change event arrives, like files created after listing.
+#[cfg(feature = "gix")]
+pub async fn get_gix_index_files(root: &Path) -> Option<GitTrackedFiles> {
+    let root = root.to_path_buf();
+    tokio::task::spawn_blocking(move || read_gix_index(&root)).await.ok().flatten()
+}
+
+#[cfg(feature = "gix")]
+fn read_gix_index(root: &Path) -> Option<GitTrackedFiles> {
+    if !root.join(".git").exists() {
+        debug!("Not a git repository: {}", root.display());
+        return None;
+    }
+    let repo = match gix::open(root) {
+        Ok(repo) => repo,
+        Err(e) => {
+            warn!("Failed to open git repository {}: {}", root.display(), e);
+            return None;
+        }
+    };
+    let index = match repo.index_or_empty() {
+        Ok(index) => index,
+        Err(e) => {
+            warn!("Failed to read git index of {}: {}", root.display(), e);
+            return None;
+        }
+    };
+    let files: HashSet<PathBuf> = index
+        .entries()
+        .iter()
+        .map(|entry| paths::normalize(root.join(gix::path::from_bstr(entry.path(&index)))))
+        .collect();
+
+    let tracked = GitTrackedFiles::new(files);
+    info!(
+        "Git filter: read {} index entries in {} (cached {} directories)",
+        tracked.len(),
+        root.display(),
+        tracked.directories.len()
+    );
+    Some(tracked)
+}
+
+/// Extensions dropped by `filter_binary` (on top of configured `binary_extensions`)
+pub const DEFAULT_BINARY_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "bmp", "ico", "webp", "tif", "tiff", "psd",
+    "zip", "tar", "gz", "tgz", "bz2", "xz", "zst", "7z", "rar", "jar", "war",
+    "exe", "dll", "so", "dylib", "a", "lib", "o", "obj", "bin", "class", "pyc", "wasm", "node",
+    "pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx",
+    "mp3", "mp4", "mov", "avi", "mkv", "wav", "flac", "ogg", "webm",
+    "ttf", "otf", "woff", "woff2", "eot", "db", "sqlite", "sqlite3", "iso", "dmg",
+];
+
+/// File filters applied to change events before the git filter
+///
+/// Oversized and binary files are dropped first. Then the `filter_include` / `filter_exclude`
+/// globs are checked against the path relative to its root (or the absolute path): exclude
+/// wins over include, and include forces a path through even when the git filter would drop it.
+#[derive(Default)]
+pub struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    /// Size limit in bytes (0 = unlimited)
+    max_file_size: u64,
+    /// Lowercase extensions (without the dot) treated as binary
+    binary_extensions: HashSet<String>,
+}
+
+/// Verdict of the include/exclude globs for one path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathVerdict {
+    Include,
+    Exclude,
+    /// No pattern matched, the git filter decides
+    Unmatched,
+}
+
+impl PathFilter {
+    pub fn new(include: &[String], exclude: &[String], max_file_size: u64, binary_extensions: &[String]) -> Self {
+        Self {
+            include: compile("filter_include", include),
+            exclude: compile("filter_exclude", exclude),
+            max_file_size,
+            binary_extensions: binary_extensions
+                .iter()
+                .map(|e| e.trim_start_matches('.').to_ascii_lowercase())
+                .collect(),
+        }
+    }
+
+    pub fn check(&self, root: Option<&Path>, path: &Path) -> PathVerdict {
+        if let Some(reason) = self.rejects_content(path) {
+            debug!("Dropping change for {} file {}", reason, path.display());
+            return PathVerdict::Exclude;
+        }
+        let relative = root.and_then(|r| path.strip_prefix(r).ok());
+        let matches = |set: &Option<GlobSet>| {
+            set.as_ref()
+                .is_some_and(|s| s.is_match(path) || relative.is_some_and(|r| s.is_match(r)))
+        };
+        if matches(&self.exclude) {
+            PathVerdict::Exclude
+        } else if matches(&self.include) {
+            PathVerdict::Include
+        } else {
+            PathVerdict::Unmatched
+        }
+    }
+}
+
+impl PathFilter {
+    /// Why a file is dropped regardless of globs (deleted files are never too large)
+    fn rejects_content(&self, path: &Path) -> Option<&'static str> {
+        let is_binary = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| self.binary_extensions.contains(&e.to_ascii_lowercase()));
+        if is_binary {
+            return Some("binary");
+        }
+        if self.max_file_size > 0 {
+            let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            if size > self.max_file_size {
+                return Some("oversized");
+            }
+        }
+        None
+    }
+}
+
+fn compile(setting: &str, patterns: &[String]) -> Option<GlobSet> {
+    if patterns.is_empty() {
+        return None;
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        match Glob::new(pattern) {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(e) => warn!("Ignoring invalid {} pattern {:?}: {}", setting, pattern, e),
+        }
+    }
+    builder.build().ok()
+}
+
+/// Whether ignore rules exclude a path below `root`
+///
+/// Applies global excludes, `.git/info/exclude` and every `.gitignore` from the root down,
+/// deeper files taking precedence; a path inside an excluded directory is excluded too.
+pub fn is_ignored(root: &Path, path: &Path) -> bool {
+    let relative = match path.strip_prefix(root) {
+        Ok(relative) => relative,
+        Err(_) => return false,
+    };
+    if relative.components().any(|c| c.as_os_str() == ".git") {
+        return true;
+    }
+
+    // Lowest precedence first
+    let mut matchers = vec![Gitignore::global().0];
+    let mut exclude = GitignoreBuilder::new(root);
+    exclude.add(root.join(".git").join("info").join("exclude"));
+    matchers.push(exclude.build().unwrap_or_else(|_| Gitignore::empty()));
+    matchers.push(Gitignore::new(root.join(".gitignore")).0);
+
+    let mut current = root.to_path_buf();
+    let mut components = relative.components().peekable();
+    while let Some(component) = components.next() {
+        current.push(component);
+        let is_dir = components.peek().is_some();
+        let decided = matchers
+            .iter()
+            .rev()
+            .map(|m| m.matched(&current, is_dir))
+            .find(|m| !m.is_none());
+        if let Some(Match::Ignore(_)) = decided {
+            return true;
+        }
+        if is_dir {
+            matchers.push(Gitignore::new(current.join(".gitignore")).0);
+        }
+    }
+    false
+}
+
+/// Cached file list of one root
+struct CacheEntry {
+    files: GitTrackedFiles,
+    loaded: Instant,
+    /// Whether ignore-file changes are watched (otherwise the list expires after a TTL)
+    watched: bool,
+}
+
+/// Per-root file lists kept current incrementally
+///
+/// A root is listed once; afterwards change events add new (not ignored) files and drop
+/// deleted ones. Only changes to the ignore rules (`.gitignore`, `.git/info/exclude`) or,
+/// for the `git` source, the index trigger a full relisting.
+pub struct GitFilterCache {
+    source: FilterSource,
+    ttl: Duration,
+    /// Per-root TTLs from `root_overrides`
+    root_ttls: HashMap<PathBuf, Duration>,
+    max_entries: usize,
+    entries: HashMap<PathBuf, CacheEntry>,
+    /// Roots whose ignore rules changed since they were listed (set from the watcher thread)
+    stale: Arc<Mutex<HashSet<PathBuf>>>,
+    watcher: Option<RecommendedWatcher>,
+}
+
+impl GitFilterCache {
+    pub fn new(source: FilterSource, ttl: Duration, max_entries: usize) -> Self {
+        let stale = Arc::new(Mutex::new(HashSet::new()));
+        let stale_roots = stale.clone();
+        let watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+            let event = match result {
+                Ok(event) => event,
+                Err(e) => {
+                    debug!("Ignore file watcher error: {}", e);
+                    return;
+                }
+            };
+            // Our own reads of .gitignore show up as access events
+            if !watch::is_change(&event.kind) {
+                return;
+            }
+            for path in &event.paths {
+                if let Some(root) = rules_file_root(path, source) {
+                    debug!("Ignore rules changed: {}", path.display());
+                    stale_roots.lock().unwrap_or_else(|e| e.into_inner()).insert(root);
+                }
+            }
+        });
+        let watcher = match watcher {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                warn!("Failed to watch ignore files, falling back to periodic refresh: {}", e);
+                None
+            }
+        };
+        Self {
+            source,
+            ttl,
+            root_ttls: HashMap::new(),
+            max_entries,
+            entries: HashMap::new(),
+            stale,
+            watcher,
+        }
+    }
+
+    /// Override the refresh interval for one root
+    pub fn set_root_ttl(&mut self, root: PathBuf, ttl: Duration) {
+        self.root_ttls.insert(root, ttl);
+    }
+
+    fn ttl_for(&self, root: &Path) -> Duration {
+        self.root_ttls.get(root).copied().unwrap_or(self.ttl)
+    }
+
+    /// Number of cached roots
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether a changed path passes the filter, updating the root's list along the way
+    pub async fn is_tracked(&mut self, root: &Path, path: &Path) -> bool {
+        let stale = self.stale.lock().unwrap_or_else(|e| e.into_inner()).remove(root);
+        let expired = self
+            .entries
+            .get(root)
+            .is_some_and(|entry| !entry.watched && entry.loaded.elapsed() > self.ttl_for(root));
+        // Nested .gitignore files aren't watched directly, but their changes come through here
+        let rules_changed = path.file_name().is_some_and(|name| name == ".gitignore");
+        if stale || expired || rules_changed {
+            self.evict(root);
+        }
+
+        if !self.entries.contains_key(root) {
+            while self.entries.len() >= self.max_entries.max(1) {
+                let oldest = self.entries.iter().min_by_key(|(_, e)| e.loaded).map(|(r, _)| r.clone());
+                match oldest {
+                    Some(oldest) => {
+                        debug!("Git cache full, evicting: {}", oldest.display());
+                        self.evict(&oldest);
+                    }
+                    None => break,
+                }
+            }
+            let files = match list_files(root, self.source).await {
+                Some(files) => files,
+                // Root can't be listed (not a directory, or git failed), allow all files
+                None => return true,
+            };
+            info!("Git filter cache populated for {}: {} files", root.display(), files.len());
+            let watched = self.watch(root);
+            self.entries.insert(root.to_path_buf(), CacheEntry { files, loaded: Instant::now(), watched });
+        }
+
+        let entry = match self.entries.get_mut(root) {
+            Some(entry) => entry,
+            None => return true,
+        };
+        if entry.files.is_tracked(path) {
+            // Deleted: still report the change, but forget the file
+            if !path.exists() {
+                entry.files.remove(path);
+            }
+            return true;
+        }
+        // Created since the root was listed
+        if path.is_file() && !is_ignored(root, path) {
+            debug!("Git filter: adding new file {}", path.display());
+            entry.files.insert(path.to_path_buf());
+            return true;
+        }
+        false
+    }
+
+    /// Watch the files whose changes invalidate a root's list
+    fn watch(&mut self, root: &Path) -> bool {
+        let watcher = match self.watcher.as_mut() {
+            Some(watcher) => watcher,
+            None => return false,
+        };
+        // The root itself for .gitignore; .git and .git/info only exist in repositories
+        if let Err(e) = watcher.watch(root, RecursiveMode::NonRecursive) {
+            debug!("Failed to watch {}: {}", root.display(), e);
+            return false;
+        }
+        for dir in [root.join(".git"), root.join(".git").join("info")] {
+            if dir.is_dir() {
+                let _ = watcher.watch(&dir, RecursiveMode::NonRecursive);
+            }
+        }
+        true
+    }
+
+    fn evict(&mut self, root: &Path) {
+        if let Some(entry) = self.entries.remove(root) {
+            if let (true, Some(watcher)) = (entry.watched, self.watcher.as_mut()) {
+                for dir in [root.to_path_buf(), root.join(".git"), root.join(".git").join("info")] {
+                    let _ = watcher.unwatch(&dir);
+                }
+            }
+        }
+    }
+}
+
+/// Root whose list a changed rules file invalidates (`root/.gitignore`,
+/// `root/.git/info/exclude`, and `root/.git/index` for the `git` source)
+fn rules_file_root(path: &Path, source: FilterSource) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let parent = path.parent()?;
+    let is_git_dir = |dir: &Path| dir.file_name().is_some_and(|n| n == ".git");
+    match name {
+        ".gitignore" => Some(parent.to_path_buf()),
+        "exclude" if parent.file_name().is_some_and(|n| n == "info") && parent.parent().is_some_and(is_git_dir) => {
+            parent.parent()?.parent().map(Path::to_path_buf)
+        }
+        "index" if source.reads_index() && is_git_dir(parent) => parent.parent().map(Path::to_path_buf),
+        _ => None,
+    }
+}
+
 /// Legacy function for backward compatibility
 /// Prefer using GitTrackedFiles::is_tracked() directly
+#[allow(dead_code)]
 pub fn is_git_tracked(path: &Path, tracked_files: &GitTrackedFiles) -> bool {
     tracked_files.is_tracked(path)
 }
@@ -180,6 +675,145 @@ mod tests {
         assert!(!is_git_tracked(Path::new("/project/node_modules/foo.js"), &tracked));
     }
     
+    #[test]
+    fn test_ignore_walk_without_git() {
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-ignore-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for (path, content) in [
+            (".gitignore", "target/\n*.log\n"),
+            (".env.example", ""),
+            ("build.log", ""),
+            ("target/debug/app", ""),
+            ("src/main.rs", ""),
+            ("src/.gitignore", "generated.rs\n"),
+            ("src/generated.rs", ""),
+        ] {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        let dir = paths::normalize(dir.canonicalize().unwrap());
+
+        let tracked = walk_unignored(&dir).unwrap();
+        assert!(tracked.is_tracked(&dir.join("src").join("main.rs")));
+        assert!(tracked.is_tracked(&dir.join(".env.example")));
+        assert!(!tracked.is_tracked(&dir.join("build.log")));
+        assert!(!tracked.is_tracked(&dir.join("target").join("debug").join("app")));
+        assert!(!tracked.is_tracked(&dir.join("src").join("generated.rs")));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_path_filter() {
+        let filter = PathFilter::new(
+            &["target/doc/**".to_string()],
+            &["**/fixtures/**".to_string(), "target/doc/big.html".to_string()],
+            0,
+            &[],
+        );
+        let root = Path::new("/repo");
+        assert_eq!(filter.check(Some(root), Path::new("/repo/target/doc/index.html")), PathVerdict::Include);
+        assert_eq!(filter.check(Some(root), Path::new("/repo/target/doc/big.html")), PathVerdict::Exclude);
+        assert_eq!(filter.check(Some(root), Path::new("/repo/tests/fixtures/a.json")), PathVerdict::Exclude);
+        assert_eq!(filter.check(Some(root), Path::new("/repo/src/main.rs")), PathVerdict::Unmatched);
+        assert_eq!(PathFilter::default().check(None, Path::new("/x")), PathVerdict::Unmatched);
+    }
+
+    #[test]
+    fn test_size_and_binary_filter() {
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-sizefilter-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("small.rs"), "fn main() {}").unwrap();
+        std::fs::write(dir.join("huge.json"), vec![b' '; 2048]).unwrap();
+
+        let filter = PathFilter::new(&["**".to_string()], &[], 1024, &[".PNG".to_string(), "wasm".to_string()]);
+        assert_eq!(filter.check(Some(&dir), &dir.join("small.rs")), PathVerdict::Include);
+        assert_eq!(filter.check(Some(&dir), &dir.join("huge.json")), PathVerdict::Exclude);
+        assert_eq!(filter.check(Some(&dir), &dir.join("logo.png")), PathVerdict::Exclude);
+        // Deleted (missing) files pass the size check
+        assert_eq!(filter.check(Some(&dir), &dir.join("gone.json")), PathVerdict::Include);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_incremental_updates() {
+        let mut tracked = GitTrackedFiles::new(HashSet::from([PathBuf::from("/project/src/a.rs")]));
+        tracked.insert(PathBuf::from("/project/src/util/b.rs"));
+        tracked.insert(PathBuf::from("/project/src/util/b.rs"));
+        assert!(tracked.is_tracked(Path::new("/project/src/util")));
+
+        tracked.remove(Path::new("/project/src/util/b.rs"));
+        assert!(!tracked.is_tracked(Path::new("/project/src/util")));
+        assert!(tracked.is_tracked(Path::new("/project/src")));
+        tracked.remove(Path::new("/project/src/a.rs"));
+        assert!(!tracked.is_tracked(Path::new("/project")));
+        assert!(tracked.is_empty());
+    }
+
+    #[test]
+    fn test_is_ignored() {
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-ignored-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join(".gitignore"), "dist/\n*.log\n").unwrap();
+        std::fs::write(dir.join("src").join(".gitignore"), "!keep.log\n").unwrap();
+
+        assert!(!is_ignored(&dir, &dir.join("src").join("main.rs")));
+        assert!(is_ignored(&dir, &dir.join("debug.log")));
+        assert!(!is_ignored(&dir, &dir.join("src").join("keep.log")));
+        assert!(is_ignored(&dir, &dir.join("dist").join("bundle.js")));
+        assert!(is_ignored(&dir, &dir.join(".git").join("index")));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_rules_file_root() {
+        let root = Path::new("/repo");
+        assert_eq!(rules_file_root(&root.join(".gitignore"), FilterSource::Ignore), Some(root.to_path_buf()));
+        let exclude = root.join(".git").join("info").join("exclude");
+        assert_eq!(rules_file_root(&exclude, FilterSource::Ignore), Some(root.to_path_buf()));
+        let index = root.join(".git").join("index");
+        assert_eq!(rules_file_root(&index, FilterSource::Ignore), None);
+        assert_eq!(rules_file_root(&index, FilterSource::Git), Some(root.to_path_buf()));
+        assert_eq!(rules_file_root(&root.join("src").join("index"), FilterSource::Git), None);
+    }
+
+    #[tokio::test]
+    async fn test_cache_tracks_new_and_deleted_files() {
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-gitcache-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir = paths::normalize(dir.canonicalize().unwrap());
+        std::fs::write(dir.join(".gitignore"), "*.tmp\n").unwrap();
+        std::fs::write(dir.join("old.rs"), "").unwrap();
+
+        let mut cache = GitFilterCache::new(FilterSource::Ignore, Duration::from_secs(60), 10);
+        assert!(cache.is_tracked(&dir, &dir.join("old.rs")).await);
+
+        std::fs::write(dir.join("new.rs"), "").unwrap();
+        std::fs::write(dir.join("scratch.tmp"), "").unwrap();
+        assert!(cache.is_tracked(&dir, &dir.join("new.rs")).await);
+        assert!(!cache.is_tracked(&dir, &dir.join("scratch.tmp")).await);
+
+        std::fs::remove_file(dir.join("old.rs")).unwrap();
+        assert!(cache.is_tracked(&dir, &dir.join("old.rs")).await);
+        assert!(!cache.entries[&dir].files.is_tracked(&dir.join("old.rs")));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(feature = "gix")]
+    #[test]
+    fn test_gix_reads_own_index() {
+        let root = paths::normalize(PathBuf::from(env!("CARGO_MANIFEST_DIR")));
+        if !root.join(".git").exists() {
+            return; // Source tarball, nothing to read
+        }
+        let tracked = read_gix_index(&root).unwrap();
+        assert!(tracked.is_tracked(&root.join("Cargo.toml")));
+        assert!(!tracked.is_tracked(&root.join("target").join("debug")));
+    }
+
     #[test]
     fn test_empty_tracked_files() {
         let tracked = GitTrackedFiles::new(HashSet::new());
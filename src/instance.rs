@@ -0,0 +1,90 @@
+//! Named instances (`--instance-name`, `--lock-per-root`)
+//! Scope the single-instance lock, the default admin socket and the daemon's default files, so
+//! separate proxies (one per IDE profile or workspace) can run side by side
+
+use crate::config::Config;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// Identifier of this instance, `None` for the default (unnamed, unscoped) one
+pub fn id(config: &Config) -> Option<String> {
+    let name = config.instance_name.as_deref().map(sanitize).filter(|name| !name.is_empty());
+    let root = config.lock_per_root.then(|| {
+        let root = config.default_root.clone().or_else(|| std::env::current_dir().ok()).unwrap_or_default();
+        // The same workspace reached through a symlink is the same instance
+        format!("root-{}", root_hash(&std::fs::canonicalize(&root).unwrap_or(root)))
+    });
+    match (name, root) {
+        (Some(name), Some(root)) => Some(format!("{}-{}", name, root)),
+        (name, root) => name.or(root),
+    }
+}
+
+/// `.mcp-proxy.<ext>`, or `.mcp-proxy.<instance>.<ext>` for a named instance
+pub fn file_name(config: &Config, ext: &str) -> String {
+    match id(config) {
+        Some(id) => format!(".mcp-proxy.{}.{}", id, ext),
+        None => format!(".mcp-proxy.{}", ext),
+    }
+}
+
+/// Name of the Windows single-instance mutex
+#[cfg_attr(not(windows), allow(dead_code))]
+pub fn mutex_name(config: &Config) -> String {
+    match id(config) {
+        Some(id) => format!("Global\\mcp_proxy_lock_{}", id),
+        None => "Global\\mcp_proxy_lock".to_string(),
+    }
+}
+
+/// Offset from the default admin port for this instance (0 for the default instance)
+#[cfg_attr(unix, allow(dead_code))]
+pub fn port_offset(config: &Config) -> u16 {
+    id(config).map_or(0, |id| {
+        let digest = Sha256::digest(id.as_bytes());
+        1 + u16::from_be_bytes([digest[0], digest[1]]) % 1000
+    })
+}
+
+/// Keep names usable in file names and kernel object names
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+fn root_hash(root: &Path) -> String {
+    Sha256::digest(root.to_string_lossy().as_bytes())
+        .iter()
+        .take(6)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_instance_id() {
+        let config = |args: &[&str]| Config::parse_from(std::iter::once("mcp-proxy").chain(args.iter().copied()));
+
+        assert_eq!(id(&config(&[])), None);
+        assert_eq!(file_name(&config(&[]), "lock"), ".mcp-proxy.lock");
+        assert_eq!(mutex_name(&config(&[])), "Global\\mcp_proxy_lock");
+        assert_eq!(port_offset(&config(&[])), 0);
+
+        let work = config(&["--instance-name", "work/profile 1"]);
+        assert_eq!(file_name(&work, "lock"), ".mcp-proxy.work_profile_1.lock");
+        assert_eq!(mutex_name(&work), "Global\\mcp_proxy_lock_work_profile_1");
+        assert!((1..=1000).contains(&port_offset(&work)));
+
+        let a = id(&config(&["--lock-per-root", "--default-root", "/nonexistent/a"])).unwrap();
+        let b = id(&config(&["--lock-per-root", "--default-root", "/nonexistent/b"])).unwrap();
+        assert!(a.starts_with("root-"));
+        assert_ne!(a, b);
+        let named = id(&config(&["--instance-name", "work", "--lock-per-root", "--default-root", "/nonexistent/a"]));
+        assert_eq!(named, Some(format!("work-{}", a)));
+    }
+}
@@ -1,11 +1,11 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
 
 /// JSON-RPC 2.0 Request
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsonRpcRequest {
     pub jsonrpc: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "deserialize_id")]
     pub id: Option<JsonRpcId>,
     pub method: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -16,7 +16,7 @@ pub struct JsonRpcRequest {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsonRpcResponse {
     pub jsonrpc: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "deserialize_id")]
     pub id: Option<JsonRpcId>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub result: Option<Value>,
@@ -30,18 +30,59 @@ pub struct JsonRpcResponse {
 pub enum JsonRpcId {
     Number(i64),
     String(String),
+    Null,
 }
 
 impl JsonRpcId {
-    #[allow(dead_code)]
     pub fn as_string(&self) -> String {
         match self {
             JsonRpcId::Number(n) => n.to_string(),
             JsonRpcId::String(s) => s.clone(),
+            JsonRpcId::Null => "null".to_string(),
         }
     }
 }
 
+/// A present `"id": null` is `Some(JsonRpcId::Null)`; only a missing id makes a notification
+fn deserialize_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<JsonRpcId>, D::Error> {
+    JsonRpcId::deserialize(deserializer).map(Some)
+}
+
+/// Check that a message other than a response is a well-formed JSON-RPC 2.0 request or
+/// notification, describing the first problem found
+pub fn validate_request(value: &Value) -> Result<(), String> {
+    let obj = match value {
+        Value::Object(obj) => obj,
+        Value::Array(_) => return Err("batch requests are not supported".to_string()),
+        _ => return Err("message is not an object".to_string()),
+    };
+    match obj.get("jsonrpc") {
+        Some(Value::String(version)) if version == "2.0" => {}
+        Some(other) => return Err(format!("unsupported jsonrpc version {}", other)),
+        None => return Err("missing \"jsonrpc\": \"2.0\"".to_string()),
+    }
+    match obj.get("method") {
+        Some(Value::String(_)) => {}
+        Some(_) => return Err("method must be a string".to_string()),
+        None => return Err("missing method".to_string()),
+    }
+    if obj.get("id").is_some_and(|id| serde_json::from_value::<JsonRpcId>(id.clone()).is_err()) {
+        return Err("id must be a string, an integer or null".to_string());
+    }
+    if obj.get("params").is_some_and(|p| !p.is_object() && !p.is_array()) {
+        return Err("params must be an object or an array".to_string());
+    }
+    Ok(())
+}
+
+/// Id to answer an invalid request with: its own id if usable, otherwise null
+pub fn error_id(value: &Value) -> JsonRpcId {
+    value
+        .get("id")
+        .and_then(|id| serde_json::from_value(id.clone()).ok())
+        .unwrap_or(JsonRpcId::Null)
+}
+
 /// JSON-RPC Error object
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsonRpcError {
@@ -60,7 +101,6 @@ impl JsonRpcError {
         }
     }
 
-    #[allow(dead_code)]
     pub fn with_data(mut self, data: Value) -> Self {
         self.data = Some(data);
         self
@@ -109,18 +149,44 @@ impl JsonRpcRequest {
 
     /// Try to extract workspace roots from initialize params
     pub fn get_roots(&self) -> Option<Vec<String>> {
-        let params = self.params.as_ref()?;
-        let roots = params.get("roots")?;
-        let arr = roots.as_array()?;
-        
-        arr.iter()
-            .filter_map(|v| {
-                v.get("uri")
-                    .and_then(|u| u.as_str())
-                    .map(|s| s.to_string())
-            })
-            .collect::<Vec<_>>()
-            .into()
+        root_uris(self.params.as_ref()?)
+    }
+
+    /// Look up a value in params by dotted path (e.g. `_meta.sessionId`)
+    pub fn get_param_path(&self, path: &str) -> Option<&Value> {
+        path.split('.')
+            .try_fold(self.params.as_ref()?, |value, key| value.get(key))
+    }
+
+    /// Extract a session identifier (string or number) from params by dotted path
+    pub fn get_session_id(&self, path: &str) -> Option<String> {
+        match self.get_param_path(path)? {
+            Value::String(s) if !s.is_empty() => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Set `params._meta.<key>`, creating params and `_meta` as needed
+    pub fn set_meta(&mut self, key: &str, value: Value) {
+        let params = self.params.get_or_insert_with(|| Value::Object(Default::default()));
+        if let Some(params) = params.as_object_mut() {
+            let meta = params.entry("_meta").or_insert_with(|| Value::Object(Default::default()));
+            if let Some(meta) = meta.as_object_mut() {
+                meta.insert(key.to_string(), value);
+            }
+        }
+    }
+
+    /// Remove and return `params._meta.<key>`, dropping `_meta` if that leaves it empty
+    pub fn take_meta(&mut self, key: &str) -> Option<Value> {
+        let params = self.params.as_mut()?.as_object_mut()?;
+        let meta = params.get_mut("_meta")?.as_object_mut()?;
+        let value = meta.remove(key);
+        if meta.is_empty() {
+            params.remove("_meta");
+        }
+        value
     }
 
     /// Try to extract a URI from the request params (for routing)
@@ -143,6 +209,29 @@ impl JsonRpcRequest {
     }
 }
 
+/// URIs of a `roots` array (initialize params, listChanged params or a `roots/list` result)
+pub fn root_uris(value: &Value) -> Option<Vec<String>> {
+    let arr = value.get("roots")?.as_array()?;
+
+    arr.iter()
+        .filter_map(|v| {
+            v.get("uri")
+                .and_then(|u| u.as_str())
+                .map(|s| s.to_string())
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
+
+/// Compact JSON of `value` cut to `max_chars` characters (marked with `…` when cut), for logs
+pub fn summarize(value: &Value, max_chars: usize) -> String {
+    let text = value.to_string();
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -207,6 +296,27 @@ mod tests {
         assert_eq!(req.get_uri(), Some("file:///doc.rs".to_string()));
     }
     
+    #[test]
+    fn test_get_session_id() {
+        let json = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"_meta":{"sessionId":"conv-1","turn":7}}}"#;
+        let req: JsonRpcRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(req.get_session_id("_meta.sessionId"), Some("conv-1".to_string()));
+        assert_eq!(req.get_session_id("_meta.turn"), Some("7".to_string()));
+        assert_eq!(req.get_session_id("_meta.missing"), None);
+        assert_eq!(req.get_session_id("sessionId"), None);
+    }
+
+    #[test]
+    fn test_meta_roundtrip() {
+        let mut req: JsonRpcRequest = serde_json::from_str(
+            r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"x"}}"#
+        ).unwrap();
+        req.set_meta("hint", Value::from("/work"));
+        assert_eq!(req.get_param_path("_meta.hint").and_then(|v| v.as_str()), Some("/work"));
+        assert_eq!(req.take_meta("hint"), Some(Value::from("/work")));
+        assert!(req.get_param_path("_meta").is_none());
+    }
+
     #[test]
     fn test_get_roots() {
         let json = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"roots":[{"uri":"file:///project1"},{"uri":"file:///project2"}]}}"#;
@@ -234,7 +344,39 @@ mod tests {
         let str_id = JsonRpcId::String("test-id".to_string());
         assert_eq!(str_id.as_string(), "test-id");
     }
+
+    #[test]
+    fn test_null_id() {
+        let req: JsonRpcRequest = serde_json::from_str(r#"{"jsonrpc":"2.0","id":null,"method":"test"}"#).unwrap();
+        assert_eq!(req.id, Some(JsonRpcId::Null));
+        assert!(!req.is_notification());
+        let response = JsonRpcResponse::success(req.id, Value::Null);
+        assert!(serde_json::to_string(&response).unwrap().contains(r#""id":null"#));
+    }
+
+    #[test]
+    fn test_validate_request() {
+        let check = |json: &str| validate_request(&serde_json::from_str(json).unwrap());
+        assert!(check(r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#).is_ok());
+        assert!(check(r#"{"jsonrpc":"2.0","method":"exit","params":[]}"#).is_ok());
+        assert!(check(r#"{"jsonrpc":"1.0","id":1,"method":"x"}"#).is_err());
+        assert!(check(r#"{"id":1,"method":"x"}"#).is_err());
+        assert!(check(r#"{"jsonrpc":"2.0","id":1,"method":7}"#).is_err());
+        assert!(check(r#"{"jsonrpc":"2.0","id":{"a":1},"method":"x"}"#).is_err());
+        assert!(check(r#"{"jsonrpc":"2.0","id":1,"method":"x","params":"y"}"#).is_err());
+        assert!(check(r#"[{"jsonrpc":"2.0","id":1,"method":"x"}]"#).is_err());
+
+        assert_eq!(error_id(&serde_json::json!({"id": "a", "method": 1})), JsonRpcId::String("a".to_string()));
+        assert_eq!(error_id(&serde_json::json!({"id": [1]})), JsonRpcId::Null);
+    }
     
+    #[test]
+    fn test_summarize() {
+        let value = serde_json::json!({ "query": "ü".repeat(10) });
+        assert_eq!(summarize(&value, 100), r#"{"query":"üüüüüüüüüü"}"#);
+        assert_eq!(summarize(&value, 12), r#"{"query":"üü…"#);
+    }
+
     #[test]
     fn test_error_with_data() {
         let error = JsonRpcError::new(-32000, "Custom error")
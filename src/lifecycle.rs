@@ -0,0 +1,123 @@
+//! The client side of the MCP lifecycle
+//! Requests that arrive before `initialize` has been handled are held and replayed once it has
+//! (or after a short wait), or rejected (`pre_init: reject`); `strict_lifecycle` rejects every
+//! message out of order
+
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+/// How long requests wait for `initialize` before they are handled anyway
+pub const PRE_INIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What to do with a request that arrives before `initialize` has been handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum PreInitPolicy {
+    /// Hold it until `initialize` has been handled
+    #[default]
+    Queue,
+    /// Answer it with an error
+    Reject,
+}
+
+/// Requests that don't wait for `initialize`: the handshake itself and the ones that don't
+/// depend on it
+pub fn is_lifecycle(method: &str) -> bool {
+    matches!(method, "initialize" | "ping" | "shutdown" | "exit")
+}
+
+/// Where the client is in the lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// `initialize` not answered yet
+    Uninitialized,
+    /// `initialize` answered, `notifications/initialized` not received yet
+    Initializing,
+    Operating,
+}
+
+/// Why a message is out of order in `phase` (`strict_lifecycle`), if it is
+pub fn violation(phase: Phase, method: &str, is_notification: bool) -> Option<String> {
+    match (phase, method) {
+        (_, "ping" | "shutdown" | "exit") => None,
+        (Phase::Uninitialized, "initialize") => None,
+        (_, "initialize") => Some("initialize received twice".to_string()),
+        (Phase::Uninitialized, method) => Some(format!("{} received before initialize", method)),
+        (Phase::Initializing, "notifications/initialized") => None,
+        (Phase::Initializing, method) if !is_notification => {
+            Some(format!("{} received before notifications/initialized", method))
+        }
+        (Phase::Operating, "notifications/initialized") => Some("notifications/initialized received twice".to_string()),
+        _ => None,
+    }
+}
+
+/// Raw messages held until `initialize` has been handled, in arrival order
+#[derive(Default)]
+pub struct PreInitQueue {
+    messages: Vec<String>,
+    /// When the first held message arrived
+    since: Option<Instant>,
+}
+
+impl PreInitQueue {
+    pub fn push(&mut self, message: String) {
+        self.since.get_or_insert_with(Instant::now);
+        self.messages.push(message);
+    }
+
+    /// When the held messages stop waiting (`None` while nothing is held)
+    pub fn deadline(&self) -> Option<Instant> {
+        self.since.map(|since| since + PRE_INIT_TIMEOUT)
+    }
+
+    /// Take every held message, oldest first
+    pub fn take(&mut self) -> Vec<String> {
+        self.since = None;
+        std::mem::take(&mut self.messages)
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pre_init_queue() {
+        assert!(is_lifecycle("initialize") && is_lifecycle("ping"));
+        assert!(!is_lifecycle("tools/list"));
+
+        let mut queue = PreInitQueue::default();
+        assert_eq!(queue.deadline(), None);
+        queue.push("first".to_string());
+        let deadline = queue.deadline().unwrap();
+        queue.push("second".to_string());
+        // The wait counts from the first message
+        assert_eq!(queue.deadline(), Some(deadline));
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.take(), vec!["first".to_string(), "second".to_string()]);
+        assert_eq!((queue.len(), queue.deadline()), (0, None));
+    }
+
+    #[test]
+    fn test_violation() {
+        use Phase::*;
+        assert_eq!(violation(Uninitialized, "initialize", false), None);
+        assert_eq!(violation(Uninitialized, "ping", false), None);
+        assert!(violation(Uninitialized, "tools/list", false).unwrap().contains("before initialize"));
+        assert!(violation(Uninitialized, "notifications/initialized", true).is_some());
+        assert!(violation(Initializing, "tools/list", false).unwrap().contains("before notifications/initialized"));
+        assert_eq!(violation(Initializing, "notifications/cancelled", true), None);
+        assert_eq!(violation(Initializing, "notifications/initialized", true), None);
+        assert_eq!(violation(Operating, "tools/call", false), None);
+        assert_eq!(violation(Operating, "initialize", false), Some("initialize received twice".to_string()));
+        assert!(violation(Operating, "notifications/initialized", true).is_some());
+        assert_eq!(violation(Operating, "shutdown", false), None);
+    }
+}
@@ -0,0 +1,356 @@
+//! Response size limit
+//! Shrinks oversized results by cutting the middle out of their text blocks, or spills the
+//! full text to a file served back as a proxy resource, so a huge retrieval result can't
+//! flood the model's context or the stdio pipe
+
+use crate::jsonrpc::{JsonRpcRequest, JsonRpcResponse};
+use crate::middleware::Middleware;
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, warn};
+
+/// URI prefix of spilled results
+pub const SPILL_URI_PREFIX: &str = "mcp-proxy://spill/";
+
+/// Spilled results kept on disk; older ones are deleted
+const MAX_SPILLS: usize = 64;
+
+/// Global counter numbering spill stores, so sessions sharing a directory (`--listen`) never
+/// write or delete each other's files
+static STORE_ID_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+/// What to do with a result above `max_response_bytes`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum OversizeMode {
+    /// Cut the middle out of text blocks
+    #[default]
+    Truncate,
+    /// Save the full text to a file and return a preview with a resource link to it
+    Spill,
+}
+
+/// Text kept from a block however small its share of the budget
+const MIN_KEEP: usize = 256;
+
+/// Rounds of truncation before giving up (JSON escaping makes the first estimate inexact)
+const MAX_ROUNDS: usize = 4;
+
+/// Files holding spilled results, deleted when the store is dropped
+pub struct SpillStore {
+    dir: PathBuf,
+    /// Part of every file name, unique in the process
+    store_id: u64,
+    state: Mutex<SpillState>,
+}
+
+#[derive(Default)]
+struct SpillState {
+    next_id: u64,
+    /// Oldest first: (id, bytes)
+    spills: VecDeque<(u64, usize)>,
+}
+
+impl SpillStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            store_id: STORE_ID_COUNTER.fetch_add(1, Ordering::Relaxed),
+            state: Mutex::new(SpillState::default()),
+        }
+    }
+
+    fn path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("spill-{}-{}-{}.txt", std::process::id(), self.store_id, id))
+    }
+
+    /// Save `text`, returning its resource URI
+    pub fn save(&self, text: &str) -> std::io::Result<String> {
+        std::fs::create_dir_all(&self.dir)?;
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.next_id += 1;
+        let id = state.next_id;
+        std::fs::write(self.path(id), text)?;
+        state.spills.push_back((id, text.len()));
+        while state.spills.len() > MAX_SPILLS {
+            if let Some((old, _)) = state.spills.pop_front() {
+                let _ = std::fs::remove_file(self.path(old));
+            }
+        }
+        Ok(format!("{}{}", SPILL_URI_PREFIX, id))
+    }
+
+    /// Text of a spilled result (`None` for unknown or expired URIs)
+    pub fn read(&self, uri: &str) -> Option<String> {
+        let id: u64 = uri.strip_prefix(SPILL_URI_PREFIX)?.parse().ok()?;
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.spills.iter().find(|(i, _)| *i == id)?;
+        std::fs::read_to_string(self.path(id)).ok()
+    }
+
+    /// `resources/list` entries for the spilled results still kept
+    pub fn resources(&self) -> Vec<Value> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.spills.iter()
+            .map(|(id, size)| spill_resource(&format!("{}{}", SPILL_URI_PREFIX, id), *size))
+            .collect()
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for SpillStore {
+    fn drop(&mut self) {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        for (id, _) in &state.spills {
+            let _ = std::fs::remove_file(self.path(*id));
+        }
+    }
+}
+
+fn spill_resource(uri: &str, size: usize) -> Value {
+    json!({
+        "uri": uri,
+        "name": format!("Full result ({} bytes)", size),
+        "mimeType": "text/plain",
+        "size": size,
+    })
+}
+
+/// Middleware shrinking results above `max_response_bytes`
+pub struct ResponseLimit {
+    max_bytes: usize,
+    /// Set in spill mode
+    spill: Option<Arc<SpillStore>>,
+}
+
+impl ResponseLimit {
+    pub fn new(max_bytes: usize, spill: Option<Arc<SpillStore>>) -> Self {
+        Self { max_bytes, spill }
+    }
+}
+
+impl Middleware for ResponseLimit {
+    fn on_response(&self, request: &JsonRpcRequest, response: &mut JsonRpcResponse) {
+        let result = match response.result {
+            Some(ref mut result) => result,
+            None => return,
+        };
+        let before = encoded_len(result);
+        if before <= self.max_bytes {
+            return;
+        }
+        // Reading a spilled result back is the point of spilling it
+        let uri = request.get_param_path("uri").and_then(|v| v.as_str()).unwrap_or_default();
+        if request.method == "resources/read" && uri.starts_with(SPILL_URI_PREFIX) {
+            return;
+        }
+        if let Some(ref store) = self.spill {
+            match spill_result(result, self.max_bytes, store) {
+                Ok(true) => {
+                    debug!("Spilled {} result of {} bytes to {}", request.method, before, store.dir().display());
+                    return;
+                }
+                Ok(false) => {}
+                Err(e) => warn!("Failed to spill {} result, truncating instead: {}", request.method, e),
+            }
+        }
+        if truncate_result(result, self.max_bytes) {
+            debug!("Truncated {} result from {} to {} bytes", request.method, before, encoded_len(result));
+        } else {
+            warn!("{} result is {} bytes but has no text to truncate", request.method, before);
+        }
+    }
+}
+
+fn encoded_len(value: &Value) -> usize {
+    serde_json::to_string(value).map(|s| s.len()).unwrap_or(0)
+}
+
+/// Text blocks of a result: `content` (tools/call, prompts) and `contents` (resources/read)
+fn text_blocks(result: &mut Value) -> Vec<&mut String> {
+    let mut blocks = Vec::new();
+    let obj = match result.as_object_mut() {
+        Some(obj) => obj,
+        None => return blocks,
+    };
+    for (key, value) in obj.iter_mut() {
+        if key != "content" && key != "contents" {
+            continue;
+        }
+        if let Some(items) = value.as_array_mut() {
+            for item in items {
+                if let Some(Value::String(text)) = item.get_mut("text") {
+                    blocks.push(text);
+                }
+            }
+        }
+    }
+    blocks
+}
+
+/// Replace the text blocks of a result with a preview and a link to the full text saved in `store`
+/// Returns false if the result has no text blocks.
+pub fn spill_result(result: &mut Value, max_bytes: usize, store: &SpillStore) -> std::io::Result<bool> {
+    let texts: Vec<String> = text_blocks(result).iter().map(|t| t.to_string()).collect();
+    if texts.is_empty() {
+        return Ok(false);
+    }
+    let full = texts.join("\n\n");
+    let uri = store.save(&full)?;
+
+    let preview_len = floor_char_boundary(&full, (max_bytes / 2).min(full.len()));
+    let preview = format!(
+        "{}\n\n[... result is {} bytes; the full text is available as resource {} (resources/read) ...]",
+        &full[..preview_len],
+        full.len(),
+        uri
+    );
+    let key = if result.get("content").is_some() { "content" } else { "contents" };
+    if let Some(items) = result.get_mut(key).and_then(|c| c.as_array_mut()) {
+        items.retain(|item| item.get("text").is_none_or(|t| !t.is_string()));
+        let mut link = spill_resource(&uri, full.len());
+        link["type"] = "resource_link".into();
+        items.insert(0, link);
+        items.insert(0, json!({ "type": "text", "text": preview }));
+    }
+    Ok(true)
+}
+
+/// Cut text blocks down (keeping each block's head and tail) until the result fits in
+/// `max_bytes`; every cut is replaced by a marker with the block's original size.
+/// Returns false if the result has no text blocks.
+pub fn truncate_result(result: &mut Value, max_bytes: usize) -> bool {
+    let texts: Vec<String> = text_blocks(result).iter().map(|t| t.to_string()).collect();
+    if texts.is_empty() {
+        return false;
+    }
+    let total: usize = texts.iter().map(|t| t.len()).sum();
+    let mut size = encoded_len(result);
+    let mut budget = total;
+
+    for _ in 0..MAX_ROUNDS {
+        if size <= max_bytes {
+            break;
+        }
+        budget = budget.saturating_sub(size - max_bytes);
+        // Every round cuts the original texts again, each keeping its share of the budget
+        for (text, original) in text_blocks(result).into_iter().zip(&texts) {
+            let share = (budget as u128 * original.len() as u128 / total as u128) as usize;
+            *text = cut_middle(original, share.max(MIN_KEEP), original.len());
+        }
+        size = encoded_len(result);
+    }
+    true
+}
+
+/// Keep about `keep` bytes of `text` (half head, half tail) around a truncation marker
+fn cut_middle(text: &str, keep: usize, original_len: usize) -> String {
+    if text.len() <= keep {
+        return text.to_string();
+    }
+    let head_end = floor_char_boundary(text, keep / 2);
+    let tail_start = ceil_char_boundary(text, text.len() - keep / 2);
+    format!(
+        "{}\n\n[... {} bytes truncated by mcp-proxy, {} bytes in total ...]\n\n{}",
+        &text[..head_end],
+        tail_start - head_end,
+        original_len,
+        &text[tail_start..]
+    )
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_small_results_untouched() {
+        let mut result = json!({"content": [{"type": "text", "text": "short"}]});
+        let copy = result.clone();
+        assert!(truncate_result(&mut result, 1000));
+        assert_eq!(result, copy);
+    }
+
+    #[test]
+    fn test_truncates_blocks_proportionally() {
+        let big = format!("HEAD{}TAIL", "x".repeat(20_000));
+        let small = "y".repeat(2_000);
+        let mut result = json!({"content": [
+            {"type": "text", "text": big},
+            {"type": "text", "text": small},
+        ]});
+        assert!(truncate_result(&mut result, 8_000));
+        assert!(encoded_len(&result) <= 8_000);
+        let first = result["content"][0]["text"].as_str().unwrap();
+        assert!(first.starts_with("HEAD") && first.ends_with("TAIL"));
+        assert!(first.contains("20008 bytes in total"));
+    }
+
+    #[test]
+    fn test_multibyte_boundaries() {
+        let text = "é".repeat(5_000);
+        let cut = cut_middle(&text, 1_001, text.len());
+        assert!(cut.starts_with('é') && cut.ends_with('é'));
+    }
+
+    #[test]
+    fn test_spill_and_read_back() {
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-spill-test-{}", std::process::id()));
+        let store = SpillStore::new(dir.clone());
+        let text = "z".repeat(10_000);
+        let mut result = json!({"content": [{"type": "text", "text": text}]});
+        assert!(spill_result(&mut result, 1_000, &store).unwrap());
+
+        let content = result["content"].as_array().unwrap();
+        assert_eq!(content.len(), 2);
+        assert!(encoded_len(&result) < 1_000 + 200);
+        let uri = content[1]["uri"].as_str().unwrap();
+        assert_eq!(content[1]["type"], "resource_link");
+        assert_eq!(store.read(uri).unwrap(), text);
+        assert_eq!(store.resources().len(), 1);
+        assert!(store.read("mcp-proxy://spill/999").is_none());
+
+        // Another session's store in the same directory keeps its own files
+        let other = SpillStore::new(dir.clone());
+        let mut result = json!({"content": [{"type": "text", "text": "y".repeat(10_000)}]});
+        assert!(spill_result(&mut result, 1_000, &other).unwrap());
+        assert_eq!(result["content"][1]["uri"], uri);
+        assert_eq!(store.read(uri).unwrap(), text);
+        drop(other);
+        assert_eq!(store.read(uri).unwrap(), text);
+
+        drop(store);
+        assert!(std::fs::read_dir(&dir).unwrap().next().is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_no_text_blocks() {
+        let mut result = json!({"tools": [{"name": "x".repeat(100)}]});
+        assert!(!truncate_result(&mut result, 10));
+    }
+}
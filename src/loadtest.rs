@@ -0,0 +1,254 @@
+//! Synthetic load (`mcp-proxy loadtest`)
+//! Sends tools/call requests at a fixed rate and concurrency to a running proxy (`--connect`)
+//! or to one started in-process with mock backends, then reports throughput and latency
+
+use crate::config::Config;
+use crate::logging::LevelHandle;
+use crate::proxy::{InProcessProxy, McpProxy};
+use crate::transport::{BackendAddress, BackendReader, BackendWriter};
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
+use tracing::level_filters::LevelFilter;
+
+/// How long outstanding requests get to finish once sending stops
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// `loadtest` options
+#[derive(Args, Debug, Clone)]
+pub struct LoadtestArgs {
+    /// Proxy to drive (`host:port` or `unix:/path`, as given to its `--listen`); without it a
+    /// proxy is started in-process with `--mock-backend` and the other options given before
+    /// `loadtest`
+    #[arg(long)]
+    pub connect: Option<String>,
+
+    /// Requests in flight at most
+    #[arg(long, default_value_t = 8)]
+    pub concurrency: usize,
+
+    /// Requests started per second (0 for as fast as `--concurrency` allows)
+    #[arg(long, default_value_t = 0)]
+    pub rps: u64,
+
+    /// Seconds to keep sending
+    #[arg(long, default_value_t = 10)]
+    pub duration: u64,
+
+    /// Tool to call
+    #[arg(long, default_value = "codebase-retrieval")]
+    pub tool: String,
+}
+
+/// Requests sent and not yet answered: send time, and the concurrency slot they hold
+type InFlight = Arc<Mutex<HashMap<u64, (Instant, OwnedSemaphorePermit)>>>;
+
+#[derive(Default)]
+struct Results {
+    latencies: Vec<Duration>,
+    errors: u64,
+}
+
+/// Run the load test and print its report
+pub async fn run(mut config: Config, args: LoadtestArgs, log_handle: LevelHandle) -> Result<()> {
+    if args.concurrency == 0 {
+        bail!("--concurrency must be at least 1");
+    }
+    // The in-process proxy, stopped at the end so it shuts its backends down
+    let mut in_process = None;
+    let (reader, writer): (BackendReader, BackendWriter) = match args.connect {
+        Some(ref address) => {
+            let parsed = BackendAddress::parse(address).map_err(|e| anyhow::anyhow!("Invalid --connect address: {}", e))?;
+            parsed.connect().await.with_context(|| format!("No proxy is listening on {}", address))?
+        }
+        None => {
+            // Per-request logging would drown the report
+            if config.log_level == "info" {
+                let _ = log_handle.modify(|filter| *filter = LevelFilter::WARN);
+            }
+            config.mock_backend = true;
+            if config.default_root.is_none() {
+                config.default_root = std::env::current_dir().ok();
+            }
+            let mut proxy = McpProxy::new(config)?;
+            proxy.set_log_handle(log_handle);
+            let (proxy, reader, writer) = InProcessProxy::start(proxy);
+            in_process = Some(proxy);
+            (reader, writer)
+        }
+    };
+
+    let in_flight: InFlight = Arc::default();
+    let results = Arc::new(Mutex::new(Results::default()));
+    let (initialized_tx, initialized_rx) = oneshot::channel();
+    let reader_task = tokio::spawn(read_responses(reader, in_flight.clone(), results.clone(), initialized_tx));
+
+    let mut writer = writer;
+    let initialize = json!({
+        "jsonrpc": "2.0",
+        "id": 0,
+        "method": "initialize",
+        "params": {
+            "protocolVersion": "2024-11-05",
+            "capabilities": {},
+            "clientInfo": { "name": "mcp-proxy-loadtest", "version": env!("CARGO_PKG_VERSION") },
+        },
+    });
+    send(&mut writer, &initialize).await?;
+    tokio::time::timeout(DRAIN_TIMEOUT, initialized_rx)
+        .await
+        .context("The proxy did not answer initialize")?
+        .context("The proxy closed the connection")?;
+    send(&mut writer, &json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).await?;
+
+    println!(
+        "Sending {} for {}s, concurrency {}, {}",
+        args.tool,
+        args.duration,
+        args.concurrency,
+        match args.rps {
+            0 => "unlimited rate".to_string(),
+            rps => format!("{} req/s", rps),
+        }
+    );
+    let slots = Arc::new(Semaphore::new(args.concurrency));
+    let mut ticker = (args.rps > 0).then(|| tokio::time::interval(Duration::from_secs_f64(1.0 / args.rps as f64)));
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(args.duration);
+    let mut sent = 0u64;
+    while Instant::now() < deadline {
+        if let Some(ref mut ticker) = ticker {
+            ticker.tick().await;
+        }
+        let permit = match tokio::time::timeout_at(deadline.into(), slots.clone().acquire_owned()).await {
+            Ok(permit) => permit?,
+            Err(_) => break,
+        };
+        sent += 1;
+        in_flight.lock().unwrap().insert(sent, (Instant::now(), permit));
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": sent,
+            "method": "tools/call",
+            "params": { "name": args.tool, "arguments": { "information_request": format!("loadtest request {}", sent) } },
+        });
+        send(&mut writer, &request).await?;
+    }
+    let sending = started.elapsed();
+
+    // Wait for the stragglers: every slot free again means nothing is in flight
+    let drained = tokio::time::timeout(DRAIN_TIMEOUT, slots.acquire_many(args.concurrency as u32)).await.is_ok();
+    let elapsed = started.elapsed();
+    reader_task.abort();
+
+    let results = std::mem::take(&mut *results.lock().unwrap());
+    let lost = in_flight.lock().unwrap().len();
+    print!("{}", report(sent, &results, lost, sending, elapsed));
+    if !drained {
+        println!("{} request(s) still unanswered after {}s", lost, DRAIN_TIMEOUT.as_secs());
+    }
+    if let Some(proxy) = in_process {
+        proxy.stop().await;
+    }
+    Ok(())
+}
+
+async fn send(writer: &mut BackendWriter, message: &Value) -> Result<()> {
+    let mut line = message.to_string();
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await.context("The proxy closed the connection")?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Match responses to requests, recording latencies and freeing their slots
+async fn read_responses(
+    reader: BackendReader,
+    in_flight: InFlight,
+    results: Arc<Mutex<Results>>,
+    initialized: oneshot::Sender<()>,
+) {
+    let mut initialized = Some(initialized);
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let Ok(message) = serde_json::from_str::<Value>(&line) else { continue };
+        // Notifications and requests from the proxy carry a method
+        if message.get("method").is_some() {
+            continue;
+        }
+        let Some(id) = message.get("id").and_then(Value::as_u64) else { continue };
+        if id == 0 {
+            if let Some(initialized) = initialized.take() {
+                let _ = initialized.send(());
+            }
+            continue;
+        }
+        let Some((sent_at, _permit)) = in_flight.lock().unwrap().remove(&id) else { continue };
+        let mut results = results.lock().unwrap();
+        results.latencies.push(sent_at.elapsed());
+        if message.get("error").is_some() || message.pointer("/result/isError") == Some(&Value::Bool(true)) {
+            results.errors += 1;
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted latencies
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn report(sent: u64, results: &Results, lost: usize, sending: Duration, elapsed: Duration) -> String {
+    let mut latencies = results.latencies.clone();
+    latencies.sort();
+    let ms = |d: Duration| format!("{:.1}ms", d.as_secs_f64() * 1000.0);
+    let completed = latencies.len();
+    let mut report = format!(
+        "Requests:   {} sent, {} completed, {} errors, {} unanswered\n",
+        sent, completed, results.errors, lost
+    );
+    report += &format!(
+        "Throughput: {:.1} req/s ({} completed in {:.2}s; sending took {:.2}s)\n",
+        completed as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        completed,
+        elapsed.as_secs_f64(),
+        sending.as_secs_f64()
+    );
+    report += &format!(
+        "Latency:    p50 {}, p90 {}, p99 {}, max {}\n",
+        ms(percentile(&latencies, 50.0)),
+        ms(percentile(&latencies, 90.0)),
+        ms(percentile(&latencies, 99.0)),
+        ms(latencies.last().copied().unwrap_or_default())
+    );
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let latencies: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&latencies, 50.0), Duration::from_millis(50));
+        assert_eq!(percentile(&latencies, 99.0), Duration::from_millis(99));
+        assert_eq!(percentile(&latencies, 100.0), Duration::from_millis(100));
+        assert_eq!(percentile(&latencies[..1], 90.0), Duration::from_millis(1));
+        assert_eq!(percentile(&[], 50.0), Duration::ZERO);
+
+        let results = Results { latencies, errors: 2 };
+        let text = report(100, &results, 0, Duration::from_secs(1), Duration::from_secs(2));
+        assert!(text.contains("100 sent, 100 completed, 2 errors"));
+        assert!(text.contains("50.0 req/s"));
+        assert!(text.contains("p99 99.0ms"));
+    }
+}
@@ -0,0 +1,150 @@
+//! Proxy log output and the MCP logging capability
+//! The tracing filter sits behind a reload handle so `logging/setLevel` can change it at runtime;
+//! the latest lines are also kept in memory for the `proxy://logs/recent` resource
+
+use crate::logfile::RotatingFile;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, reload, Registry};
+
+/// Handle for changing the proxy's log level after startup
+pub type LevelHandle = reload::Handle<LevelFilter, Registry>;
+
+/// `params._meta` key carrying a request's trace id (reused when the client sends one, added
+/// for the backend with `--inject-trace-id`)
+pub const TRACE_ID_META: &str = "traceId";
+
+/// MCP (RFC 5424) logging levels, least to most severe
+const MCP_LEVELS: &[&str] = &["debug", "info", "notice", "warning", "error", "critical", "alert", "emergency"];
+
+/// Log lines kept for `proxy://logs/recent`
+const RECENT_LINES: usize = 200;
+
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Install the subscriber (writing to stderr, or to `log_file`) and return the handle to its level filter
+pub fn init(level: LevelFilter, log_file: Option<RotatingFile>) -> LevelHandle {
+    let (filter, handle) = reload::Layer::new(level);
+    let writer = match log_file {
+        Some(file) => BoxMakeWriter::new(Mutex::new(file)),
+        None => BoxMakeWriter::new(std::io::stderr),
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(writer).with_ansi(false))
+        .with(fmt::layer().with_writer(RecentWriter::default).with_ansi(false))
+        .init();
+    handle
+}
+
+/// The latest log lines, oldest first
+pub fn recent_lines() -> Vec<String> {
+    RECENT.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+}
+
+/// Collects one formatted event, appending its lines to `RECENT` when dropped (dropping the
+/// oldest beyond `RECENT_LINES`)
+#[derive(Default)]
+struct RecentWriter(Vec<u8>);
+
+impl std::io::Write for RecentWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for RecentWriter {
+    fn drop(&mut self) {
+        let text = String::from_utf8_lossy(&self.0);
+        let mut recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            if recent.len() == RECENT_LINES {
+                recent.pop_front();
+            }
+            recent.push_back(line.to_string());
+        }
+    }
+}
+
+/// A fresh correlation id for a request: 16 hex digits, unique within the process and unlikely
+/// to repeat across processes (seeded from the start time and PID)
+pub fn new_trace_id() -> String {
+    static SEED: OnceLock<u64> = OnceLock::new();
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let seed = *SEED.get_or_init(|| {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+        nanos ^ (u64::from(std::process::id()) << 32)
+    });
+    // Odd multiplier: a bijection, so ids don't repeat until the counter wraps
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    format!("{:016x}", seed.wrapping_add(n.wrapping_mul(0x9e37_79b9_7f4a_7c15)))
+}
+
+/// `--log-level` value to a filter (unknown values mean info)
+pub fn parse_level(level: &str) -> LevelFilter {
+    match level {
+        "trace" => LevelFilter::TRACE,
+        "debug" => LevelFilter::DEBUG,
+        "warn" => LevelFilter::WARN,
+        "error" => LevelFilter::ERROR,
+        _ => LevelFilter::INFO,
+    }
+}
+
+/// Severity rank of an MCP logging level (`None` if it isn't one)
+pub fn mcp_rank(level: &str) -> Option<usize> {
+    MCP_LEVELS.iter().position(|l| *l == level)
+}
+
+/// Tracing filter matching an MCP logging level
+pub fn mcp_to_filter(level: &str) -> Option<LevelFilter> {
+    Some(match mcp_rank(level)? {
+        0 => LevelFilter::DEBUG,
+        1 | 2 => LevelFilter::INFO,
+        3 => LevelFilter::WARN,
+        _ => LevelFilter::ERROR,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mcp_levels() {
+        assert_eq!(mcp_to_filter("notice"), Some(LevelFilter::INFO));
+        assert_eq!(mcp_to_filter("warning"), Some(LevelFilter::WARN));
+        assert_eq!(mcp_to_filter("emergency"), Some(LevelFilter::ERROR));
+        assert_eq!(mcp_to_filter("verbose"), None);
+        assert!(mcp_rank("error") > mcp_rank("warning"));
+    }
+
+    #[test]
+    fn test_recent_lines() {
+        use std::io::Write;
+        for n in 0..RECENT_LINES + 5 {
+            writeln!(RecentWriter::default(), "line {}", n).unwrap();
+        }
+        let lines = recent_lines();
+        assert_eq!(lines.len(), RECENT_LINES);
+        assert_eq!(lines.first().map(String::as_str), Some("line 5"));
+        assert_eq!(lines.last().map(String::as_str), Some(format!("line {}", RECENT_LINES + 4).as_str()));
+    }
+
+    #[test]
+    fn test_new_trace_id() {
+        let ids: std::collections::HashSet<String> = (0..1000).map(|_| new_trace_id()).collect();
+        assert_eq!(ids.len(), 1000);
+        assert!(ids.iter().all(|id| id.len() == 16 && id.chars().all(|c| c.is_ascii_hexdigit())));
+    }
+}
@@ -1,27 +1,90 @@
+mod admin;
+mod audit;
+mod backend;
+mod backend_log;
+mod balance;
+mod canary;
 mod config;
+mod config_file;
+mod confirm;
+mod container;
+mod crash;
+mod diagnostics;
+mod dirs;
+mod env;
 mod error;
+mod eviction;
+mod git_filter;
+mod instance;
 mod jsonrpc;
-mod backend;
+mod lifecycle;
+mod limit;
+mod loadtest;
+mod logfile;
+mod logging;
+mod metrics;
+mod middleware;
+mod mock;
+mod order;
+mod parent;
+mod paths;
+mod policy;
 mod proxy;
+mod queue;
+mod redact;
+mod repl;
+mod retry;
+mod routing;
+mod runtime;
+mod server;
+mod servers;
+mod shadow;
+mod ssh;
+mod state;
 mod throttle;
-mod git_filter;
+mod tools;
+mod transport;
+mod version;
+mod watch;
+mod wsl;
+
+#[cfg(feature = "wasm-plugins")]
+mod plugin;
+
+#[cfg(feature = "scripting")]
+mod script;
 
 #[cfg(windows)]
 mod job_object;
 
+#[cfg(windows)]
+mod service;
+
 #[cfg(unix)]
 mod process_group;
 
+#[cfg(unix)]
+mod daemon;
+
+#[cfg(target_os = "linux")]
+mod systemd;
+
 use anyhow::Result;
 use clap::Parser;
-use tracing::{error, info, Level};
-use tracing_subscriber::FmtSubscriber;
+use tracing::{error, info};
+#[cfg(unix)]
+use tracing::warn;
 
-use config::Config;
+use admin::AdminHub;
+use config::{Command, Config};
 use proxy::McpProxy;
 
+/// Rotation of `--log-file`
+const LOG_FILE_MAX_BYTES: u64 = 10 * 1024 * 1024;
+const LOG_FILE_COUNT: usize = 5;
+
 #[cfg(windows)]
-use windows::core::w;
+use windows::core::HSTRING;
 
 #[cfg(windows)]
 use windows::Win32::Foundation::{CloseHandle, GetLastError, HANDLE, ERROR_ALREADY_EXISTS};
@@ -46,13 +109,13 @@ impl Drop for SingleInstanceMutex {
 }
 
 #[cfg(windows)]
-fn acquire_single_instance_mutex() -> Result<SingleInstanceMutex> {
+fn acquire_single_instance_mutex(name: &str) -> Result<SingleInstanceMutex> {
     unsafe {
-        let handle = CreateMutexW(None, false, w!("Global\\mcp_proxy_lock"))?;
+        let handle = CreateMutexW(None, false, &HSTRING::from(name))?;
         let last_error = GetLastError();
         if last_error == ERROR_ALREADY_EXISTS {
             let _ = CloseHandle(handle);
-            anyhow::bail!("mcp-proxy is already running (Global\\mcp_proxy_lock exists)");
+            anyhow::bail!("mcp-proxy is already running ({} exists)", name);
         }
         Ok(SingleInstanceMutex { handle })
     }
@@ -61,6 +124,8 @@ fn acquire_single_instance_mutex() -> Result<SingleInstanceMutex> {
 #[cfg(unix)]
 use std::fs::{File, OpenOptions};
 #[cfg(unix)]
+use std::io::Write;
+#[cfg(unix)]
 use std::os::unix::fs::OpenOptionsExt;
 
 #[cfg(unix)]
@@ -72,61 +137,163 @@ struct SingleInstanceLock {
 #[cfg(unix)]
 impl Drop for SingleInstanceLock {
     fn drop(&mut self) {
-        let _ = std::fs::remove_file(&self.path);
+        // Leave the file alone if another instance has reclaimed it meanwhile
+        if daemon::read_pid(&self.path) == Some(std::process::id()) {
+            let _ = std::fs::remove_file(&self.path);
+        }
     }
 }
 
+/// Outcome of trying to lock the lock file
 #[cfg(unix)]
-fn acquire_single_instance_lock() -> Result<SingleInstanceLock> {
-    let lock_path = std::env::var("HOME")
-        .map(|h| std::path::PathBuf::from(h).join(".mcp-proxy.lock"))
-        .unwrap_or_else(|_| std::path::PathBuf::from("/tmp/mcp-proxy.lock"));
-    
+enum LockAttempt {
+    Acquired(File),
+    /// Another process holds the lock
+    Held,
+    /// The filesystem doesn't support locking; only the PID in the file tells
+    Unsupported(File),
+}
+
+#[cfg(unix)]
+fn try_lock(lock_path: &std::path::Path) -> Result<LockAttempt> {
     let file = OpenOptions::new()
         .write(true)
         .create(true)
+        .truncate(false)
         .mode(0o600)
-        .open(&lock_path)?;
+        .open(lock_path)?;
     
     // Use libc flock directly for simpler API
     let fd = std::os::unix::io::AsRawFd::as_raw_fd(&file);
     let result = unsafe { libc::flock(fd, libc::LOCK_EX | libc::LOCK_NB) };
     
     if result == 0 {
-        Ok(SingleInstanceLock { _file: file, path: lock_path })
-    } else {
-        let errno = std::io::Error::last_os_error();
-        if errno.raw_os_error() == Some(libc::EWOULDBLOCK) {
-            anyhow::bail!("mcp-proxy is already running (lock file: {})", lock_path.display());
-        } else {
-            anyhow::bail!("Failed to acquire lock: {}", errno);
-        }
+        return Ok(LockAttempt::Acquired(file));
+    }
+    let errno = std::io::Error::last_os_error();
+    match errno.raw_os_error() {
+        Some(libc::EWOULDBLOCK) => Ok(LockAttempt::Held),
+        Some(libc::ENOLCK) | Some(libc::EOPNOTSUPP) => Ok(LockAttempt::Unsupported(file)),
+        _ => anyhow::bail!("Failed to acquire lock: {}", errno),
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let config = Config::parse();
-    
-    // Initialize logging
-    let log_level = match config.log_level.as_str() {
-        "trace" => Level::TRACE,
-        "debug" => Level::DEBUG,
-        "info" => Level::INFO,
-        "warn" => Level::WARN,
-        "error" => Level::ERROR,
-        _ => Level::INFO,
+#[cfg(unix)]
+fn acquire_single_instance_lock(file_name: &str) -> Result<SingleInstanceLock> {
+    let lock_path = dirs::runtime_file(file_name);
+    let holder = || daemon::read_pid(&lock_path).filter(|pid| daemon::is_mcp_proxy(*pid));
+    let already_running = |pid: Option<u32>| match pid {
+        Some(pid) => anyhow::anyhow!("mcp-proxy is already running (PID {}, lock file: {})", pid, lock_path.display()),
+        None => anyhow::anyhow!("mcp-proxy is already running (lock file: {})", lock_path.display()),
+    };
+
+    let mut file = match try_lock(&lock_path)? {
+        LockAttempt::Acquired(file) => file,
+        LockAttempt::Unsupported(file) => {
+            if let Some(pid) = holder() {
+                return Err(already_running(Some(pid)));
+            }
+            warn!("{} cannot be locked on this filesystem, relying on the PID it holds", lock_path.display());
+            file
+        }
+        LockAttempt::Held => {
+            // A lock outliving its process (e.g. on network filesystems after SIGKILL)
+            if let Some(pid) = daemon::read_pid(&lock_path).filter(|pid| !daemon::is_mcp_proxy(*pid)) {
+                warn!("Reclaiming stale lock {} (PID {} is not a running mcp-proxy)", lock_path.display(), pid);
+                std::fs::remove_file(&lock_path)?;
+                match try_lock(&lock_path)? {
+                    LockAttempt::Acquired(file) => file,
+                    _ => return Err(already_running(holder())),
+                }
+            } else {
+                return Err(already_running(holder()));
+            }
+        }
+    };
+    file.set_len(0)?;
+    writeln!(file, "{}", std::process::id())?;
+    Ok(SingleInstanceLock { _file: file, path: lock_path })
+}
+
+fn main() -> Result<()> {
+    let mut config = Config::parse();
+    // The lock scope and the daemon's files must not depend on whether a setting came from the
+    // command line or the file
+    config.merge_early_file_settings();
+
+    // Detach before the runtime starts its threads
+    #[cfg(unix)]
+    let _pid_file = {
+        if config.daemonize {
+            let log_file = dirs::log_file(&instance::file_name(&config, "log"));
+            let pid_file = dirs::runtime_file(&instance::file_name(&config, "pid"));
+            config.log_file.get_or_insert(log_file);
+            let pid_file = config.pid_file.get_or_insert(pid_file);
+            // Checked here too, while errors still reach the terminal
+            daemon::ensure_not_running(pid_file)?;
+            daemon::daemonize(&config)?;
+        }
+        config.pid_file.as_deref().map(daemon::PidFile::create).transpose()?
     };
-    
-    FmtSubscriber::builder()
-        .with_max_level(log_level)
-        .with_writer(std::io::stderr)
-        .with_ansi(false)
-        .init();
+
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+    let result = runtime.block_on(run(config));
+    // Don't wait for the blocking stdin read when stopping with the client still connected
+    runtime.shutdown_background();
+    result
+}
+
+async fn run(mut config: Config) -> Result<()> {
+    // Initialize logging (the level can be changed later through logging/setLevel)
+    let log_file = match config.log_file.as_deref() {
+        Some(path) => Some(
+            logfile::RotatingFile::open(path, LOG_FILE_MAX_BYTES, LOG_FILE_COUNT)
+                .map_err(|e| anyhow::anyhow!("Failed to open log file {}: {}", path.display(), e))?,
+        ),
+        None => None,
+    };
+    let log_handle = logging::init(logging::parse_level(&config.log_level), log_file);
+    crash::install_hook();
+
+    match config.command.take() {
+        Some(Command::Service { action }) => {
+            #[cfg(windows)]
+            return service::run(action, config, log_handle).await;
+            #[cfg(not(windows))]
+            {
+                let _ = action;
+                anyhow::bail!("`service` is only available on Windows");
+            }
+        }
+        Some(Command::SystemdInstall) => {
+            #[cfg(target_os = "linux")]
+            return systemd::install(&config);
+            #[cfg(not(target_os = "linux"))]
+            anyhow::bail!("`systemd-install` is only available on Linux");
+        }
+        Some(Command::Status) => return admin::run_command(&config, "status").await,
+        Some(Command::Stop) => return admin::run_command(&config, "stop").await,
+        Some(Command::Reload) => return admin::run_command(&config, "reload").await,
+        Some(Command::MockBackend(args)) => return mock::run(args).await,
+        Some(Command::Loadtest(args)) => return loadtest::run(config, args, log_handle).await,
+        Some(Command::CheckConfig) => return config.check_config_file(),
+        Some(Command::ConfigSchema) => {
+            println!("{}", serde_json::to_string_pretty(&Config::config_schema())?);
+            return Ok(());
+        }
+        Some(Command::Repl(args)) => return repl::run(config, args, log_handle).await,
+        None => {}
+    }
+
+    // A socket passed by systemd socket activation takes the place of binding `--listen`
+    #[cfg(target_os = "linux")]
+    let inherited = systemd::take_listener()?;
+    #[cfg(not(target_os = "linux"))]
+    let inherited = None;
 
     #[cfg(windows)]
     let _single_instance_mutex = if config.single_instance {
-        match acquire_single_instance_mutex() {
+        match acquire_single_instance_mutex(&instance::mutex_name(&config)) {
             Ok(m) => Some(m),
             Err(e) => {
                 error!("{}", e);
@@ -139,7 +306,7 @@ async fn main() -> Result<()> {
 
     #[cfg(unix)]
     let _single_instance_lock = if config.single_instance {
-        match acquire_single_instance_lock() {
+        match acquire_single_instance_lock(&instance::file_name(&config, "lock")) {
             Ok(l) => Some(l),
             Err(e) => {
                 error!("{}", e);
@@ -151,10 +318,47 @@ async fn main() -> Result<()> {
     };
     
     info!("MCP Proxy starting with config: {:?}", config);
+
+    // Backends survive a proxy that was killed outright (no Job Object on Unix)
+    #[cfg(unix)]
+    tokio::task::spawn_blocking(state::reap_orphans).await?;
+
+    if inherited.is_some() || config.listen.is_some() {
+        config.check_strict_config()?;
+    }
+    let listener = match (inherited, config.listen.as_deref()) {
+        (Some(listener), _) => Some(listener),
+        (None, Some(address)) => Some(server::bind(address).await?),
+        (None, None) => None,
+    };
+    let hub = AdminHub::new(config.clone());
+    if let Some(address) = admin::address(&config) {
+        admin::spawn(hub.clone(), &address).await;
+    }
+    if let Some(listener) = listener {
+        let stop = async {
+            tokio::select! {
+                _ = server::shutdown_signal() => {}
+                _ = hub.stopped() => {}
+            }
+        };
+        return server::serve(hub.clone(), listener, log_handle, stop).await;
+    }
     
     // Create and run proxy
+    let exit_with_parent = config.exit_with_parent;
     let mut proxy = McpProxy::new(config)?;
-    proxy.run().await?;
+    proxy.set_log_handle(log_handle);
+    proxy.set_inflight_limiter(hub.inflight_limiter());
+    let (_session, admin_rx) = hub.register();
+    proxy.set_admin_channel(admin_rx);
+    let stop = async {
+        tokio::select! {
+            _ = hub.stopped() => {}
+            _ = parent::exited(), if exit_with_parent => info!("Parent process exited, shutting down"),
+        }
+    };
+    proxy.run(stop).await?;
     
     Ok(())
 }
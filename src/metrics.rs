@@ -0,0 +1,65 @@
+//! Request and error counters
+//! Collected by a middleware layer so every request path is counted the same way
+
+use crate::jsonrpc::{JsonRpcRequest, JsonRpcResponse};
+use crate::middleware::{Middleware, RequestContext};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Counters shared between the middleware chain and the proxy
+pub struct Metrics {
+    total_requests: AtomicU64,
+    total_errors: AtomicU64,
+    started: Instant,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            total_requests: AtomicU64::new(0),
+            total_errors: AtomicU64::new(0),
+            started: Instant::now(),
+        }
+    }
+
+    /// Count an error that never became a response (e.g. a failed notification forward)
+    pub fn record_error(&self) {
+        self.total_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn total_requests(&self) -> u64 {
+        self.total_requests.load(Ordering::Relaxed)
+    }
+
+    pub fn total_errors(&self) -> u64 {
+        self.total_errors.load(Ordering::Relaxed)
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Middleware for Metrics {
+    fn on_request(&self, _ctx: &RequestContext, _request: &mut JsonRpcRequest) -> Option<JsonRpcResponse> {
+        self.total_requests.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+
+    fn on_notification(&self, _ctx: &RequestContext, _notification: &mut JsonRpcRequest) -> bool {
+        self.total_requests.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    fn on_response(&self, _request: &JsonRpcRequest, response: &mut JsonRpcResponse) {
+        if response.error.is_some() {
+            self.record_error();
+        }
+    }
+}
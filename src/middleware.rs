@@ -0,0 +1,171 @@
+//! Request/response middleware
+//! Composable layers that see every request on the way in and every response on the way out,
+//! so cross-cutting features (redaction, policy, metrics, ...) stay out of the routing code
+
+use crate::jsonrpc::{JsonRpcRequest, JsonRpcResponse};
+use crate::paths::PathResolver;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// What a layer can see about the proxy's state while handling a request
+pub struct RequestContext<'a> {
+    /// Root the request will be routed to (if any)
+    pub root: Option<&'a Path>,
+    /// Known workspace roots: client roots, roots with overrides and the default root
+    pub roots: &'a [PathBuf],
+    /// Resolver used for all routing paths, so layers compare paths in the same namespace
+    pub resolver: &'a PathResolver,
+}
+
+/// A request/response processing layer
+///
+/// Layers run in the order they were added for requests and notifications, and in
+/// reverse order for responses. Every method has a pass-through default.
+pub trait Middleware: Send + Sync {
+    /// Inspect or rewrite a request; returning a response stops processing and answers
+    /// the client directly (layers that already ran still see it in `on_response`)
+    fn on_request(&self, _ctx: &RequestContext, _request: &mut JsonRpcRequest) -> Option<JsonRpcResponse> {
+        None
+    }
+
+    /// Inspect or rewrite a notification; returning `false` drops it
+    fn on_notification(&self, _ctx: &RequestContext, _notification: &mut JsonRpcRequest) -> bool {
+        true
+    }
+
+    /// Inspect or rewrite the response to `request` before it is sent to the client
+    fn on_response(&self, _request: &JsonRpcRequest, _response: &mut JsonRpcResponse) {}
+}
+
+/// Ordered stack of layers
+#[derive(Default, Clone)]
+pub struct MiddlewareChain {
+    layers: Vec<Arc<dyn Middleware>>,
+}
+
+impl MiddlewareChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a layer after (inside of) the existing ones
+    pub fn push(&mut self, layer: Arc<dyn Middleware>) {
+        self.layers.push(layer);
+    }
+
+    /// Run `on_request` through the layers, stopping at the first that answers
+    pub fn on_request(&self, ctx: &RequestContext, request: &mut JsonRpcRequest) -> Option<JsonRpcResponse> {
+        for (i, layer) in self.layers.iter().enumerate() {
+            if let Some(mut response) = layer.on_request(ctx, request) {
+                for outer in self.layers[..i].iter().rev() {
+                    outer.on_response(request, &mut response);
+                }
+                return Some(response);
+            }
+        }
+        None
+    }
+
+    /// Run `on_notification` through the layers; `false` if any of them dropped it
+    pub fn on_notification(&self, ctx: &RequestContext, notification: &mut JsonRpcRequest) -> bool {
+        self.layers.iter().all(|layer| layer.on_notification(ctx, notification))
+    }
+
+    /// Run `on_response` through the layers, innermost first
+    pub fn on_response(&self, request: &JsonRpcRequest, response: &mut JsonRpcResponse) {
+        for layer in self.layers.iter().rev() {
+            layer.on_response(request, response);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jsonrpc::{JsonRpcError, JsonRpcId};
+    use serde_json::{json, Value};
+    use std::sync::Mutex;
+
+    /// Appends its name to `trace` in params (requests) and result (responses)
+    struct Tracer {
+        name: &'static str,
+        answer: bool,
+        seen: Mutex<Vec<String>>,
+    }
+
+    impl Tracer {
+        fn new(name: &'static str, answer: bool) -> Arc<Self> {
+            Arc::new(Self { name, answer, seen: Mutex::new(Vec::new()) })
+        }
+    }
+
+    impl Middleware for Tracer {
+        fn on_request(&self, _ctx: &RequestContext, request: &mut JsonRpcRequest) -> Option<JsonRpcResponse> {
+            self.seen.lock().unwrap().push(format!("request:{}", request.method));
+            if self.answer {
+                return Some(JsonRpcResponse::error(request.id.clone(), JsonRpcError::new(-1, self.name)));
+            }
+            if let Some(params) = request.params.as_mut() {
+                params["trace"] = json!(format!("{}{}", params["trace"].as_str().unwrap_or_default(), self.name));
+            }
+            None
+        }
+
+        fn on_notification(&self, _ctx: &RequestContext, _notification: &mut JsonRpcRequest) -> bool {
+            !self.answer
+        }
+
+        fn on_response(&self, _request: &JsonRpcRequest, response: &mut JsonRpcResponse) {
+            self.seen.lock().unwrap().push("response".to_string());
+            if let Some(result) = response.result.as_mut() {
+                result["trace"] = json!(format!("{}{}", result["trace"].as_str().unwrap_or_default(), self.name));
+            }
+        }
+    }
+
+    fn request() -> JsonRpcRequest {
+        JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "tools/call".to_string(),
+            id: Some(JsonRpcId::Number(1)),
+            params: Some(json!({})),
+        }
+    }
+
+    #[test]
+    fn test_layer_order() {
+        let resolver = PathResolver::new(false);
+        let ctx = RequestContext { root: None, roots: &[], resolver: &resolver };
+        let mut chain = MiddlewareChain::new();
+        chain.push(Tracer::new("a", false));
+        chain.push(Tracer::new("b", false));
+
+        let mut req = request();
+        assert!(chain.on_request(&ctx, &mut req).is_none());
+        assert_eq!(req.params.as_ref().unwrap()["trace"], "ab");
+
+        let mut response = JsonRpcResponse::success(req.id.clone(), json!({}));
+        chain.on_response(&req, &mut response);
+        assert_eq!(response.result.unwrap()["trace"], Value::from("ba"));
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        let resolver = PathResolver::new(false);
+        let ctx = RequestContext { root: None, roots: &[], resolver: &resolver };
+        let (outer, blocker, inner) = (Tracer::new("outer", false), Tracer::new("blocker", true), Tracer::new("inner", false));
+        let mut chain = MiddlewareChain::new();
+        chain.push(outer.clone());
+        chain.push(blocker.clone());
+        chain.push(inner.clone());
+
+        let response = chain.on_request(&ctx, &mut request()).expect("short-circuited");
+        assert_eq!(response.error.unwrap().message, "blocker");
+        assert_eq!(*outer.seen.lock().unwrap(), ["request:tools/call", "response"]);
+        assert_eq!(*blocker.seen.lock().unwrap(), ["request:tools/call"]);
+        assert!(inner.seen.lock().unwrap().is_empty());
+
+        let mut notification = JsonRpcRequest { id: None, ..request() };
+        assert!(!chain.on_notification(&ctx, &mut notification));
+    }
+}
@@ -0,0 +1,191 @@
+//! Mock backend (`mcp-proxy mock-backend`)
+//! A minimal scriptable MCP server on stdio, run in place of auggie with `--mock-backend`, so
+//! routing, retries and eviction can be tested and benchmarked without auggie installed
+
+use crate::error::{ERROR_INVALID_PARAMS, ERROR_METHOD_NOT_FOUND};
+use crate::jsonrpc::{JsonRpcError, JsonRpcRequest, JsonRpcResponse};
+use anyhow::{Context, Result};
+use clap::Args;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+
+/// `mock-backend` options; the `MCP_PROXY_MOCK_*` variables set them for backends the proxy
+/// starts, since the proxy passes its environment on
+#[derive(Args, Debug, Clone, Default)]
+pub struct MockBackendArgs {
+    /// Workspace root (as passed to auggie), echoed in tool results
+    #[arg(long)]
+    pub workspace_root: Option<PathBuf>,
+
+    /// Accepted and ignored, like the rest of auggie's command line
+    #[arg(long, hide = true)]
+    pub mcp: bool,
+
+    #[arg(short = 'm', long = "mode", hide = true)]
+    pub mode: Option<String>,
+
+    /// Wait this many milliseconds before each answer
+    #[arg(long, env = "MCP_PROXY_MOCK_LATENCY_MS", default_value_t = 0)]
+    pub latency_ms: u64,
+
+    /// Exit with status 1 on receiving the Nth request (0 to never crash)
+    #[arg(long, env = "MCP_PROXY_MOCK_CRASH_ON", default_value_t = 0)]
+    pub crash_on: u64,
+
+    /// JSON file with the `tools` array for tools/list (default: a `codebase-retrieval` tool)
+    #[arg(long, env = "MCP_PROXY_MOCK_TOOLS")]
+    pub tools: Option<PathBuf>,
+}
+
+/// Canned answers of the mock server
+struct MockServer {
+    root: Option<PathBuf>,
+    tools: Vec<Value>,
+}
+
+impl MockServer {
+    fn new(args: &MockBackendArgs) -> Result<Self> {
+        let tools = match args.tools {
+            Some(ref path) => {
+                let contents = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+                serde_json::from_slice(&contents).with_context(|| format!("Invalid tools in {}", path.display()))?
+            }
+            None => vec![json!({
+                "name": "codebase-retrieval",
+                "description": "Mock codebase retrieval: echoes the request",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": { "information_request": { "type": "string" } },
+                    "required": ["information_request"],
+                },
+            })],
+        };
+        Ok(Self { root: args.workspace_root.clone(), tools })
+    }
+
+    /// Answer to a request (`None` for notifications)
+    fn respond(&self, request: &JsonRpcRequest) -> Option<JsonRpcResponse> {
+        if request.is_notification() {
+            return None;
+        }
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            "initialize" => json!({
+                "protocolVersion": request.get_param_path("protocolVersion").cloned().unwrap_or("2024-11-05".into()),
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "mcp-proxy-mock", "version": env!("CARGO_PKG_VERSION") },
+            }),
+            "ping" | "shutdown" => json!({}),
+            "tools/list" => json!({ "tools": self.tools }),
+            "tools/call" => {
+                let name = request.get_param_path("name").and_then(Value::as_str).unwrap_or_default();
+                if !self.tools.iter().any(|tool| tool["name"] == name) {
+                    return Some(JsonRpcResponse::error(
+                        id,
+                        JsonRpcError::new(ERROR_INVALID_PARAMS, format!("Unknown tool: {}", name)),
+                    ));
+                }
+                let text = json!({
+                    "tool": name,
+                    "arguments": request.get_param_path("arguments"),
+                    "root": self.root,
+                    "pid": std::process::id(),
+                });
+                json!({ "content": [{ "type": "text", "text": text.to_string() }] })
+            }
+            method => {
+                return Some(JsonRpcResponse::error(
+                    id,
+                    JsonRpcError::new(ERROR_METHOD_NOT_FOUND, format!("Method not found: {}", method)),
+                ));
+            }
+        };
+        Some(JsonRpcResponse::success(id, result))
+    }
+}
+
+/// Serve stdin/stdout until stdin closes or `exit` arrives
+pub async fn run(args: MockBackendArgs) -> Result<()> {
+    let server = MockServer::new(&args)?;
+    let latency = Duration::from_millis(args.latency_ms);
+
+    // One task writes every answer, so delayed answers never interleave mid-line
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(mut line) = rx.recv().await {
+            line.push('\n');
+            if stdout.write_all(line.as_bytes()).await.is_err() || stdout.flush().await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut requests = 0;
+    while let Some(line) = lines.next_line().await? {
+        let request: JsonRpcRequest = match serde_json::from_str(line.trim()) {
+            Ok(request) => request,
+            Err(_) => continue,
+        };
+        if request.is_exit() {
+            break;
+        }
+        if !request.is_notification() {
+            requests += 1;
+            if requests == args.crash_on {
+                eprintln!("mock backend crashing on request {} ({})", requests, request.method);
+                std::process::exit(1);
+            }
+        }
+        let Some(response) = server.respond(&request) else { continue };
+        let line = serde_json::to_string(&response)?;
+        if latency.is_zero() {
+            let _ = tx.send(line);
+        } else {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(latency).await;
+                let _ = tx.send(line);
+            });
+        }
+    }
+    drop(tx);
+    let _ = writer.await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, params: Value) -> JsonRpcRequest {
+        serde_json::from_value(json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params })).unwrap()
+    }
+
+    #[test]
+    fn test_mock_responses() {
+        let args = MockBackendArgs { workspace_root: Some(PathBuf::from("/repo")), ..Default::default() };
+        let server = MockServer::new(&args).unwrap();
+
+        let init = server.respond(&request("initialize", json!({ "protocolVersion": "2025-06-18" }))).unwrap();
+        assert_eq!(init.result.unwrap()["protocolVersion"], "2025-06-18");
+        let tools = server.respond(&request("tools/list", json!({}))).unwrap();
+        assert_eq!(tools.result.unwrap()["tools"][0]["name"], "codebase-retrieval");
+
+        let call = request("tools/call", json!({ "name": "codebase-retrieval", "arguments": { "information_request": "auth" } }));
+        let result = server.respond(&call).unwrap().result.unwrap();
+        let text: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!((text["root"].as_str(), text["arguments"]["information_request"].as_str()), (Some("/repo"), Some("auth")));
+
+        let unknown = server.respond(&request("tools/call", json!({ "name": "nope" }))).unwrap();
+        assert_eq!(unknown.error.unwrap().code, ERROR_INVALID_PARAMS);
+        assert_eq!(server.respond(&request("resources/list", json!({}))).unwrap().error.unwrap().code, ERROR_METHOD_NOT_FOUND);
+        let notification: JsonRpcRequest =
+            serde_json::from_value(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).unwrap();
+        assert!(server.respond(&notification).is_none());
+    }
+}
@@ -0,0 +1,98 @@
+//! Response ordering (`--preserve-order`)
+//! Responses that finish before those of earlier requests are held until the earlier ones have
+//! gone out, for clients that expect answers in request order
+
+use crate::jsonrpc::JsonRpcId;
+use std::collections::VecDeque;
+
+/// Requests waiting for their responses to go out, in arrival order, with the responses that
+/// are ready but held behind an earlier one
+#[derive(Default)]
+pub struct ResponseOrder {
+    pending: VecDeque<(JsonRpcId, Option<String>)>,
+}
+
+impl ResponseOrder {
+    /// A request whose response must wait for those of the requests before it
+    pub fn expect(&mut self, id: JsonRpcId) {
+        self.pending.push_back((id, None));
+    }
+
+    /// Hand over the response to `id`: returns the responses that can be sent now, in order
+    /// (one not expected goes out right away)
+    pub fn respond(&mut self, id: Option<&JsonRpcId>, line: String) -> Vec<String> {
+        let slot = id.and_then(|id| self.pending.iter_mut().find(|(pending, held)| pending == id && held.is_none()));
+        match slot {
+            Some((_, held)) => {
+                *held = Some(line);
+                self.release()
+            }
+            None => vec![line],
+        }
+    }
+
+    /// Stop waiting for a request that won't be answered; returns the responses it held up
+    pub fn forget(&mut self, id: &JsonRpcId) -> Vec<String> {
+        if let Some(index) = self.pending.iter().position(|(pending, held)| pending == id && held.is_none()) {
+            self.pending.remove(index);
+        }
+        self.release()
+    }
+
+    /// Every held response, in order, skipping requests still unanswered (the session is over)
+    pub fn flush(&mut self) -> Vec<String> {
+        self.pending.drain(..).filter_map(|(_, held)| held).collect()
+    }
+
+    /// Responses at the front that are ready
+    fn release(&mut self) -> Vec<String> {
+        let mut ready = Vec::new();
+        while let Some((_, Some(_))) = self.pending.front() {
+            if let Some((_, Some(line))) = self.pending.pop_front() {
+                ready.push(line);
+            }
+        }
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(n: i64) -> JsonRpcId {
+        JsonRpcId::Number(n)
+    }
+
+    #[test]
+    fn test_response_order() {
+        let mut order = ResponseOrder::default();
+        order.expect(id(1));
+        order.expect(id(2));
+        order.expect(id(3));
+        assert!(order.respond(Some(&id(3)), "three".to_string()).is_empty());
+        assert!(order.respond(Some(&id(2)), "two".to_string()).is_empty());
+        // Not expected (a parse error, say): not held
+        assert_eq!(order.respond(None, "error".to_string()), vec!["error"]);
+        assert_eq!(order.respond(Some(&id(1)), "one".to_string()), vec!["one", "two", "three"]);
+        assert_eq!(order.respond(Some(&id(1)), "again".to_string()), vec!["again"]);
+    }
+
+    #[test]
+    fn test_forget_and_flush() {
+        let mut order = ResponseOrder::default();
+        order.expect(id(1));
+        order.expect(id(2));
+        order.expect(id(3));
+        assert!(order.respond(Some(&id(2)), "two".to_string()).is_empty());
+        assert_eq!(order.forget(&id(1)), vec!["two"]);
+
+        // A reused id fills the earliest slot still waiting
+        order.expect(id(3));
+        order.expect(id(4));
+        assert!(order.respond(Some(&id(4)), "four".to_string()).is_empty());
+        assert_eq!(order.respond(Some(&id(3)), "three".to_string()), vec!["three"]);
+        assert_eq!(order.flush(), vec!["four"]);
+        assert!(order.flush().is_empty());
+    }
+}
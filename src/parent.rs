@@ -0,0 +1,119 @@
+//! Exiting with the parent process (`--exit-with-parent`)
+//! Some clients keep the stdio pipe open through a wrapper after the IDE dies, so EOF never
+//! comes; the proxy then shuts down (backends included) once its parent is gone
+
+use std::future::pending;
+use tracing::debug;
+
+/// Completes when the process that started the proxy exits; never completes when the proxy
+/// was started by init/launchd or the parent can't be watched
+pub async fn exited() {
+    #[cfg(target_os = "linux")]
+    linux().await;
+    #[cfg(all(unix, not(target_os = "linux")))]
+    poll().await;
+    #[cfg(windows)]
+    windows().await;
+}
+
+/// The kernel sends SIGTERM when the parent dies (PR_SET_PDEATHSIG)
+#[cfg(target_os = "linux")]
+async fn linux() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let parent = unsafe { libc::getppid() };
+    if parent == 1 {
+        return pending().await;
+    }
+    let Ok(mut term) = signal(SignalKind::terminate()) else { return pending().await };
+    if unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM) } != 0 {
+        debug!("PR_SET_PDEATHSIG failed: {}", std::io::Error::last_os_error());
+        return pending().await;
+    }
+    // The parent may have died before the request took effect
+    if unsafe { libc::getppid() } != parent {
+        return;
+    }
+    term.recv().await;
+}
+
+/// Orphans are re-parented, so a changed parent PID means the parent is gone
+#[cfg(all(unix, not(target_os = "linux")))]
+async fn poll() {
+    let parent = unsafe { libc::getppid() };
+    if parent == 1 {
+        return pending().await;
+    }
+    let mut tick = tokio::time::interval(std::time::Duration::from_secs(1));
+    while unsafe { libc::getppid() } == parent {
+        tick.tick().await;
+    }
+}
+
+/// Wait on a handle to the parent; backends follow the proxy out through its Job Object
+#[cfg(windows)]
+async fn windows() {
+    use windows::Win32::Foundation::{CloseHandle, ERROR_INVALID_PARAMETER, FILETIME};
+    use windows::Win32::System::Threading::{
+        GetCurrentProcess, GetProcessTimes, OpenProcess, WaitForSingleObject, INFINITE,
+        PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_SYNCHRONIZE,
+    };
+
+    let Some(pid) = parent_pid() else { return pending().await };
+    let parent = match unsafe { OpenProcess(PROCESS_SYNCHRONIZE | PROCESS_QUERY_LIMITED_INFORMATION, false, pid) } {
+        Ok(handle) => handle,
+        Err(e) if e.code() == ERROR_INVALID_PARAMETER.to_hresult() => return,
+        Err(e) => {
+            debug!("Cannot watch parent process {}: {}", pid, e);
+            return pending().await;
+        }
+    };
+    let created = |process| unsafe {
+        let (mut creation, mut exit, mut kernel, mut user) =
+            (FILETIME::default(), FILETIME::default(), FILETIME::default(), FILETIME::default());
+        GetProcessTimes(process, &mut creation, &mut exit, &mut kernel, &mut user)
+            .ok()
+            .map(|_| (u64::from(creation.dwHighDateTime) << 32) | u64::from(creation.dwLowDateTime))
+    };
+    // A parent started after the proxy is an unrelated process that reused the PID
+    if let (Some(parent_created), Some(own_created)) = (created(parent), created(unsafe { GetCurrentProcess() })) {
+        if parent_created > own_created {
+            unsafe {
+                let _ = CloseHandle(parent);
+            }
+            return;
+        }
+    }
+    let handle = parent.0 as usize;
+    let _ = tokio::task::spawn_blocking(move || unsafe {
+        let parent = windows::Win32::Foundation::HANDLE(handle as *mut _);
+        WaitForSingleObject(parent, INFINITE);
+        let _ = CloseHandle(parent);
+    })
+    .await;
+}
+
+#[cfg(windows)]
+fn parent_pid() -> Option<u32> {
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W, TH32CS_SNAPPROCESS,
+    };
+
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0).ok()?;
+        let own = std::process::id();
+        let mut entry = PROCESSENTRY32W { dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32, ..Default::default() };
+        let mut parent = None;
+        let mut more = Process32FirstW(snapshot, &mut entry).is_ok();
+        while more {
+            if entry.th32ProcessID == own {
+                parent = Some(entry.th32ParentProcessID);
+                break;
+            }
+            more = Process32NextW(snapshot, &mut entry).is_ok();
+        }
+        let _ = CloseHandle(snapshot);
+        parent
+    }
+}
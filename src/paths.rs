@@ -0,0 +1,337 @@
+//! Path canonicalization and normalization for routing
+//! Resolves symlinks (e.g. `/var` vs `/private/var` on macOS) and normalizes Windows paths
+//! (case, separators, `\\?\` prefixes, UNC) so prefix matching is reliable
+
+use crate::wsl::WslPath;
+use lru::LruCache;
+use percent_encoding::percent_decode_str;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::debug;
+
+/// Number of resolved paths kept in the cache
+const RESOLVE_CACHE_CAPACITY: usize = 4096;
+
+/// Canonicalizes paths with an LRU cache of previous results
+///
+/// Used for roots, request URIs, and git filter lookups alike, so that all
+/// path comparisons happen in the same (resolved) namespace.
+pub struct PathResolver {
+    enabled: bool,
+    cache: Mutex<LruCache<PathBuf, PathBuf>>,
+}
+
+impl PathResolver {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            cache: Mutex::new(LruCache::new(
+                NonZeroUsize::new(RESOLVE_CACHE_CAPACITY).unwrap(),
+            )),
+        }
+    }
+
+    /// Resolve a path to its canonical, normalized form
+    /// (only normalized when symlink resolution is disabled)
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        if !self.enabled {
+            return normalize(path.to_path_buf());
+        }
+
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(resolved) = cache.get(path) {
+            return resolved.clone();
+        }
+
+        let resolved = normalize(canonicalize_lenient(path));
+        if resolved != path {
+            debug!("Resolved path {} -> {}", path.display(), resolved.display());
+        }
+        cache.put(path.to_path_buf(), resolved.clone());
+        resolved
+    }
+
+    /// Number of cached resolutions
+    pub fn cache_len(&self) -> usize {
+        self.cache.lock().map(|c| c.len()).unwrap_or(0)
+    }
+}
+
+/// Canonicalize a path that may not exist (yet, or anymore)
+///
+/// Resolves the longest existing ancestor and re-appends the missing tail, so
+/// events for deleted or not-yet-created files still land under the right root.
+fn canonicalize_lenient(path: &Path) -> PathBuf {
+    let mut tail = Vec::new();
+    let mut current = path;
+
+    loop {
+        if let Ok(mut resolved) = std::fs::canonicalize(current) {
+            for component in tail.iter().rev() {
+                resolved.push(component);
+            }
+            return resolved;
+        }
+        match (current.parent(), current.file_name()) {
+            (Some(parent), Some(name)) => {
+                tail.push(name.to_os_string());
+                current = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+/// Normalize a path for comparison (identity outside Windows)
+#[cfg(windows)]
+pub fn normalize(path: PathBuf) -> PathBuf {
+    match path.to_str() {
+        Some(s) => PathBuf::from(normalize_windows_path(s)),
+        None => path,
+    }
+}
+
+#[cfg(not(windows))]
+pub fn normalize(path: PathBuf) -> PathBuf {
+    path
+}
+
+/// Normalize a Windows path string: `\\?\` prefixes stripped, `/` -> `\`,
+/// duplicate and trailing separators removed, lowercased (NTFS is case-insensitive)
+///
+/// WSL shares hold case-sensitive Linux paths, so those keep their case and only
+/// get the `\\wsl$` / `\\wsl.localhost` host unified.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn normalize_windows_path(path: &str) -> String {
+    let path = path.replace('/', "\\");
+    let path = path.strip_prefix(r"\\?\UNC\").map(|unc| format!(r"\\{}", unc)).unwrap_or(path);
+    if let Some(wsl_path) = WslPath::parse(&path) {
+        return wsl_path.to_unc();
+    }
+
+    let (prefix, rest) = if let Some(rest) = path.strip_prefix(r"\\?\") {
+        ("", rest)
+    } else if let Some(rest) = path.strip_prefix(r"\\") {
+        (r"\\", rest)
+    } else {
+        ("", path.as_str())
+    };
+
+    let mut normalized = String::with_capacity(path.len());
+    normalized.push_str(prefix);
+    for (i, part) in rest.split('\\').enumerate() {
+        if part.is_empty() && i > 0 {
+            continue;
+        }
+        if i > 0 {
+            normalized.push('\\');
+        }
+        normalized.push_str(part);
+    }
+    // A bare drive needs its separator to stay a root (`c:\`)
+    if normalized.len() == 2 && normalized.ends_with(':') {
+        normalized.push('\\');
+    }
+    normalized.to_lowercase()
+}
+
+/// Convert a file URI (or plain path) to a normalized path, decoding percent-escapes
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let decoded = percent_decode_str(uri).decode_utf8().ok()?;
+    let path = match decoded.strip_prefix("file://") {
+        Some(rest) => file_uri_path(rest),
+        // Assume it's already a path
+        None => decoded.to_string(),
+    };
+    Some(normalize(PathBuf::from(path)))
+}
+
+/// Convert a path to a file URI
+pub fn path_to_uri(path: &Path) -> String {
+    #[cfg(windows)]
+    {
+        windows_path_to_uri(&path.display().to_string())
+    }
+    #[cfg(not(windows))]
+    {
+        format!("file://{}", path.display())
+    }
+}
+
+/// Path portion of a file URI (after `file://`) in platform form
+#[cfg(windows)]
+fn file_uri_path(rest: &str) -> String {
+    windows_path_from_file_uri(rest)
+}
+
+#[cfg(not(windows))]
+fn file_uri_path(rest: &str) -> String {
+    // file:///path or file://localhost/path -> /path
+    rest.strip_prefix("localhost").unwrap_or(rest).to_string()
+}
+
+/// `file:///C:/path` -> `C:\path`, `file://server/share/path` -> `\\server\share\path`
+#[cfg_attr(not(windows), allow(dead_code))]
+fn windows_path_from_file_uri(rest: &str) -> String {
+    let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+    let local = rest.strip_prefix('/').unwrap_or(rest);
+    let is_drive_path = local.len() >= 2 && local.as_bytes()[1] == b':';
+
+    if is_drive_path {
+        local.replace('/', "\\")
+    } else {
+        format!(r"\\{}", rest.trim_start_matches('/').replace('/', "\\"))
+    }
+}
+
+/// `C:\path` -> `file:///C:/path`, `\\server\share\path` -> `file://server/share/path`
+#[cfg_attr(not(windows), allow(dead_code))]
+fn windows_path_to_uri(path: &str) -> String {
+    match path.strip_prefix(r"\\") {
+        Some(unc) => format!("file://{}", unc.replace('\\', "/")),
+        None => format!("file:///{}", path.replace('\\', "/")),
+    }
+}
+
+/// Expand a leading `~` and environment variables (`$VAR`, `${VAR}`, and `%VAR%` on Windows)
+/// in a path from the config file; unset variables are left as written
+pub fn expand(path: &Path) -> PathBuf {
+    let Some(text) = path.to_str() else { return path.to_path_buf() };
+    let home = crate::runtime::home_dir();
+    PathBuf::from(expand_with(text, home.as_deref(), |name| std::env::var(name).ok()))
+}
+
+fn expand_with(text: &str, home: Option<&Path>, var: impl Fn(&str) -> Option<String>) -> String {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    if let (Some(home), Some(tail)) = (home, text.strip_prefix('~')) {
+        if tail.is_empty() || tail.starts_with(['/', '\\']) {
+            expanded.push_str(&home.to_string_lossy());
+            rest = tail;
+        }
+    }
+    while let Some(start) = rest.find(['$', '%']) {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        // (name, length of the reference after its first character)
+        let reference = match rest.as_bytes()[start] {
+            b'$' if after.starts_with('{') => after.find('}').map(|end| (&after[1..end], end + 1)),
+            b'$' => {
+                let end = after.find(|c| !is_name(c)).unwrap_or(after.len());
+                Some((&after[..end], end))
+            }
+            _ if cfg!(windows) => after.find('%').map(|end| (&after[..end], end + 1)),
+            _ => None,
+        };
+        match reference.filter(|(name, _)| !name.is_empty() && name.chars().all(is_name)) {
+            Some((name, len)) => {
+                match var(name) {
+                    Some(value) => expanded.push_str(&value),
+                    None => expanded.push_str(&rest[start..start + 1 + len]),
+                }
+                rest = &after[len..];
+            }
+            None => {
+                expanded.push_str(&rest[start..start + 1]);
+                rest = after;
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-paths-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::canonicalize(dir).unwrap()
+    }
+
+    #[test]
+    fn test_disabled_resolver_is_identity() {
+        let resolver = PathResolver::new(false);
+        let path = Path::new("some/relative/../path");
+        assert_eq!(resolver.resolve(path), path);
+        assert_eq!(resolver.cache_len(), 0);
+    }
+
+    #[test]
+    fn test_missing_tail_is_preserved() {
+        let dir = temp_dir("missing");
+        let resolver = PathResolver::new(true);
+        let missing = dir.join("not").join("there.rs");
+        assert_eq!(resolver.resolve(&missing), missing);
+        assert_eq!(resolver.cache_len(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_is_resolved() {
+        let dir = temp_dir("symlink");
+        std::fs::create_dir_all(dir.join("real/src")).unwrap();
+        std::os::unix::fs::symlink(dir.join("real"), dir.join("link")).unwrap();
+
+        let resolver = PathResolver::new(true);
+        assert_eq!(resolver.resolve(&dir.join("link/src")), dir.join("real/src"));
+        assert_eq!(resolver.resolve(&dir.join("link/src/new.rs")), dir.join("real/src/new.rs"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_normalize_windows_path() {
+        assert_eq!(normalize_windows_path(r"C:\Repo\Src"), r"c:\repo\src");
+        assert_eq!(normalize_windows_path("c:/repo/src/"), r"c:\repo\src");
+        assert_eq!(normalize_windows_path(r"\\?\C:\Repo"), r"c:\repo");
+        assert_eq!(normalize_windows_path(r"\\?\UNC\Server\Share\Repo"), r"\\server\share\repo");
+        assert_eq!(normalize_windows_path(r"\\Server\Share\\Repo"), r"\\server\share\repo");
+        assert_eq!(normalize_windows_path("C:"), r"c:\");
+        assert_eq!(normalize_windows_path(r"\\wsl$\Ubuntu\home\Me"), r"\\wsl.localhost\ubuntu\home\Me");
+    }
+
+    #[test]
+    fn test_windows_file_uri_conversion() {
+        assert_eq!(windows_path_from_file_uri("/C:/Repo/main.rs"), r"C:\Repo\main.rs");
+        assert_eq!(windows_path_from_file_uri("localhost/C:/Repo"), r"C:\Repo");
+        assert_eq!(windows_path_from_file_uri("server/share/repo"), r"\\server\share\repo");
+        assert_eq!(windows_path_from_file_uri("wsl$/Ubuntu/home/me"), r"\\wsl$\Ubuntu\home\me");
+        assert_eq!(windows_path_to_uri(r"c:\repo\main.rs"), "file:///c:/repo/main.rs");
+        assert_eq!(windows_path_to_uri(r"\\server\share\repo"), "file://server/share/repo");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_unix_uri_round_trip() {
+        assert_eq!(uri_to_path("file:///home/me/a%20b.rs"), Some(PathBuf::from("/home/me/a b.rs")));
+        assert_eq!(uri_to_path("file://localhost/home/me"), Some(PathBuf::from("/home/me")));
+        assert_eq!(uri_to_path("/already/a/path"), Some(PathBuf::from("/already/a/path")));
+        assert_eq!(path_to_uri(Path::new("/home/me/a.rs")), "file:///home/me/a.rs");
+    }
+
+    #[test]
+    fn test_expand() {
+        let home = Path::new("/home/me");
+        let var = |name: &str| (name == "TOOLS").then(|| "/opt/tools".to_string());
+        assert_eq!(expand_with("~/repo", Some(home), var), "/home/me/repo");
+        assert_eq!(expand_with("~", Some(home), var), "/home/me");
+        assert_eq!(expand_with("~other/repo", Some(home), var), "~other/repo");
+        assert_eq!(expand_with("$TOOLS/auggie/augment.mjs", Some(home), var), "/opt/tools/auggie/augment.mjs");
+        assert_eq!(expand_with("${TOOLS}x/$TOOLS", None, var), "/opt/toolsx//opt/tools");
+        // Unset variables and lone `$` stay as written
+        assert_eq!(expand_with("$UNSET/${UNSET}/a$/b", None, var), "$UNSET/${UNSET}/a$/b");
+        assert_eq!(expand_with(r"\\wsl$\Ubuntu\home", None, var), r"\\wsl$\Ubuntu\home");
+        assert_eq!(expand_with("${TOOLS", None, var), "${TOOLS");
+        if cfg!(windows) {
+            assert_eq!(expand_with(r"%TOOLS%\bin", None, var), r"/opt/tools\bin");
+        } else {
+            assert_eq!(expand_with("%TOOLS%/bin", None, var), "%TOOLS%/bin");
+        }
+    }
+}
@@ -0,0 +1,273 @@
+//! WASM plugins (`wasm-plugins` feature)
+//! Runs sandboxed wasmtime modules as middleware layers that can inspect, rewrite or deny messages
+//!
+//! ABI: the module exports `memory`, `alloc(len: i32) -> i32` and any of the hooks
+//! `on_request`, `on_notification`, `on_response`, each `(ptr: i32, len: i32) -> i64`.
+//! The proxy writes the message as JSON into memory obtained from `alloc`; a hook returns
+//! `0` to pass the message through unchanged, or `(ptr << 32) | len` of a JSON object:
+//!
+//! - `{"request": {...}}` replaces the request or notification
+//! - `{"result": ...}` answers a request without forwarding it
+//! - `{"deny": {"code": -32004, "message": "..."}}` rejects a request
+//! - `{"drop": true}` drops a notification
+//! - `{"response": {...}}` replaces a response (`on_response` gets `{"request", "response"}`)
+//!
+//! Modules get no imports (no WASI), so they can't touch the filesystem or network.
+
+use crate::error::{ProxyError, ERROR_INTERNAL_ERROR, ERROR_POLICY_DENIED};
+use crate::jsonrpc::{JsonRpcError, JsonRpcRequest, JsonRpcResponse};
+use crate::middleware::{Middleware, RequestContext};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::{debug, info, warn};
+use wasmtime::{Config as EngineConfig, Engine, Instance, Memory, Module, Store, TypedFunc};
+
+/// Fuel budget per hook call, so a looping plugin can't stall the proxy
+const FUEL_PER_CALL: u64 = 50_000_000;
+
+type Hook = TypedFunc<(i32, i32), i64>;
+
+/// Instantiated module with its exports
+struct PluginInstance {
+    store: Store<()>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    on_request: Option<Hook>,
+    on_notification: Option<Hook>,
+    on_response: Option<Hook>,
+}
+
+/// A loaded WASM plugin
+pub struct WasmPlugin {
+    name: String,
+    instance: Mutex<PluginInstance>,
+}
+
+impl WasmPlugin {
+    /// Load a plugin from a `.wasm` (or `.wat`) file
+    pub fn load(path: &Path) -> Result<Self, ProxyError> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| ProxyError::ConfigError(format!("Failed to read plugin {}: {}", path.display(), e)))?;
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string());
+        let plugin = Self::from_bytes(&name, &bytes)?;
+        info!("Loaded WASM plugin {} from {}", name, path.display());
+        Ok(plugin)
+    }
+
+    pub fn from_bytes(name: &str, bytes: &[u8]) -> Result<Self, ProxyError> {
+        let err = |e: wasmtime::Error| ProxyError::ConfigError(format!("Plugin {}: {:#}", name, e));
+
+        let mut engine_config = EngineConfig::new();
+        engine_config.consume_fuel(true);
+        let engine = Engine::new(&engine_config).map_err(err)?;
+        let module = Module::new(&engine, bytes).map_err(err)?;
+        let mut store = Store::new(&engine, ());
+        store.set_fuel(FUEL_PER_CALL).map_err(err)?;
+        let instance = Instance::new(&mut store, &module, &[]).map_err(err)?;
+
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| ProxyError::ConfigError(format!("Plugin {} does not export `memory`", name)))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc").map_err(err)?;
+        let mut hook = |export: &str| instance.get_typed_func::<(i32, i32), i64>(&mut store, export).ok();
+        let (on_request, on_notification, on_response) = (hook("on_request"), hook("on_notification"), hook("on_response"));
+        if on_request.is_none() && on_notification.is_none() && on_response.is_none() {
+            warn!("Plugin {} exports no hooks", name);
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            instance: Mutex::new(PluginInstance {
+                store,
+                memory,
+                alloc,
+                on_request,
+                on_notification,
+                on_response,
+            }),
+        })
+    }
+
+    /// Call a hook; `Ok(None)` when the plugin passes the message through (or lacks the hook)
+    fn call(&self, select: fn(&PluginInstance) -> Option<Hook>, input: &Value) -> Result<Option<Value>, String> {
+        let mut guard = self.instance.lock().unwrap_or_else(|e| e.into_inner());
+        let instance = &mut *guard;
+        let hook = match select(instance) {
+            Some(hook) => hook,
+            None => return Ok(None),
+        };
+        let store = &mut instance.store;
+        store.set_fuel(FUEL_PER_CALL).map_err(|e| e.to_string())?;
+
+        let input = input.to_string();
+        let len = i32::try_from(input.len()).map_err(|_| "message too large".to_string())?;
+        let ptr = instance.alloc.call(&mut *store, len).map_err(|e| format!("alloc failed: {:#}", e))?;
+        instance
+            .memory
+            .write(&mut *store, ptr as u32 as usize, input.as_bytes())
+            .map_err(|e| format!("alloc returned an invalid pointer: {}", e))?;
+
+        let packed = hook.call(&mut *store, (ptr, len)).map_err(|e| format!("{:#}", e))? as u64;
+        if packed == 0 {
+            return Ok(None);
+        }
+        let (out_ptr, out_len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        let output = instance
+            .memory
+            .data(&*store)
+            .get(out_ptr..out_ptr + out_len)
+            .ok_or_else(|| "hook returned an out-of-bounds result".to_string())?;
+        serde_json::from_slice(output)
+            .map(Some)
+            .map_err(|e| format!("hook returned invalid JSON: {}", e))
+    }
+
+    fn replacement_request(&self, output: &Value) -> Option<JsonRpcRequest> {
+        let request = output.get("request")?;
+        match serde_json::from_value(request.clone()) {
+            Ok(request) => Some(request),
+            Err(e) => {
+                warn!("Plugin {} returned an invalid request: {}", self.name, e);
+                None
+            }
+        }
+    }
+}
+
+impl Middleware for WasmPlugin {
+    fn on_request(&self, _ctx: &RequestContext, request: &mut JsonRpcRequest) -> Option<JsonRpcResponse> {
+        let input = serde_json::to_value(&*request).ok()?;
+        // Fail closed: a broken plugin must not let requests slip past it
+        let output = match self.call(|i| i.on_request.clone(), &input) {
+            Ok(Some(output)) => output,
+            Ok(None) => return None,
+            Err(e) => {
+                warn!("Plugin {} failed on {}: {}", self.name, request.method, e);
+                return Some(JsonRpcResponse::error(
+                    request.id.clone(),
+                    JsonRpcError::new(ERROR_INTERNAL_ERROR, format!("Plugin {} failed: {}", self.name, e)),
+                ));
+            }
+        };
+
+        if let Some(deny) = output.get("deny") {
+            let code = deny.get("code").and_then(|c| c.as_i64()).map(|c| c as i32).unwrap_or(ERROR_POLICY_DENIED);
+            let message = deny.get("message").and_then(|m| m.as_str()).unwrap_or("Denied by plugin");
+            debug!("Plugin {} denied {}: {}", self.name, request.method, message);
+            return Some(JsonRpcResponse::error(request.id.clone(), JsonRpcError::new(code, message)));
+        }
+        if let Some(result) = output.get("result") {
+            return Some(JsonRpcResponse::success(request.id.clone(), result.clone()));
+        }
+        if let Some(replacement) = self.replacement_request(&output) {
+            *request = replacement;
+        }
+        None
+    }
+
+    fn on_notification(&self, _ctx: &RequestContext, notification: &mut JsonRpcRequest) -> bool {
+        let input = match serde_json::to_value(&*notification) {
+            Ok(input) => input,
+            Err(_) => return true,
+        };
+        let output = match self.call(|i| i.on_notification.clone(), &input) {
+            Ok(Some(output)) => output,
+            Ok(None) => return true,
+            Err(e) => {
+                warn!("Plugin {} failed on {}, dropping it: {}", self.name, notification.method, e);
+                return false;
+            }
+        };
+        if output.get("drop").and_then(|d| d.as_bool()).unwrap_or(false) {
+            return false;
+        }
+        if let Some(replacement) = self.replacement_request(&output) {
+            *notification = replacement;
+        }
+        true
+    }
+
+    fn on_response(&self, request: &JsonRpcRequest, response: &mut JsonRpcResponse) {
+        let input = json!({"request": request, "response": &*response});
+        match self.call(|i| i.on_response.clone(), &input) {
+            Ok(Some(output)) => {
+                if let Some(replacement) = output.get("response") {
+                    match serde_json::from_value(replacement.clone()) {
+                        Ok(replacement) => *response = replacement,
+                        Err(e) => warn!("Plugin {} returned an invalid response: {}", self.name, e),
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Plugin {} failed on response to {}: {}", self.name, request.method, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jsonrpc::JsonRpcId;
+    use crate::paths::PathResolver;
+
+    /// Bump allocator at 1024; `on_request` answers with the constant at offset 0
+    fn plugin(output: &str, body: &str) -> WasmPlugin {
+        let wat = format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "{output}")
+                (func (export "alloc") (param i32) (result i32) i32.const 1024)
+                (func (export "on_request") (param i32 i32) (result i64) {body}))"#,
+            output = output.replace('"', "\\\""),
+            body = body,
+        );
+        WasmPlugin::from_bytes("test", wat.as_bytes()).unwrap()
+    }
+
+    fn run(plugin: &WasmPlugin) -> (JsonRpcRequest, Option<JsonRpcResponse>) {
+        let resolver = PathResolver::new(false);
+        let ctx = RequestContext { root: None, roots: &[], resolver: &resolver };
+        let mut request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "tools/call".to_string(),
+            id: Some(JsonRpcId::Number(7)),
+            params: Some(json!({"name": "save-file"})),
+        };
+        let response = plugin.on_request(&ctx, &mut request);
+        (request, response)
+    }
+
+    fn returning(output: &str) -> WasmPlugin {
+        plugin(output, &format!("i64.const {}", output.len()))
+    }
+
+    #[test]
+    fn test_pass_through_and_deny() {
+        let (request, response) = run(&plugin("", "i64.const 0"));
+        assert!(response.is_none());
+        assert_eq!(request.method, "tools/call");
+
+        let (_, response) = run(&returning(r#"{"deny": {"message": "no saving"}}"#));
+        let error = response.unwrap().error.unwrap();
+        assert_eq!((error.code, error.message.as_str()), (ERROR_POLICY_DENIED, "no saving"));
+    }
+
+    #[test]
+    fn test_rewrite_request() {
+        let (request, response) = run(&returning(
+            r#"{"request": {"jsonrpc": "2.0", "id": 7, "method": "tools/call", "params": {"name": "view"}}}"#,
+        ));
+        assert!(response.is_none());
+        assert_eq!(request.params.unwrap()["name"], "view");
+    }
+
+    #[test]
+    fn test_runaway_plugin_fails_closed() {
+        let (_, response) = run(&plugin("", "(loop (br 0)) i64.const 0"));
+        assert_eq!(response.unwrap().error.unwrap().code, ERROR_INTERNAL_ERROR);
+    }
+}
@@ -0,0 +1,370 @@
+//! Method and tool allow/deny policy
+//! Blocks MCP methods or tools at the proxy before anything reaches a backend
+
+use crate::error::{ERROR_METHOD_NOT_FOUND, ERROR_POLICY_DENIED};
+use crate::jsonrpc::{JsonRpcError, JsonRpcRequest, JsonRpcResponse};
+use crate::middleware::{Middleware, RequestContext};
+use crate::paths;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::{Component, Path, PathBuf};
+use tracing::warn;
+
+/// Lifecycle methods that are never blocked, so a broad allowlist can't break the session
+const ALWAYS_ALLOWED: &[&str] = &[
+    "initialize",
+    "notifications/initialized",
+    "shutdown",
+    "exit",
+    "ping",
+    "notifications/cancelled",
+];
+
+/// `policy` section of the config file
+///
+/// Allow/deny entries are globs. Deny always wins; a non-empty allowlist permits
+/// only what it matches.
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(default)]
+pub struct PolicyConfig {
+    pub allow_methods: Vec<String>,
+    pub deny_methods: Vec<String>,
+    pub allow_tools: Vec<String>,
+    pub deny_tools: Vec<String>,
+    /// Reject `tools/call` requests whose path arguments fall outside the workspace roots
+    pub confine_paths: bool,
+    /// Extra argument names treated as paths (on top of the built-in heuristics)
+    pub path_arguments: Vec<String>,
+    /// Directories allowed in addition to the workspace roots
+    pub allowed_paths: Vec<PathBuf>,
+}
+
+/// Why a request was blocked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyViolation {
+    Method(String),
+    Tool(String),
+}
+
+/// Allow/deny lists with their globs compiled
+#[derive(Default)]
+pub struct Policy {
+    allow_methods: Option<GlobSet>,
+    deny_methods: Option<GlobSet>,
+    allow_tools: Option<GlobSet>,
+    deny_tools: Option<GlobSet>,
+    confine_paths: bool,
+    path_arguments: Vec<String>,
+    allowed_paths: Vec<PathBuf>,
+}
+
+impl Policy {
+    /// Compile from config, skipping (and warning about) invalid globs
+    pub fn new(config: &PolicyConfig) -> Self {
+        Self {
+            allow_methods: compile("allow_methods", &config.allow_methods),
+            deny_methods: compile("deny_methods", &config.deny_methods),
+            allow_tools: compile("allow_tools", &config.allow_tools),
+            deny_tools: compile("deny_tools", &config.deny_tools),
+            confine_paths: config.confine_paths,
+            path_arguments: config.path_arguments.clone(),
+            allowed_paths: config.allowed_paths.clone(),
+        }
+    }
+
+    /// Whether any rule is configured
+    pub fn is_active(&self) -> bool {
+        self.allow_methods.is_some()
+            || self.deny_methods.is_some()
+            || self.allow_tools.is_some()
+            || self.deny_tools.is_some()
+            || self.confine_paths
+    }
+
+    /// Check a request against the method and (for `tools/call`) tool lists
+    pub fn check(&self, request: &JsonRpcRequest) -> Result<(), PolicyViolation> {
+        if ALWAYS_ALLOWED.contains(&request.method.as_str()) {
+            return Ok(());
+        }
+        if !permitted(&self.allow_methods, &self.deny_methods, &request.method) {
+            return Err(PolicyViolation::Method(request.method.clone()));
+        }
+        if request.method == "tools/call" {
+            let tool = request
+                .get_param_path("name")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default();
+            if !self.tool_permitted(tool) {
+                return Err(PolicyViolation::Tool(tool.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a tool may be listed and called
+    pub fn tool_permitted(&self, tool: &str) -> bool {
+        permitted(&self.allow_tools, &self.deny_tools, tool)
+    }
+
+    /// Remove blocked tools from a `tools/list` result so the model never sees them
+    pub fn filter_tools_list(&self, result: &mut Value) {
+        if self.allow_tools.is_none() && self.deny_tools.is_none() {
+            return;
+        }
+        if let Some(tools) = result.get_mut("tools").and_then(|t| t.as_array_mut()) {
+            tools.retain(|tool| {
+                tool.get("name")
+                    .and_then(|n| n.as_str())
+                    .is_some_and(|name| self.tool_permitted(name))
+            });
+        }
+    }
+
+    /// Check that every path argument of a tool call stays inside a known root
+    /// Returns the first offending path as written in the request
+    pub fn check_paths(&self, ctx: &RequestContext, arguments: &Value) -> Result<(), String> {
+        let allowed: Vec<PathBuf> = ctx.roots.iter()
+            .cloned()
+            .chain(self.allowed_paths.iter().map(|p| ctx.resolver.resolve(p)))
+            .collect();
+
+        for raw in argument_paths(arguments, &self.path_arguments) {
+            let path = paths::uri_to_path(&raw).ok_or_else(|| raw.clone())?;
+            // Relative paths are relative to the root the request is routed to
+            let path = match (path.is_absolute(), ctx.root) {
+                (true, _) => path,
+                (false, Some(base)) => base.join(path),
+                (false, None) => return Err(raw),
+            };
+            // Lexically first so `..` can't escape through a missing tail, then resolve symlinks
+            let resolved = normalize_lexically(&ctx.resolver.resolve(&normalize_lexically(&path)));
+            if !allowed.iter().any(|root| resolved.starts_with(root)) {
+                return Err(raw);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Middleware for Policy {
+    fn on_request(&self, ctx: &RequestContext, request: &mut JsonRpcRequest) -> Option<JsonRpcResponse> {
+        if let Err(violation) = self.check(request) {
+            warn!("Blocked by policy: {:?}", violation);
+            let error = match violation {
+                PolicyViolation::Method(method) => JsonRpcError::new(
+                    ERROR_METHOD_NOT_FOUND,
+                    format!("Method not found: {} (blocked by proxy policy)", method),
+                ),
+                PolicyViolation::Tool(tool) => JsonRpcError::new(
+                    ERROR_POLICY_DENIED,
+                    format!("Tool '{}' is blocked by proxy policy", tool),
+                ),
+            };
+            return Some(JsonRpcResponse::error(request.id.clone(), error));
+        }
+
+        if self.confine_paths && request.method == "tools/call" {
+            if let Some(arguments) = request.get_param_path("arguments") {
+                if let Err(path) = self.check_paths(ctx, arguments) {
+                    warn!("Blocked {}: path {:?} is outside the workspace roots", request.method, path);
+                    return Some(JsonRpcResponse::error(
+                        request.id.clone(),
+                        JsonRpcError::new(ERROR_POLICY_DENIED, format!("Path '{}' is outside the workspace roots", path)),
+                    ));
+                }
+            }
+        }
+        None
+    }
+
+    fn on_notification(&self, _ctx: &RequestContext, notification: &mut JsonRpcRequest) -> bool {
+        match self.check(notification) {
+            Ok(()) => true,
+            Err(violation) => {
+                warn!("Dropped notification blocked by policy: {:?}", violation);
+                false
+            }
+        }
+    }
+
+    fn on_response(&self, request: &JsonRpcRequest, response: &mut JsonRpcResponse) {
+        if request.method == "tools/list" {
+            if let Some(ref mut result) = response.result {
+                self.filter_tools_list(result);
+            }
+        }
+    }
+}
+
+/// Argument names (normalized, see `normalize_key`) that always hold paths
+const PATH_KEYS: &[&str] = &[
+    "path", "paths", "file", "files", "filename", "directory", "dir", "folder", "uri", "uris",
+    "root", "cwd", "target", "source", "destination",
+];
+
+/// Collect path-like strings from `tools/call` arguments
+///
+/// A string counts as a path when its key looks like one (`path`, `filePath`,
+/// `target_dir`, configured `extra_keys`, ...) or when it is a `file://` URI.
+pub fn argument_paths(arguments: &Value, extra_keys: &[String]) -> Vec<String> {
+    let extra: Vec<String> = extra_keys.iter().map(|k| normalize_key(k)).collect();
+    let mut found = Vec::new();
+    collect_paths(arguments, false, &extra, &mut found);
+    found
+}
+
+fn collect_paths(value: &Value, is_path_key: bool, extra: &[String], found: &mut Vec<String>) {
+    match value {
+        Value::String(s) if is_path_key || s.starts_with("file://") => found.push(s.clone()),
+        Value::Array(items) => items.iter().for_each(|v| collect_paths(v, is_path_key, extra, found)),
+        Value::Object(map) => {
+            for (key, v) in map {
+                let key = normalize_key(key);
+                let is_path_key = PATH_KEYS.contains(&key.as_str())
+                    || key.ends_with("path")
+                    || key.ends_with("uri")
+                    || key.ends_with("dir")
+                    || extra.contains(&key);
+                collect_paths(v, is_path_key, extra, found);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn normalize_key(key: &str) -> String {
+    key.chars().filter(|c| *c != '_' && *c != '-').flat_map(char::to_lowercase).collect()
+}
+
+/// Resolve `.` and `..` lexically, so a missing path can't climb out of a root
+pub fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+fn permitted(allow: &Option<GlobSet>, deny: &Option<GlobSet>, name: &str) -> bool {
+    if deny.as_ref().is_some_and(|d| d.is_match(name)) {
+        return false;
+    }
+    allow.as_ref().is_none_or(|a| a.is_match(name))
+}
+
+fn compile(section: &str, patterns: &[String]) -> Option<GlobSet> {
+    if patterns.is_empty() {
+        return None;
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        match Glob::new(pattern) {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(e) => warn!("Ignoring invalid {} pattern {:?}: {}", section, pattern, e),
+        }
+    }
+    builder.build().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(method: &str, params: Option<Value>) -> JsonRpcRequest {
+        JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method.to_string(),
+            id: None,
+            params,
+        }
+    }
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_method_lists() {
+        let policy = Policy::new(&PolicyConfig {
+            allow_methods: strings(&["tools/*", "resources/*"]),
+            deny_methods: strings(&["resources/subscribe"]),
+            ..Default::default()
+        });
+        assert!(policy.check(&request("tools/list", None)).is_ok());
+        assert!(policy.check(&request("initialize", None)).is_ok());
+        assert_eq!(
+            policy.check(&request("resources/subscribe", None)),
+            Err(PolicyViolation::Method("resources/subscribe".to_string()))
+        );
+        assert!(policy.check(&request("prompts/list", None)).is_err());
+    }
+
+    #[test]
+    fn test_tool_lists() {
+        let policy = Policy::new(&PolicyConfig {
+            deny_tools: strings(&["*write*", "save-file"]),
+            ..Default::default()
+        });
+        let call = |name: &str| request("tools/call", Some(json!({"name": name})));
+        assert!(policy.check(&call("codebase-retrieval")).is_ok());
+        assert_eq!(policy.check(&call("save-file")), Err(PolicyViolation::Tool("save-file".to_string())));
+        assert!(policy.check(&call("file_write")).is_err());
+
+        let mut result = json!({"tools": [{"name": "codebase-retrieval"}, {"name": "save-file"}]});
+        policy.filter_tools_list(&mut result);
+        assert_eq!(result, json!({"tools": [{"name": "codebase-retrieval"}]}));
+    }
+
+    #[test]
+    fn test_argument_paths() {
+        let arguments = json!({
+            "filePath": "/repo/src/main.rs",
+            "target_dir": "../other",
+            "query": "/etc/passwd is not a path argument",
+            "include": ["file:///etc/hosts"],
+            "options": {"paths": ["a.rs", "b.rs"], "glob": "**/*.rs"}
+        });
+        let mut found = argument_paths(&arguments, &strings(&["glob"]));
+        found.sort();
+        assert_eq!(found, strings(&["**/*.rs", "../other", "/repo/src/main.rs", "a.rs", "b.rs", "file:///etc/hosts"]));
+    }
+
+    #[test]
+    fn test_normalize_lexically() {
+        assert_eq!(normalize_lexically(Path::new("/repo/missing/../../etc")), PathBuf::from("/etc"));
+        assert_eq!(normalize_lexically(Path::new("/repo/./src/../lib")), PathBuf::from("/repo/lib"));
+    }
+
+    #[test]
+    fn test_check_paths() {
+        let policy = Policy::new(&PolicyConfig {
+            confine_paths: true,
+            allowed_paths: vec![PathBuf::from("/shared")],
+            ..Default::default()
+        });
+        let resolver = crate::paths::PathResolver::new(false);
+        let roots = [PathBuf::from("/repo")];
+        let ctx = RequestContext { root: Some(Path::new("/repo")), roots: &roots, resolver: &resolver };
+        assert!(policy.check_paths(&ctx, &json!({"path": "src/../lib/a.rs"})).is_ok());
+        assert!(policy.check_paths(&ctx, &json!({"path": "/shared/notes.md"})).is_ok());
+        assert_eq!(policy.check_paths(&ctx, &json!({"path": "../etc/passwd"})), Err("../etc/passwd".to_string()));
+    }
+
+    #[test]
+    fn test_empty_policy_allows_everything() {
+        let policy = Policy::new(&PolicyConfig::default());
+        assert!(!policy.is_active());
+        assert!(policy.check(&request("anything/at/all", None)).is_ok());
+    }
+}
@@ -2,17 +2,21 @@
 //! Ensures all child processes are killed when the proxy exits
 
 use crate::error::ProxyError;
+use crate::state::{BackendRecord, RecordedBackend};
 use nix::sys::signal::{kill, Signal};
 use nix::unistd::Pid;
-use std::collections::HashSet;
+use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Mutex;
 use tracing::{debug, info, warn};
 
 /// Wrapper around Unix Process Group management
 /// Tracks child PIDs and kills them on drop
 pub struct ProcessGroup {
-    /// Set of child process PIDs to manage
-    children: Mutex<HashSet<i32>>,
+    /// Child processes to manage, by PID
+    children: Mutex<HashMap<u32, RecordedBackend>>,
+    /// The children on disk, for the next run in case this one dies uncleanly
+    record: BackendRecord,
 }
 
 impl ProcessGroup {
@@ -20,34 +24,39 @@ impl ProcessGroup {
     pub fn new() -> Result<Self, ProxyError> {
         info!("ProcessGroup created for child process management");
         Ok(Self {
-            children: Mutex::new(HashSet::new()),
+            children: Mutex::new(HashMap::new()),
+            record: BackendRecord::new(),
         })
     }
 
-    /// Add a child process to the group by PID
-    pub fn add_process(&self, pid: u32) -> Result<(), ProxyError> {
+    /// Add a child process (the backend for `root`) to the group by PID
+    pub fn add_process(&self, pid: u32, root: &Path) -> Result<(), ProxyError> {
         let mut children = self.children.lock().map_err(|e| {
             ProxyError::JobObjectError(format!("Failed to lock children set: {}", e))
         })?;
         
-        children.insert(pid as i32);
+        let name = crate::daemon::process_name(pid);
+        children.insert(pid, RecordedBackend { pid, root: root.to_path_buf(), name });
+        self.record.save(&children);
         debug!("Process PID {} added to ProcessGroup", pid);
         Ok(())
     }
 
-    /// Remove a process from tracking (called when process exits normally)
+    /// Remove a process from tracking (called when the backend is gone)
     pub fn remove_process(&self, pid: u32) {
         if let Ok(mut children) = self.children.lock() {
-            children.remove(&(pid as i32));
-            debug!("Process PID {} removed from ProcessGroup", pid);
+            if children.remove(&pid).is_some() {
+                self.record.save(&children);
+                debug!("Process PID {} removed from ProcessGroup", pid);
+            }
         }
     }
 
     /// Kill all tracked child processes
     fn kill_all(&self) {
         if let Ok(children) = self.children.lock() {
-            for &pid in children.iter() {
-                let nix_pid = Pid::from_raw(pid);
+            for &pid in children.keys() {
+                let nix_pid = Pid::from_raw(pid as i32);
                 
                 // First try SIGTERM for graceful shutdown
                 match kill(nix_pid, Signal::SIGTERM) {
@@ -64,8 +73,8 @@ impl ProcessGroup {
             std::thread::sleep(std::time::Duration::from_millis(100));
             
             // Then SIGKILL any remaining
-            for &pid in children.iter() {
-                let nix_pid = Pid::from_raw(pid);
+            for &pid in children.keys() {
+                let nix_pid = Pid::from_raw(pid as i32);
                 match kill(nix_pid, Signal::SIGKILL) {
                     Ok(_) => debug!("Sent SIGKILL to process {}", pid),
                     Err(nix::errno::Errno::ESRCH) => {}
@@ -1,21 +1,57 @@
 //! MCP Proxy - main proxy logic coordinating stdio, routing, and backends
 
-use crate::backend::BackendInstance;
-use crate::config::Config;
-use crate::error::{ProxyError, ERROR_BACKEND_SPAWN_FAILED, ERROR_BACKEND_UNAVAILABLE, ERROR_INTERNAL_ERROR};
-use crate::git_filter::{self, GitTrackedFiles};
-use crate::jsonrpc::{JsonRpcError, JsonRpcRequest, JsonRpcResponse};
-use crate::throttle::EventThrottler;
+use crate::admin::AdminCommand;
+use crate::audit::{AuditEntry, AuditLog};
+use crate::backend::{self, BackendEvent, BackendHandle, BackendInstance, EventSink};
+use crate::backend_log::{self, StderrSink};
+use crate::balance;
+use crate::canary::CanaryGate;
+use crate::config::{Config, RootConfig};
+use crate::confirm::{self, ConfirmChannel, ConfirmDecision, Confirmer};
+use crate::crash::{self, CrashSession};
+use crate::diagnostics;
+use crate::error::{
+    ProxyError, ERROR_BACKEND_SPAWN_FAILED, ERROR_BACKEND_UNAVAILABLE, ERROR_CONFIRMATION_REJECTED,
+    ERROR_INTERNAL_ERROR, ERROR_INVALID_PARAMS, ERROR_INVALID_REQUEST, ERROR_METHOD_NOT_FOUND, ERROR_PARSE_ERROR,
+    ERROR_NOT_INITIALIZED, ERROR_QUEUE_FULL, ERROR_ROUTING_FAILED,
+};
+use crate::eviction;
+use crate::git_filter::{self, GitFilterCache, PathFilter, PathVerdict};
+use crate::jsonrpc::{self, JsonRpcError, JsonRpcId, JsonRpcRequest, JsonRpcResponse};
+use crate::lifecycle::{self, Phase, PreInitPolicy, PreInitQueue};
+use crate::limit::{OversizeMode, ResponseLimit, SpillStore, SPILL_URI_PREFIX};
+use crate::logging::{self, LevelHandle};
+use crate::metrics::Metrics;
+use crate::middleware::{MiddlewareChain, RequestContext};
+use crate::order::ResponseOrder;
+use crate::paths::{self, PathResolver};
+use crate::policy::Policy;
+use crate::queue::{self, InflightLimiter};
+use crate::redact::{self, RedactionLayer};
+use crate::retry::RetryPolicy;
+use crate::routing::{self, RoutingRules};
+use crate::servers::{self, ServerConfig};
+use crate::shadow::{self, ShadowSampler};
+use crate::state::SessionState;
+use crate::throttle::{ChangeKind, EventThrottler};
+use crate::tools::{self, ToolNamespacing, ToolOverrides};
+use crate::transport::{BackendReader, BackendWriter};
+use crate::version::Versions;
+use crate::watch::RootWatcher;
+use crate::wsl::WslPath;
 use lru::LruCache;
-use percent_encoding::percent_decode_str;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::future::Future;
 use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
-use tokio::sync::Semaphore;
-use tracing::{debug, error, info, warn};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, OwnedSemaphorePermit};
+use tracing::{debug, error, info, warn, Instrument};
+
+#[cfg(feature = "scripting")]
+use crate::script::{HookOutcome, ScriptHooks};
 
 #[cfg(windows)]
 use crate::job_object::JobObject;
@@ -23,6 +59,11 @@ use crate::job_object::JobObject;
 #[cfg(unix)]
 use crate::process_group::ProcessGroup;
 
+/// Change batches kept per root until its backend spawns (oldest dropped first)
+const MAX_UNDELIVERED_BATCHES: usize = 64;
+/// Characters of params shown in a slow-request warning
+const SLOW_REQUEST_PARAMS_CHARS: usize = 300;
+
 /// MCP Proxy managing communication between IDE and backend(s)
 pub struct McpProxy {
     config: Config,
@@ -32,6 +73,96 @@ pub struct McpProxy {
     backends: LruCache<PathBuf, BackendInstance>,
     /// Default/fallback root when routing fails
     default_root: Option<PathBuf>,
+    /// Config-driven routing rules, evaluated before prefix matching
+    routing_rules: RoutingRules,
+    /// Request/response layers (metrics, redaction, policy, ...) run around routing
+    middleware: MiddlewareChain,
+    /// Append-only log of tool invocations (when configured)
+    audit_log: Option<AuditLog>,
+    /// Human confirmation gate for dangerous tools
+    confirmer: Arc<Confirmer>,
+    /// Tool calls waiting for confirmation, keyed by confirmation id, with their deadline
+    parked_calls: HashMap<String, (JsonRpcRequest, Instant)>,
+    /// Decisions from confirm commands, consumed by the run loop
+    confirm_tx: Option<mpsc::UnboundedSender<(String, ConfirmDecision)>>,
+    /// Queue of messages to the client, drained by the writer task
+    client_tx: Option<mpsc::UnboundedSender<String>>,
+    /// Responses held until those of earlier requests have gone out (`--preserve-order`)
+    response_order: Option<ResponseOrder>,
+    /// Notifications and unexpected exits of backends
+    backend_event_tx: Option<EventSink>,
+    /// Whether the client has sent `notifications/initialized`
+    client_initialized: bool,
+    /// Whether the client's `initialize` has been answered
+    initialize_handled: bool,
+    /// Requests held until then (`pre_init: queue`); `None` once they have been released
+    pre_init_queue: Option<PreInitQueue>,
+    /// Backend stderr lines to turn into logging notifications (`stderr_notifications`)
+    stderr_tx: Option<StderrSink>,
+    /// Backends started on a task, consumed by the run loop
+    spawn_tx: Option<mpsc::UnboundedSender<SpawnResult>>,
+    /// Roots whose backend is starting, with the messages waiting for it (in arrival order)
+    spawning: HashMap<PathBuf, VecDeque<QueuedMessage>>,
+    /// When the request being handled reached the proxy (for `slow_request_ms`)
+    request_arrived: Instant,
+    /// Backend answers awaited on tasks, consumed by the run loop
+    call_tx: Option<mpsc::UnboundedSender<CallResult>>,
+    /// Requests sent off to a task whose answer hasn't been handled yet
+    calls_in_flight: usize,
+    /// Audit entry of the tool call being handled, taken along when it is sent off to a task
+    call_audit: Option<(AuditEntry, Instant)>,
+    /// Further instances of roots running several backends (`instances_per_root`); they share
+    /// the root's LRU slot and leave with its backend
+    replicas: HashMap<PathBuf, Vec<BackendInstance>>,
+    /// Replicas still starting per root
+    replicas_starting: HashMap<PathBuf, usize>,
+    /// Picks requests to mirror to shadow backends (`shadow`, when it has an auggie entry)
+    shadow_sampler: Option<ShadowSampler>,
+    /// Shadow backend per root, started on the first mirrored request
+    shadows: HashMap<PathBuf, Arc<tokio::sync::Mutex<Option<BackendInstance>>>>,
+    /// Share of requests served by canary backends and their health (`canary`, when it has an auggie entry)
+    canary_gate: Option<CanaryGate>,
+    /// Node.js and auggie versions found at startup, for `proxy_status`
+    versions: Versions,
+    /// Canary backend per root, started in the background once the root's backend is up
+    canaries: HashMap<PathBuf, BackendInstance>,
+    /// Roots whose canary is starting
+    canaries_starting: HashSet<PathBuf>,
+    /// Running extra MCP servers (`servers`) by name
+    servers: BTreeMap<String, BackendInstance>,
+    /// Extra MCP servers starting
+    servers_starting: HashSet<String>,
+    /// Server each resource URI listed by an extra MCP server came from
+    server_resources: HashMap<String, String>,
+    /// Roots whose replacement backend is starting (`proxy_upgrade_backend`), with the change
+    /// batches it has to catch up on before taking over
+    upgrading: HashMap<PathBuf, Vec<JsonRpcRequest>>,
+    /// Dispatch counters per root for `load_balancing`
+    balance_counters: HashMap<PathBuf, usize>,
+    /// Reload handle of the proxy's log filter (`logging/setLevel`)
+    log_handle: Option<LevelHandle>,
+    /// Minimum MCP log level rank the client asked for
+    client_log_level: Option<usize>,
+    /// Counter for proxy-initiated request ids
+    next_client_request_id: u64,
+    /// The client's initialize params, passed on in each backend handshake
+    client_init_params: Option<serde_json::Value>,
+    /// Whether the client advertised the elicitation capability
+    client_supports_elicitation: bool,
+    /// Whether the client advertised the roots capability (answers `roots/list`)
+    client_supports_roots: bool,
+    /// Id of our outstanding `roots/list` request, if any
+    pending_roots_request: Option<String>,
+    /// Canonicalizes roots and request paths so symlinked paths match
+    path_resolver: PathResolver,
+    /// Spilled oversized results, served through resources/read (`oversize_mode: spill`)
+    spill_store: Option<Arc<SpillStore>>,
+    /// Tool namespaces handed out so far (`tool_namespacing`), namespace -> root
+    tool_namespaces: HashMap<String, PathBuf>,
+    /// When and how failed backend requests are retried
+    retry_policy: Arc<RetryPolicy>,
+    /// Sticky session -> root bindings so a conversation keeps hitting the same backend
+    sessions: LruCache<String, PathBuf>,
     /// Windows Job Object for process cleanup (Arc for sharing with backends)
     #[cfg(windows)]
     job_object: Option<Arc<JobObject>>,
@@ -42,25 +173,43 @@ pub struct McpProxy {
     server_capabilities: serde_json::Value,
     /// Whether we're shutting down
     shutting_down: bool,
-    /// Optional global inflight limiter
-    global_inflight: Option<Arc<Semaphore>>,
+    /// Backends shutting down on tasks (`retire`), waited for on exit
+    retiring: tokio::task::JoinSet<()>,
+    /// Optional global inflight limiter with bounded wait queue
+    global_inflight: Option<Arc<InflightLimiter>>,
     /// Event throttler for file change notifications
     event_throttler: Option<EventThrottler>,
-    /// Git tracked files cache per root
-    git_tracked_cache: HashMap<PathBuf, GitTrackedFiles>,
-    /// Git cache timestamps for TTL
-    git_cache_timestamps: HashMap<PathBuf, Instant>,
-    /// Metrics: total requests processed
-    metrics_total_requests: u64,
-    /// Metrics: total errors
-    metrics_total_errors: u64,
-    /// Metrics: start time for uptime calculation
-    metrics_start_time: Instant,
+    /// Change batches for roots without a running backend, delivered when it spawns
+    undelivered_changes: HashMap<PathBuf, VecDeque<JsonRpcRequest>>,
+    /// Built-in watcher over roots with a backend (`--watch`)
+    watcher: Option<RootWatcher>,
+    /// Git filter file lists per root
+    git_filter_cache: GitFilterCache,
+    /// Include/exclude globs layered on the git filter
+    path_filter: PathFilter,
+    /// Request/error counters (also a middleware layer)
+    metrics: Arc<Metrics>,
+    /// Rhai hooks around routing and forwarding
+    #[cfg(feature = "scripting")]
+    scripts: Option<ScriptHooks>,
+    /// Commands from the admin socket, taken by the run loop
+    admin_rx: Option<mpsc::UnboundedReceiver<AdminCommand>>,
+    /// Where the session state is saved on exit (`persist_session`, stdio only)
+    session_file: Option<PathBuf>,
+    /// Shut down after this long without client messages (`exit_after_idle`, stdio only)
+    exit_after_idle: Option<Duration>,
+    /// This session's backends and pending requests, as shown in crash reports
+    crash: CrashSession,
 }
 
 impl McpProxy {
     pub fn new(config: Config) -> Result<Self, ProxyError> {
-        let config = config.with_auto_detect();
+        Self::with_metrics(config, Arc::new(Metrics::new()))
+    }
+
+    /// Build a proxy counting into `metrics` (kept across reloads)
+    fn with_metrics(config: Config, metrics: Arc<Metrics>) -> Result<Self, ProxyError> {
+        let mut config = config.with_auto_detect()?;
         
         // Create Job Object on Windows
         #[cfg(windows)]
@@ -82,70 +231,433 @@ impl McpProxy {
             }
         };
 
-        let default_root = config.default_root.clone();
+        redact::init(&config.redaction);
 
-        let global_inflight = if config.max_inflight_global > 0 {
-            Some(Arc::new(Semaphore::new(config.max_inflight_global)))
-        } else {
-            None
-        };
+        let path_resolver = PathResolver::new(config.canonicalize_paths);
+        let default_root = config.default_root.as_deref().map(|root| path_resolver.resolve(root));
+        // Override keys must live in the same namespace as the roots they are looked up by
+        config.root_overrides = std::mem::take(&mut config.root_overrides)
+            .into_iter()
+            .map(|(root, overrides)| (path_resolver.resolve(&root), overrides))
+            .collect();
+        // Shards become roots of their own, found by the usual longest-prefix match
+        let sharded: Vec<(PathBuf, RootConfig)> = config.root_overrides.iter()
+            .filter(|(_, overrides)| !overrides.shards.is_empty())
+            .map(|(root, overrides)| (root.clone(), overrides.clone()))
+            .collect();
+        for (root, overrides) in sharded {
+            if overrides.ssh.is_some() || overrides.container.is_some() || overrides.connect.is_some() {
+                warn!("Ignoring shards of {}: sharding only applies to local backends", root.display());
+                continue;
+            }
+            for shard in &overrides.shards {
+                let shard_root = path_resolver.resolve(&root.join(shard));
+                if shard_root == root || !shard_root.starts_with(&root) {
+                    warn!("Ignoring shard {} of {}: not a subdirectory", shard.display(), root.display());
+                    continue;
+                }
+                info!("Sharding {} off {}", shard_root.display(), root.display());
+                // Shards share the root's settings unless they have overrides of their own
+                config.root_overrides.entry(shard_root).or_insert_with(|| RootConfig {
+                    shards: Vec::new(),
+                    namespace: None,
+                    ..overrides.clone()
+                });
+            }
+        }
+        config.pinned_roots = config.pinned_roots.iter().map(|root| path_resolver.resolve(root)).collect();
+        if !config.pinned_roots.is_empty() {
+            info!("Pinned roots: {:?}", config.pinned_roots);
+            if config.pinned_roots.len() >= config.max_backends {
+                warn!(
+                    "{} pinned roots fill all {} backend slots; other roots only get burst capacity",
+                    config.pinned_roots.len(),
+                    config.max_backends
+                );
+            }
+        }
+
+        let global_inflight = queue::global_limiter(&config);
 
-        let server_capabilities = serde_json::json!({
+        let mut server_capabilities = serde_json::json!({
             "protocolVersion": "2024-11-05",
             "capabilities": {
                 "tools": {
-                    "listChanged": false
-                }
+                    "listChanged": true
+                },
+                "logging": {}
             },
             "serverInfo": {
-                "name": "mcp-proxy",
-                "version": env!("CARGO_PKG_VERSION")
+                "name": config.server_name,
+                "version": config.server_version.as_deref().unwrap_or(env!("CARGO_PKG_VERSION"))
             }
         });
+        // Spilled results and diagnostics are served as resources
+        if (config.oversize_mode == OversizeMode::Spill && config.max_response_bytes > 0) || config.diagnostic_resources {
+            server_capabilities["capabilities"]["resources"] = serde_json::json!({});
+        }
+        // Extra MCP servers may have either
+        if !config.servers.is_empty() {
+            server_capabilities["capabilities"]["resources"] = serde_json::json!({});
+            server_capabilities["capabilities"]["prompts"] = serde_json::json!({});
+        }
+        if let Some(ref instructions) = config.instructions {
+            server_capabilities["instructions"] = instructions.clone().into();
+        }
 
         let event_throttler = if config.debounce_ms > 0 {
             info!("Event throttler enabled with {}ms debounce window", config.debounce_ms);
-            Some(EventThrottler::new(config.debounce_ms))
+            let mut throttler = EventThrottler::new(config.debounce_ms, config.max_batch_size);
+            throttler.set_adaptive(config.storm_threshold, config.max_debounce_ms);
+            for (root, overrides) in &config.root_overrides {
+                if let Some(ms) = overrides.debounce_ms {
+                    throttler.set_root_debounce(root.clone(), ms);
+                }
+            }
+            Some(throttler)
         } else {
             None
         };
 
+        let mut git_filter_cache = GitFilterCache::new(
+            config.filter_source,
+            Duration::from_secs(config.git_cache_ttl_secs),
+            config.git_cache_max_entries,
+        );
+        for (root, overrides) in &config.root_overrides {
+            if let Some(secs) = overrides.git_cache_ttl_secs {
+                git_filter_cache.set_root_ttl(root.clone(), Duration::from_secs(secs));
+            }
+        }
+        let binary_extensions: Vec<String> = match config.filter_binary {
+            true => git_filter::DEFAULT_BINARY_EXTENSIONS.iter().map(|e| e.to_string())
+                .chain(config.binary_extensions.iter().cloned())
+                .collect(),
+            false => Vec::new(),
+        };
+        let path_filter = PathFilter::new(
+            &config.filter_include,
+            &config.filter_exclude,
+            config.filter_max_file_size,
+            &binary_extensions,
+        );
+
+        let routing_rules = RoutingRules::new(&config.routing);
+        if routing_rules.len() > 0 {
+            info!("Loaded {} routing rules", routing_rules.len());
+        }
+
+        // Outermost first: metrics and redaction see every response, including policy denials
+        let mut middleware = MiddlewareChain::new();
+        middleware.push(metrics.clone());
+        // Outside redaction, so secrets are redacted before a cut could split them
+        let spill_store = match config.oversize_mode {
+            OversizeMode::Spill if config.max_response_bytes > 0 => {
+                let dir = config
+                    .spill_dir
+                    .clone()
+                    .or_else(|| crate::dirs::cache_dir().map(|dir| dir.join("spill")))
+                    .unwrap_or_else(|| std::env::temp_dir().join("mcp-proxy-spill"));
+                info!("Spilling results over {} bytes to {}", config.max_response_bytes, dir.display());
+                Some(Arc::new(SpillStore::new(dir)))
+            }
+            _ => None,
+        };
+        if config.max_response_bytes > 0 {
+            middleware.push(Arc::new(ResponseLimit::new(config.max_response_bytes, spill_store.clone())));
+        }
+        middleware.push(Arc::new(RedactionLayer));
+        // Outside the policy, so it checks and filters backend tool names rather than aliases
+        let tool_overrides = ToolOverrides::new(&config.tools);
+        if tool_overrides.is_active() {
+            info!("Tool overrides enabled for {} tools", config.tools.len());
+            middleware.push(Arc::new(tool_overrides));
+        }
+        let policy = Policy::new(&config.policy);
+        if policy.is_active() {
+            info!("Method/tool policy enabled");
+            middleware.push(Arc::new(policy));
+        }
+        // Plugins may enforce policy of their own, so never run without them
+        #[cfg(feature = "wasm-plugins")]
+        for path in &config.plugins {
+            middleware.push(Arc::new(crate::plugin::WasmPlugin::load(path)?));
+        }
+        #[cfg(not(feature = "wasm-plugins"))]
+        if !config.plugins.is_empty() {
+            return Err(ProxyError::ConfigError(
+                "`plugins` is configured but this build lacks the wasm-plugins feature".to_string(),
+            ));
+        }
+
+        #[cfg(feature = "scripting")]
+        let scripts = ScriptHooks::load(&config.scripts)?;
+        #[cfg(not(feature = "scripting"))]
+        {
+            let s = &config.scripts;
+            if s.pre_route.is_some() || s.pre_forward.is_some() || s.post_response.is_some() {
+                return Err(ProxyError::ConfigError(
+                    "`scripts` is configured but this build lacks the scripting feature".to_string(),
+                ));
+            }
+        }
+
+        let confirmer = Arc::new(Confirmer::new(&config.confirm));
+
+        let audit_log = match config.audit.path {
+            Some(ref path) => match AuditLog::open(path, config.audit.max_bytes, config.audit.max_files) {
+                Ok(log) => {
+                    info!("Audit log: {}", path.display());
+                    Some(log)
+                }
+                Err(e) => {
+                    warn!("Failed to open audit log {}: {}", path.display(), e);
+                    None
+                }
+            },
+            None => None,
+        };
+
         // Create LRU cache for backends with configured max capacity
         let backends_capacity = NonZeroUsize::new(config.max_backends.max(1))
             .unwrap_or(NonZeroUsize::new(3).unwrap());
         info!("Backend LRU cache initialized with capacity: {}", backends_capacity);
 
+        let sessions_capacity = NonZeroUsize::new(config.max_sessions.max(1))
+            .unwrap_or(NonZeroUsize::new(256).unwrap());
+        let retry_policy = Arc::new(RetryPolicy::new(&config.retry));
+        let canary_gate = config.canary.auggie_entry.as_ref().map(|entry| {
+            info!("Routing {}% of requests to canary backends running {}", config.canary.percent, entry.display());
+            CanaryGate::new(&config.canary)
+        });
+        let shadow_sampler = config.shadow.auggie_entry.as_ref().map(|entry| {
+            info!("Mirroring {}% of {:?} to shadow backends running {}", config.shadow.percent, config.shadow.methods, entry.display());
+            ShadowSampler::new(&config.shadow)
+        });
+
+        let versions = Versions::detect(config.runtime, config.node.as_deref(), config.auggie_entry.as_deref());
+        info!(
+            "{} version: {}, auggie version: {}",
+            config.runtime,
+            versions.node.as_deref().unwrap_or("unknown"),
+            versions.auggie.as_deref().unwrap_or("unknown")
+        );
+        if let Err(e) = versions.check(config.min_node_version.as_deref(), config.min_auggie_version.as_deref()) {
+            warn!("{}; local backends will be refused", e);
+        }
+        // Strict lifecycle rejects early requests rather than holding them
+        let pre_init_queue =
+            (config.pre_init == PreInitPolicy::Queue && !config.strict_lifecycle).then(PreInitQueue::default);
+
         Ok(Self {
             config,
             roots: Vec::new(),
             backends: LruCache::new(backends_capacity),
             default_root,
+            routing_rules,
+            middleware,
+            audit_log,
+            confirmer,
+            parked_calls: HashMap::new(),
+            confirm_tx: None,
+            client_tx: None,
+            response_order: None,
+            backend_event_tx: None,
+            spawn_tx: None,
+            spawning: HashMap::new(),
+            request_arrived: Instant::now(),
+            call_tx: None,
+            calls_in_flight: 0,
+            call_audit: None,
+            replicas: HashMap::new(),
+            replicas_starting: HashMap::new(),
+            upgrading: HashMap::new(),
+            shadow_sampler,
+            canary_gate,
+            versions,
+            canaries: HashMap::new(),
+            canaries_starting: HashSet::new(),
+            servers: BTreeMap::new(),
+            servers_starting: HashSet::new(),
+            server_resources: HashMap::new(),
+            shadows: HashMap::new(),
+            balance_counters: HashMap::new(),
+            client_initialized: false,
+            initialize_handled: false,
+            pre_init_queue,
+            stderr_tx: None,
+            log_handle: None,
+            client_log_level: None,
+            next_client_request_id: 0,
+            client_init_params: None,
+            client_supports_elicitation: false,
+            client_supports_roots: false,
+            pending_roots_request: None,
+            path_resolver,
+            spill_store,
+            tool_namespaces: HashMap::new(),
+            retry_policy,
+            sessions: LruCache::new(sessions_capacity),
             #[cfg(windows)]
             job_object,
             #[cfg(unix)]
             process_group,
             server_capabilities,
             shutting_down: false,
+            retiring: tokio::task::JoinSet::new(),
             global_inflight,
             event_throttler,
-            git_tracked_cache: HashMap::new(),
-            git_cache_timestamps: HashMap::new(),
-            metrics_total_requests: 0,
-            metrics_total_errors: 0,
-            metrics_start_time: Instant::now(),
+            undelivered_changes: HashMap::new(),
+            watcher: None,
+            git_filter_cache,
+            path_filter,
+            metrics,
+            #[cfg(feature = "scripting")]
+            scripts,
+            admin_rx: None,
+            session_file: None,
+            exit_after_idle: None,
+            crash: CrashSession::new(),
         })
     }
 
-    /// Main run loop - read from stdin, process, write to stdout
-    pub async fn run(&mut self) -> Result<(), ProxyError> {
-        let stdin = tokio::io::stdin();
-        let stdout = tokio::io::stdout();
-        
-        let mut reader = BufReader::new(stdin);
-        let mut writer = stdout;
+    /// Let `logging/setLevel` change the proxy's own log level
+    pub fn set_log_handle(&mut self, handle: LevelHandle) {
+        self.log_handle = Some(handle);
+    }
+
+    /// Answer `status` and `reload` from the admin socket
+    pub fn set_admin_channel(&mut self, admin_rx: mpsc::UnboundedReceiver<AdminCommand>) {
+        self.admin_rx = Some(admin_rx);
+    }
+
+    /// Count requests against a global inflight limiter shared with other sessions, so
+    /// `max_inflight_global` holds across `--listen` connections
+    pub fn set_inflight_limiter(&mut self, limiter: Option<Arc<InflightLimiter>>) {
+        self.global_inflight = limiter;
+    }
+
+    /// Main run loop - read from stdin, process, write to stdout until `stop` completes
+    pub async fn run(&mut self, stop: impl Future<Output = ()>) -> Result<(), ProxyError> {
+        if self.config.persist_session {
+            let path = SessionState::path(crate::instance::id(&self.config).as_deref(), self.default_root.as_deref());
+            self.restore_session(&path);
+            self.session_file = Some(path);
+        }
+        if self.config.exit_after_idle > 0 {
+            self.exit_after_idle = Some(Duration::from_secs(self.config.exit_after_idle * 60));
+        }
+        self.serve(tokio::io::stdin(), tokio::io::stdout(), stop).await
+    }
+
+    /// Pick up the routing context saved by the previous run, until the client reports its own
+    fn restore_session(&mut self, path: &Path) {
+        let Some(state) = SessionState::take(path) else { return };
+        info!(
+            "Restored session state: {} roots, {} session bindings, change batches for {} roots",
+            state.roots.len(),
+            state.sessions.len(),
+            state.changes.len()
+        );
+        self.roots = state.roots;
+        for (session, root) in state.sessions {
+            self.sessions.put(session, root);
+        }
+        // Delivered (and replayed) when each root's backend starts
+        for (root, batches) in state.changes {
+            for batch in batches {
+                self.hold_undelivered(root.clone(), batch);
+            }
+        }
+    }
+
+    /// Save the routing context for the next run (`persist_session`)
+    fn save_session(&self) {
+        let Some(ref path) = self.session_file else { return };
+        let mut changes: HashMap<PathBuf, Vec<JsonRpcRequest>> = HashMap::new();
+        for (root, backend) in self.backends.iter() {
+            let recent = backend.recent_changes();
+            if !recent.is_empty() {
+                changes.insert(root.clone(), recent);
+            }
+        }
+        for (root, batches) in &self.undelivered_changes {
+            changes.entry(root.clone()).or_default().extend(batches.iter().cloned());
+        }
+        let sessions = self.sessions.iter().rev().map(|(session, root)| (session.clone(), root.clone())).collect();
+        SessionState { saved_at: 0, roots: self.roots.clone(), sessions, changes }.save(path);
+    }
+
+    /// Serve one client over `input`/`output` until it disconnects, asks to exit, or `stop` completes
+    pub async fn serve<R, W>(&mut self, input: R, output: W, stop: impl Future<Output = ()>) -> Result<(), ProxyError>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let mut reader = BufReader::new(input);
         let mut msg = String::new();
+        tokio::pin!(stop);
+
+        // All output to the client (responses and proxy-initiated requests) goes through one writer
+        let (client_tx, mut client_rx) = mpsc::unbounded_channel::<String>();
+        self.client_tx = Some(client_tx);
+        self.response_order = self.config.preserve_order.then(ResponseOrder::default);
+        let writer_task = tokio::spawn(async move {
+            let mut writer = output;
+            while let Some(line) = client_rx.recv().await {
+                crash::record_wire("proxy->client", &line);
+                let written = async {
+                    writer.write_all(line.as_bytes()).await?;
+                    writer.write_all(b"\n").await?;
+                    writer.flush().await
+                };
+                if let Err(e) = written.await {
+                    error!("Error writing to client: {}", e);
+                    break;
+                }
+            }
+        });
 
-        info!("MCP Proxy started, waiting for requests on stdin");
+        // Changed paths from the built-in watcher (the channel stays empty without `--watch`)
+        let (watch_tx, mut watch_rx) = mpsc::unbounded_channel();
+        if self.config.watch {
+            if self.event_throttler.is_none() {
+                warn!("--watch needs the event throttler (--debounce-ms > 0), file watching disabled");
+            } else {
+                match RootWatcher::new(watch_tx) {
+                    Ok(watcher) => self.watcher = Some(watcher),
+                    Err(e) => warn!("Failed to start file watcher: {}", e),
+                }
+            }
+        }
+
+        // Backend stderr lines (the channel stays empty without `stderr_notifications`)
+        let (stderr_tx, mut stderr_rx) = mpsc::unbounded_channel();
+        if self.config.stderr_notifications {
+            self.stderr_tx = Some(stderr_tx);
+        }
+
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        self.backend_event_tx = Some(event_tx);
+
+        let (spawn_tx, mut spawn_rx) = mpsc::unbounded_channel();
+        self.spawn_tx = Some(spawn_tx);
+
+        let (call_tx, mut call_rx) = mpsc::unbounded_channel();
+        self.call_tx = Some(call_tx);
+
+        let (confirm_tx, mut confirm_rx) = mpsc::unbounded_channel();
+        self.confirm_tx = Some(confirm_tx);
+        let mut confirm_tick = tokio::time::interval(Duration::from_secs(1));
+        confirm_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        let mut admin_rx = self.admin_rx.take();
+
+        let exit_after_idle = self.exit_after_idle;
+        let client_idle = tokio::time::sleep(exit_after_idle.unwrap_or_default());
+        tokio::pin!(client_idle);
+
+        info!("MCP Proxy started, waiting for requests");
 
         let idle_ttl = Duration::from_secs(self.config.idle_ttl_seconds);
         let cleanup_interval = Duration::from_secs(60);
@@ -153,44 +665,48 @@ impl McpProxy {
         cleanup_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
         cleanup_tick.tick().await;
 
-        let throttle_interval = Duration::from_millis(self.config.debounce_ms.max(100));
+        let min_debounce = self.event_throttler.as_ref().map_or(Duration::ZERO, |t| t.min_debounce());
+        let throttle_interval = min_debounce.max(Duration::from_millis(100));
         let mut throttle_tick = tokio::time::interval(throttle_interval);
         throttle_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
         throttle_tick.tick().await;
         
         loop {
             msg.clear();
+            self.crash.update(self.crash_summary());
+            let pre_init_deadline = self.pre_init_queue.as_ref().and_then(PreInitQueue::deadline);
             
             tokio::select! {
                 result = Self::read_next_message(&mut reader, &mut msg) => {
                     match result {
                         Ok(None) => {
-                            info!("Stdin closed (EOF), shutting down");
+                            info!("Client input closed (EOF), shutting down");
                             break;
                         }
                         Ok(Some(())) => {
+                            if let Some(idle) = exit_after_idle {
+                                client_idle.as_mut().reset(tokio::time::Instant::now() + idle);
+                            }
                             let trimmed = msg.trim();
                             if trimmed.is_empty() {
                                 continue;
                             }
+                            crash::record_wire("client->proxy", trimmed);
 
-                            debug!("Received from IDE: {}", trimmed);
+                            debug!("Received from IDE: {}", redact::global().redact_str(trimmed));
 
                             match self.handle_message(trimmed).await {
-                                Ok(Some(response)) => {
-                                    let response_json = serde_json::to_string(&response)?;
-                                    debug!("Sending to IDE: {}", response_json);
-                                    writer.write_all(response_json.as_bytes()).await?;
-                                    writer.write_all(b"\n").await?;
-                                    writer.flush().await?;
-                                }
+                                Ok(Some(response)) => self.send_response(response)?,
                                 Ok(None) => {
-                                    // Notification - no response needed
+                                    // Notification (or parked request) - no response yet
                                 }
                                 Err(e) => {
                                     error!("Error handling message: {}", e);
                                 }
                             }
+                            if self.initialize_handled && self.pre_init_queue.is_some() {
+                                self.release_pre_init().await?;
+                            }
 
                             if self.shutting_down {
                                 info!("Exit requested, shutting down");
@@ -198,12 +714,34 @@ impl McpProxy {
                             }
                         }
                         Err(e) => {
-                            error!("Error reading stdin: {}", e);
+                            error!("Error reading from client: {}", e);
                             break;
                         }
                     }
                 }
 
+                _ = &mut stop => {
+                    info!("Stop requested, shutting down");
+                    break;
+                }
+
+                _ = tokio::time::sleep_until(pre_init_deadline.unwrap_or_else(Instant::now).into()), if pre_init_deadline.is_some() => {
+                    let held = self.pre_init_queue.as_ref().map_or(0, PreInitQueue::len);
+                    warn!("No initialize within {}s, handling {} held request(s)", lifecycle::PRE_INIT_TIMEOUT.as_secs(), held);
+                    self.release_pre_init().await?;
+                }
+
+                _ = &mut client_idle, if exit_after_idle.is_some() => {
+                    // Still busy for the client: a call in flight, one awaiting confirmation, a backend starting
+                    if self.calls_in_flight > 0 || !self.parked_calls.is_empty() || !self.spawning.is_empty() {
+                        debug!("No client messages for {} minutes, but requests are outstanding", self.config.exit_after_idle);
+                        client_idle.as_mut().reset(tokio::time::Instant::now() + exit_after_idle.unwrap_or_default());
+                        continue;
+                    }
+                    info!("No client messages for {} minutes, shutting down", self.config.exit_after_idle);
+                    break;
+                }
+
                 _ = cleanup_tick.tick() => {
                     self.cleanup_idle_backends(idle_ttl).await;
                 }
@@ -211,11 +749,91 @@ impl McpProxy {
                 _ = throttle_tick.tick() => {
                     self.flush_throttled_events().await;
                 }
+
+                Some((path, kind)) = watch_rx.recv() => {
+                    self.enqueue_changed_path(path, kind).await;
+                }
+
+                Some((root, line)) = stderr_rx.recv() => {
+                    self.send_stderr_notification(&root, &line);
+                }
+
+                Some((root, event)) = event_rx.recv() => {
+                    match event {
+                        BackendEvent::Notification(notification) => self.handle_backend_notification(&root, notification),
+                        BackendEvent::Exited(status) => self.handle_backend_exit(&root, status).await,
+                    }
+                }
+
+                Some((root, purpose, mut result)) = spawn_rx.recv() => {
+                    // The client may have finished initializing while it started
+                    if let Ok(backend) = result.as_mut() {
+                        if self.client_initialized {
+                            backend.client_initialized().await;
+                        }
+                    }
+                    match purpose {
+                        SpawnPurpose::Root => self.finish_spawn(root, result).await?,
+                        SpawnPurpose::Replica => self.finish_replica(root, result).await,
+                        SpawnPurpose::Replacement => self.finish_upgrade(root, result).await,
+                        SpawnPurpose::Canary => self.finish_canary(root, result).await,
+                        SpawnPurpose::Server => self.finish_server(root, result).await,
+                        SpawnPurpose::Restart { canary, instance, replacing } => {
+                            self.finish_restart(root, canary, instance, replacing, result).await?
+                        }
+                    }
+                }
+
+                Some((call, result)) = call_rx.recv() => {
+                    self.finish_call(call, result).await?;
+                }
+
+                Some((key, decision)) = confirm_rx.recv() => {
+                    self.resolve_confirmation(&key, decision).await?;
+                }
+
+                _ = confirm_tick.tick(), if !self.parked_calls.is_empty() => {
+                    self.expire_confirmations().await?;
+                }
+
+                Some(command) = async { admin_rx.as_mut()?.recv().await }, if admin_rx.is_some() => {
+                    match command {
+                        AdminCommand::Status(reply) => {
+                            let _ = reply.send(self.status());
+                        }
+                        AdminCommand::Reload(config, limiter, reply) => {
+                            let _ = reply.send(self.reload(*config, limiter));
+                        }
+                    }
+                }
             }
         }
 
+        // Answer what's still in flight before the backends go
+        while self.calls_in_flight > 0 {
+            let Some((call, result)) = call_rx.recv().await else { break };
+            self.finish_call(call, result).await?;
+        }
+
+        if self.session_file.is_some() {
+            // Pending changes end up in a backend's recent batches or held for their root
+            self.flush_throttled_events().await;
+            self.save_session();
+        }
+
         // Cleanup all backends on exit
         self.shutdown_all_backends().await;
+
+        // Responses held behind requests that were never answered
+        if let Some(mut order) = self.response_order.take() {
+            for line in order.flush() {
+                self.send_to_client(line);
+            }
+        }
+
+        // Let queued output drain before exiting
+        self.client_tx = None;
+        let _ = writer_task.await;
         
         info!("MCP Proxy exiting");
         Ok(())
@@ -228,24 +846,187 @@ impl McpProxy {
         
         debug!("Parsing message (len={}): first 100 chars = {:?}", 
                message.len(), 
-               &message.chars().take(100).collect::<String>());
+               redact::global().redact_str(message).chars().take(100).collect::<String>());
         
-        let request: JsonRpcRequest = match serde_json::from_str(message) {
-            Ok(req) => req,
+        let value: serde_json::Value = match serde_json::from_str(message) {
+            Ok(value) => value,
             Err(e) => {
-                warn!("Failed to parse JSON-RPC request: {} | Raw bytes: {:?}", e, message.as_bytes().iter().take(50).collect::<Vec<_>>());
+                warn!("Failed to parse JSON-RPC request: {} | Raw bytes: {:?}", e, redact::global().redact_str(message).as_bytes().iter().take(50).collect::<Vec<_>>());
                 return Ok(Some(JsonRpcResponse::error(
-                    None,
-                    JsonRpcError::new(-32700, format!("Parse error: {}", e)),
+                    Some(JsonRpcId::Null),
+                    JsonRpcError::new(ERROR_PARSE_ERROR, format!("Parse error: {}", e)),
                 )));
             }
         };
 
-        info!("Handling request: {} (id: {:?})", request.method, request.id);
-        
-        // Record metrics
-        self.record_request();
+        // Responses to proxy-initiated requests (e.g. elicitation) carry an id but no method
+        if value.get("method").is_none() && value.get("id").is_some() {
+            if let Ok(response) = serde_json::from_value::<JsonRpcResponse>(value.clone()) {
+                if response.result.is_some() || response.error.is_some() {
+                    self.handle_client_response(response).await?;
+                    return Ok(None);
+                }
+            }
+        }
+
+        if let Err(reason) = jsonrpc::validate_request(&value) {
+            warn!("Invalid JSON-RPC request: {}", reason);
+            return Ok(Some(JsonRpcResponse::error(
+                Some(jsonrpc::error_id(&value)),
+                JsonRpcError::new(ERROR_INVALID_REQUEST, format!("Invalid Request: {}", reason)),
+            )));
+        }
+        let mut request: JsonRpcRequest = serde_json::from_value(value)?;
+        if let (Some(order), Some(id)) = (self.response_order.as_mut(), request.id.as_ref()) {
+            order.expect(id.clone());
+        }
+
+        if self.config.strict_lifecycle {
+            if let Some(violation) = lifecycle::violation(self.lifecycle_phase(), &request.method, request.is_notification()) {
+                warn!("Lifecycle violation: {}", violation);
+                if request.is_notification() {
+                    return Ok(None);
+                }
+                return Ok(Some(JsonRpcResponse::error(request.id, JsonRpcError::new(ERROR_INVALID_REQUEST, violation))));
+            }
+        }
+        if !self.initialize_handled && !request.is_notification() && !lifecycle::is_lifecycle(&request.method) {
+            if let Some(ref mut queue) = self.pre_init_queue {
+                debug!("Holding {} until initialize", request.method);
+                queue.push(message.to_string());
+                // It takes its place in the response order once it is handled
+                self.abandon_response(request.id.as_ref());
+                return Ok(None);
+            }
+            if self.config.pre_init == PreInitPolicy::Reject {
+                return Ok(Some(JsonRpcResponse::error(
+                    request.id,
+                    JsonRpcError::new(ERROR_NOT_INITIALIZED, format!("{} received before initialize", request.method)),
+                )));
+            }
+        }
+        self.request_arrived = Instant::now();
+
+        // Every log line for the request carries its trace id; a client-supplied one is kept
+        let trace_id = request
+            .get_param_path(&format!("_meta.{}", logging::TRACE_ID_META))
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_else(logging::new_trace_id);
+        let span = tracing::info_span!("request", trace_id = %trace_id);
+        async move {
+            info!("Handling request: {} (id: {:?})", request.method, request.id);
+
+            // Liveness checks are answered here, without going through the middleware or spawning a backend
+            if request.method == "ping" && !request.is_notification() {
+                return self.handle_ping(&request).await;
+            }
+
+            // Only the proxy itself may pin a request to a root
+            request.take_meta(routing::ROOT_HINT_META);
+            request.take_meta(routing::REQUESTED_ROOT_META);
+            if let Some(response) = self.take_requested_root(&mut request) {
+                return Ok(response);
+            }
+            if self.config.inject_trace_id {
+                request.set_meta(logging::TRACE_ID_META, trace_id.into());
+            }
+            // Before anything looks at the tool name, so policy and audit see the backend's name
+            self.resolve_tool_namespace(&mut request);
+
+            let id = request.id.clone();
+            let result = self.dispatch(request, None).await;
+            if result.is_err() {
+                self.abandon_response(id.as_ref());
+            }
+            result
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Handle a request, recording tool calls in the audit log
+    /// `decision` is set when a parked call is resumed after confirmation
+    async fn dispatch(
+        &mut self,
+        request: JsonRpcRequest,
+        decision: Option<ConfirmDecision>,
+    ) -> Result<Option<JsonRpcResponse>, ProxyError> {
+        if self.audit_log.is_some() && request.method == "tools/call" && !request.is_notification() {
+            let entry = AuditEntry::start(
+                request.id.clone(),
+                request.get_session_id(&self.config.session_id_field),
+                self.determine_root(&request).as_deref(),
+                request.get_param_path("name").and_then(|v| v.as_str()).unwrap_or_default(),
+                request.get_param_path("arguments"),
+            );
+            self.call_audit = Some((entry, Instant::now()));
+            let result = self.handle_request(request, decision).await;
+            // Parked calls are recorded once they are resumed, calls sent off once answered
+            if let Some((entry, started)) = self.call_audit.take() {
+                if !matches!(result, Ok(None)) {
+                    if let Some(audit_log) = self.audit_log.as_mut() {
+                        audit_log.record(&entry.finish(&result, started.elapsed()));
+                    }
+                }
+            }
+            return result;
+        }
+
+        self.handle_request(request, decision).await
+    }
+
+    /// Run a request through the middleware chain around `process_request`
+    async fn handle_request(
+        &mut self,
+        mut request: JsonRpcRequest,
+        decision: Option<ConfirmDecision>,
+    ) -> Result<Option<JsonRpcResponse>, ProxyError> {
+        // Calls resumed after confirmation already went through the request layers
+        if decision.is_none() {
+            let root = self.determine_root(&request);
+            let roots = self.known_roots();
+            let ctx = RequestContext {
+                root: root.as_deref(),
+                roots: &roots,
+                resolver: &self.path_resolver,
+            };
+            if request.is_notification() {
+                if !self.middleware.on_notification(&ctx, &mut request) {
+                    return Ok(None);
+                }
+            } else if let Some(response) = self.middleware.on_request(&ctx, &mut request) {
+                return Ok(Some(response));
+            }
+        }
+
+        if request.is_notification() {
+            return self.process_request(request, decision).await;
+        }
+        let response = match self.process_request(request.clone(), decision).await? {
+            Some(response) => response,
+            None => return Ok(None),
+        };
+        Ok(Some(self.finish_response(&request, response)))
+    }
+
+    /// Run a response through the middleware chain
+    fn finish_response(&self, request: &JsonRpcRequest, mut response: JsonRpcResponse) -> JsonRpcResponse {
+        self.middleware.on_response(request, &mut response);
+        // After the layers, which filter tools by their backend names
+        if request.method == "tools/list" && self.config.tool_namespacing != ToolNamespacing::Off {
+            if let Some(ref mut result) = response.result {
+                tools::apply_namespacing(result, self.config.tool_namespacing);
+            }
+        }
+        response
+    }
 
+    /// Handle a parsed JSON-RPC message
+    async fn process_request(
+        &mut self,
+        request: JsonRpcRequest,
+        decision: Option<ConfirmDecision>,
+    ) -> Result<Option<JsonRpcResponse>, ProxyError> {
         // Handle protocol-level messages
         if request.is_initialize() {
             return Ok(Some(self.handle_initialize(&request).await?));
@@ -260,79 +1041,180 @@ impl McpProxy {
             return Ok(None);
         }
 
+        if request.method == "tools/call" && !request.is_notification() {
+            let tool = request.get_param_path("name").and_then(|v| v.as_str()).unwrap_or_default();
+            match decision {
+                None if self.confirmer.requires(tool) => {
+                    return self.request_confirmation(request).await;
+                }
+                Some(ConfirmDecision::Rejected(reason)) => {
+                    info!("Tool call {} rejected: {}", tool, reason);
+                    return Ok(Some(JsonRpcResponse::error(
+                        request.id.clone(),
+                        JsonRpcError::new(ERROR_CONFIRMATION_REJECTED, reason),
+                    )));
+                }
+                _ => {}
+            }
+        }
+
+        if request.method == "tools/call" && !request.is_notification() && self.config.virtual_tools {
+            let tool = request.get_param_path("name").and_then(|v| v.as_str()).unwrap_or_default();
+            if tools::VIRTUAL_TOOLS.contains(&tool) {
+                return Ok(Some(self.call_virtual_tool(&request).await));
+            }
+        }
+
+        if let Some((server, call)) = self.server_call(&request) {
+            return self.forward_to_server(server, call, request).await;
+        }
+
+        if request.method == "tools/list"
+            && !request.is_notification()
+            && (self.config.tool_namespacing != ToolNamespacing::Off || self.config.virtual_tools || !self.config.servers.is_empty())
+        {
+            return match self.send_list(request).await {
+                Ok(response) => Ok(response),
+                Err(e) => {
+                    self.metrics.record_error();
+                    Err(e)
+                }
+            };
+        }
+
+        if request.method == "resources/read" && !request.is_notification() {
+            let uri = request.get_param_path("uri").and_then(|v| v.as_str()).unwrap_or_default();
+            if self.config.diagnostic_resources && diagnostics::is_diagnostic(uri) {
+                let contents = diagnostics::contents(uri, self.read_diagnostic(uri));
+                return Ok(Some(JsonRpcResponse::success(
+                    request.id.clone(),
+                    serde_json::json!({ "contents": [contents] }),
+                )));
+            }
+            if let Some(store) = self.spill_store.as_ref().filter(|_| uri.starts_with(SPILL_URI_PREFIX)) {
+                return Ok(Some(match store.read(uri) {
+                    Some(text) => JsonRpcResponse::success(
+                        request.id.clone(),
+                        serde_json::json!({
+                            "contents": [{ "uri": uri, "mimeType": "text/plain", "text": text }],
+                        }),
+                    ),
+                    None => JsonRpcResponse::error(
+                        request.id.clone(),
+                        JsonRpcError::new(ERROR_INVALID_PARAMS, format!("Resource not found: {}", uri)),
+                    ),
+                }));
+            }
+        }
+        if request.method == "resources/list"
+            && !request.is_notification()
+            && (self.spill_store.is_some() || self.config.diagnostic_resources || !self.config.servers.is_empty())
+        {
+            return self.send_list(request).await;
+        }
+
+        if request.method == "prompts/list" && !request.is_notification() && !self.config.servers.is_empty() {
+            return self.send_list(request).await;
+        }
+
+        if request.method == "logging/setLevel" && !request.is_notification() {
+            return self.handle_set_level(&request).await;
+        }
+
         // Handle roots/workspace changed notifications
         if request.method == "notifications/roots/listChanged" {
             self.handle_roots_changed(&request).await;
             return Ok(None);
         }
 
+        // The client only answers requests once initialization is complete; backends held
+        // their own initialized for it
+        if request.method == "notifications/initialized" {
+            self.client_initialized = true;
+            self.request_client_roots();
+            self.broadcast_initialized().await;
+            return Ok(None);
+        }
+
         // JSON-RPC notifications must not receive a response
         if request.is_notification() {
             // Check if this is a file change notification that should be throttled
             if self.should_throttle_notification(&request) {
                 if let Some(uri) = request.get_uri() {
-                    if let Some(path) = Self::uri_to_path(&uri) {
-                        // Apply git filter if enabled
-                        if self.config.git_filter {
-                            if !self.is_path_git_tracked(&path).await {
-                                debug!("Ignoring non-git-tracked file: {}", path.display());
-                                return Ok(None);
-                            }
-                        }
-                        
-                        if let Some(throttler) = self.event_throttler.as_mut() {
-                            throttler.add_path(path);
-                            debug!("File change throttled, pending: {}", throttler.pending_count());
-                            return Ok(None);
-                        }
+                    if let Some(path) = self.resolve_uri(&uri) {
+                        let kind = match request.method.as_str() {
+                            "notifications/file/didCreate" => ChangeKind::Created,
+                            "notifications/file/didDelete" => ChangeKind::Deleted,
+                            _ => ChangeKind::Changed,
+                        };
+                        self.enqueue_changed_path(path, kind).await;
+                        return Ok(None);
                     }
                 }
             }
             // Forward non-throttled notifications directly
             if let Err(e) = self.forward_notification_to_backend(request).await {
                 warn!("Failed to forward notification: {}", e);
-                self.record_error();
+                self.metrics.record_error();
             }
             return Ok(None);
         }
 
+        // Wait for a starting backend without holding up other roots
+        let request = match self.queue_for_spawn(request).await {
+            Some(request) => request,
+            None => return Ok(None),
+        };
+
         // Route to backend
-        let response = match self.route_to_backend(request).await {
-            Ok(resp) => resp,
+        match self.forward_to_backend(request).await {
+            Ok(response) => Ok(response),
             Err(e) => {
-                self.record_error();
-                return Err(e);
+                self.metrics.record_error();
+                Err(e)
             }
-        };
-        Ok(Some(response))
+        }
     }
 
     /// Handle initialize request
     async fn handle_initialize(&mut self, request: &JsonRpcRequest) -> Result<JsonRpcResponse, ProxyError> {
         info!("Handling initialize request");
+
+        let reinitialize = self.initialize_handled;
+        self.client_init_params = request.params.clone();
+        self.client_supports_elicitation = request.get_param_path("capabilities.elicitation").is_some();
+        self.client_supports_roots = request.get_param_path("capabilities.roots").is_some();
         
         // Extract roots if provided
         if let Some(roots) = request.get_roots() {
             info!("Received roots: {:?}", roots);
-            self.roots = roots
-                .into_iter()
-                .filter_map(|uri| Self::uri_to_path(&uri))
-                .collect();
-            
-            // Set default root to first root if not configured
-            if self.default_root.is_none() && !self.roots.is_empty() {
-                self.default_root = Some(self.roots[0].clone());
+            self.set_roots(roots);
+        }
+
+        self.initialize_handled = true;
+        self.client_initialized = false;
+
+        // Backends were initialized with the old params; replace them so they see the new ones
+        if reinitialize {
+            let roots: Vec<PathBuf> = self.backends.iter().map(|(root, _)| root.clone()).collect();
+            let replaced = roots.into_iter().filter(|root| self.start_upgrade(root).is_ok()).count();
+            info!("Client initialized again, replacing {} backend(s)", replaced);
+        }
+
+        if !reinitialize && !self.config.dry_run {
+            let names: Vec<String> = self.config.servers.keys().cloned().collect();
+            for name in names {
+                self.start_server(name);
             }
         }
 
         // Optionally pre-spawn backend for default root during initialize
-        if self.config.prewarm_default_root {
+        if self.config.prewarm_default_root && !self.config.dry_run {
             if let Some(ref root) = self.default_root.clone() {
                 if !self.backends.contains(root) {
                     info!("Pre-spawning backend for default root: {}", root.display());
-                    match self.get_or_create_backend(root.clone()).await {
-                        Ok(_) => info!("Backend ready for default root"),
-                        Err(e) => warn!("Failed to pre-spawn backend: {}", e),
+                    if let Err(e) = self.start_spawn(root.clone()).await {
+                        warn!("Failed to pre-spawn backend: {}", e);
                     }
                 }
             }
@@ -344,6 +1226,38 @@ impl McpProxy {
         ))
     }
 
+    /// Pass the client's `notifications/initialized` on to every backend holding its own (shadow
+    /// backends never hold theirs)
+    async fn broadcast_initialized(&mut self) {
+        for (_, backend) in self.backends.iter_mut() {
+            backend.client_initialized().await;
+        }
+        for backend in self.replicas.values_mut().flatten().chain(self.canaries.values_mut()).chain(self.servers.values_mut()) {
+            backend.client_initialized().await;
+        }
+    }
+
+    fn lifecycle_phase(&self) -> Phase {
+        match (self.initialize_handled, self.client_initialized) {
+            (false, _) => Phase::Uninitialized,
+            (true, false) => Phase::Initializing,
+            (true, true) => Phase::Operating,
+        }
+    }
+
+    /// Handle the requests held until initialize, in arrival order; later ones aren't held
+    async fn release_pre_init(&mut self) -> Result<(), ProxyError> {
+        let Some(mut queue) = self.pre_init_queue.take() else { return Ok(()) };
+        for message in queue.take() {
+            match self.handle_message(&message).await {
+                Ok(Some(response)) => self.send_response(response)?,
+                Ok(None) => {}
+                Err(e) => error!("Error handling message: {}", e),
+            }
+        }
+        Ok(())
+    }
+
     /// Handle shutdown request
     async fn handle_shutdown(&mut self, request: &JsonRpcRequest) -> Result<JsonRpcResponse, ProxyError> {
         info!("Handling shutdown request");
@@ -355,172 +1269,1951 @@ impl McpProxy {
         Ok(JsonRpcResponse::success(request.id.clone(), serde_json::Value::Null))
     }
 
-    /// Handle roots changed notification
-    async fn handle_roots_changed(&mut self, request: &JsonRpcRequest) {
-        if let Some(roots) = request.get_roots() {
-            info!("Roots changed: {:?}", roots);
-            self.roots = roots
-                .into_iter()
-                .filter_map(|uri| Self::uri_to_path(&uri))
-                .collect();
-        }
+    /// Handle ping, optionally checking the relevant root's backend as well (on a task, like a
+    /// forwarded request)
+    async fn handle_ping(&mut self, request: &JsonRpcRequest) -> Result<Option<JsonRpcResponse>, ProxyError> {
+        let Some(root) = self.determine_root(request).filter(|_| self.config.ping_backend) else {
+            return Ok(Some(JsonRpcResponse::success(request.id.clone(), serde_json::json!({}))));
+        };
+        let handle = match self.backends.get_mut(&root).map(BackendInstance::handle) {
+            Some(Ok(handle)) => handle,
+            Some(Err(e)) => return Ok(Some(ping_failed(request, &root, e))),
+            None => return Ok(Some(JsonRpcResponse::success(request.id.clone(), serde_json::json!({})))),
+        };
+        let call = self.new_call(request.clone(), root, CallKind::Ping);
+        let timeout = Duration::from_secs(self.config.request_timeout_seconds);
+        let retry = self.call_retry(&call);
+        self.send_off(call, CallTarget::Backend(handle, timeout, retry), Vec::new()).await
     }
 
-    /// Route a request to the appropriate backend
-    async fn route_to_backend(&mut self, request: JsonRpcRequest) -> Result<JsonRpcResponse, ProxyError> {
-        let _permit = match self.global_inflight.clone() {
-            Some(sem) => Some(sem.acquire_owned().await.map_err(|_| {
-                ProxyError::BackendUnavailable("Global inflight limiter closed".to_string())
-            })?),
-            None => None,
+    /// Handle logging/setLevel: adjust our own filter and pass it on to running backends (on a
+    /// task, answering once they have)
+    async fn handle_set_level(&mut self, request: &JsonRpcRequest) -> Result<Option<JsonRpcResponse>, ProxyError> {
+        let level = request.get_param_path("level").and_then(|v| v.as_str()).unwrap_or_default();
+        let (rank, filter) = match (logging::mcp_rank(level), logging::mcp_to_filter(level)) {
+            (Some(rank), Some(filter)) => (rank, filter),
+            _ => {
+                return Ok(Some(JsonRpcResponse::error(
+                    request.id.clone(),
+                    JsonRpcError::new(ERROR_INVALID_PARAMS, format!("Invalid log level: {:?}", level)),
+                )));
+            }
         };
-
+        info!("Client set log level to {}", level);
+        if let Some(ref handle) = self.log_handle {
+            if let Err(e) = handle.reload(filter) {
+                warn!("Failed to change log level: {}", e);
+            }
+        }
+        self.client_log_level = Some(rank);
+
+        let done = JsonRpcResponse::success(request.id.clone(), serde_json::json!({}));
+        let roots: Vec<PathBuf> = self.backends.iter().map(|(root, _)| root.clone()).collect();
+        let mut gather = Vec::new();
+        for root in roots {
+            let Some(Ok(handle)) = self.backends.peek_mut(&root).map(BackendInstance::handle) else { continue };
+            let call = self.new_call(request.clone(), root, CallKind::Backend);
+            let retry = self.call_retry(&call);
+            gather.push((call, CallTarget::Backend(handle, Duration::from_secs(2), retry)));
+        }
+        if gather.is_empty() {
+            return Ok(Some(done));
+        }
+        let call = self.new_call(request.clone(), PathBuf::new(), CallKind::SetLevel);
+        self.send_off(call, CallTarget::Answered(done), gather).await
+    }
+
+    /// Handle roots changed notification
+    async fn handle_roots_changed(&mut self, request: &JsonRpcRequest) {
+        // Some clients include the new roots; per spec they have to be fetched with roots/list
+        if let Some(roots) = request.get_roots() {
+            info!("Roots changed: {:?}", roots);
+            self.set_roots(roots);
+        }
+        self.request_client_roots();
+    }
+
+    fn set_roots(&mut self, roots: Vec<String>) {
+        self.roots = roots
+            .into_iter()
+            .filter_map(|uri| self.resolve_uri(&uri))
+            .collect();
+
+        // Set default root to first root if not configured
+        if self.default_root.is_none() && !self.roots.is_empty() {
+            self.default_root = Some(self.roots[0].clone());
+        }
+    }
+
+    /// Ask the client for its roots (if it supports `roots/list`); the answer arrives in `handle_client_response`
+    fn request_client_roots(&mut self) {
+        if !self.client_supports_roots {
+            return;
+        }
+        self.next_client_request_id += 1;
+        let key = format!("mcp-proxy-roots-{}", self.next_client_request_id);
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "roots/list".to_string(),
+            id: Some(JsonRpcId::String(key.clone())),
+            params: None,
+        };
+        match serde_json::to_string(&request) {
+            Ok(json) => {
+                debug!("Requesting roots from the client ({})", key);
+                self.send_to_client(json);
+                // Only the latest answer matters
+                self.pending_roots_request = Some(key);
+            }
+            Err(e) => warn!("Failed to serialize roots/list request: {}", e),
+        }
+    }
+
+    /// Serialize a response and queue it for the client (behind those of earlier requests
+    /// with `--preserve-order`)
+    fn send_response(&mut self, response: JsonRpcResponse) -> Result<(), ProxyError> {
+        let response_json = serde_json::to_string(&response)?;
+        match self.response_order.as_mut() {
+            Some(order) => {
+                for line in order.respond(response.id.as_ref(), response_json) {
+                    self.send_to_client(line);
+                }
+            }
+            None => self.send_to_client(response_json),
+        }
+        Ok(())
+    }
+
+    /// A request won't be answered: stop holding later responses for it
+    fn abandon_response(&mut self, id: Option<&JsonRpcId>) {
+        let (Some(order), Some(id)) = (self.response_order.as_mut(), id) else { return };
+        for line in order.forget(id) {
+            self.send_to_client(line);
+        }
+    }
+
+    /// Queue a raw message for the client
+    fn send_to_client(&self, json: String) {
+        debug!("Sending to IDE: {}", redact::global().redact_str(&json));
+        match self.client_tx {
+            Some(ref tx) => {
+                if tx.send(json).is_err() {
+                    error!("Client writer closed, dropping message");
+                }
+            }
+            None => warn!("No client writer available, dropping message"),
+        }
+    }
+
+    /// Relay a backend stderr line as an MCP logging notification
+    fn send_stderr_notification(&self, root: &Path, line: &str) {
+        let level = backend_log::infer_level(line);
+        if logging::mcp_rank(level) < self.client_log_level {
+            return;
+        }
+        let name = root.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let notification = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "notifications/message".to_string(),
+            id: None,
+            params: Some(serde_json::json!({
+                "level": level,
+                "logger": format!("auggie:{}", name),
+                "data": redact::global().redact_str(line),
+            })),
+        };
+        match serde_json::to_string(&notification) {
+            Ok(json) => self.send_to_client(json),
+            Err(e) => warn!("Failed to serialize log notification: {}", e),
+        }
+    }
+
+    /// Tell the client the tool list may have changed (the set of backends serving it did)
+    fn send_tools_changed(&self) {
+        // Nothing but the initialize response may go out before the client is initialized
+        if !self.client_initialized {
+            return;
+        }
+        let notification = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "notifications/tools/list_changed".to_string(),
+            id: None,
+            params: None,
+        };
+        match serde_json::to_string(&notification) {
+            Ok(json) => self.send_to_client(json),
+            Err(e) => warn!("Failed to serialize tools/list_changed: {}", e),
+        }
+    }
+
+    /// Handle a notification a backend sent on its own
+    fn handle_backend_notification(&self, root: &Path, notification: JsonRpcRequest) {
+        match notification.method.as_str() {
+            "notifications/tools/list_changed" => {
+                debug!("Backend {} changed its tools", root.display());
+                self.send_tools_changed();
+            }
+            // Its token was mapped back to the client's by the backend's reader
+            "notifications/progress" => match serde_json::to_string(&notification) {
+                Ok(json) => self.send_to_client(json),
+                Err(e) => warn!("Failed to serialize progress from {}: {}", root.display(), e),
+            },
+            method => debug!("Ignoring backend notification {} from {}", method, root.display()),
+        }
+    }
+
+    /// A backend process exited on its own: restart it right away on a task when
+    /// `restart_crashed_backends` is set (holding its root's messages in `spawning` meanwhile),
+    /// otherwise drop it so the next request for its root spawns a fresh one
+    async fn handle_backend_exit(&mut self, root: &Path, status: std::process::ExitStatus) {
+        // Extra MCP servers start again when next needed
+        let name = root.to_string_lossy().to_string();
+        if self.servers.get(&name).is_some_and(|s| s.is_dead()) {
+            warn!("MCP server {} exited ({})", name, status);
+            if let Some(server) = self.servers.remove(&name) {
+                self.retire(server);
+            }
+            self.send_tools_changed();
+            return;
+        }
+        self.recover_backend(root, &status.to_string());
+    }
+
+    /// Restart a root's dead backend on a task (`restart_crashed_backends`) or drop it
+    fn recover_backend(&mut self, root: &Path, reason: &str) {
+        // Already restarted, restarting or removed since
+        let serial = match self.backends.peek(root) {
+            Some(backend) if backend.is_dead() && !self.spawning.contains_key(root) => backend.serial(),
+            _ => return,
+        };
+        if self.config.restart_crashed_backends {
+            let purpose = SpawnPurpose::Restart { canary: false, instance: 0, replacing: serial };
+            if self.spawn_in_background(root, purpose) {
+                info!("Restarting crashed backend for {} ({})", root.display(), reason);
+                self.spawning.insert(root.to_path_buf(), VecDeque::new());
+                return;
+            }
+        }
+        info!("Removing crashed backend for {} ({})", root.display(), reason);
+        self.remove_backend(root);
+    }
+
+    /// Drop a root's backend and its further instances, shutting them down on tasks
+    fn remove_backend(&mut self, root: &Path) {
+        if let Some(backend) = self.backends.pop(root) {
+            self.retire(backend);
+        }
+        self.shutdown_extra_instances(root);
+        self.unwatch_root(root);
+        self.send_tools_changed();
+    }
+
+    /// Park a tool call and ask for confirmation, or reject it if there's no way to ask
+    async fn request_confirmation(&mut self, request: JsonRpcRequest) -> Result<Option<JsonRpcResponse>, ProxyError> {
+        let tool = request.get_param_path("name").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        let channel = match self.confirmer.channel(self.client_supports_elicitation) {
+            Some(channel) => channel,
+            None => {
+                warn!("Tool call {} requires confirmation but no confirmation channel is available", tool);
+                return Ok(Some(JsonRpcResponse::error(
+                    request.id.clone(),
+                    JsonRpcError::new(
+                        ERROR_CONFIRMATION_REJECTED,
+                        "Tool requires confirmation but the client supports no way to ask for it",
+                    ),
+                )));
+            }
+        };
+
+        self.next_client_request_id += 1;
+        let key = format!("mcp-proxy-confirm-{}", self.next_client_request_id);
+        info!("Tool call {} needs confirmation ({:?}, {})", tool, channel, key);
+
+        match channel {
+            ConfirmChannel::Elicitation => {
+                let elicitation = JsonRpcRequest {
+                    jsonrpc: "2.0".to_string(),
+                    method: "elicitation/create".to_string(),
+                    id: Some(JsonRpcId::String(key.clone())),
+                    params: Some(confirm::elicitation_params(&tool, request.get_param_path("arguments"))),
+                };
+                self.send_to_client(serde_json::to_string(&elicitation)?);
+            }
+            ConfirmChannel::Command => {
+                let payload = serde_json::json!({
+                    "tool": tool,
+                    "arguments": request.get_param_path("arguments"),
+                    "root": self.determine_root(&request),
+                });
+                let confirmer = self.confirmer.clone();
+                let confirm_tx = self.confirm_tx.clone();
+                let key = key.clone();
+                tokio::spawn(async move {
+                    let decision = confirmer.run_command(&tool, payload).await;
+                    if let Some(tx) = confirm_tx {
+                        let _ = tx.send((key, decision));
+                    }
+                });
+            }
+        }
+
+        let deadline = Instant::now() + self.confirmer.timeout();
+        self.parked_calls.insert(key, (request, deadline));
+        Ok(None)
+    }
+
+    /// Resume a parked tool call once its confirmation is decided
+    async fn resolve_confirmation(&mut self, key: &str, decision: ConfirmDecision) -> Result<(), ProxyError> {
+        let request = match self.parked_calls.remove(key) {
+            Some((request, _)) => request,
+            None => {
+                debug!("Confirmation {} already resolved", key);
+                return Ok(());
+            }
+        };
+        let id = request.id.clone();
+        match self.dispatch(request, Some(decision)).await {
+            Ok(Some(response)) => self.send_response(response)?,
+            Ok(None) => {}
+            Err(e) => {
+                error!("Error handling confirmed request: {}", e);
+                self.abandon_response(id.as_ref());
+            }
+        }
+        Ok(())
+    }
+
+    /// Reject parked tool calls whose confirmation timed out
+    async fn expire_confirmations(&mut self) -> Result<(), ProxyError> {
+        let now = Instant::now();
+        let expired: Vec<String> = self.parked_calls.iter()
+            .filter(|(_, (_, deadline))| *deadline <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in expired {
+            warn!("Confirmation {} timed out", key);
+            self.resolve_confirmation(&key, ConfirmDecision::Rejected("Confirmation timed out".to_string())).await?;
+        }
+        Ok(())
+    }
+
+    /// Handle a response from the client to a proxy-initiated request
+    async fn handle_client_response(&mut self, response: JsonRpcResponse) -> Result<(), ProxyError> {
+        let key = match response.id {
+            Some(ref id) => id.as_string(),
+            None => return Ok(()),
+        };
+        if key.starts_with("mcp-proxy-roots-") {
+            if self.pending_roots_request.as_deref() != Some(key.as_str()) {
+                debug!("Ignoring superseded roots/list response {}", key);
+                return Ok(());
+            }
+            self.pending_roots_request = None;
+            match (response.result.as_ref().and_then(jsonrpc::root_uris), response.error) {
+                (Some(roots), _) => {
+                    info!("Client roots: {:?}", roots);
+                    self.set_roots(roots);
+                }
+                (None, Some(error)) => warn!("Client failed roots/list: {}", error.message),
+                (None, None) => warn!("Client answered roots/list without roots"),
+            }
+            return Ok(());
+        }
+        if !self.parked_calls.contains_key(&key) {
+            debug!("Ignoring response to unknown client request {}", key);
+            return Ok(());
+        }
+        let decision = match (response.result, response.error) {
+            (Some(result), _) => confirm::elicitation_decision(&result),
+            (None, Some(error)) => ConfirmDecision::Rejected(format!("Confirmation failed: {}", error.message)),
+            (None, None) => ConfirmDecision::Rejected("Empty confirmation response".to_string()),
+        };
+        self.resolve_confirmation(&key, decision).await
+    }
+
+    /// Send a list request off to a task along with the same request to the extra MCP servers
+    /// and, for tools/list under `tool_namespacing`, to every other running backend (first page
+    /// only); `finish_call` merges the answers (`merge_list`)
+    async fn send_list(&mut self, request: JsonRpcRequest) -> Result<Option<JsonRpcResponse>, ProxyError> {
+        let received = request.clone();
+        let (mut call, target) = match self.prepare_call(request).await? {
+            Routed::Answered(response) => {
+                let root = self.determine_root(&received).unwrap_or_default();
+                let call = self.new_call(received.clone(), root, CallKind::List { routed: false });
+                (call, CallTarget::Answered(response))
+            }
+            Routed::Call(mut call, handle, timeout) => {
+                call.kind = CallKind::List { routed: true };
+                let retry = self.call_retry(&call);
+                (call, CallTarget::Backend(handle, timeout, retry))
+            }
+            Routed::Queued => return Ok(None),
+        };
+        call.received = Some(received.clone());
+        let mut gather = Vec::new();
+        if received.get_param_path("cursor").is_none() {
+            if received.method == "tools/list" && self.config.tool_namespacing != ToolNamespacing::Off {
+                let others: Vec<PathBuf> = self.backends.iter()
+                    .map(|(root, _)| root.clone())
+                    .filter(|root| *root != call.root)
+                    .collect();
+                for root in others {
+                    let Some(backend) = self.backends.peek_mut(&root) else { continue };
+                    match backend.begin_request(&received.method) {
+                        Ok((handle, timeout)) => {
+                            let other = self.new_call(received.clone(), root, CallKind::Backend);
+                            let retry = self.call_retry(&other);
+                            gather.push((other, CallTarget::Backend(handle, timeout, retry)));
+                        }
+                        Err(e) => warn!("Backend {} failed tools/list: {}", root.display(), e),
+                    }
+                }
+            }
+            gather.extend(self.server_list_calls(&received.method));
+        }
+        self.send_off(call, target, gather).await
+    }
+
+    /// Merge what was gathered alongside a list request (`send_list`) into its answer
+    fn merge_list(&mut self, request: &JsonRpcRequest, root: Option<&Path>, response: JsonRpcResponse, gathered: Vec<CallResult>) -> JsonRpcResponse {
+        let (servers, backends): (Vec<CallResult>, Vec<CallResult>) =
+            gathered.into_iter().partition(|(call, _)| call.kind == CallKind::Server);
+        match request.method.as_str() {
+            "resources/list" => self.merge_resources(request, response, servers),
+            "prompts/list" => merge_prompts(request, response, servers),
+            _ => self.merge_tools(request, root, response, backends, servers),
+        }
+    }
+
+    /// tools/list with the proxy's additions: tools of all backends (`tool_namespacing`)
+    /// and the virtual tools (`virtual_tools`)
+    fn merge_tools(
+        &mut self,
+        request: &JsonRpcRequest,
+        root: Option<&Path>,
+        response: JsonRpcResponse,
+        backends: Vec<CallResult>,
+        servers: Vec<CallResult>,
+    ) -> JsonRpcResponse {
+        let mut response = match self.config.tool_namespacing {
+            ToolNamespacing::Off => response,
+            _ => self.namespace_tools(root, response, backends),
+        };
+        // Listed on the first page only
+        if (!self.config.virtual_tools && self.config.servers.is_empty()) || request.get_param_path("cursor").is_some() {
+            return response;
+        }
+        // Still offered when the backend fails, so the model can use them to recover
+        if let Some(error) = response.error.take() {
+            warn!("Listing only the proxy's and MCP servers' tools, backend failed tools/list: {}", error.message);
+            response.result = Some(serde_json::json!({ "tools": [] }));
+        }
+        let mut extra = match self.config.virtual_tools {
+            true => tools::virtual_tool_definitions(),
+            false => Vec::new(),
+        };
+        for (server, mut tools) in server_lists("tools/list", "tools", servers) {
+            servers::prefix_names(&mut tools, &server);
+            extra.extend(tools);
+        }
+        if let Some(tools) = response.result.as_mut().and_then(|r| r.get_mut("tools")).and_then(|t| t.as_array_mut()) {
+            tools.extend(extra);
+        }
+        response
+    }
+
+    /// resources/list with the proxy's own resources and the extra MCP servers'
+    fn merge_resources(&mut self, request: &JsonRpcRequest, mut response: JsonRpcResponse, servers: Vec<CallResult>) -> JsonRpcResponse {
+        // Backends without resources still leave the proxy's own listable
+        if response.error.take().is_some() {
+            response.result = Some(serde_json::json!({ "resources": [] }));
+        }
+        if request.get_param_path("cursor").is_some() {
+            return response;
+        }
+        let mut extra = Vec::new();
+        if let Some(ref store) = self.spill_store {
+            extra.extend(store.resources());
+        }
+        if self.config.diagnostic_resources {
+            extra.extend(diagnostics::resources());
+        }
+        for (server, resources) in server_lists("resources/list", "resources", servers) {
+            // resources/read finds the server by URI
+            for uri in resources.iter().filter_map(|r| r.get("uri").and_then(|u| u.as_str())) {
+                self.server_resources.insert(uri.to_string(), server.clone());
+            }
+            extra.extend(resources);
+        }
+        if let Some(list) = response.result.as_mut()
+            .and_then(|r| r.get_mut("resources"))
+            .and_then(|r| r.as_array_mut())
+        {
+            list.extend(extra);
+        }
+        response
+    }
+
+    /// Run one of the proxy's own tools
+    async fn call_virtual_tool(&mut self, request: &JsonRpcRequest) -> JsonRpcResponse {
+        let tool = request.get_param_path("name").and_then(|v| v.as_str()).unwrap_or_default();
+        info!("Running proxy tool {}", tool);
+        let root_arg = request
+            .get_param_path("arguments.root")
+            .and_then(|v| v.as_str())
+            .and_then(|root| self.resolve_uri(root));
+        let result = match tool {
+            "proxy_status" => tools::text_result(
+                serde_json::to_string_pretty(&self.status()).unwrap_or_default(),
+                false,
+            ),
+            "proxy_restart_backend" => match root_arg.or_else(|| self.determine_root(request)) {
+                Some(root) => match self.restart_backend(&root).await {
+                    Ok(()) => tools::text_result(
+                        format!("Restarting the backend for {}; its requests wait for the new one", root.display()),
+                        false,
+                    ),
+                    Err(e) => tools::text_result(format!("Failed to restart backend for {}: {}", root.display(), e), true),
+                },
+                None => tools::text_result("No workspace root to restart", true),
+            },
+            "proxy_upgrade_backend" => match root_arg.or_else(|| self.determine_root(request)) {
+                Some(root) => match self.start_upgrade(&root) {
+                    Ok(()) => tools::text_result(
+                        format!(
+                            "Starting a replacement backend for {}; it takes over once its handshake completes",
+                            root.display()
+                        ),
+                        false,
+                    ),
+                    Err(reason) => tools::text_result(format!("Cannot upgrade backend for {}: {}", root.display(), reason), true),
+                },
+                None => tools::text_result("No workspace root to upgrade", true),
+            },
+            "proxy_switch_root" => match root_arg {
+                Some(root) if self.config.policy.confine_paths && !self.switchable_root(&root) => tools::text_result(
+                    format!("{} is outside the workspace roots and allowed_paths", root.display()),
+                    true,
+                ),
+                Some(root) if root.is_dir() => {
+                    info!("Switching default root to {}", root.display());
+                    // Session bindings take precedence over the default root
+                    if let Some(session) = request.get_session_id(&self.config.session_id_field) {
+                        self.sessions.put(session, root.clone());
+                    }
+                    self.default_root = Some(root.clone());
+                    tools::text_result(format!("Requests without a file path now go to {}", root.display()), false)
+                }
+                Some(root) => tools::text_result(format!("{} is not a directory", root.display()), true),
+                None => tools::text_result("Missing argument: root", true),
+            },
+            _ => tools::text_result(format!("Unknown proxy tool: {}", tool), true),
+        };
+        JsonRpcResponse::success(request.id.clone(), result)
+    }
+
+    /// Restart a root's backend on a task, or start it if it isn't running; the root's
+    /// messages wait in `spawning` for the new process, which `finish_restart` swaps in
+    async fn restart_backend(&mut self, root: &Path) -> Result<(), ProxyError> {
+        let Some(serial) = self.backends.peek(root).map(BackendInstance::serial) else {
+            return self.start_spawn(root.to_path_buf()).await;
+        };
+        if self.spawning.contains_key(root) {
+            return Ok(());
+        }
+        let purpose = SpawnPurpose::Restart { canary: false, instance: 0, replacing: serial };
+        if !self.spawn_in_background(root, purpose) {
+            return Err(ProxyError::BackendUnavailable("The proxy isn't serving a client".to_string()));
+        }
+        info!("Restarting backend for {}", root.display());
+        self.spawning.insert(root.to_path_buf(), VecDeque::new());
+        Ok(())
+    }
+
+    /// Blue/green restart: start a replacement for a root's backend while the current one keeps serving
+    fn start_upgrade(&mut self, root: &Path) -> Result<(), String> {
+        if !self.backends.contains(root) {
+            return Err("no backend is running for it".to_string());
+        }
+        if self.upgrading.contains_key(root) {
+            return Err("an upgrade is already in progress".to_string());
+        }
+        if !self.spawn_in_background(root, SpawnPurpose::Replacement) {
+            return Err("the proxy isn't running".to_string());
+        }
+        info!("Starting replacement backend for {}", root.display());
+        self.upgrading.insert(root.to_path_buf(), Vec::new());
+        Ok(())
+    }
+
+    /// Switch a root over to its replacement backend and drain the old one
+    async fn finish_upgrade(&mut self, root: PathBuf, result: Result<BackendInstance, ProxyError>) {
+        let batches = self.upgrading.remove(&root).unwrap_or_default();
+        let mut backend = match result {
+            Ok(backend) => backend,
+            Err(e) => {
+                error!("Replacement backend for {} failed to start, keeping the current one: {}", root.display(), e);
+                return;
+            }
+        };
+        if !self.backends.contains(&root) {
+            info!("Backend for {} is gone, dropping its replacement", root.display());
+            self.retire(backend);
+            return;
+        }
+        for batch in batches {
+            if let Err(e) = backend.send_file_changes(batch).await {
+                warn!("Failed to deliver change batch to the replacement backend: {}", e);
+                break;
+            }
+        }
+
+        // put() swaps the value in place, keeping the root's LRU slot
+        let retiring: Vec<BackendInstance> = self.backends.put(root.clone(), backend)
+            .into_iter()
+            .chain(self.replicas.remove(&root).unwrap_or_default())
+            .collect();
+        info!("Switched {} to its replacement backend, draining {} old instances", root.display(), retiring.len());
+        for old in retiring {
+            self.retire(old);
+        }
+        self.start_replicas(&root);
+        self.send_tools_changed();
+    }
+
+    /// Shut down a backend on a task once the requests still waiting on it are answered
+    /// (for at most `request_timeout_seconds`), so its staged shutdown never holds up the loop
+    fn retire(&mut self, mut old: BackendInstance) {
+        while self.retiring.try_join_next().is_some() {}
+        let drain_timeout = Duration::from_secs(self.config.request_timeout_seconds);
+        self.retiring.spawn(async move {
+            let deadline = Instant::now() + drain_timeout;
+            while old.has_pending().await && Instant::now() < deadline {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            old.shutdown().await;
+        });
+    }
+
+    /// Swap in a backend restarted on a task (by a call task for a retry, or for a crash with
+    /// `restart_crashed_backends`) in place of the process that died or hung, replaying the old
+    /// one's change batches to it and delivering the messages held for a crashed root
+    /// A root's crashed backend removed meanwhile is taken over too; one replaced some other
+    /// way (or a replica or canary since removed) keeps its replacement, and the restarted
+    /// backend is retired.
+    async fn finish_restart(
+        &mut self,
+        root: PathBuf,
+        canary: bool,
+        instance: usize,
+        replacing: u64,
+        result: Result<BackendInstance, ProxyError>,
+    ) -> Result<(), ProxyError> {
+        let primary = !canary && instance == 0;
+        let current = self.backends.peek(&root).map(|b| b.serial());
+        // Messages held while a crashed backend restarted
+        let queued = match primary && current.is_none_or(|serial| serial == replacing) {
+            true => self.spawning.remove(&root),
+            false => None,
+        };
+        let mut backend = match result {
+            Ok(backend) => backend,
+            Err(e) => {
+                let Some(queued) = queued else {
+                    warn!("Failed to restart the backend for {}: {}", root.display(), e);
+                    return Ok(());
+                };
+                if current == Some(replacing) {
+                    self.remove_backend(&root);
+                }
+                return self.deliver_queued(&root, queued, Err(e)).await;
+            }
+        };
+        if primary && current.is_none() {
+            if let Err(e) = self.make_room(&root).await {
+                self.retire(backend);
+                return match queued {
+                    Some(queued) => self.deliver_queued(&root, queued, Err(e)).await,
+                    None => Ok(()),
+                };
+            }
+            info!("Backend for {} was removed while it restarted, taking the restarted one", root.display());
+            self.install_backend(root.clone(), backend).await;
+            return match queued {
+                Some(queued) => self.deliver_queued(&root, queued, Ok(())).await,
+                None => Ok(()),
+            };
+        }
+        let Some(current) = self.instance_mut(&root, canary, instance).filter(|b| b.serial() == replacing) else {
+            info!("Backend for {} changed while it restarted, retiring the restarted one", root.display());
+            self.retire(backend);
+            return Ok(());
+        };
+        backend.adopt_changes(current).await;
+        let old = std::mem::replace(current, backend);
+        info!("Swapped in the restarted backend for {}", root.display());
+        self.retire(old);
+        match queued {
+            Some(queued) => self.deliver_queued(&root, queued, Ok(())).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Apply a new configuration (admin `reload`): routing, middleware, filters, limits and the
+    /// rest are rebuilt, then every running backend is replaced so it starts with the new
+    /// settings; the client session, metrics and pending change events are kept
+    fn reload(&mut self, config: Config, limiter: Option<Arc<InflightLimiter>>) -> serde_json::Value {
+        let fresh = match Self::with_metrics(config, self.metrics.clone()) {
+            Ok(fresh) => fresh,
+            Err(e) => {
+                error!("Reload failed, keeping the current configuration: {}", e);
+                return serde_json::json!({ "error": e.to_string() });
+            }
+        };
+        if let Some(ref handle) = self.log_handle {
+            if let Err(e) = handle.reload(logging::parse_level(&fresh.config.log_level)) {
+                warn!("Failed to change log level: {}", e);
+            }
+        }
+        self.config = fresh.config;
+        self.default_root = fresh.default_root;
+        self.routing_rules = fresh.routing_rules;
+        self.middleware = fresh.middleware;
+        self.audit_log = fresh.audit_log;
+        self.confirmer = fresh.confirmer;
+        self.retry_policy = fresh.retry_policy;
+        self.canary_gate = fresh.canary_gate;
+        self.shadow_sampler = fresh.shadow_sampler;
+        self.versions = fresh.versions;
+        self.path_resolver = fresh.path_resolver;
+        self.spill_store = fresh.spill_store;
+        self.global_inflight = limiter;
+        self.git_filter_cache = fresh.git_filter_cache;
+        self.path_filter = fresh.path_filter;
+        #[cfg(feature = "scripting")]
+        {
+            self.scripts = fresh.scripts;
+        }
+
+        let roots: Vec<PathBuf> = self.backends.iter().map(|(root, _)| root.clone()).collect();
+        let upgrading: Vec<PathBuf> = roots.into_iter()
+            .filter(|root| match self.start_upgrade(root) {
+                Ok(()) => true,
+                Err(e) => {
+                    debug!("Not replacing backend for {} after reload: {}", root.display(), e);
+                    false
+                }
+            })
+            .collect();
+        info!("Configuration reloaded, replacing {} backend(s)", upgrading.len());
+        serde_json::json!({ "reloaded": true, "upgrading": upgrading })
+    }
+
+    /// Backends and pending requests for crash reports
+    fn crash_summary(&self) -> serde_json::Value {
+        let backends: Vec<serde_json::Value> = self.backends.iter()
+            .map(|(root, backend)| serde_json::json!({
+                "root": root,
+                "pid": backend.pid(),
+                "state": format!("{:?}", backend.state),
+                "pending": backend.pending_client_ids(),
+            }))
+            .collect();
+        let queued: Vec<serde_json::Value> = self.spawning.iter()
+            .flat_map(|(root, queued)| queued.iter().map(move |queued| serde_json::json!({
+                "root": root,
+                "id": queued.request.id,
+                "method": queued.request.method,
+            })))
+            .collect();
+        let parked: Vec<serde_json::Value> = self.parked_calls.values()
+            .map(|(request, _)| serde_json::json!({
+                "id": request.id,
+                "tool": request.get_param_path("name"),
+            }))
+            .collect();
+        serde_json::json!({
+            "default_root": self.default_root,
+            "roots": self.roots,
+            "backends": backends,
+            "waiting_for_spawn": queued,
+            "awaiting_confirmation": parked,
+            "upgrading": self.upgrading.keys().collect::<Vec<_>>(),
+        })
+    }
+
+    /// Roots, backends and metrics for `proxy_status`
+    fn status(&self) -> serde_json::Value {
+        let backends: Vec<serde_json::Value> = self.backends.iter()
+            .map(|(root, backend)| serde_json::json!({
+                "root": root,
+                "pid": backend.pid(),
+                "mode": backend.mode(),
+                "state": format!("{:?}", backend.state),
+                "warmup": backend.warmup_status(),
+                "pinned": self.is_pinned(root),
+                "instances": 1 + self.replicas.get(root).map_or(0, Vec::len),
+                "idle_seconds": backend.last_used.elapsed().as_secs(),
+            }))
+            .collect();
+        let servers: Vec<serde_json::Value> = self.config.servers.keys()
+            .map(|name| {
+                let state = match self.servers.get(name) {
+                    Some(server) => format!("{:?}", server.state),
+                    None if self.servers_starting.contains(name) => "Starting".to_string(),
+                    None => "Stopped".to_string(),
+                };
+                serde_json::json!({
+                    "name": name,
+                    "pid": self.servers.get(name).and_then(BackendInstance::pid),
+                    "state": state,
+                })
+            })
+            .collect();
+        let upgrading: Vec<&PathBuf> = self.upgrading.keys().collect();
+        let starting: Vec<serde_json::Value> = self.spawning.iter()
+            .map(|(root, queued)| serde_json::json!({ "root": root, "queued": queued.len() }))
+            .collect();
+        serde_json::json!({
+            "default_root": self.default_root,
+            "roots": self.roots,
+            "backends": backends,
+            "starting": starting,
+            "upgrading": upgrading,
+            "servers": servers,
+            "canary": self.canary_gate.as_ref().map(|gate| gate.status()),
+            "versions": self.versions,
+            "metrics": self.get_metrics(),
+        })
+    }
+
+    /// Value of a diagnostic resource (`diagnostic_resources`)
+    fn read_diagnostic(&self, uri: &str) -> serde_json::Value {
+        match uri {
+            diagnostics::LOGS_URI => logging::recent_lines().join("\n").into(),
+            diagnostics::METRICS_URI => self.get_metrics(),
+            _ => {
+                let mut status = self.status();
+                serde_json::json!({
+                    "backends": status["backends"].take(),
+                    "starting": status["starting"].take(),
+                    "upgrading": status["upgrading"].take(),
+                })
+            }
+        }
+    }
+
+    /// A tools/list answer merged with the other running backends' (`tool_namespacing`); tools
+    /// stay tagged with their namespace until `finish_response` finalizes the names
+    fn namespace_tools(&mut self, root: Option<&Path>, mut response: JsonRpcResponse, others: Vec<CallResult>) -> JsonRpcResponse {
+        let mut result = match response.result.take() {
+            Some(result) => result,
+            None => return response,
+        };
+
+        let mut tools = Vec::new();
+        if let Some(root) = root {
+            let namespace = self.tool_namespace(root);
+            tools.extend(tools::take_tools(&mut result, &namespace));
+        }
+        for (other, answer) in others {
+            let namespace = self.tool_namespace(&other.root);
+            match answer {
+                Ok(JsonRpcResponse { result: Some(mut listed), .. }) => {
+                    tools.extend(tools::take_tools(&mut listed, &namespace));
+                }
+                Ok(JsonRpcResponse { error, .. }) => {
+                    warn!("Backend {} failed tools/list: {:?}", other.root.display(), error.map(|e| e.message));
+                }
+                Err(e) => warn!("Backend {} failed tools/list: {}", other.root.display(), e),
+            }
+        }
+        result["tools"] = tools.into();
+        response.result = Some(result);
+        response
+    }
+
+    /// Namespace of a root's tools, assigned on first use (`<name>-2` etc. when the name is taken)
+    fn tool_namespace(&mut self, root: &Path) -> String {
+        if let Some((namespace, _)) = self.tool_namespaces.iter().find(|(_, r)| r.as_path() == root) {
+            return namespace.clone();
+        }
+        let base = self.config.root_config(root)
+            .and_then(|rc| rc.namespace.clone())
+            .unwrap_or_else(|| tools::default_namespace(root));
+        let mut namespace = base.clone();
+        let mut n = 1;
+        while self.tool_namespaces.contains_key(&namespace) {
+            n += 1;
+            namespace = format!("{}-{}", base, n);
+        }
+        self.tool_namespaces.insert(namespace.clone(), root.to_path_buf());
+        namespace
+    }
+
+    /// Map a namespaced tools/call back to the backend's tool name, pinning the call to that root
+    fn resolve_tool_namespace(&mut self, request: &mut JsonRpcRequest) {
+        if self.config.tool_namespacing == ToolNamespacing::Off || request.method != "tools/call" {
+            return;
+        }
+        let name = match request.get_param_path("name").and_then(|v| v.as_str()) {
+            Some(name) if name.contains(tools::SEPARATOR) => name.to_string(),
+            _ => return,
+        };
+        // The extra MCP servers' tools keep their prefix until they are routed
+        if servers::split_name(&name, self.config.servers.keys()).is_some() {
+            return;
+        }
+        // Names listed before a restart of the proxy still resolve
+        for root in self.known_roots() {
+            self.tool_namespace(&root);
+        }
+        let (namespace, tool) = match tools::split_name(&name, self.tool_namespaces.keys().map(String::as_str)) {
+            Some(split) => split,
+            None => return,
+        };
+        let root = self.tool_namespaces[&namespace].clone();
+        debug!("Tool {} -> {} on {}", name, tool, root.display());
+        if let Some(params) = request.params.as_mut() {
+            params["name"] = tool.into();
+        }
+        request.set_meta(routing::ROOT_HINT_META, root.to_string_lossy().into());
+    }
+
+    /// The extra MCP server a request is for, and the request as the server expects it (its own
+    /// tool or prompt name; resources by the URI it listed)
+    fn server_call(&self, request: &JsonRpcRequest) -> Option<(String, JsonRpcRequest)> {
+        if request.is_notification() || self.config.servers.is_empty() {
+            return None;
+        }
+        match request.method.as_str() {
+            "tools/call" | "prompts/get" => {
+                let name = request.get_param_path("name")?.as_str()?;
+                let (server, name) = servers::split_name(name, self.config.servers.keys())?;
+                let mut call = request.clone();
+                if let Some(params) = call.params.as_mut() {
+                    params["name"] = name.into();
+                }
+                Some((server, call))
+            }
+            "resources/read" => {
+                let uri = request.get_param_path("uri")?.as_str()?;
+                Some((self.server_resources.get(uri)?.clone(), request.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Send a request to one of the extra MCP servers; like `forward_to_backend`, the answer
+    /// is handled by the run loop (`received` is the request as the response layers see it)
+    /// A server that isn't running is started again and the request refused meanwhile.
+    async fn forward_to_server(
+        &mut self,
+        name: String,
+        request: JsonRpcRequest,
+        received: JsonRpcRequest,
+    ) -> Result<Option<JsonRpcResponse>, ProxyError> {
+        if let Some(server) = self.servers.remove(&name) {
+            if !server.is_dead() {
+                self.servers.insert(name.clone(), server);
+            } else {
+                self.retire(server);
+            }
+        }
+        let begun = match self.servers.get_mut(&name) {
+            Some(server) => server.begin_request(&request.method),
+            None => {
+                self.start_server(name.clone());
+                Err(ProxyError::BackendUnavailable(format!("MCP server {} is not running yet", name)))
+            }
+        };
+        let (handle, timeout) = match begun {
+            Ok(begun) => begun,
+            Err(e) => return Ok(Some(self.complete_server_call(&name, &request, Err(e)))),
+        };
+        let mut call = self.new_call(request, PathBuf::from(name), CallKind::Server);
+        call.received = Some(received);
+        call.audit = self.call_audit.take();
+        self.send_off(call, CallTarget::Server(handle, timeout), Vec::new()).await
+    }
+
+    /// Turn an extra MCP server's answer (or the failure to get one) into the response
+    fn complete_server_call(&self, name: &str, request: &JsonRpcRequest, result: Result<JsonRpcResponse, ProxyError>) -> JsonRpcResponse {
+        match result {
+            Ok(response) => response,
+            Err(e) => {
+                error!("MCP server {} failed {}: {}", name, request.method, e);
+                let error = JsonRpcError::new(e.code(), e.to_string()).with_data(serde_json::json!({ "server": name }));
+                JsonRpcResponse::error(request.id.clone(), error)
+            }
+        }
+    }
+
+    /// `method` (a list method) for every running extra MCP server, to send alongside a list
+    /// request; servers that aren't running are started again for next time
+    fn server_list_calls(&mut self, method: &str) -> Vec<(Box<BackendCall>, CallTarget)> {
+        let stopped: Vec<String> = self.config.servers.keys()
+            .filter(|name| self.servers.get(*name).is_none_or(BackendInstance::is_dead))
+            .cloned()
+            .collect();
+        for name in stopped {
+            if let Some(server) = self.servers.remove(&name) {
+                self.retire(server);
+            }
+            self.start_server(name);
+        }
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method.to_string(),
+            id: Some(JsonRpcId::String("mcp-proxy-list".to_string())),
+            params: None,
+        };
+        let names: Vec<String> = self.servers.keys().cloned().collect();
+        let mut calls = Vec::new();
+        for name in names {
+            let Some(server) = self.servers.get_mut(&name) else { continue };
+            match server.begin_request(method) {
+                Ok((handle, timeout)) => {
+                    let call = self.new_call(request.clone(), PathBuf::from(name), CallKind::Server);
+                    calls.push((call, CallTarget::Server(handle, timeout)));
+                }
+                Err(e) => warn!("MCP server {} failed {}: {}", name, method, e),
+            }
+        }
+        calls
+    }
+
+    /// Replace a root the client picked (`_meta.workspaceRoot` / `_meta.targetRoot`) with the
+    /// resolved one for routing; a value that isn't an absolute path or file URI, or (under
+    /// `policy.confine_paths`) one outside the known roots, is refused (requests get an error,
+    /// notifications are dropped)
+    fn take_requested_root(&self, request: &mut JsonRpcRequest) -> Option<Option<JsonRpcResponse>> {
+        let values: Vec<(&str, serde_json::Value)> = routing::CLIENT_ROOT_META.iter()
+            .filter_map(|key| Some((*key, request.take_meta(key)?)))
+            .collect();
+        let (key, value) = values.into_iter().next()?;
+        let root = match value.as_str().and_then(paths::uri_to_path).filter(|path| path.is_absolute()) {
+            Some(root) => {
+                let root = self.path_resolver.resolve(&root);
+                match self.config.policy.confine_paths && !self.within_known_roots(&root) {
+                    true => Err(format!("_meta.{} {} is outside the workspace roots", key, root.display())),
+                    false => Ok(root),
+                }
+            }
+            None => Err(format!("_meta.{} must be an absolute path or file URI, got {}", key, value)),
+        };
+        match root {
+            Ok(root) => {
+                debug!("{} asks for root {}", request.method, root.display());
+                request.set_meta(routing::REQUESTED_ROOT_META, root.to_string_lossy().into());
+                None
+            }
+            Err(message) => {
+                if request.is_notification() {
+                    warn!("Dropping {}: {}", request.method, message);
+                    return Some(None);
+                }
+                Some(Some(JsonRpcResponse::error(
+                    request.id.clone(),
+                    JsonRpcError::new(ERROR_INVALID_PARAMS, message),
+                )))
+            }
+        }
+    }
+
+    /// Whether a resolved path lies inside one of the `known_roots`
+    fn within_known_roots(&self, path: &Path) -> bool {
+        self.known_roots().iter().any(|root| path.starts_with(self.path_resolver.resolve(root)))
+    }
+
+    /// Whether `proxy_switch_root` may move the default root to a resolved path under
+    /// `policy.confine_paths`: inside the known roots or `allowed_paths`
+    fn switchable_root(&self, path: &Path) -> bool {
+        self.within_known_roots(path)
+            || self.config.policy.allowed_paths.iter().any(|allowed| path.starts_with(self.path_resolver.resolve(allowed)))
+    }
+
+    /// Roots requests may legitimately touch: client roots, roots with overrides and the default root
+    fn known_roots(&self) -> Vec<PathBuf> {
+        self.roots.iter()
+            .chain(self.config.root_overrides.keys())
+            .chain(self.default_root.iter())
+            .cloned()
+            .collect()
+    }
+
+    /// Route a request to the appropriate backend without waiting for its answer: that is
+    /// awaited (once it has a global slot, and retried, restarting the backend if need be) on
+    /// a task and handed to the run loop (`finish_call`), which takes further messages
+    /// meanwhile, so several requests can be in flight to one backend
+    /// `None` once the request has been sent off.
+    async fn forward_to_backend(&mut self, request: JsonRpcRequest) -> Result<Option<JsonRpcResponse>, ProxyError> {
+        let received = request.clone();
+        let (mut call, handle, timeout) = match self.prepare_call(request).await? {
+            Routed::Answered(response) => return Ok(Some(response)),
+            Routed::Call(call, handle, timeout) => (call, handle, timeout),
+            Routed::Queued => return Ok(None),
+        };
+        call.received = Some(received);
+        call.audit = self.call_audit.take();
+        let retry = self.call_retry(&call);
+        self.send_off(call, CallTarget::Backend(handle, timeout, retry), Vec::new()).await
+    }
+
+    /// What a call task needs to retry a request the way the retry policy allows
+    fn call_retry(&self, call: &BackendCall) -> Box<CallRetry> {
+        let restart = match self.spawn_tx.clone() {
+            Some(spawn_tx) if self.retry_policy.max_retries(&call.request) > 0 => {
+                let context = match call.canary {
+                    true => self.variant_context(&self.config.canary.auggie_entry, &self.config.canary.node),
+                    false => self.spawn_context(),
+                };
+                Some((context, spawn_tx))
+            }
+            _ => None,
+        };
+        Box::new(CallRetry { policy: self.retry_policy.clone(), restart })
+    }
+
+    /// Handle the final answer to a request sent off to a task (retries are over by now) the
+    /// way the rest of the request path would have, and send it to the client
+    async fn finish_call(&mut self, mut call: Box<BackendCall>, result: Result<JsonRpcResponse, ProxyError>) -> Result<(), ProxyError> {
+        self.calls_in_flight -= 1;
+        let span = call.span.clone();
+        async move {
+            let received = call.received.take().unwrap_or_else(|| call.request.clone());
+            let audit = call.audit.take();
+            let gathered = std::mem::take(&mut call.gathered);
+            let response = match call.kind {
+                CallKind::Backend => self.respond_to_call(*call, result).await,
+                CallKind::Server => self.complete_server_call(&call.root.to_string_lossy(), &call.request, result),
+                // Answered without the response layers, like a ping the proxy answers itself
+                CallKind::Ping => {
+                    let response = match result {
+                        Ok(_) => JsonRpcResponse::success(call.request.id.clone(), serde_json::json!({})),
+                        Err(e) => ping_failed(&call.request, &call.root, e),
+                    };
+                    return self.send_response(response);
+                }
+                // Backends without logging support answer with an error (or not at all); either is fine
+                CallKind::SetLevel => {
+                    for (backend, result) in gathered {
+                        match result {
+                            Ok(response) if response.error.is_none() => debug!("Backend {} log level set", backend.root.display()),
+                            _ => debug!("Backend {} did not accept logging/setLevel", backend.root.display()),
+                        }
+                    }
+                    result.unwrap_or_else(|e| JsonRpcResponse::error(call.request.id.clone(), backend_error(&e)))
+                }
+                CallKind::List { routed } => {
+                    let root = match routed {
+                        true => Some(call.root.clone()),
+                        false => self.determine_root(&received),
+                    };
+                    let response = match routed {
+                        true => self.respond_to_call(*call, result).await,
+                        false => result.unwrap_or_else(|e| JsonRpcResponse::error(call.request.id.clone(), backend_error(&e))),
+                    };
+                    self.merge_list(&received, root.as_deref(), response, gathered)
+                }
+            };
+            let result = Ok(Some(self.finish_response(&received, response)));
+            if let (Some((entry, started)), Some(audit_log)) = (audit, self.audit_log.as_mut()) {
+                audit_log.record(&entry.finish(&result, started.elapsed()));
+            }
+            match result {
+                Ok(Some(response)) => self.send_response(response),
+                _ => Ok(()),
+            }
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Get a call's answer on a task (`run_call`) that hands it to `finish_call`, so the loop
+    /// takes further messages meanwhile; `None` once sent off
+    async fn send_off(
+        &mut self,
+        call: Box<BackendCall>,
+        target: CallTarget,
+        gather: Vec<(Box<BackendCall>, CallTarget)>,
+    ) -> Result<Option<JsonRpcResponse>, ProxyError> {
+        // Liveness checks never wait for a global slot
+        let limiter = match call.kind {
+            CallKind::Ping => None,
+            _ => self.global_inflight.clone(),
+        };
+        let span = call.span.clone();
+        let task = run_call(call, target, gather, limiter).instrument(span);
+        self.calls_in_flight += 1;
+        match self.call_tx.clone() {
+            Some(call_tx) => {
+                tokio::spawn(async move {
+                    let _ = call_tx.send(task.await);
+                });
+            }
+            // Not serving a client, so there's nothing else to do meanwhile
+            None => {
+                let (call, result) = task.await;
+                self.finish_call(call, result).await?;
+            }
+        }
+        Ok(None)
+    }
+
+    /// A call for a request the proxy sends on its own behalf (not routed by `prepare_call`),
+    /// to `root`'s primary instance or the extra MCP server `root` names
+    fn new_call(&self, request: JsonRpcRequest, root: PathBuf, kind: CallKind) -> Box<BackendCall> {
+        Box::new(BackendCall {
+            request,
+            received: None,
+            root,
+            canary: false,
+            instance: 0,
+            arrived: self.request_arrived,
+            started: Instant::now(),
+            attempts: 1,
+            audit: None,
+            span: tracing::Span::current(),
+            kind,
+            gathered: Vec::new(),
+        })
+    }
+
+    /// Pick the backend for a request, or answer it right away (no root, a dry run, a script)
+    async fn prepare_call(&mut self, mut request: JsonRpcRequest) -> Result<Routed, ProxyError> {
+        let arrived = self.request_arrived;
+
         // Determine which root to use
-        let root = self.determine_root(&request);
+        let decision = self.route_decision(&request);
+        let root = decision.as_ref().map(|(root, _)| root.clone());
+        request.take_meta(routing::ROOT_HINT_META);
+        request.take_meta(routing::REQUESTED_ROOT_META);
+
+        #[cfg(feature = "scripting")]
+        let root = match self.scripts {
+            Some(ref scripts) => {
+                let mut root = root;
+                if let HookOutcome::Respond(response) = scripts.pre_route(&mut request, &mut root) {
+                    return Ok(Routed::Answered(response));
+                }
+                root.map(|r| self.path_resolver.resolve(&r))
+            }
+            None => root,
+        };
         
         info!("Routing {} to root: {:?}", request.method, root);
 
+        // Bind the session to this root so follow-up requests without a URI stick to it
+        if let (Some(session), Some(ref root)) = (request.get_session_id(&self.config.session_id_field), &root) {
+            if self.sessions.peek(&session) != Some(root) {
+                debug!("Binding session {} to root {}", session, root.display());
+            }
+            self.sessions.put(session, root.clone());
+        }
+
         let root = match root {
             Some(r) => r,
             None => {
-                return Ok(JsonRpcResponse::error(
+                return Ok(Routed::Answered(JsonRpcResponse::error(
                     request.id.clone(),
                     JsonRpcError::new(
-                        ERROR_BACKEND_UNAVAILABLE,
+                        ERROR_ROUTING_FAILED,
                         "No workspace root available for routing",
                     ),
-                ));
+                )));
+            }
+        };
+
+        // A backend found dead before its exit was reported goes the way of a crashed one
+        if self.backends.peek(&root).is_some_and(BackendInstance::is_dead) {
+            self.recover_backend(&root, "found dead");
+        }
+        // Started (or restarted) on a task; the request waits for it, pinned to this root
+        if !self.config.dry_run && (!self.backends.contains(&root) || self.spawning.contains_key(&root)) {
+            if let Err(e) = self.start_spawn(root.clone()).await {
+                error!("Failed to start backend: {}", e);
+                return Ok(Routed::Answered(JsonRpcResponse::error(request.id.clone(), backend_error(&e))));
+            }
+            if let Some(queue) = self.spawning.get_mut(&root) {
+                debug!("Queueing {} until the backend for {} is up", request.method, root.display());
+                request.set_meta(routing::ROOT_HINT_META, root.to_string_lossy().into());
+                queue.push_back(QueuedMessage::new(request, arrived));
+                return Ok(Routed::Queued);
             }
+        }
+
+        #[cfg(feature = "scripting")]
+        if let Some(ref scripts) = self.scripts {
+            if let HookOutcome::Respond(response) = scripts.pre_forward(&mut request, &root) {
+                return Ok(Routed::Answered(response));
+            }
+        }
+
+        if self.config.dry_run {
+            let routed_by = match decision {
+                Some((decided, routed_by)) if decided == root => routed_by,
+                _ => "pre_route script",
+            };
+            return Ok(Routed::Answered(self.dry_run_response(&request, &root, routed_by).await));
+        }
+
+        let canary = self.use_canary(&root);
+        let instance = match canary {
+            true => 0,
+            false => self.pick_instance(&root).await,
         };
+        let begun = match self.instance_mut(&root, canary, instance) {
+            Some(backend) => backend.begin_request(&request.method),
+            None => Err(ProxyError::BackendUnavailable(format!("No backend for {}", root.display()))),
+        };
+        let call = BackendCall {
+            request,
+            received: None,
+            root,
+            canary,
+            instance,
+            arrived,
+            started: Instant::now(),
+            attempts: 1,
+            audit: None,
+            span: tracing::Span::current(),
+            kind: CallKind::Backend,
+            gathered: Vec::new(),
+        };
+        match begun {
+            Ok((handle, timeout)) => Ok(Routed::Call(Box::new(call), handle, timeout)),
+            Err(e) => Ok(Routed::Answered(self.respond_to_call(call, Err(e)).await)),
+        }
+    }
 
-        // Get or create backend for this root
-        let backend = match self.get_or_create_backend(root.clone()).await {
-            Ok(b) => b,
+    /// Turn the final outcome of a request into the response for the client
+    async fn respond_to_call(&mut self, call: BackendCall, result: Result<JsonRpcResponse, ProxyError>) -> JsonRpcResponse {
+        let BackendCall { request, root, canary, instance, arrived, started, attempts, .. } = call;
+        // Never reached the backend: no room in the global queue
+        if let Err(ProxyError::QueueFull(msg)) = result {
+            warn!("Rejecting {}: {}", request.method, msg);
+            return JsonRpcResponse::error(request.id.clone(), JsonRpcError::new(ERROR_QUEUE_FULL, msg));
+        }
+        // Exit status and last stderr lines usually explain a crash or failed start
+        let error_data = match result {
+            Err(_) => self.instance_mut(&root, canary, instance).map(|backend| backend.error_data()),
+            Ok(_) => None,
+        };
+        let elapsed = started.elapsed();
+        self.log_if_slow(&request, &root, started - arrived, elapsed);
+        if canary {
+            self.record_canary_outcome(&root, &result).await;
+        }
+        if let Ok(ref response) = result {
+            self.mirror_to_shadow(&root, &request, response, elapsed);
+        }
+        match result {
+            #[cfg(feature = "scripting")]
+            Ok(mut response) => {
+                if let Some(ref scripts) = self.scripts {
+                    scripts.post_response(&request, &root, &mut response);
+                }
+                response
+            }
+            #[cfg(not(feature = "scripting"))]
+            Ok(response) => response,
             Err(e) => {
-                error!("Failed to get backend: {}", e);
-                let code = match e {
-                    ProxyError::BackendUnavailable(_) => ERROR_BACKEND_UNAVAILABLE,
-                    _ => ERROR_BACKEND_SPAWN_FAILED,
-                };
-                return Ok(JsonRpcResponse::error(
-                    request.id.clone(),
-                    JsonRpcError::new(code, e.to_string()),
-                ));
+                error!("Backend request failed after {} attempt(s): {}", attempts, e);
+                let mut data = error_data.unwrap_or_default();
+                data["attempts"] = attempts.into();
+                data["elapsedMs"] = (elapsed.as_millis() as u64).into();
+                let error = JsonRpcError::new(e.code(), e.to_string()).with_data(data);
+                JsonRpcResponse::error(request.id.clone(), error)
             }
+        }
+    }
+
+    /// Synthetic answer describing where a request would have gone (`dry_run`)
+    async fn dry_run_response(&self, request: &JsonRpcRequest, root: &Path, routed_by: &str) -> JsonRpcResponse {
+        info!("Dry run: {} -> {} ({})", request.method, root.display(), routed_by);
+        let command = match BackendInstance::command_line(&self.config, root).await {
+            Ok(command) => serde_json::json!(command),
+            Err(e) => serde_json::json!({ "error": e.to_string() }),
         };
+        JsonRpcResponse::success(
+            request.id.clone(),
+            serde_json::json!({
+                "dryRun": true,
+                "method": request.method,
+                "root": root,
+                "routedBy": routed_by,
+                "instances": self.config.instances_for(root),
+                "command": command,
+            }),
+        )
+    }
 
-        // Send request to backend with retry (max 1 retry for crash recovery)
-        match backend.send_request_with_retry(request.clone(), 1).await {
-            Ok(response) => Ok(response),
+    /// Warn about a request slower than `slow_request_ms`, splitting the time spent waiting
+    /// (for a global slot, or for the backend to start) from the time the backend took
+    fn log_if_slow(&self, request: &JsonRpcRequest, root: &Path, queued: Duration, backend: Duration) {
+        let total = queued + backend;
+        if self.config.slow_request_ms == 0 || total < Duration::from_millis(self.config.slow_request_ms) {
+            return;
+        }
+        let mut params = request.params.clone().unwrap_or(serde_json::Value::Null);
+        redact::global().redact_value(&mut params);
+        warn!(
+            "Slow request: {} on {} took {}ms (queued {}ms, backend {}ms), params: {}",
+            request.method,
+            root.display(),
+            total.as_millis(),
+            queued.as_millis(),
+            backend.as_millis(),
+            jsonrpc::summarize(&params, SLOW_REQUEST_PARAMS_CHARS)
+        );
+    }
+
+    /// Whether this request goes to the root's canary (starting the canary if it isn't up yet)
+    fn use_canary(&mut self, root: &Path) -> bool {
+        let gate = match self.canary_gate.as_mut() {
+            Some(gate) if gate.is_active() => gate,
+            _ => return false,
+        };
+        if self.canaries.get(root).is_some_and(|c| !c.is_dead()) {
+            if !gate.sample() {
+                return false;
+            }
+            // The root's slot stays in use whichever backend answers
+            if let Some(primary) = self.backends.get_mut(root) {
+                primary.last_used = Instant::now();
+            }
+            return true;
+        }
+        // A connected root's canary would reach the same server
+        if !self.canaries_starting.contains(root)
+            && self.config.root_config(root).is_none_or(|rc| rc.connect.is_none())
+            && self.spawn_in_background(root, SpawnPurpose::Canary)
+        {
+            info!("Starting canary backend for {}", root.display());
+            self.canaries.remove(root);
+            self.canaries_starting.insert(root.to_path_buf());
+        }
+        false
+    }
+
+    /// Add a canary started on a task, unless its root's backend is gone or the canary was rolled back
+    async fn finish_canary(&mut self, root: PathBuf, result: Result<BackendInstance, ProxyError>) {
+        self.canaries_starting.remove(&root);
+        let backend = match result {
+            Ok(backend) => backend,
             Err(e) => {
-                error!("Backend request failed after retries: {}", e);
-                Ok(JsonRpcResponse::error(
-                    request.id.clone(),
-                    JsonRpcError::new(ERROR_INTERNAL_ERROR, e.to_string()),
-                ))
+                warn!("Failed to start canary backend for {}: {}", root.display(), e);
+                return;
+            }
+        };
+        if !self.backends.contains(&root) || !self.canary_gate.as_ref().is_some_and(|g| g.is_active()) {
+            self.retire(backend);
+            return;
+        }
+        info!("Canary backend for {} is up", root.display());
+        self.canaries.insert(root, backend);
+    }
+
+    /// Start one of the extra MCP servers on a task
+    fn start_server(&mut self, name: String) {
+        let (Some(spawn_tx), Some(server)) = (self.spawn_tx.clone(), self.config.servers.get(&name).cloned()) else {
+            return;
+        };
+        if self.config.dry_run || !self.servers_starting.insert(name.clone()) {
+            return;
+        }
+        let context = self.spawn_context();
+        tokio::spawn(async move {
+            let result = context.spawn_server(&name, &server).await;
+            let _ = spawn_tx.send((PathBuf::from(name), SpawnPurpose::Server, result));
+        });
+    }
+
+    /// Add an extra MCP server started on a task; its tools join the list
+    async fn finish_server(&mut self, root: PathBuf, result: Result<BackendInstance, ProxyError>) {
+        let name = root.to_string_lossy().to_string();
+        self.servers_starting.remove(&name);
+        let server = match result {
+            Ok(server) => server,
+            Err(e) => {
+                error!("Failed to start MCP server {}: {}", name, e);
+                return;
             }
+        };
+        if self.shutting_down {
+            self.retire(server);
+            return;
         }
+        info!("MCP server {} is up", name);
+        self.servers.insert(name, server);
+        self.send_tools_changed();
     }
 
-    /// Determine which root to use for a request
-    fn determine_root(&self, request: &JsonRpcRequest) -> Option<PathBuf> {
-        // Try to extract URI from request and match to a root
-        if let Some(uri) = request.get_uri() {
-            if let Some(path) = Self::uri_to_path(&uri) {
-                // Find longest prefix match among known roots
-                let matched = self.roots.iter()
-                    .filter(|root| path.starts_with(root))
-                    .max_by_key(|root| root.as_os_str().len());
-                
-                if let Some(root) = matched {
-                    return Some(root.clone());
+    /// Count a canary request against the canary's error rate, rolling every canary back when it's too high
+    async fn record_canary_outcome(&mut self, root: &Path, result: &Result<JsonRpcResponse, ProxyError>) {
+        // Errors the client caused don't count against the canary
+        let failed = match result {
+            Ok(response) => response.error.as_ref()
+                .is_some_and(|e| e.code != ERROR_INVALID_PARAMS && e.code != ERROR_METHOD_NOT_FOUND),
+            Err(_) => true,
+        };
+        let gate = match self.canary_gate.as_mut() {
+            Some(gate) => gate,
+            None => return,
+        };
+        if !gate.record(failed) {
+            return;
+        }
+        error!(
+            "Canary error rate {:.0}% (last failure on {}), rolling back to 0%",
+            gate.error_rate() * 100.0,
+            root.display()
+        );
+        for (_, mut canary) in self.canaries.drain() {
+            tokio::spawn(async move { canary.shutdown().await });
+        }
+    }
+
+    /// What a different backend version (shadow, canary) needs to start
+    fn variant_context(&self, auggie_entry: &Option<PathBuf>, node: &Option<PathBuf>) -> SpawnContext {
+        let mut context = self.spawn_context();
+        context.config.auggie_entry = auggie_entry.clone();
+        if let Some(node) = node {
+            context.config.node = Some(node.clone());
+        }
+        context
+    }
+
+    /// Send a sampled request to the root's shadow backend on a task and log how its answer
+    /// compares; the client gets the primary's response either way
+    fn mirror_to_shadow(&mut self, root: &Path, request: &JsonRpcRequest, primary: &JsonRpcResponse, primary_elapsed: Duration) {
+        let sampler = match self.shadow_sampler.as_mut() {
+            Some(sampler) => sampler,
+            None => return,
+        };
+        if !sampler.sample(&request.method) {
+            return;
+        }
+        // A connected root's shadow would reach the same server
+        if self.config.root_config(root).is_some_and(|rc| rc.connect.is_some()) {
+            return;
+        }
+        let mut context = self.variant_context(&self.config.shadow.auggie_entry, &self.config.shadow.node);
+        // Its notifications and stderr would only confuse the client
+        context.event_tx = None;
+        context.stderr_tx = None;
+        // Nor does it wait for the client's initialized (it only sees mirrored requests)
+        context.client_ready = true;
+
+        let shadow = self.shadows.entry(root.to_path_buf()).or_default().clone();
+        let root = root.to_path_buf();
+        let request = request.clone();
+        let primary = primary.clone();
+        tokio::spawn(async move {
+            // One mirrored request at a time; skip while the shadow is busy or starting
+            let mut slot = match shadow.try_lock() {
+                Ok(slot) => slot,
+                Err(_) => {
+                    debug!("Shadow backend for {} is busy, not mirroring {}", root.display(), request.method);
+                    return;
+                }
+            };
+            if slot.as_ref().is_none_or(|b| b.is_dead()) {
+                info!("Starting shadow backend for {}", root.display());
+                match context.spawn(root.clone()).await {
+                    Ok(backend) => *slot = Some(backend),
+                    Err(e) => {
+                        warn!("Failed to start shadow backend for {}: {}", root.display(), e);
+                        return;
+                    }
                 }
-                
-                // Auto-detect git root from file path
-                if let Some(git_root) = Self::find_git_root(&path) {
-                    info!("Auto-detected git root from URI: {}", git_root.display());
-                    return Some(git_root);
+            }
+            let backend = match slot.as_mut() {
+                Some(backend) => backend,
+                None => return,
+            };
+            let started = Instant::now();
+            match backend.send_request(request.clone()).await {
+                Ok(response) => {
+                    let (primary_ms, shadow_ms) = (primary_elapsed.as_millis(), started.elapsed().as_millis());
+                    match shadow::diff(&primary, &response) {
+                        None => info!(
+                            "Shadow {} for {} matches ({}ms vs {}ms primary)",
+                            request.method, root.display(), shadow_ms, primary_ms
+                        ),
+                        Some(difference) => warn!(
+                            "Shadow {} for {} differs: {} ({}ms vs {}ms primary)",
+                            request.method, root.display(), difference, shadow_ms, primary_ms
+                        ),
+                    }
                 }
+                Err(e) => warn!("Shadow {} for {} failed: {}", request.method, root.display(), e),
+            }
+        });
+    }
+
+    /// Determine which root to use for a request
+    fn determine_root(&self, request: &JsonRpcRequest) -> Option<PathBuf> {
+        self.route_decision(request).map(|(root, _)| root)
+    }
+
+    /// The root for a request, with what decided it
+    fn route_decision(&self, request: &JsonRpcRequest) -> Option<(PathBuf, &'static str)> {
+        // Pinned by the proxy itself (e.g. a namespaced tool call)
+        let hint = request.get_param_path("_meta").and_then(|meta| meta.get(routing::ROOT_HINT_META));
+        if let Some(root) = hint.and_then(|v| v.as_str()) {
+            return Some((PathBuf::from(root), "tool namespace"));
+        }
+
+        // Picked by the client
+        let requested = request.get_param_path("_meta").and_then(|meta| meta.get(routing::REQUESTED_ROOT_META));
+        if let Some(root) = requested.and_then(|v| v.as_str()) {
+            return Some((PathBuf::from(root), "_meta.workspaceRoot"));
+        }
+
+        let uri = request.get_uri();
+        let path = uri.as_deref().and_then(|uri| self.resolve_uri(uri));
+
+        // Configured routing rules take precedence over heuristics
+        if let Some(root) = self.routing_rules.route(&request.method, uri.as_deref(), path.as_deref()) {
+            return Some((self.path_resolver.resolve(&root), "routing rule"));
+        }
+
+        // Try to match the request's file path to a root
+        if let Some(path) = path {
+            // Find longest prefix match among known roots (including ones with overrides)
+            let matched = self.roots.iter()
+                .chain(self.config.root_overrides.keys())
+                .filter(|root| path.starts_with(root))
+                .max_by_key(|root| root.as_os_str().len());
+            
+            if let Some(root) = matched {
+                return Some((root.clone(), "known root"));
+            }
+            
+            // Auto-detect workspace root from file path
+            if let Some(workspace_root) = routing::find_workspace_root(&path, &self.config.workspace_markers) {
+                info!("Auto-detected workspace root from URI: {}", workspace_root.display());
+                return Some((workspace_root, "workspace marker"));
+            }
+        }
+
+        // Stick to the root this session was last routed to
+        if let Some(session) = request.get_session_id(&self.config.session_id_field) {
+            if let Some(root) = self.sessions.peek(&session) {
+                debug!("Routing via session binding {} -> {}", session, root.display());
+                return Some((root.clone(), "session binding"));
             }
         }
 
         // Fall back to default root if configured
         if let Some(ref root) = self.default_root {
-            return Some(root.clone());
+            return Some((root.clone(), "default root"));
         }
         
         // Fall back to first known root
         if !self.roots.is_empty() {
-            return Some(self.roots[0].clone());
+            return Some((self.roots[0].clone(), "first client root"));
         }
         
         None
     }
     
-    /// Find git root by walking up from the given path
-    fn find_git_root(path: &Path) -> Option<PathBuf> {
-        let mut current = if path.is_file() {
-            path.parent()?.to_path_buf()
-        } else {
-            path.to_path_buf()
-        };
-        
-        loop {
-            let git_dir = current.join(".git");
-            if git_dir.exists() {
-                return Some(current);
-            }
-            
-            if !current.pop() {
-                break;
-            }
-        }
-        
-        None
-    }
-
-    /// Get existing backend or create new one for the given root
-    async fn get_or_create_backend(&mut self, root: PathBuf) -> Result<&mut BackendInstance, ProxyError> {
+    /// Make sure one more backend fits, counting the ones still starting
+    async fn make_room(&mut self, root: &Path) -> Result<(), ProxyError> {
         // LRU cache handles eviction automatically when capacity is exceeded
         // But we need to ensure evicted backends are properly shut down
         // Check if we need to make room (LRU will auto-evict, but we want graceful shutdown)
-        if self.backends.len() >= self.backends.cap().get() && !self.backends.contains(&root) {
+        self.retire_drained_backends().await;
+        let starting = self.spawning.keys().filter(|r| r.as_path() != root && !self.backends.contains(*r)).count();
+        if self.backends.len() + starting >= self.backends.cap().get() && !self.backends.contains(root) {
             // Evict LRU backend gracefully before LRU auto-evicts
-            if !self.evict_lru_backend().await {
+            if !self.evict_backend().await && !self.overflow_backends() {
                 return Err(ProxyError::BackendUnavailable(
                     "All backends are busy (pending requests), cannot evict LRU".to_string(),
                 ));
             }
         }
+        Ok(())
+    }
 
-        // Create backend if it doesn't exist
-        if !self.backends.contains(&root) {
-            info!("Creating new backend for root: {}", root.display());
-            
+    /// What a backend needs to start, detached from the proxy so it can start on a task
+    fn spawn_context(&self) -> SpawnContext {
+        SpawnContext {
+            config: self.config.clone(),
+            init_params: backend::handshake_params(self.client_init_params.as_ref()),
+            client_ready: self.lifecycle_phase() != Phase::Initializing,
+            stderr_tx: self.stderr_tx.clone(),
+            event_tx: self.backend_event_tx.clone(),
             #[cfg(windows)]
-            let backend = BackendInstance::spawn(
-                &self.config,
-                root.clone(),
-                self.job_object.clone(),
-            ).await?;
-            
+            job_object: self.job_object.clone(),
             #[cfg(unix)]
-            let backend = BackendInstance::spawn(
-                &self.config,
-                root.clone(),
-                self.process_group.clone(),
-            ).await?;
-            
-            // put() returns the evicted entry if any (but we already handled eviction above)
-            self.backends.put(root.clone(), backend);
+            process_group: self.process_group.clone(),
+        }
+    }
+
+    /// Add a started backend and hand it what was held for its root
+    async fn install_backend(&mut self, root: PathBuf, backend: BackendInstance) {
+        // put() returns the evicted entry if any (but we already handled eviction above)
+        self.backends.put(root.clone(), backend);
+        self.send_tools_changed();
+        if let Some(batches) = self.undelivered_changes.remove(&root) {
+            info!("Delivering {} change batches held for {}", batches.len(), root.display());
+            if let Some(backend) = self.backends.get_mut(&root) {
+                for batch in batches {
+                    if let Err(e) = backend.send_file_changes(batch).await {
+                        warn!("Failed to deliver held change batch: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+        if let Some(watcher) = self.watcher.as_mut() {
+            watcher.watch(&root);
+        }
+        self.start_replicas(&root);
+    }
+
+    /// Start the instances a root is missing beyond its first one on tasks
+    fn start_replicas(&mut self, root: &Path) {
+        let wanted = self.config.instances_for(root) - 1;
+        let have = self.replicas.get(root).map_or(0, Vec::len) + self.replicas_starting.get(root).copied().unwrap_or(0);
+        for _ in have..wanted {
+            if !self.spawn_in_background(root, SpawnPurpose::Replica) {
+                return;
+            }
+            info!("Starting another backend instance for root: {}", root.display());
+            *self.replicas_starting.entry(root.to_path_buf()).or_default() += 1;
+        }
+    }
+
+    /// Start a backend on a task, reporting back to the run loop (false when it isn't running)
+    fn spawn_in_background(&self, root: &Path, purpose: SpawnPurpose) -> bool {
+        let spawn_tx = match self.spawn_tx.clone() {
+            Some(tx) => tx,
+            None => return false,
+        };
+        let context = match purpose {
+            SpawnPurpose::Canary => self.variant_context(&self.config.canary.auggie_entry, &self.config.canary.node),
+            _ => self.spawn_context(),
+        };
+        let root = root.to_path_buf();
+        tokio::spawn(async move {
+            let result = context.spawn(root.clone()).await;
+            let _ = spawn_tx.send((root, purpose, result));
+        });
+        true
+    }
+
+    /// Add a replica started on a task, unless its root's backend is gone by now
+    async fn finish_replica(&mut self, root: PathBuf, result: Result<BackendInstance, ProxyError>) {
+        if let Some(starting) = self.replicas_starting.get_mut(&root) {
+            *starting = starting.saturating_sub(1);
+        }
+        let backend = match result {
+            Ok(backend) => backend,
+            Err(e) => {
+                warn!("Failed to start another backend instance for {}: {}", root.display(), e);
+                return;
+            }
+        };
+        let wanted = self.config.instances_for(&root) - 1;
+        let replicas = self.replicas.entry(root.clone()).or_default();
+        if !self.backends.contains(&root) || replicas.len() >= wanted {
+            self.retire(backend);
+            return;
+        }
+        replicas.push(backend);
+        info!("Backend for {} now runs {} instances", root.display(), replicas.len() + 1);
+    }
+
+    /// Shut down a root's further instances along with its backend
+    fn shutdown_extra_instances(&mut self, root: &Path) {
+        self.replicas_starting.remove(root);
+        self.balance_counters.remove(root);
+        for replica in self.replicas.remove(root).unwrap_or_default() {
+            self.retire(replica);
+        }
+        if let Some(canary) = self.canaries.remove(root) {
+            self.retire(canary);
+        }
+        if let Some(shadow) = self.shadows.remove(root) {
+            tokio::spawn(async move {
+                if let Some(mut backend) = shadow.lock().await.take() {
+                    backend.shutdown().await;
+                }
+            });
+        }
+    }
+
+    /// Drop dead replicas and worn ones between requests, and start the missing ones on tasks
+    async fn tend_replicas(&mut self, root: &Path) {
+        if let Some(replicas) = self.replicas.get_mut(root) {
+            let mut healthy = Vec::with_capacity(replicas.len());
+            let mut replaced = Vec::new();
+            for mut replica in replicas.drain(..) {
+                if !replica.health_check().await {
+                    info!("Backend instance for {} failed health check, replacing it", root.display());
+                    replaced.push(replica);
+                    continue;
+                }
+                if let Some(reason) = replica.recycle_reason() {
+                    if !replica.has_pending().await {
+                        info!("Backend instance for {} has {}, replacing it", root.display(), reason);
+                        replaced.push(replica);
+                        continue;
+                    }
+                }
+                healthy.push(replica);
+            }
+            *replicas = healthy;
+            for replica in replaced {
+                self.retire(replica);
+            }
+        }
+        self.start_replicas(root);
+    }
+
+    /// The instance of a root's backend to send the next request to (`load_balancing`)
+    async fn pick_instance(&mut self, root: &Path) -> usize {
+        if let Some(replicas) = self.replicas.get_mut(root) {
+            replicas.retain(|r| !r.is_dead());
+        }
+        let mut pending = Vec::new();
+        if let Some(replicas) = self.replicas.get(root).filter(|r| !r.is_empty()) {
+            if let Some(primary) = self.backends.peek(root) {
+                pending.push(primary.pending_count().await);
+            }
+            for replica in replicas {
+                pending.push(replica.pending_count().await);
+            }
+        }
+        let counter = self.balance_counters.entry(root.to_path_buf()).or_default();
+        let index = balance::pick(self.config.load_balancing, counter, &pending);
+
+        // get() promotes to most recently used; the root's slot stays in use whichever instance answers
+        if let Some(primary) = self.backends.get_mut(root) {
+            if index != 0 {
+                primary.last_used = Instant::now();
+            }
+        }
+        index
+    }
+
+    /// The backend a request went to: the root's canary, or its primary (0) or a replica
+    /// (`None` once it's gone)
+    fn instance_mut(&mut self, root: &Path, canary: bool, index: usize) -> Option<&mut BackendInstance> {
+        match (canary, index) {
+            (true, _) => self.canaries.get_mut(root),
+            (false, 0) => self.backends.peek_mut(root),
+            (false, index) => self.replicas.get_mut(root)?.get_mut(index - 1),
+        }
+    }
+
+    /// Start a root's backend on a task; messages for the root wait in `spawning` meanwhile
+    async fn start_spawn(&mut self, root: PathBuf) -> Result<(), ProxyError> {
+        if self.config.dry_run {
+            return Err(ProxyError::BackendUnavailable("Dry run: backends are not started".to_string()));
+        }
+        if self.backends.contains(&root) || self.spawning.contains_key(&root) {
+            return Ok(());
+        }
+        if self.spawn_tx.is_none() {
+            return Err(ProxyError::BackendUnavailable("The proxy isn't serving a client".to_string()));
+        }
+        self.make_room(&root).await?;
+        info!("Starting backend for root in the background: {}", root.display());
+        self.spawning.insert(root.clone(), VecDeque::new());
+        self.spawn_in_background(&root, SpawnPurpose::Root);
+        Ok(())
+    }
+
+    /// Queue a request for a root whose backend is starting, starting it if needed
+    /// Returns the request when it can be routed right away.
+    async fn queue_for_spawn(&mut self, request: JsonRpcRequest) -> Option<JsonRpcRequest> {
+        if self.spawn_tx.is_none() || self.config.dry_run {
+            return Some(request);
+        }
+        // A crashed backend restarting (`restart_crashed_backends`) holds its root's messages too
+        let root = match self.determine_root(&request) {
+            Some(root) if !self.backends.contains(&root) || self.spawning.contains_key(&root) => root,
+            _ => return Some(request),
+        };
+        if !self.spawning.contains_key(&root) {
+            // prepare_call reports why there's no room
+            if self.start_spawn(root.clone()).await.is_err() {
+                return Some(request);
+            }
         }
+        debug!("Queueing {} until the backend for {} is up", request.method, root.display());
+        let queued = QueuedMessage::new(request, self.request_arrived);
+        self.spawning.get_mut(&root)?.push_back(queued);
+        None
+    }
+
+    /// Take in a backend started on a task and deliver the messages queued for it
+    async fn finish_spawn(&mut self, root: PathBuf, result: Result<BackendInstance, ProxyError>) -> Result<(), ProxyError> {
+        let queued = self.spawning.remove(&root).unwrap_or_default();
+        let result = match result {
+            // Taken over in the meantime (by a backend restarted for a retry)
+            Ok(backend) if self.backends.contains(&root) => {
+                self.retire(backend);
+                Ok(())
+            }
+            Ok(backend) => match self.make_room(&root).await {
+                Ok(()) => {
+                    self.install_backend(root.clone(), backend).await;
+                    Ok(())
+                }
+                Err(e) => {
+                    self.retire(backend);
+                    Err(e)
+                }
+            },
+            Err(e) => Err(e),
+        };
+        self.deliver_queued(&root, queued, result).await
+    }
 
-        // get() promotes to most recently used
-        Ok(self.backends.get_mut(&root).unwrap())
+    /// Deliver the messages held while a root's backend started, or fail them if it didn't
+    async fn deliver_queued(&mut self, root: &Path, queued: VecDeque<QueuedMessage>, result: Result<(), ProxyError>) -> Result<(), ProxyError> {
+        match result {
+            Ok(()) => {
+                info!("Backend for {} is up, delivering {} queued messages", root.display(), queued.len());
+                for QueuedMessage { request: message, span, arrived } in queued {
+                    self.request_arrived = arrived;
+                    if message.is_notification() {
+                        if let Err(e) = self.forward_notification_to_backend(message).instrument(span).await {
+                            warn!("Failed to forward queued notification: {}", e);
+                        }
+                        continue;
+                    }
+                    // Queued requests already went through the request layers and confirmation
+                    let id = message.id.clone();
+                    match self.dispatch(message, Some(ConfirmDecision::Approved)).instrument(span).await {
+                        Ok(Some(response)) => self.send_response(response)?,
+                        Ok(None) => {}
+                        Err(e) => {
+                            error!("Error handling queued request: {}", e);
+                            self.abandon_response(id.as_ref());
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to start backend for {}: {}", root.display(), e);
+                for message in queued.into_iter().map(|queued| queued.request).filter(|m| !m.is_notification()) {
+                    self.metrics.record_error();
+                    self.send_response(JsonRpcResponse::error(message.id.clone(), backend_error(&e)))?;
+                }
+            }
+        }
+        Ok(())
     }
 
-    /// Evict the least recently used backend (with graceful shutdown)
-    async fn evict_lru_backend(&mut self) -> bool {
+    /// Evict an idle backend chosen by `eviction_policy` (with graceful shutdown)
+    async fn evict_backend(&mut self) -> bool {
         // Peek at LRU entries without promoting them
         let mut candidates: Vec<PathBuf> = self
             .backends
@@ -531,25 +3224,97 @@ impl McpProxy {
         // Iterate from LRU (oldest) to MRU (newest) - LruCache iter is MRU-first, so reverse
         candidates.reverse();
 
+        // Idle, unpinned backends can go
+        let mut idle = Vec::new();
         for root in candidates {
-            // Check if backend has pending requests (peek doesn't promote)
-            let has_pending = match self.backends.peek(&root) {
-                Some(b) => b.has_pending().await,
-                None => continue,
-            };
+            if self.is_pinned(&root) {
+                continue;
+            }
+            // peek doesn't promote
+            if let Some(backend) = self.backends.peek(&root) {
+                if !backend.has_pending().await && !self.replicas_busy(&root).await {
+                    let cost = backend.eviction_cost();
+                    idle.push((root, cost));
+                }
+            }
+        }
+
+        let policy = self.config.eviction_policy;
+        let root = match eviction::pick_victim(policy, idle) {
+            Some(root) => root,
+            None => return false,
+        };
+        info!("Evicting backend ({:?} policy): {}", policy, root.display());
+        if let Some(backend) = self.backends.pop(&root) {
+            self.retire(backend);
+        }
+        self.shutdown_extra_instances(&root);
+        self.unwatch_root(&root);
+        self.send_tools_changed();
+        true
+    }
+
+    /// Every backend is busy: drain the least recently used one and make room for one more,
+    /// up to `max_backends_burst` beyond `max_backends`
+    fn overflow_backends(&mut self) -> bool {
+        let cap = self.backends.cap().get();
+        if cap >= self.config.max_backends.max(1) + self.config.max_backends_burst {
+            return false;
+        }
+        let victim = self.backends
+            .iter()
+            .rev()
+            .find(|(root, b)| !b.is_draining() && !self.is_pinned(root))
+            .map(|(root, _)| root.clone());
+        if let Some(root) = victim {
+            info!("All backends are busy, draining {} and allowing {} backends meanwhile", root.display(), cap + 1);
+            if let Some(backend) = self.backends.peek_mut(&root) {
+                backend.start_draining();
+            }
+        }
+        self.backends.resize(NonZeroUsize::new(cap + 1).unwrap());
+        true
+    }
+
+    /// Whether any of a root's further instances has requests in flight
+    async fn replicas_busy(&self, root: &Path) -> bool {
+        for replica in self.replicas.get(root).into_iter().flatten() {
+            if replica.has_pending().await {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Whether a root's backend is exempt from eviction and idle cleanup (`pinned_roots`)
+    fn is_pinned(&self, root: &Path) -> bool {
+        self.config.pinned_roots.iter().any(|pinned| pinned == root)
+    }
 
-            if has_pending {
-                continue;
+    /// Shut down draining backends whose requests have finished and give back the extra capacity
+    async fn retire_drained_backends(&mut self) {
+        let draining: Vec<PathBuf> = self.backends
+            .iter()
+            .filter(|(_, b)| b.is_draining())
+            .map(|(root, _)| root.clone())
+            .collect();
+        for root in draining {
+            match self.backends.peek(&root) {
+                Some(backend) if !backend.has_pending().await => {}
+                _ => continue,
             }
-
-            info!("Evicting LRU backend: {}", root.display());
-            if let Some(mut backend) = self.backends.pop(&root) {
-                backend.shutdown().await;
+            info!("Drained backend {} finished its requests, shutting it down", root.display());
+            if let Some(backend) = self.backends.pop(&root) {
+                self.retire(backend);
             }
-            return true;
+            self.shutdown_extra_instances(&root);
+            self.unwatch_root(&root);
+            self.send_tools_changed();
+        }
+        let cap = self.backends.len().max(self.config.max_backends.max(1));
+        if cap < self.backends.cap().get() {
+            self.backends.resize(NonZeroUsize::new(cap).unwrap());
         }
-
-        false
     }
 
     async fn forward_notification_to_backend(&mut self, request: JsonRpcRequest) -> Result<(), ProxyError> {
@@ -560,9 +3325,21 @@ impl McpProxy {
                 return Ok(());
             }
         };
+        if self.config.dry_run {
+            info!("Dry run: notification {} -> {}", request.method, root.display());
+            return Ok(());
+        }
+        // Started on a task if need be; the notification waits for it
+        self.start_spawn(root.clone()).await?;
+        if let Some(queue) = self.spawning.get_mut(&root) {
+            queue.push_back(QueuedMessage::new(request, self.request_arrived));
+            return Ok(());
+        }
 
-        let backend = self.get_or_create_backend(root).await?;
-        backend.send_notification(request).await
+        match self.backends.get_mut(&root) {
+            Some(backend) => backend.send_notification(request).await,
+            None => Err(ProxyError::BackendUnavailable(format!("No backend for {}", root.display()))),
+        }
     }
 
     async fn read_next_message<R: tokio::io::AsyncBufRead + Unpin>(
@@ -586,7 +3363,7 @@ impl McpProxy {
             }
 
             if line.to_ascii_lowercase().starts_with("content-length:") {
-                let len_str = line.splitn(2, ':').nth(1).unwrap_or("").trim();
+                let len_str = line.split_once(':').map(|(_, v)| v).unwrap_or("").trim();
                 let content_length: usize = len_str.parse().map_err(|e| {
                     ProxyError::JsonRpcParseError(format!("Invalid Content-Length header: {}", e))
                 })?;
@@ -614,66 +3391,70 @@ impl McpProxy {
         }
     }
 
-    /// Check if a path is git-tracked (with caching, TTL, and size limit)
-    async fn is_path_git_tracked(&mut self, path: &PathBuf) -> bool {
-        const GIT_CACHE_TTL_SECS: u64 = 60;
-        const GIT_CACHE_MAX_ENTRIES: usize = 10;
-        
-        // Find the root for this path
-        let root = self.roots.iter()
-            .filter(|r| path.starts_with(r))
-            .max_by_key(|r| r.as_os_str().len())
-            .cloned()
-            .or_else(|| self.default_root.clone());
+    /// Check if a path passes the include/exclude globs and the git filter
+    /// (git file lists are cached per root and kept current)
+    async fn passes_file_filters(&mut self, root: Option<PathBuf>, path: &Path) -> bool {
+        match self.path_filter.check(root.as_deref(), path) {
+            PathVerdict::Exclude => false,
+            PathVerdict::Include => true,
+            PathVerdict::Unmatched => match root {
+                Some(root) if self.git_filter_enabled(&root) => self.git_filter_cache.is_tracked(&root, path).await,
+                Some(_) => true,
+                None => true, // No root found, allow by default
+            },
+        }
+    }
 
-        let root = match root {
-            Some(r) => r,
-            None => return true, // No root found, allow by default
-        };
+    /// Global `git_filter`, unless the root's overrides say otherwise
+    fn git_filter_enabled(&self, root: &Path) -> bool {
+        self.config
+            .root_config(root)
+            .and_then(|rc| rc.git_filter)
+            .unwrap_or(self.config.git_filter)
+    }
 
-        // Check if cache is expired (TTL)
-        let cache_expired = self.git_cache_timestamps
-            .get(&root)
-            .map(|ts| ts.elapsed().as_secs() > GIT_CACHE_TTL_SECS)
-            .unwrap_or(true);
-        
-        if cache_expired {
-            self.git_tracked_cache.remove(&root);
-            self.git_cache_timestamps.remove(&root);
+    /// Queue a changed file for the next batched notification, applying the file filters
+    async fn enqueue_changed_path(&mut self, path: PathBuf, kind: ChangeKind) {
+        let root = self.root_for_path(&path);
+        if !self.passes_file_filters(root.clone(), &path).await {
+            debug!("Ignoring filtered file: {}", path.display());
+            return;
         }
-
-        // Evict oldest entries if cache is too large
-        while self.git_tracked_cache.len() >= GIT_CACHE_MAX_ENTRIES {
-            // Find the oldest entry
-            if let Some(oldest_root) = self.git_cache_timestamps
-                .iter()
-                .min_by_key(|(_, ts)| *ts)
-                .map(|(k, _)| k.clone())
-            {
-                debug!("Git cache full, evicting: {}", oldest_root.display());
-                self.git_tracked_cache.remove(&oldest_root);
-                self.git_cache_timestamps.remove(&oldest_root);
-            } else {
-                break;
+        let root = match root {
+            Some(root) => root,
+            None => {
+                debug!("No root for changed file: {}", path.display());
+                return;
             }
-        }
-
-        // Check cache or populate it
-        if !self.git_tracked_cache.contains_key(&root) {
-            if let Some(tracked) = git_filter::get_git_tracked_files(&root).await {
-                info!("Git filter cache populated for {}: {} files", root.display(), tracked.len());
-                self.git_tracked_cache.insert(root.clone(), tracked);
-                self.git_cache_timestamps.insert(root.clone(), Instant::now());
-            } else {
-                // Not a git repo or git failed, allow all files
-                return true;
+        };
+        let full = match self.event_throttler.as_mut() {
+            Some(throttler) => {
+                throttler.add_path(&root, path, kind);
+                debug!("File change throttled, pending: {}", throttler.pending_count());
+                throttler.is_full(&root)
             }
+            None => false,
+        };
+        // Don't let one huge batch wait for the window (or grow without bound)
+        if full {
+            self.flush_throttled_events().await;
         }
+    }
 
-        if let Some(tracked) = self.git_tracked_cache.get(&root) {
-            git_filter::is_git_tracked(path, tracked)
-        } else {
-            true
+    /// Root a changed file belongs to: longest known root containing it, else the default root
+    fn root_for_path(&self, path: &Path) -> Option<PathBuf> {
+        self.roots.iter()
+            .chain(self.config.root_overrides.keys())
+            .filter(|r| path.starts_with(r))
+            .max_by_key(|r| r.as_os_str().len())
+            .cloned()
+            .or_else(|| self.default_root.clone())
+    }
+
+    /// Stop watching a root whose backend is gone
+    fn unwatch_root(&mut self, root: &Path) {
+        if let Some(watcher) = self.watcher.as_mut() {
+            watcher.unwatch(root);
         }
     }
 
@@ -696,56 +3477,87 @@ impl McpProxy {
 
     /// Flush throttled events to backends (batched by root)
     async fn flush_throttled_events(&mut self) {
-        let throttler = match self.event_throttler.as_mut() {
-            Some(t) => t,
+        let events = match self.event_throttler.as_mut() {
+            Some(throttler) => throttler.flush(),
             None => return,
         };
 
-        if !throttler.should_flush() {
-            return;
-        }
+        for event in events {
+            let root = event.root;
+            debug!("Flushing {} throttled file change events for {}", event.changes.len(), root.display());
 
-        if let Some(event) = throttler.flush() {
-            debug!("Flushing {} throttled file change events", event.paths.len());
-            
-            // Group paths by root for batch notifications
-            let mut paths_by_root: HashMap<PathBuf, Vec<String>> = HashMap::new();
-            
-            for path in &event.paths {
-                let root = self.roots.iter()
-                    .filter(|r| path.starts_with(r))
-                    .max_by_key(|r| r.as_os_str().len())
-                    .cloned()
-                    .or_else(|| self.default_root.clone());
+            // Remote, container and WSL backends see paths in their own namespace
+            let changes: Vec<(String, ChangeKind)> = event
+                .changes
+                .iter()
+                .map(|(path, kind)| {
+                    let backend_path = self.config.root_config(&root).and_then(|rc| rc.backend_path(&root, path));
+                    let uri = match (backend_path, WslPath::from_path(path)) {
+                        (Some(backend_path), _) => format!("file://{}", backend_path),
+                        (None, Some(wsl_path)) if self.config.wsl.enabled => wsl_path.to_linux_uri(),
+                        _ => paths::path_to_uri(path),
+                    };
+                    (uri, *kind)
+                })
+                .collect();
+
+            let of_kind = |kind: ChangeKind| -> Vec<&str> {
+                changes.iter().filter(|(_, k)| *k == kind).map(|(uri, _)| uri.as_str()).collect()
+            };
+            let uris: Vec<&str> = changes.iter().map(|(uri, _)| uri.as_str()).collect();
+            // `uris` lists everything for backends that don't look at the kinds
+            let notification = JsonRpcRequest {
+                jsonrpc: "2.0".to_string(),
+                method: "notifications/files/didChange".to_string(),
+                id: None,
+                params: Some(serde_json::json!({
+                    "uris": uris,
+                    "created": of_kind(ChangeKind::Created),
+                    "changed": of_kind(ChangeKind::Changed),
+                    "deleted": of_kind(ChangeKind::Deleted),
+                })),
+            };
 
-                if let Some(root) = root {
-                    let uri = format!("file:///{}", path.display().to_string().replace('\\', "/"));
-                    paths_by_root.entry(root).or_default().push(uri);
+            // Started on a task; install_backend delivers the held batch
+            if self.config.spawn_on_change && !self.backends.contains(&root) {
+                if let Err(e) = self.start_spawn(root.clone()).await {
+                    warn!("Failed to spawn backend for changes in {}: {}", root.display(), e);
                 }
             }
-            
-            // Send batch notification per root
-            for (root, uris) in paths_by_root {
-                if let Some(backend) = self.backends.get_mut(&root) {
-                    let notification = JsonRpcRequest {
-                        jsonrpc: "2.0".to_string(),
-                        method: "notifications/files/didChange".to_string(),
-                        id: None,
-                        params: Some(serde_json::json!({
-                            "uris": uris
-                        })),
-                    };
+            match self.backends.get_mut(&root) {
+                Some(backend) => {
                     debug!("Sending batch notification with {} uris to {}", uris.len(), root.display());
-                    if let Err(e) = backend.send_notification(notification).await {
+                    if let Err(e) = backend.send_file_changes(notification.clone()).await {
                         warn!("Failed to send throttled notification: {}", e);
                     }
+                    if let Some(held) = self.upgrading.get_mut(&root) {
+                        held.push(notification.clone());
+                    }
+                    // Every instance keeps its own index
+                    for replica in self.replicas.get_mut(&root).into_iter().flatten() {
+                        if let Err(e) = replica.send_file_changes(notification.clone()).await {
+                            warn!("Failed to send throttled notification to another instance: {}", e);
+                        }
+                    }
                 }
+                None => self.hold_undelivered(root, notification),
             }
         }
     }
 
+    /// Keep a batch for a root whose backend isn't running yet
+    fn hold_undelivered(&mut self, root: PathBuf, batch: JsonRpcRequest) {
+        let queue = self.undelivered_changes.entry(root).or_default();
+        if queue.len() == MAX_UNDELIVERED_BATCHES {
+            warn!("Too many undelivered change batches, dropping the oldest");
+            queue.pop_front();
+        }
+        queue.push_back(batch);
+    }
+
     /// Cleanup idle backends and unhealthy backends
     async fn cleanup_idle_backends(&mut self, idle_ttl: Duration) {
+        self.retire_drained_backends().await;
         let now = Instant::now();
         
         // First, collect backends to check
@@ -755,8 +3567,14 @@ impl McpProxy {
             .collect();
 
         let mut roots_to_remove = Vec::new();
+        let mut roots_to_recycle = Vec::new();
 
         for root in roots_to_check {
+            let idle_ttl = self.config
+                .root_config(&root)
+                .and_then(|rc| rc.idle_ttl_seconds)
+                .map(Duration::from_secs)
+                .unwrap_or(idle_ttl);
             if let Some(backend) = self.backends.peek_mut(&root) {
                 // Check health first
                 if !backend.health_check().await {
@@ -765,8 +3583,17 @@ impl McpProxy {
                     continue;
                 }
 
+                // Restart long-lived backends between requests
+                if let Some(reason) = backend.recycle_reason() {
+                    if !backend.has_pending().await && !self.upgrading.contains_key(&root) {
+                        info!("Backend {} has {}, recycling it", root.display(), reason);
+                        roots_to_recycle.push(root.clone());
+                        continue;
+                    }
+                }
+
                 // Check idle timeout
-                if now.duration_since(backend.last_used) > idle_ttl {
+                if now.duration_since(backend.last_used) > idle_ttl && !self.config.pinned_roots.contains(&root) {
                     if !backend.has_pending().await {
                         info!("Backend {} is idle, marking for removal", root.display());
                         roots_to_remove.push(root.clone());
@@ -777,74 +3604,834 @@ impl McpProxy {
             }
         }
 
+        let with_replicas: Vec<PathBuf> = self.backends
+            .iter()
+            .map(|(root, _)| root.clone())
+            .filter(|root| !roots_to_remove.contains(root) && self.config.instances_for(root) > 1)
+            .collect();
+        for root in with_replicas {
+            self.tend_replicas(&root).await;
+        }
+
+        // Started on a task and swapped in by finish_upgrade; the old one serves meanwhile
+        for root in roots_to_recycle {
+            if let Err(e) = self.start_upgrade(&root) {
+                error!("Failed to recycle backend {}: {}", root.display(), e);
+            }
+        }
+
         // Remove marked backends
         for root in roots_to_remove {
             info!("Cleaning up backend: {}", root.display());
-            if let Some(mut backend) = self.backends.pop(&root) {
-                backend.shutdown().await;
+            if let Some(backend) = self.backends.pop(&root) {
+                self.retire(backend);
             }
+            self.shutdown_extra_instances(&root);
+            self.unwatch_root(&root);
+            self.send_tools_changed();
         }
     }
 
-    /// Shutdown all backends
+    /// Shutdown all backends concurrently, killing whatever is left at `shutdown_deadline_seconds`
     async fn shutdown_all_backends(&mut self) {
         info!("Shutting down all backends");
+        let mut tasks = tokio::task::JoinSet::new();
         // Drain all entries from LRU cache
         while let Some((root, mut backend)) = self.backends.pop_lru() {
             info!("Shutting down backend: {}", root.display());
-            backend.shutdown().await;
+            self.unwatch_root(&root);
+            tasks.spawn(async move { backend.shutdown().await });
+        }
+        for (_, replicas) in self.replicas.drain() {
+            for mut replica in replicas {
+                tasks.spawn(async move { replica.shutdown().await });
+            }
+        }
+        for (_, mut canary) in self.canaries.drain() {
+            tasks.spawn(async move { canary.shutdown().await });
+        }
+        while let Some((name, mut server)) = self.servers.pop_first() {
+            info!("Shutting down MCP server: {}", name);
+            tasks.spawn(async move { server.shutdown().await });
+        }
+        for (_, shadow) in self.shadows.drain() {
+            tasks.spawn(async move {
+                if let Some(mut backend) = shadow.lock().await.take() {
+                    backend.shutdown().await;
+                }
+            });
         }
-    }
 
-    /// Convert file URI to path (with URL decoding for special characters)
-    fn uri_to_path(uri: &str) -> Option<PathBuf> {
-        let decoded_uri = percent_decode_str(uri)
-            .decode_utf8()
-            .ok()?;
-        let uri = decoded_uri.as_ref();
-        
-        if uri.starts_with("file:///") {
-            #[cfg(windows)]
-            {
-                // file:///C:/path -> C:/path
-                let path = uri.strip_prefix("file:///")?;
-                Some(PathBuf::from(path.replace('/', "\\")))
-            }
-            #[cfg(not(windows))]
-            {
-                // file:///path -> /path
-                let path = uri.strip_prefix("file://")?;
-                Some(PathBuf::from(path))
-            }
-        } else if uri.starts_with("file://") {
-            let path = uri.strip_prefix("file://")?;
-            Some(PathBuf::from(path))
-        } else {
-            // Assume it's already a path
-            Some(PathBuf::from(uri))
+        // Retired ones may still be on their way out
+        let mut retiring = std::mem::take(&mut self.retiring);
+
+        let deadline = Duration::from_secs(self.config.shutdown_deadline_seconds);
+        let all_stopped = async {
+            while tasks.join_next().await.is_some() {}
+            while retiring.join_next().await.is_some() {}
+        };
+        if tokio::time::timeout(deadline, all_stopped).await.is_err() {
+            warn!("{} backend(s) still stopping after {:?}, killing them", tasks.len() + retiring.len(), deadline);
+            // Aborting a task drops its backend, which kills the process
+            tasks.shutdown().await;
+            retiring.shutdown().await;
         }
     }
 
+    /// Convert file URI to a resolved (canonical) path used for all routing decisions
+    fn resolve_uri(&self, uri: &str) -> Option<PathBuf> {
+        paths::uri_to_path(uri).map(|path| self.path_resolver.resolve(&path))
+    }
+
     /// Get current metrics as a JSON value
-    #[allow(dead_code)]
     pub fn get_metrics(&self) -> serde_json::Value {
         serde_json::json!({
-            "uptime_seconds": self.metrics_start_time.elapsed().as_secs(),
-            "total_requests": self.metrics_total_requests,
-            "total_errors": self.metrics_total_errors,
+            "uptime_seconds": self.metrics.uptime().as_secs(),
+            "total_requests": self.metrics.total_requests(),
+            "total_errors": self.metrics.total_errors(),
             "active_backends": self.backends.len(),
             "max_backends": self.backends.cap().get(),
-            "git_cache_entries": self.git_tracked_cache.len(),
+            "git_cache_entries": self.git_filter_cache.len(),
+            "events_pending": self.event_throttler.as_ref().map(|t| t.pending_count()).unwrap_or(0),
+            "events_suppressed": self.event_throttler.as_ref().map(|t| t.suppressed_count()).unwrap_or(0),
+            "event_storm_roots": self.event_throttler.as_ref().map(|t| t.storm_roots()).unwrap_or(0),
+            "sessions": self.sessions.len(),
+            "resolved_paths_cached": self.path_resolver.cache_len(),
+            "queue_length": self.global_inflight.as_ref().map(|l| l.queue_length()).unwrap_or(0),
+            "queue_peak": self.global_inflight.as_ref().map(|l| l.peak_queue_length()).unwrap_or(0),
+            "queue_rejected": self.global_inflight.as_ref().map(|l| l.rejected_count()).unwrap_or(0),
         })
     }
+}
+
+/// Why a backend was started on a task
+#[derive(Debug, Clone, Copy)]
+enum SpawnPurpose {
+    /// A root's first backend (`spawning`)
+    Root,
+    /// A further instance of a running root (`instances_per_root`)
+    Replica,
+    /// A replacement taking over from a root's backend (`proxy_upgrade_backend`)
+    Replacement,
+    /// Another backend version serving a share of a root's requests (`canary`)
+    Canary,
+    /// One of the extra MCP servers (`servers`); its name stands in for the root
+    Server,
+    /// A restart of a root's instance, by a call task for a retry or for a crash
+    /// (`restart_crashed_backends`); takes over from process `replacing` if that is still the
+    /// instance's
+    Restart { canary: bool, instance: usize, replacing: u64 },
+}
+
+/// A request on its way to a backend
+struct BackendCall {
+    /// As sent to the backend
+    request: JsonRpcRequest,
+    /// As it reached `process_request`, for the response layers (requests sent off to a task)
+    received: Option<JsonRpcRequest>,
+    root: PathBuf,
+    /// Whether it went to the root's canary
+    canary: bool,
+    /// Which of the root's instances it went to (0 for the primary)
+    instance: usize,
+    arrived: Instant,
+    started: Instant,
+    /// Attempts made, retries included
+    attempts: u32,
+    /// Audit entry to finish once answered (requests sent off to a task)
+    audit: Option<(AuditEntry, Instant)>,
+    /// The span it arrived in, so its answer still logs under its trace id
+    span: tracing::Span,
+    kind: CallKind,
+    /// Answers to the requests sent alongside it (`send_off`)
+    gathered: Vec<CallResult>,
+}
+
+/// What a call is for, and so how `finish_call` answers the client
+#[derive(Clone, Copy, PartialEq)]
+enum CallKind {
+    /// A request routed to a root's backend
+    Backend,
+    /// A request for one of the extra MCP servers (`root` holds its name)
+    Server,
+    /// A ping checking the root's backend (`ping_backend`)
+    Ping,
+    /// logging/setLevel, passed on to the running backends alongside
+    SetLevel,
+    /// tools/list, resources/list or prompts/list, merged with the lists gathered alongside it;
+    /// not `routed` when it was answered without the root's backend
+    List { routed: bool },
+}
+
+/// Where a call task gets a call's answer
+enum CallTarget {
+    /// A root's backend, retrying as the policy allows
+    Backend(BackendHandle, Duration, Box<CallRetry>),
+    /// One of the extra MCP servers, which aren't retried
+    Server(BackendHandle, Duration),
+    /// Already answered without a backend
+    Answered(JsonRpcResponse),
+}
+
+impl CallTarget {
+    /// The answer to `call`, counting the attempts made
+    async fn answer(self, call: &mut BackendCall) -> Result<JsonRpcResponse, ProxyError> {
+        match self {
+            CallTarget::Backend(handle, timeout, retry) => {
+                let (result, attempts) = retry.send(call, handle, timeout).await;
+                call.attempts = attempts;
+                result
+            }
+            CallTarget::Server(handle, timeout) => handle.send_request_within(call.request.clone(), timeout).await,
+            CallTarget::Answered(response) => Ok(response),
+        }
+    }
+}
+
+/// Where `prepare_call` left a request
+enum Routed {
+    /// Answered without the backend
+    Answered(JsonRpcResponse),
+    /// Ready to send through the handle within the timeout, once it has a global slot
+    Call(Box<BackendCall>, BackendHandle, Duration),
+    /// Waiting in `spawning` for its root's backend to start
+    Queued,
+}
+
+type CallResult = (Box<BackendCall>, Result<JsonRpcResponse, ProxyError>);
+
+/// Wait for a global inflight slot (`max_inflight_global`), if requests are limited
+/// Fails with `ProxyError::QueueFull` when the overflow policy turns the request away.
+async fn acquire_slot(limiter: Option<Arc<InflightLimiter>>) -> Result<Option<OwnedSemaphorePermit>, ProxyError> {
+    match limiter {
+        Some(limiter) => limiter.acquire().await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Get a call's answer, waiting for a global slot first when it goes to a root's backend, with
+/// the requests in `gather` answered alongside it (for `finish_call`)
+async fn run_call(
+    mut call: Box<BackendCall>,
+    target: CallTarget,
+    gather: Vec<(Box<BackendCall>, CallTarget)>,
+    limiter: Option<Arc<InflightLimiter>>,
+) -> CallResult {
+    let mut others = tokio::task::JoinSet::new();
+    for (mut other, target) in gather {
+        let span = other.span.clone();
+        others.spawn(
+            async move {
+                let result = target.answer(&mut other).await;
+                (other, result)
+            }
+            .instrument(span),
+        );
+    }
+    // Waiting for a global slot here leaves the loop free for pings and cancellations
+    let slot = match target {
+        CallTarget::Backend(..) => acquire_slot(limiter).await,
+        _ => Ok(None),
+    };
+    let result = match slot {
+        Ok(permit) => {
+            call.started = Instant::now();
+            let result = target.answer(&mut call).await;
+            // The global slot is free once the backend has answered, whenever the loop gets to it
+            drop(permit);
+            result
+        }
+        Err(e) => Err(e),
+    };
+    call.gathered = others.join_all().await;
+    (call, result)
+}
+
+/// A message waiting for its root's backend to start
+struct QueuedMessage {
+    request: JsonRpcRequest,
+    /// The span it arrived in, so delivering it still logs under its trace id
+    span: tracing::Span,
+    arrived: Instant,
+}
+
+impl QueuedMessage {
+    fn new(request: JsonRpcRequest, arrived: Instant) -> Self {
+        Self { request, span: tracing::Span::current(), arrived }
+    }
+}
+
+/// A proxy serving one client over an in-memory pipe on a task (`loadtest`, `repl`)
+pub struct InProcessProxy {
+    stop: tokio::sync::oneshot::Sender<()>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl InProcessProxy {
+    /// Start serving and return the client's end of the pipe
+    pub fn start(mut proxy: McpProxy) -> (Self, BackendReader, BackendWriter) {
+        let (client, server) = tokio::io::duplex(1 << 20);
+        let (server_reader, server_writer) = tokio::io::split(server);
+        let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+        let task = tokio::spawn(async move {
+            let stop = async {
+                let _ = stop_rx.await;
+            };
+            if let Err(e) = proxy.serve(server_reader, server_writer, stop).await {
+                eprintln!("In-process proxy failed: {}", e);
+            }
+        });
+        let (client_reader, client_writer) = tokio::io::split(client);
+        (Self { stop: stop_tx, task }, Box::new(client_reader), Box::new(client_writer))
+    }
+
+    /// Stop serving, waiting for the backends to shut down
+    pub async fn stop(self) {
+        let _ = self.stop.send(());
+        let _ = self.task.await;
+    }
+}
+
+/// Backend started on a task, with its root and purpose
+type SpawnResult = (PathBuf, SpawnPurpose, Result<BackendInstance, ProxyError>);
+
+/// Everything a backend needs to start, so it can start on a task
+#[derive(Clone)]
+struct SpawnContext {
+    config: Config,
+    init_params: serde_json::Value,
+    /// Whether the client has sent `notifications/initialized`
+    client_ready: bool,
+    stderr_tx: Option<StderrSink>,
+    event_tx: Option<EventSink>,
+    #[cfg(windows)]
+    job_object: Option<Arc<JobObject>>,
+    #[cfg(unix)]
+    process_group: Option<Arc<ProcessGroup>>,
+}
+
+impl SpawnContext {
+    /// Spawn the backend and run its handshake
+    async fn spawn(self, root: PathBuf) -> Result<BackendInstance, ProxyError> {
+        #[cfg(windows)]
+        let mut backend = BackendInstance::spawn(&self.config, root, self.job_object).await?;
+        #[cfg(unix)]
+        let mut backend = BackendInstance::spawn(&self.config, root, self.process_group).await?;
+
+        if let Some(sink) = self.stderr_tx {
+            backend.forward_stderr(sink);
+        }
+        if let Some(sink) = self.event_tx {
+            backend.forward_events(sink);
+        }
+        backend.initialize(self.init_params, self.client_ready).await;
+        Ok(backend)
+    }
+
+    /// Spawn one of the extra MCP servers and run its handshake (its stderr isn't relayed as
+    /// auggie's)
+    async fn spawn_server(self, name: &str, server: &ServerConfig) -> Result<BackendInstance, ProxyError> {
+        #[cfg(windows)]
+        let mut backend = BackendInstance::spawn_server(&self.config, name, server, self.job_object)?;
+        #[cfg(unix)]
+        let mut backend = BackendInstance::spawn_server(&self.config, name, server, self.process_group)?;
+        if let Some(sink) = self.event_tx {
+            backend.forward_events(sink);
+        }
+        backend.initialize(self.init_params, self.client_ready).await;
+        Ok(backend)
+    }
+}
+
+/// What a call task needs to retry its request as the retry policy allows
+struct CallRetry {
+    policy: Arc<RetryPolicy>,
+    /// Starts a backend in place of one that failed, for the run loop to take over (`None`
+    /// when the request isn't retried)
+    restart: Option<(SpawnContext, mpsc::UnboundedSender<SpawnResult>)>,
+}
+
+impl CallRetry {
+    /// Send a call's request through `handle`, retrying on failure (crash recovery) as the
+    /// policy allows, off the run loop: a retry goes to a process restarted here and handed
+    /// to the loop (`SpawnPurpose::Restart`)
+    /// Also returns how many attempts were made.
+    async fn send(
+        self,
+        call: &BackendCall,
+        mut handle: BackendHandle,
+        timeout: Duration,
+    ) -> (Result<JsonRpcResponse, ProxyError>, u32) {
+        let max_retries = self.policy.max_retries(&call.request);
+        let mut result = handle.send_request_within(call.request.clone(), timeout).await;
+        for attempt in 1..=max_retries {
+            let e = match result {
+                Ok(response) => return (Ok(response), attempt),
+                Err(e) => e,
+            };
+            let recycled = handle.is_hung();
+            if recycled {
+                handle.fail_hung().await;
+            }
+            if !self.policy.should_retry(&e, recycled) {
+                return (Err(e), attempt);
+            }
+            warn!("Request failed (attempt {}/{}): {}, will retry", attempt, max_retries + 1, e);
+            tokio::time::sleep(self.policy.backoff(attempt)).await;
+            result = match self.restart(call, &handle).await {
+                Ok(restarted) => {
+                    handle = restarted;
+                    handle.send_request_within(call.request.clone(), timeout).await
+                }
+                Err(e) => {
+                    error!("Failed to restart backend: {}", e);
+                    Err(e)
+                }
+            };
+        }
+        (result, max_retries + 1)
+    }
+
+    /// A handle to the process restarted in place of the one `failed` talks to; requests that
+    /// failed on the same process share one restart
+    async fn restart(&self, call: &BackendCall, failed: &BackendHandle) -> Result<BackendHandle, ProxyError> {
+        let Some((ref context, ref spawn_tx)) = self.restart else {
+            return Err(ProxyError::BackendUnavailable("Backend can't be restarted".to_string()));
+        };
+        failed
+            .replace_with(|| async {
+                warn!("Restarting the backend for {} for a retry", call.root.display());
+                let mut backend = context.clone().spawn(call.root.clone()).await?;
+                let handle = backend.handle()?;
+                let purpose = SpawnPurpose::Restart { canary: call.canary, instance: call.instance, replacing: failed.serial() };
+                // Dropped (and stopped) if the run loop is gone
+                let _ = spawn_tx.send((call.root.clone(), purpose, Ok(backend)));
+                Ok(handle)
+            })
+            .await
+    }
+}
+
+/// prompts/list with the extra MCP servers' prompts
+fn merge_prompts(request: &JsonRpcRequest, mut response: JsonRpcResponse, servers: Vec<CallResult>) -> JsonRpcResponse {
+    // auggie has no prompts
+    if response.error.take().is_some() {
+        response.result = Some(serde_json::json!({ "prompts": [] }));
+    }
+    if request.get_param_path("cursor").is_some() {
+        return response;
+    }
+    let mut extra = Vec::new();
+    for (server, mut prompts) in server_lists("prompts/list", "prompts", servers) {
+        servers::prefix_names(&mut prompts, &server);
+        extra.extend(prompts);
+    }
+    if let Some(list) = response.result.as_mut()
+        .and_then(|r| r.get_mut("prompts"))
+        .and_then(|r| r.as_array_mut())
+    {
+        list.extend(extra);
+    }
+    response
+}
+
+/// The entries under `key` in the extra MCP servers' answers to `method` (a list method)
+fn server_lists(method: &str, key: &str, answers: Vec<CallResult>) -> Vec<(String, Vec<serde_json::Value>)> {
+    let mut lists = Vec::new();
+    for (call, answer) in answers {
+        let name = call.root.to_string_lossy().to_string();
+        match answer {
+            Ok(JsonRpcResponse { result: Some(mut result), .. }) => {
+                lists.push((name, servers::take_items(&mut result, key)));
+            }
+            // Servers without prompts or resources say so
+            Ok(JsonRpcResponse { error, .. }) => {
+                debug!("MCP server {} failed {}: {:?}", name, method, error.map(|e| e.message));
+            }
+            Err(e) => warn!("MCP server {} failed {}: {}", name, method, e),
+        }
+    }
+    lists
+}
+
+/// Error answering a ping the root's backend didn't answer (`ping_backend`)
+fn ping_failed(request: &JsonRpcRequest, root: &Path, e: ProxyError) -> JsonRpcResponse {
+    warn!("Backend {} did not answer ping: {}", root.display(), e);
+    JsonRpcResponse::error(
+        request.id.clone(),
+        JsonRpcError::new(ERROR_BACKEND_UNAVAILABLE, format!("Backend did not answer ping: {}", e)),
+    )
+}
+
+/// Error answering a request whose backend couldn't be started
+fn backend_error(e: &ProxyError) -> JsonRpcError {
+    // Anything without a code of its own went wrong while starting the backend
+    let code = match e.code() {
+        ERROR_INTERNAL_ERROR => ERROR_BACKEND_SPAWN_FAILED,
+        code => code,
+    };
+    JsonRpcError::new(code, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool_call(meta: serde_json::Value) -> JsonRpcRequest {
+        JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "tools/call".to_string(),
+            id: Some(JsonRpcId::Number(1)),
+            params: Some(serde_json::json!({ "name": "codebase-retrieval", "_meta": meta })),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_requested_root_confined() {
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-confine-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        let mut config = <Config as clap::Parser>::parse_from(["mcp-proxy"]);
+        config.default_root = Some(dir.clone());
+        config.policy.confine_paths = true;
+        let proxy = McpProxy::new(config).unwrap();
+
+        let mut request = tool_call(serde_json::json!({ "workspaceRoot": dir.join("sub") }));
+        assert!(proxy.take_requested_root(&mut request).is_none());
+        let root = request.get_param_path(&format!("_meta.{}", routing::REQUESTED_ROOT_META)).cloned();
+        assert_eq!(root, Some(proxy.path_resolver.resolve(&dir.join("sub")).to_string_lossy().into()));
+
+        let mut request = tool_call(serde_json::json!({ "workspaceRoot": "/elsewhere" }));
+        let Some(Some(response)) = proxy.take_requested_root(&mut request) else { panic!("root outside accepted") };
+        assert_eq!(response.error.unwrap().code, ERROR_INVALID_PARAMS);
+        // Climbing out of a known root is outside it too
+        let mut request = tool_call(serde_json::json!({ "targetRoot": dir.join("sub/../../elsewhere") }));
+        assert!(matches!(proxy.take_requested_root(&mut request), Some(Some(_))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Config running a shell loop in place of auggie for a default root of `dir`: each process
+    /// start is counted in `dir/starts`, and every request runs `on_request` (with the request
+    /// in `$line`) and then gets an empty result
+    #[cfg(unix)]
+    fn shell_backend(dir: &Path, on_request: &str) -> Config {
+        std::fs::create_dir_all(dir).unwrap();
+        let script = dir.join("backend.sh");
+        let body = r#"echo started >> '{starts}'
+while read -r line; do
+  id=$(printf '%s' "$line" | sed -nE 's/.*"id":("[^"]*"|[0-9]+).*/\1/p')
+  [ -n "$id" ] || continue
+  {on_request}
+  printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id"
+done
+"#;
+        let starts = dir.join("starts");
+        std::fs::write(&script, body.replace("{starts}", &starts.to_string_lossy()).replace("{on_request}", on_request)).unwrap();
+        let mut config = <Config as clap::Parser>::parse_from([
+            "mcp-proxy",
+            "--node",
+            "/bin/sh",
+            "--auggie-entry",
+            script.to_str().unwrap(),
+        ]);
+        config.default_root = Some(dir.to_path_buf());
+        config
+    }
+
+    /// A client of a proxy serving it on a task over an in-memory pipe
+    struct TestClient {
+        session: tokio::task::JoinHandle<Result<(), ProxyError>>,
+        lines: tokio::io::Lines<BufReader<tokio::io::ReadHalf<tokio::io::DuplexStream>>>,
+        writer: tokio::io::WriteHalf<tokio::io::DuplexStream>,
+    }
+
+    impl TestClient {
+        fn start(mut proxy: McpProxy) -> Self {
+            let (client, server) = tokio::io::duplex(64 * 1024);
+            let (input, output) = tokio::io::split(server);
+            let session = tokio::spawn(async move { proxy.serve(input, output, std::future::pending()).await });
+            let (reader, writer) = tokio::io::split(client);
+            Self { session, lines: BufReader::new(reader).lines(), writer }
+        }
+
+        async fn send(&mut self, message: serde_json::Value) {
+            self.writer.write_all(format!("{}\n", message).as_bytes()).await.unwrap();
+        }
+
+        async fn request(&mut self, id: impl Into<serde_json::Value>, method: &str, params: serde_json::Value) {
+            self.send(serde_json::json!({ "jsonrpc": "2.0", "id": id.into(), "method": method, "params": params })).await;
+        }
+
+        /// Run the handshake
+        async fn initialize(&mut self) {
+            self.request(0, "initialize", serde_json::json!({})).await;
+            self.send(serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).await;
+            assert_eq!(self.next_response().await["id"], 0);
+        }
+
+        /// The next message with an id, skipping notifications
+        async fn next_response(&mut self) -> serde_json::Value {
+            loop {
+                let line = self.lines.next_line().await.unwrap().unwrap();
+                let message: serde_json::Value = serde_json::from_str(&line).unwrap();
+                if message.get("id").is_some() {
+                    return message;
+                }
+            }
+        }
+
+        /// Close the pipe and wait for the session to end
+        async fn finish(mut self) {
+            self.writer.shutdown().await.unwrap();
+            self.session.await.unwrap().unwrap();
+        }
+    }
+
+    fn retrieval() -> serde_json::Value {
+        serde_json::json!({ "name": "codebase-retrieval", "arguments": {} })
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_global_queue_off_the_loop() {
+        // Answers tools/call after a while, one at a time
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-queue-{}", std::process::id()));
+        let mut config = shell_backend(&dir, r#"case "$line" in *'"method":"tools/call"'*) sleep 0.3;; esac"#);
+        config.max_inflight_global = 1;
+        let limiter = queue::global_limiter(&config);
+        let mut proxy = McpProxy::new(config).unwrap();
+        proxy.set_inflight_limiter(limiter.clone());
+        let limiter = limiter.unwrap();
+
+        let mut client = TestClient::start(proxy);
+        client.initialize().await;
+        for id in 1..=4 {
+            client.request(id, "tools/call", retrieval()).await;
+        }
+        // One call holds the only slot, the rest wait behind it
+        for _ in 0..200 {
+            if limiter.queue_length() > 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(limiter.queue_length() > 1);
+
+        // The loop still reads and answers while they wait
+        client.request("ping", "ping", serde_json::json!({})).await;
+        let mut answered = Vec::new();
+        while answered.len() < 5 {
+            let id = client.next_response().await["id"].clone();
+            if id == "ping" {
+                assert!(limiter.queue_length() > 0, "ping answered only after the queue drained");
+            }
+            answered.push(id);
+        }
+        assert!(answered.contains(&serde_json::json!("ping")));
+        assert_eq!(limiter.peak_queue_length(), 3);
+
+        client.finish().await;
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_retry_off_the_loop() {
+        // The first process to get a tools/call exits a while after instead of answering
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-retry-{}", std::process::id()));
+        let crashed = dir.join("crashed");
+        let on_request = format!(
+            r#"case "$line" in *'"method":"tools/call"'*) [ -e '{0}' ] || {{ sleep 0.2; touch '{0}'; exit 1; }};; esac"#,
+            crashed.display()
+        );
+        let mut config = shell_backend(&dir, &on_request);
+        config.retry.backoff_ms = 300;
+        config.retry.jitter = false;
+
+        let mut client = TestClient::start(McpProxy::new(config).unwrap());
+        client.initialize().await;
+        for id in 1..=2 {
+            client.request(id, "tools/call", retrieval()).await;
+        }
+        while !crashed.exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // Both calls wait out the backoff and the restart on their task, not on the loop
+        client.request("ping", "ping", serde_json::json!({})).await;
+        assert_eq!(client.next_response().await["id"], "ping");
+        let mut answered = Vec::new();
+        for _ in 1..=2 {
+            let response = client.next_response().await;
+            assert!(response.get("error").is_none(), "{}", response);
+            answered.push(response["id"].clone());
+        }
+        answered.sort_by_key(|id| id.as_i64());
+        assert_eq!(answered, vec![1, 2]);
+
+        // Restarted once for both, and the restarted backend serves the root from now on
+        client.request(3, "tools/call", retrieval()).await;
+        assert!(client.next_response().await.get("error").is_none());
+        assert_eq!(std::fs::read_to_string(dir.join("starts")).unwrap().lines().count(), 2);
+
+        client.finish().await;
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_list_retry_off_the_loop() {
+        // The first process to get a tools/list exits a while after instead of answering
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-list-{}", std::process::id()));
+        let crashed = dir.join("crashed");
+        let on_request = format!(
+            r#"case "$line" in *'"method":"tools/list"'*) [ -e '{0}' ] || {{ sleep 0.2; touch '{0}'; exit 1; }}; printf '{{"jsonrpc":"2.0","id":%s,"result":{{"tools":[]}}}}\n' "$id"; continue;; esac"#,
+            crashed.display()
+        );
+        let mut config = shell_backend(&dir, &on_request);
+        config.virtual_tools = true;
+        config.retry.backoff_ms = 300;
+        config.retry.jitter = false;
+
+        let mut client = TestClient::start(McpProxy::new(config).unwrap());
+        client.initialize().await;
+        client.request(1, "tools/list", serde_json::json!({})).await;
+        while !crashed.exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // The list waits out the backoff and the restart on its task, not on the loop
+        let sent = Instant::now();
+        client.request("ping", "ping", serde_json::json!({})).await;
+        assert_eq!(client.next_response().await["id"], "ping");
+        assert!(sent.elapsed() < Duration::from_millis(250));
+        let response = client.next_response().await;
+        assert_eq!(response["id"], 1);
+        let tools = response["result"]["tools"].as_array().unwrap();
+        assert!(tools.iter().any(|tool| tool["name"] == "proxy_status"), "{}", response);
+        assert_eq!(std::fs::read_to_string(dir.join("starts")).unwrap().lines().count(), 2);
+
+        client.finish().await;
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_crash_restart_off_the_loop() {
+        // The first process exits on a tools/call; the restarted one is slow to initialize
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-crash-restart-{}", std::process::id()));
+        let crashed = dir.join("crashed");
+        let on_request = format!(
+            r#"case "$line" in *'"method":"tools/call"'*) [ -e '{0}' ] || {{ touch '{0}'; exit 1; }};; *'"method":"initialize"'*) [ -e '{0}' ] && sleep 0.5;; esac"#,
+            crashed.display()
+        );
+        let mut config = shell_backend(&dir, &on_request);
+        config.retry.max_retries = 0;
+        config.restart_crashed_backends = true;
+
+        let mut client = TestClient::start(McpProxy::new(config).unwrap());
+        client.initialize().await;
+        client.request(1, "tools/call", retrieval()).await;
+        assert!(client.next_response().await.get("error").is_some());
+
+        // The call waits for the restart while the loop keeps answering
+        client.request(2, "tools/call", retrieval()).await;
+        let sent = Instant::now();
+        client.request("ping", "ping", serde_json::json!({})).await;
+        assert_eq!(client.next_response().await["id"], "ping");
+        assert!(sent.elapsed() < Duration::from_millis(300));
+        let response = client.next_response().await;
+        assert_eq!(response["id"], 2);
+        assert!(response.get("error").is_none(), "{}", response);
+        assert_eq!(std::fs::read_to_string(dir.join("starts")).unwrap().lines().count(), 2);
+
+        client.finish().await;
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_restart_off_the_loop() {
+        // Processes after the first are slow to initialize
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-restart-{}", std::process::id()));
+        let starts = dir.join("starts");
+        let on_request = format!(
+            r#"case "$line" in *'"method":"initialize"'*) [ "$(wc -l < '{0}')" -gt 1 ] && sleep 0.5;; esac"#,
+            starts.display()
+        );
+        let mut config = shell_backend(&dir, &on_request);
+        config.virtual_tools = true;
+
+        let mut client = TestClient::start(McpProxy::new(config).unwrap());
+        client.initialize().await;
+        client.request(1, "tools/call", retrieval()).await;
+        assert!(client.next_response().await.get("error").is_none());
+        let restart = serde_json::json!({ "name": "proxy_restart_backend", "arguments": {} });
+        client.request(2, "tools/call", restart).await;
+        assert_eq!(client.next_response().await["result"]["isError"], false);
+
+        // The next call waits for the new process while the loop keeps answering
+        client.request(3, "tools/call", retrieval()).await;
+        let sent = Instant::now();
+        client.request("ping", "ping", serde_json::json!({})).await;
+        assert_eq!(client.next_response().await["id"], "ping");
+        assert!(sent.elapsed() < Duration::from_millis(300));
+        let response = client.next_response().await;
+        assert_eq!(response["id"], 3);
+        assert!(response.get("error").is_none(), "{}", response);
+        assert_eq!(std::fs::read_to_string(&starts).unwrap().lines().count(), 2);
+
+        client.finish().await;
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_idle_exit_waits_for_calls() {
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-idle-{}", std::process::id()));
+        let config = shell_backend(&dir, r#"case "$line" in *'"method":"tools/call"'*) sleep 0.6;; esac"#);
+        let mut proxy = McpProxy::new(config).unwrap();
+        proxy.exit_after_idle = Some(Duration::from_millis(200));
+
+        let mut client = TestClient::start(proxy);
+        client.initialize().await;
+        client.request(1, "tools/call", retrieval()).await;
+        // Idle for longer than the limit, but the call is still in flight
+        let response = client.next_response().await;
+        assert_eq!(response["id"], 1);
+        assert!(response.get("error").is_none(), "{}", response);
 
-    /// Increment request counter
-    fn record_request(&mut self) {
-        self.metrics_total_requests += 1;
+        // With nothing outstanding, the session ends on its own
+        let ended = tokio::time::timeout(Duration::from_secs(2), client.session).await;
+        assert!(matches!(ended, Ok(Ok(Ok(())))));
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
-    /// Increment error counter
-    fn record_error(&mut self) {
-        self.metrics_total_errors += 1;
+    #[tokio::test]
+    async fn test_switch_root_confined() {
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-switch-{}", std::process::id()));
+        for sub in ["work/api", "scratch", "other"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        let mut config = <Config as clap::Parser>::parse_from(["mcp-proxy"]);
+        config.default_root = Some(dir.join("work"));
+        config.policy.confine_paths = true;
+        config.policy.allowed_paths = vec![dir.join("scratch")];
+        let mut proxy = McpProxy::new(config).unwrap();
+        let switch = |root: PathBuf| JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "tools/call".to_string(),
+            id: Some(JsonRpcId::Number(1)),
+            params: Some(serde_json::json!({ "name": "proxy_switch_root", "arguments": { "root": root } })),
+        };
+        let is_error = |response: JsonRpcResponse| response.result.unwrap()["isError"] == true;
+
+        assert!(is_error(proxy.call_virtual_tool(&switch(dir.join("other"))).await));
+        assert_eq!(proxy.default_root, Some(proxy.path_resolver.resolve(&dir.join("work"))));
+        assert!(!is_error(proxy.call_virtual_tool(&switch(dir.join("work/api"))).await));
+        assert!(!is_error(proxy.call_virtual_tool(&switch(dir.join("scratch"))).await));
+        assert_eq!(proxy.default_root, Some(proxy.path_resolver.resolve(&dir.join("scratch"))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
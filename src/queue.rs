@@ -0,0 +1,239 @@
+//! Bounded wait queue in front of the global inflight limiter
+//! Keeps the number of waiting requests bounded so the proxy degrades predictably under load
+
+use crate::config::Config;
+use crate::error::ProxyError;
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
+use tracing::{debug, info, warn};
+
+/// What to do when a request arrives and the wait queue is already full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverflowPolicy {
+    /// Reject the request that just arrived
+    #[default]
+    RejectNewest,
+    /// Reject the request that has been waiting the longest and queue the new one
+    RejectOldest,
+    /// Wait anyway (unbounded queue)
+    Block,
+}
+
+/// The limiter for `max_inflight_global` (`None` when unlimited)
+pub fn global_limiter(config: &Config) -> Option<Arc<InflightLimiter>> {
+    if config.max_inflight_global == 0 {
+        return None;
+    }
+    info!(
+        "Global inflight limit: {} (queue depth: {}, overflow: {:?})",
+        config.max_inflight_global, config.max_queue_depth, config.queue_overflow
+    );
+    Some(Arc::new(InflightLimiter::new(
+        config.max_inflight_global,
+        config.max_queue_depth,
+        config.queue_overflow,
+    )))
+}
+
+/// A request waiting for an inflight slot
+struct Waiter {
+    id: u64,
+    /// Signalled when this waiter is pushed out by the reject-oldest policy
+    evict_tx: oneshot::Sender<()>,
+}
+
+/// Global inflight limiter with a bounded FIFO wait queue
+pub struct InflightLimiter {
+    semaphore: Arc<Semaphore>,
+    max_queue_depth: usize,
+    overflow: OverflowPolicy,
+    waiters: Mutex<VecDeque<Waiter>>,
+    next_waiter_id: AtomicU64,
+    /// Metrics: highest queue length observed
+    peak_queue_length: AtomicUsize,
+    /// Metrics: requests rejected because the queue was full
+    rejected: AtomicU64,
+}
+
+impl InflightLimiter {
+    pub fn new(max_inflight: usize, max_queue_depth: usize, overflow: OverflowPolicy) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(max_inflight)),
+            max_queue_depth,
+            overflow,
+            waiters: Mutex::new(VecDeque::new()),
+            next_waiter_id: AtomicU64::new(1),
+            peak_queue_length: AtomicUsize::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Acquire an inflight slot, waiting in the queue if none is free
+    /// Returns `ProxyError::QueueFull` when the overflow policy rejects this request
+    pub async fn acquire(&self) -> Result<OwnedSemaphorePermit, ProxyError> {
+        // Fast path: a slot is free, no need to queue
+        if let Ok(permit) = self.semaphore.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+
+        let (id, evict_rx) = self.enqueue()?;
+
+        let result = tokio::select! {
+            permit = self.semaphore.clone().acquire_owned() => permit.map_err(|_| {
+                ProxyError::BackendUnavailable("Global inflight limiter closed".to_string())
+            }),
+            _ = evict_rx => Err(ProxyError::QueueFull(
+                "Request was dropped from the wait queue in favour of a newer request".to_string(),
+            )),
+        };
+
+        self.dequeue(id);
+        result
+    }
+
+    /// Register a new waiter, applying the overflow policy if the queue is full
+    fn enqueue(&self) -> Result<(u64, oneshot::Receiver<()>), ProxyError> {
+        let mut waiters = self.waiters.lock().unwrap_or_else(|e| e.into_inner());
+
+        if waiters.len() >= self.max_queue_depth {
+            match self.overflow {
+                OverflowPolicy::Block => {
+                    debug!("Wait queue full ({}), blocking anyway", waiters.len());
+                }
+                OverflowPolicy::RejectOldest if !waiters.is_empty() => {
+                    if let Some(oldest) = waiters.pop_front() {
+                        warn!("Wait queue full, rejecting oldest waiting request");
+                        let _ = oldest.evict_tx.send(());
+                        self.rejected.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                _ => {
+                    warn!("Wait queue full ({}), rejecting new request", waiters.len());
+                    self.rejected.fetch_add(1, Ordering::Relaxed);
+                    return Err(ProxyError::QueueFull(format!(
+                        "Too many requests waiting (max queue depth: {})",
+                        self.max_queue_depth
+                    )));
+                }
+            }
+        }
+
+        let id = self.next_waiter_id.fetch_add(1, Ordering::Relaxed);
+        let (evict_tx, evict_rx) = oneshot::channel();
+        waiters.push_back(Waiter { id, evict_tx });
+        self.peak_queue_length.fetch_max(waiters.len(), Ordering::Relaxed);
+
+        Ok((id, evict_rx))
+    }
+
+    /// Remove a waiter once it got a slot or gave up (no-op if already evicted)
+    fn dequeue(&self, id: u64) {
+        let mut waiters = self.waiters.lock().unwrap_or_else(|e| e.into_inner());
+        waiters.retain(|w| w.id != id);
+    }
+
+    /// Current number of waiting requests
+    pub fn queue_length(&self) -> usize {
+        self.waiters.lock().map(|w| w.len()).unwrap_or(0)
+    }
+
+    /// Highest number of waiting requests observed
+    pub fn peak_queue_length(&self) -> usize {
+        self.peak_queue_length.load(Ordering::Relaxed)
+    }
+
+    /// Total requests rejected by the overflow policy
+    pub fn rejected_count(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    async fn wait_for_queue_length(limiter: &InflightLimiter, len: usize) {
+        for _ in 0..100 {
+            if limiter.queue_length() == len {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!("queue never reached length {}", len);
+    }
+
+    #[tokio::test]
+    async fn test_fast_path_does_not_queue() {
+        let limiter = InflightLimiter::new(2, 0, OverflowPolicy::RejectNewest);
+        let _a = limiter.acquire().await.unwrap();
+        let _b = limiter.acquire().await.unwrap();
+        assert_eq!(limiter.queue_length(), 0);
+        assert_eq!(limiter.rejected_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_reject_newest_when_full() {
+        let limiter = Arc::new(InflightLimiter::new(1, 1, OverflowPolicy::RejectNewest));
+        let held = limiter.acquire().await.unwrap();
+
+        let waiter = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire().await.map(|_| ()) })
+        };
+        wait_for_queue_length(&limiter, 1).await;
+
+        let rejected = limiter.acquire().await;
+        assert!(matches!(rejected, Err(ProxyError::QueueFull(_))));
+        assert_eq!(limiter.rejected_count(), 1);
+
+        drop(held);
+        assert!(waiter.await.unwrap().is_ok());
+        assert_eq!(limiter.queue_length(), 0);
+        assert_eq!(limiter.peak_queue_length(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reject_oldest_when_full() {
+        let limiter = Arc::new(InflightLimiter::new(1, 1, OverflowPolicy::RejectOldest));
+        let held = limiter.acquire().await.unwrap();
+
+        let oldest = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire().await.map(|_| ()) })
+        };
+        wait_for_queue_length(&limiter, 1).await;
+
+        let newest = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire().await.map(|_| ()) })
+        };
+
+        assert!(matches!(oldest.await.unwrap(), Err(ProxyError::QueueFull(_))));
+        drop(held);
+        assert!(newest.await.unwrap().is_ok());
+        assert_eq!(limiter.rejected_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_block_ignores_depth() {
+        let limiter = Arc::new(InflightLimiter::new(1, 0, OverflowPolicy::Block));
+        let held = limiter.acquire().await.unwrap();
+
+        let waiter = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire().await.map(|_| ()) })
+        };
+        wait_for_queue_length(&limiter, 1).await;
+
+        drop(held);
+        assert!(waiter.await.unwrap().is_ok());
+        assert_eq!(limiter.rejected_count(), 0);
+    }
+}
@@ -0,0 +1,214 @@
+//! Secrets redaction for logs and error data
+//! Masks configured field names and regex matches before message content leaves the proxy
+
+use crate::jsonrpc::{JsonRpcRequest, JsonRpcResponse};
+use crate::middleware::Middleware;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use tracing::warn;
+
+/// Replacement for redacted content
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Field names always redacted (matched case-insensitively, ignoring `-` and `_`)
+const DEFAULT_FIELDS: &[&str] = &[
+    "token", "accesstoken", "refreshtoken", "apikey", "apitoken", "authorization",
+    "password", "passwd", "secret", "clientsecret", "cookie", "privatekey",
+];
+
+/// Patterns always redacted inside string values
+const DEFAULT_PATTERNS: &[&str] = &[
+    r"(?i)bearer\s+[a-z0-9._~+/=-]+",
+    r"\b(?:sk|pk|ghp|gho|ghs|xox[abprs])[-_][A-Za-z0-9_-]{16,}",
+];
+
+/// `redaction` section of the config file
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(default)]
+pub struct RedactionConfig {
+    /// Redact at all (disable only for local debugging)
+    pub enabled: bool,
+    /// Extra field names whose values are masked (e.g. `"content"` to hide code)
+    pub fields: Vec<String>,
+    /// Extra regex patterns masked inside string values
+    pub patterns: Vec<String>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            fields: Vec::new(),
+            patterns: Vec::new(),
+        }
+    }
+}
+
+/// Compiled redaction rules
+pub struct Redactor {
+    enabled: bool,
+    fields: HashSet<String>,
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    /// Compile rules from config on top of the built-in defaults, skipping invalid patterns
+    pub fn new(config: &RedactionConfig) -> Self {
+        let fields = DEFAULT_FIELDS
+            .iter()
+            .map(|f| f.to_string())
+            .chain(config.fields.iter().map(|f| normalize_field(f)))
+            .collect();
+        let patterns = DEFAULT_PATTERNS
+            .iter()
+            .map(|p| p.to_string())
+            .chain(config.patterns.iter().cloned())
+            .filter_map(|p| match Regex::new(&p) {
+                Ok(re) => Some(re),
+                Err(e) => {
+                    warn!("Ignoring invalid redaction pattern {:?}: {}", p, e);
+                    None
+                }
+            })
+            .collect();
+        Self {
+            enabled: config.enabled,
+            fields,
+            patterns,
+        }
+    }
+
+    /// Redact a JSON value in place
+    pub fn redact_value(&self, value: &mut Value) {
+        if !self.enabled {
+            return;
+        }
+        match value {
+            Value::Object(map) => {
+                for (key, v) in map.iter_mut() {
+                    if self.fields.contains(&normalize_field(key)) && !v.is_null() {
+                        *v = Value::String(REDACTED.to_string());
+                    } else {
+                        self.redact_value(v);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|v| self.redact_value(v)),
+            Value::String(s) => {
+                if let Cow::Owned(redacted) = self.redact_patterns(s) {
+                    *s = redacted;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Redact a raw message for logging (JSON-aware when it parses, patterns only otherwise)
+    pub fn redact_str<'a>(&self, message: &'a str) -> Cow<'a, str> {
+        if !self.enabled {
+            return Cow::Borrowed(message);
+        }
+        match serde_json::from_str::<Value>(message) {
+            Ok(mut value) => {
+                self.redact_value(&mut value);
+                Cow::Owned(value.to_string())
+            }
+            Err(_) => self.redact_patterns(message),
+        }
+    }
+
+    fn redact_patterns<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        let mut result = Cow::Borrowed(s);
+        for re in &self.patterns {
+            if let Cow::Owned(replaced) = re.replace_all(&result, REDACTED) {
+                result = Cow::Owned(replaced);
+            }
+        }
+        result
+    }
+}
+
+/// Lowercase and drop `-`/`_` so `api_key`, `apiKey` and `API-KEY` compare equal
+fn normalize_field(field: &str) -> String {
+    field.chars().filter(|c| *c != '_' && *c != '-').flat_map(char::to_lowercase).collect()
+}
+
+static GLOBAL: OnceLock<Redactor> = OnceLock::new();
+
+/// Install the process-wide redactor (first call wins)
+pub fn init(config: &RedactionConfig) {
+    let _ = GLOBAL.set(Redactor::new(config));
+}
+
+/// Process-wide redactor (built-in defaults until `init` is called)
+pub fn global() -> &'static Redactor {
+    GLOBAL.get_or_init(|| Redactor::new(&RedactionConfig::default()))
+}
+
+/// Middleware that redacts the `data` of error responses, which often echoes request params
+pub struct RedactionLayer;
+
+impl Middleware for RedactionLayer {
+    fn on_response(&self, _request: &JsonRpcRequest, response: &mut JsonRpcResponse) {
+        if let Some(data) = response.error.as_mut().and_then(|e| e.data.as_mut()) {
+            global().redact_value(data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redacts_fields_and_patterns() {
+        let redactor = Redactor::new(&RedactionConfig {
+            fields: vec!["content".to_string()],
+            patterns: vec![r"ACME-\d+".to_string(), "(unclosed".to_string()],
+            ..Default::default()
+        });
+        let mut value = json!({
+            "params": {
+                "arguments": {
+                    "api_key": "abc",
+                    "Content": "fn secret_algorithm() {}",
+                    "query": "ticket ACME-1234 with Bearer eyJhbGciOi.x",
+                    "nested": [{"Authorization": "Basic Zm9v"}],
+                    "password": null
+                }
+            }
+        });
+        redactor.redact_value(&mut value);
+        assert_eq!(
+            value,
+            json!({
+                "params": {
+                    "arguments": {
+                        "api_key": REDACTED,
+                        "Content": REDACTED,
+                        "query": format!("ticket {} with {}", REDACTED, REDACTED),
+                        "nested": [{"Authorization": REDACTED}],
+                        "password": null
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_redact_str() {
+        let redactor = Redactor::new(&RedactionConfig::default());
+        assert_eq!(redactor.redact_str(r#"{"token":"t0p"}"#), format!(r#"{{"token":"{}"}}"#, REDACTED));
+        assert_eq!(redactor.redact_str("not json, Bearer abc.def"), format!("not json, {}", REDACTED));
+        assert!(matches!(redactor.redact_str("plain text"), Cow::Borrowed(_)));
+
+        let disabled = Redactor::new(&RedactionConfig { enabled: false, ..Default::default() });
+        assert_eq!(disabled.redact_str(r#"{"token":"t0p"}"#), r#"{"token":"t0p"}"#);
+    }
+}
@@ -0,0 +1,326 @@
+//! Interactive debugging session (`mcp-proxy repl`)
+//! Sends methods or JSON-RPC bodies typed at a prompt to a running proxy (`--connect`) or to one
+//! started in-process, and pretty-prints what comes back
+
+use crate::config::Config;
+use crate::logging::LevelHandle;
+use crate::proxy::{InProcessProxy, McpProxy};
+use crate::transport::{BackendAddress, BackendReader, BackendWriter};
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use serde_json::{json, Value};
+use std::io::{IsTerminal, Write as _};
+use std::time::Instant;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tracing::level_filters::LevelFilter;
+
+const HELP: &str = r#"Type a method with optional JSON params, a JSON-RPC message, or a command:
+  tools/list
+  tools/call {"name": "codebase-retrieval", "arguments": {"information_request": "auth"}}
+  {"jsonrpc": "2.0", "id": "x", "method": "ping"}
+  :backends          roots, backends and metrics (proxy_status)
+  :restart [root]    restart the backend of a root (default: the current one)
+  :upgrade [root]    replace the backend of a root without downtime
+  :switch <root>     route requests without a file path to another root
+  :help              this text
+  :quit              leave (or Ctrl-D)"#;
+
+/// `repl` options
+#[derive(Args, Debug, Clone)]
+pub struct ReplArgs {
+    /// Proxy to talk to (`host:port` or `unix:/path`, as given to its `--listen`); without it a
+    /// proxy is started in-process with the options given before `repl`
+    #[arg(long)]
+    pub connect: Option<String>,
+}
+
+/// A line typed at the prompt
+#[derive(Debug, PartialEq)]
+enum Input {
+    /// A request to send with the next id
+    Request(Value),
+    /// A message to send as typed (a JSON body or a notification)
+    Message(Value),
+    Help,
+    Quit,
+}
+
+fn parse_input(line: &str) -> Result<Input, String> {
+    let line = line.trim();
+    if line.starts_with('{') {
+        let message: Value = serde_json::from_str(line).map_err(|e| format!("Invalid JSON: {}", e))?;
+        if message.get("method").and_then(Value::as_str).is_none() {
+            return Err("The message has no method".to_string());
+        }
+        return Ok(Input::Message(message));
+    }
+    if let Some(command) = line.strip_prefix(':') {
+        let (command, root) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+        let tool = match command {
+            "help" | "h" => return Ok(Input::Help),
+            "quit" | "q" | "exit" => return Ok(Input::Quit),
+            "backends" | "status" => "proxy_status",
+            "restart" => "proxy_restart_backend",
+            "upgrade" => "proxy_upgrade_backend",
+            "switch" => "proxy_switch_root",
+            other => return Err(format!("Unknown command :{} (:help lists them)", other)),
+        };
+        let root = root.trim();
+        let arguments = if root.is_empty() { json!({}) } else { json!({ "root": root }) };
+        return Ok(Input::Request(json!({ "method": "tools/call", "params": { "name": tool, "arguments": arguments } })));
+    }
+    let (method, params) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let mut message = json!({ "method": method });
+    if !params.trim().is_empty() {
+        message["params"] = serde_json::from_str(params).map_err(|e| format!("Invalid params: {}", e))?;
+    }
+    if method.starts_with("notifications/") {
+        Ok(Input::Message(message))
+    } else {
+        Ok(Input::Request(message))
+    }
+}
+
+/// The message for display: tool results whose text is JSON (such as `proxy_status`) are
+/// shown parsed rather than as an escaped string
+fn expand_text(mut message: Value) -> Value {
+    if let Some(content) = message.pointer_mut("/result/content").and_then(Value::as_array_mut) {
+        for block in content {
+            let parsed = block.get("text").and_then(Value::as_str).and_then(|text| serde_json::from_str::<Value>(text).ok());
+            if let Some(parsed) = parsed.filter(|value| value.is_object() || value.is_array()) {
+                block["text"] = parsed;
+            }
+        }
+    }
+    message
+}
+
+fn pretty(message: Value) -> String {
+    serde_json::to_string_pretty(&expand_text(message)).unwrap_or_default()
+}
+
+fn show_reply(reply: Value, started: Instant) {
+    println!("{}", pretty(reply));
+    println!("({:.1}ms)", started.elapsed().as_secs_f64() * 1000.0);
+}
+
+/// Run the session until the input ends, `:quit`, or Ctrl-C at the prompt
+pub async fn run(mut config: Config, args: ReplArgs, log_handle: LevelHandle) -> Result<()> {
+    let mut in_process = None;
+    let (reader, writer): (BackendReader, BackendWriter) = match args.connect {
+        Some(ref address) => {
+            let parsed = BackendAddress::parse(address).map_err(|e| anyhow::anyhow!("Invalid --connect address: {}", e))?;
+            parsed.connect().await.with_context(|| format!("No proxy is listening on {}", address))?
+        }
+        None => {
+            // Log lines would interleave with the responses
+            if config.log_level == "info" {
+                let _ = log_handle.modify(|filter| *filter = LevelFilter::WARN);
+            }
+            // The commands are the proxy's own tools
+            config.virtual_tools = true;
+            let mut proxy = McpProxy::new(config)?;
+            proxy.set_log_handle(log_handle);
+            let (proxy, reader, writer) = InProcessProxy::start(proxy);
+            in_process = Some(proxy);
+            (reader, writer)
+        }
+    };
+
+    let (incoming_tx, incoming) = mpsc::unbounded_channel();
+    let reader_task = tokio::spawn(async move {
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let message = serde_json::from_str(&line).unwrap_or(Value::String(line));
+            if incoming_tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    let mut session = Session { writer, incoming, next_id: 1 };
+    let result = session.run().await;
+    reader_task.abort();
+    if let Some(proxy) = in_process {
+        proxy.stop().await;
+    }
+    result
+}
+
+struct Session {
+    writer: BackendWriter,
+    /// Messages from the proxy (lines that aren't JSON as strings)
+    incoming: mpsc::UnboundedReceiver<Value>,
+    next_id: u64,
+}
+
+impl Session {
+    async fn run(&mut self) -> Result<()> {
+        let initialize = json!({
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2024-11-05",
+                "capabilities": {},
+                "clientInfo": { "name": "mcp-proxy-repl", "version": env!("CARGO_PKG_VERSION") },
+            },
+        });
+        let Some(reply) = self.request(initialize).await? else { bail!("The proxy did not answer initialize") };
+        let server = reply.pointer("/result/serverInfo").cloned().unwrap_or_default();
+        println!(
+            "Connected to {} {} (protocol {}); :help lists commands",
+            server["name"].as_str().unwrap_or("?"),
+            server["version"].as_str().unwrap_or("?"),
+            reply.pointer("/result/protocolVersion").and_then(Value::as_str).unwrap_or("?")
+        );
+        self.send(json!({ "method": "notifications/initialized" })).await?;
+
+        let interactive = std::io::stdin().is_terminal();
+        let mut stdin = BufReader::new(tokio::io::stdin()).lines();
+        loop {
+            if interactive {
+                print!("mcp> ");
+                let _ = std::io::stdout().flush();
+            }
+            let line = tokio::select! {
+                line = stdin.next_line() => match line? {
+                    Some(line) => line,
+                    None => break,
+                },
+                message = self.incoming.recv() => match message {
+                    Some(message) => {
+                        println!();
+                        self.unsolicited(message).await?;
+                        continue;
+                    }
+                    None => bail!("The proxy closed the connection"),
+                },
+                _ = tokio::signal::ctrl_c() => break,
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_input(&line) {
+                Ok(Input::Help) => println!("{}", HELP),
+                Ok(Input::Quit) => break,
+                Ok(Input::Request(message)) => {
+                    let started = Instant::now();
+                    if let Some(reply) = self.request(message).await? {
+                        show_reply(reply, started);
+                    }
+                }
+                Ok(Input::Message(message)) => match message.get("id").cloned() {
+                    Some(id) => {
+                        let started = Instant::now();
+                        self.send(message).await?;
+                        if let Some(reply) = self.wait_for(&id).await? {
+                            show_reply(reply, started);
+                        }
+                    }
+                    None => self.send(message).await?,
+                },
+                Err(e) => println!("{}", e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a request with the next id and wait for its response (`None` if given up with Ctrl-C)
+    async fn request(&mut self, mut message: Value) -> Result<Option<Value>> {
+        let id = json!(self.next_id);
+        self.next_id += 1;
+        message["id"] = id.clone();
+        self.send(message).await?;
+        self.wait_for(&id).await
+    }
+
+    async fn wait_for(&mut self, id: &Value) -> Result<Option<Value>> {
+        loop {
+            let message = tokio::select! {
+                message = self.incoming.recv() => message,
+                _ = tokio::signal::ctrl_c() => {
+                    println!("Stopped waiting for the response to {}", id);
+                    return Ok(None);
+                }
+            };
+            let Some(message) = message else { bail!("The proxy closed the connection") };
+            if message.get("method").is_none() && message.get("id") == Some(id) {
+                return Ok(Some(message));
+            }
+            self.unsolicited(message).await?;
+        }
+    }
+
+    /// Show a notification or request from the proxy; requests are refused so the proxy
+    /// doesn't wait on them
+    async fn unsolicited(&mut self, message: Value) -> Result<()> {
+        let method = message.get("method").and_then(Value::as_str).map(str::to_string);
+        match (method, message.get("id").cloned()) {
+            (Some(method), Some(id)) => {
+                println!("<- request {}: {}", method, pretty(message));
+                self.send(json!({
+                    "id": id,
+                    "error": { "code": crate::error::ERROR_METHOD_NOT_FOUND, "message": "Not supported by the repl" },
+                }))
+                .await?;
+            }
+            (Some(method), None) => println!("<- {}: {}", method, pretty(message)),
+            (None, _) => println!("<- {}", pretty(message)),
+        }
+        Ok(())
+    }
+
+    async fn send(&mut self, mut message: Value) -> Result<()> {
+        if message.get("jsonrpc").is_none() {
+            message["jsonrpc"] = json!("2.0");
+        }
+        let mut line = message.to_string();
+        line.push('\n');
+        self.writer.write_all(line.as_bytes()).await.context("The proxy closed the connection")?;
+        self.writer.flush().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_input() {
+        assert_eq!(parse_input("tools/list"), Ok(Input::Request(json!({ "method": "tools/list" }))));
+        assert_eq!(
+            parse_input(r#"tools/call {"name": "x"}"#),
+            Ok(Input::Request(json!({ "method": "tools/call", "params": { "name": "x" } })))
+        );
+        assert_eq!(
+            parse_input("notifications/initialized"),
+            Ok(Input::Message(json!({ "method": "notifications/initialized" })))
+        );
+        assert_eq!(
+            parse_input(r#" {"jsonrpc": "2.0", "id": 7, "method": "ping"} "#),
+            Ok(Input::Message(json!({ "jsonrpc": "2.0", "id": 7, "method": "ping" })))
+        );
+        assert_eq!(
+            parse_input(":restart /repo"),
+            Ok(Input::Request(json!({
+                "method": "tools/call",
+                "params": { "name": "proxy_restart_backend", "arguments": { "root": "/repo" } },
+            })))
+        );
+        assert_eq!(parse_input(":q"), Ok(Input::Quit));
+        assert!(parse_input(":nope").is_err());
+        assert!(parse_input(r#"{"id": 1}"#).is_err());
+        assert!(parse_input("tools/call {oops").is_err());
+    }
+
+    #[test]
+    fn test_expand_text() {
+        let reply = json!({ "id": 1, "result": { "content": [
+            { "type": "text", "text": "{\"backends\": []}" },
+            { "type": "text", "text": "42" },
+        ] } });
+        let expanded = expand_text(reply);
+        assert_eq!(expanded["result"]["content"][0]["text"], json!({ "backends": [] }));
+        assert_eq!(expanded["result"]["content"][1]["text"], "42");
+    }
+}
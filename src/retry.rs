@@ -0,0 +1,192 @@
+//! Retry policy for backend requests
+//! Decides which failures are retried, how many times and after what delay, and which
+//! requests must never be sent twice
+
+use crate::error::ProxyError;
+use crate::jsonrpc::JsonRpcRequest;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::time::{Duration, SystemTime};
+use tracing::warn;
+
+/// Failures a request can be retried after
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum RetryClass {
+    /// The backend didn't answer within the request timeout
+    Timeout,
+    /// Starting the backend failed
+    Spawn,
+    /// The backend's process or connection went away
+    Closed,
+}
+
+/// `retry` section of the config file
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Retries after the first attempt
+    pub max_retries: u32,
+    /// Failures worth retrying (a timeout is also retried once hang detection restarted the backend)
+    pub retry_on: Vec<RetryClass>,
+    /// Delay before the first retry, doubled for each further one
+    pub backoff_ms: u64,
+    /// Upper bound of the delay
+    pub max_backoff_ms: u64,
+    /// Randomize each delay between half and all of it
+    pub jitter: bool,
+    /// Methods (globs) never retried, since running them twice isn't safe
+    pub non_idempotent_methods: Vec<String>,
+    /// Tools (globs) whose `tools/call` is never retried
+    pub non_idempotent_tools: Vec<String>,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 1,
+            retry_on: vec![RetryClass::Closed, RetryClass::Spawn],
+            backoff_ms: 200,
+            max_backoff_ms: 5000,
+            jitter: true,
+            non_idempotent_methods: Vec::new(),
+            non_idempotent_tools: Vec::new(),
+        }
+    }
+}
+
+/// Retry config with its globs compiled
+pub struct RetryPolicy {
+    config: RetryConfig,
+    non_idempotent_methods: Option<GlobSet>,
+    non_idempotent_tools: Option<GlobSet>,
+}
+
+impl RetryPolicy {
+    pub fn new(config: &RetryConfig) -> Self {
+        Self {
+            config: config.clone(),
+            non_idempotent_methods: compile("non_idempotent_methods", &config.non_idempotent_methods),
+            non_idempotent_tools: compile("non_idempotent_tools", &config.non_idempotent_tools),
+        }
+    }
+
+    /// Retries allowed for a request (none for non-idempotent ones)
+    pub fn max_retries(&self, request: &JsonRpcRequest) -> u32 {
+        let matches = |set: &Option<GlobSet>, name: &str| set.as_ref().is_some_and(|s| s.is_match(name));
+        if matches(&self.non_idempotent_methods, &request.method) {
+            return 0;
+        }
+        if request.method == "tools/call" {
+            let tool = request.get_param_path("name").and_then(|v| v.as_str()).unwrap_or_default();
+            if matches(&self.non_idempotent_tools, tool) {
+                return 0;
+            }
+        }
+        self.config.max_retries
+    }
+
+    /// Whether a failure may be retried (`recycled`: hang detection just replaced the process)
+    pub fn should_retry(&self, error: &ProxyError, recycled: bool) -> bool {
+        let class = match error {
+            ProxyError::BackendTimeout(_) if recycled => return true,
+            ProxyError::BackendTimeout(_) => RetryClass::Timeout,
+            ProxyError::BackendSpawnFailed(_) => RetryClass::Spawn,
+            ProxyError::BackendUnavailable(_) | ProxyError::IoError(_) => RetryClass::Closed,
+            _ => return false,
+        };
+        self.config.retry_on.contains(&class)
+    }
+
+    /// Delay before retry number `retry` (starting at 1)
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let doubled = self.config.backoff_ms.saturating_mul(1 << retry.saturating_sub(1).min(16));
+        let delay = doubled.min(self.config.max_backoff_ms);
+        if !self.config.jitter || delay == 0 {
+            return Duration::from_millis(delay);
+        }
+        let random = RandomState::new().hash_one(SystemTime::now());
+        Duration::from_millis(delay / 2 + random % (delay / 2 + 1))
+    }
+}
+
+fn compile(section: &str, patterns: &[String]) -> Option<GlobSet> {
+    if patterns.is_empty() {
+        return None;
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        match Glob::new(pattern) {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(e) => warn!("Ignoring invalid retry.{} pattern {:?}: {}", section, pattern, e),
+        }
+    }
+    builder.build().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(method: &str, tool: &str) -> JsonRpcRequest {
+        serde_json::from_value(json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": {"name": tool}})).unwrap()
+    }
+
+    #[test]
+    fn test_retry_classes() {
+        let policy = RetryPolicy::new(&RetryConfig::default());
+        let timeout = ProxyError::BackendTimeout("slow".to_string());
+        assert!(!policy.should_retry(&timeout, false));
+        assert!(policy.should_retry(&timeout, true));
+        assert!(policy.should_retry(&ProxyError::BackendUnavailable("closed".to_string()), false));
+        assert!(policy.should_retry(&ProxyError::BackendSpawnFailed("no node".to_string()), false));
+        assert!(!policy.should_retry(&ProxyError::RoutingFailed("no root".to_string()), false));
+
+        let config: RetryConfig = serde_json::from_value(json!({"retry_on": ["timeout"]})).unwrap();
+        let policy = RetryPolicy::new(&config);
+        assert!(policy.should_retry(&timeout, false));
+        assert!(!policy.should_retry(&ProxyError::BackendUnavailable("closed".to_string()), false));
+    }
+
+    #[test]
+    fn test_non_idempotent() {
+        let config: RetryConfig = serde_json::from_value(json!({
+            "max_retries": 3,
+            "non_idempotent_methods": ["prompts/*"],
+            "non_idempotent_tools": ["save-*"],
+        }))
+        .unwrap();
+        let policy = RetryPolicy::new(&config);
+        assert_eq!(policy.max_retries(&request("tools/call", "codebase-retrieval")), 3);
+        assert_eq!(policy.max_retries(&request("tools/call", "save-memory")), 0);
+        assert_eq!(policy.max_retries(&request("prompts/get", "x")), 0);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let config = RetryConfig {
+            backoff_ms: 100,
+            max_backoff_ms: 300,
+            jitter: false,
+            ..RetryConfig::default()
+        };
+        let policy = RetryPolicy::new(&config);
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(300));
+        assert_eq!(policy.backoff(40), Duration::from_millis(300));
+
+        let policy = RetryPolicy::new(&RetryConfig { jitter: true, ..config });
+        for retry in 1..5 {
+            let delay = policy.backoff(retry).as_millis() as u64;
+            let full = (100 << (retry - 1)).min(300);
+            assert!(delay >= full / 2 && delay <= full, "{} not within jitter of {}", delay, full);
+        }
+    }
+}
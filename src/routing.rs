@@ -0,0 +1,256 @@
+//! Config-driven routing rules and workspace root detection
+//! Ordered rules matching on request URI/method that pin requests to a specific root
+
+use globset::{Glob, GlobMatcher};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// A routing rule as written in the config file
+///
+/// All conditions that are set must match; a rule with no conditions matches everything.
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+pub struct RoutingRule {
+    /// Glob matched against the request's file path (e.g. `**/*.proto`)
+    pub uri: Option<String>,
+    /// Regex matched against the raw request URI
+    pub uri_regex: Option<String>,
+    /// Glob matched against the JSON-RPC method (e.g. `tools/*`)
+    pub method: Option<String>,
+    /// Root that matching requests are routed to
+    pub root: PathBuf,
+}
+
+/// Routing rule with its patterns compiled
+struct CompiledRule {
+    uri: Option<GlobMatcher>,
+    uri_regex: Option<Regex>,
+    method: Option<GlobMatcher>,
+    root: PathBuf,
+}
+
+impl CompiledRule {
+    fn compile(rule: &RoutingRule) -> Result<Self, String> {
+        let glob = |pattern: &String| {
+            Glob::new(pattern)
+                .map(|g| g.compile_matcher())
+                .map_err(|e| format!("invalid glob {:?}: {}", pattern, e))
+        };
+        Ok(Self {
+            uri: rule.uri.as_ref().map(glob).transpose()?,
+            uri_regex: rule
+                .uri_regex
+                .as_ref()
+                .map(|r| Regex::new(r).map_err(|e| format!("invalid regex {:?}: {}", r, e)))
+                .transpose()?,
+            method: rule.method.as_ref().map(glob).transpose()?,
+            root: rule.root.clone(),
+        })
+    }
+
+    fn matches(&self, method: &str, uri: Option<&str>, path: Option<&Path>) -> bool {
+        if let Some(ref m) = self.method {
+            if !m.is_match(method) {
+                return false;
+            }
+        }
+        if let Some(ref g) = self.uri {
+            match path {
+                Some(p) if g.is_match(p) => {}
+                _ => return false,
+            }
+        }
+        if let Some(ref r) = self.uri_regex {
+            match uri {
+                Some(u) if r.is_match(u) => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+/// Ordered set of routing rules; the first matching rule wins
+#[derive(Default)]
+pub struct RoutingRules {
+    rules: Vec<CompiledRule>,
+}
+
+impl RoutingRules {
+    /// Compile rules from config, skipping (and warning about) invalid ones
+    pub fn new(rules: &[RoutingRule]) -> Self {
+        let rules = rules
+            .iter()
+            .enumerate()
+            .filter_map(|(i, rule)| match CompiledRule::compile(rule) {
+                Ok(compiled) => Some(compiled),
+                Err(e) => {
+                    warn!("Ignoring routing rule #{}: {}", i + 1, e);
+                    None
+                }
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Find the target root for a request, if any rule matches
+    pub fn route(&self, method: &str, uri: Option<&str>, path: Option<&Path>) -> Option<PathBuf> {
+        let (index, rule) = self
+            .rules
+            .iter()
+            .enumerate()
+            .find(|(_, rule)| rule.matches(method, uri, path))?;
+        debug!("Routing rule #{} matched {} -> {}", index + 1, method, rule.root.display());
+        Some(rule.root.clone())
+    }
+
+    /// Number of active rules
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+}
+
+/// `_meta` key the proxy uses internally to pin a request to a root (stripped before forwarding)
+pub const ROOT_HINT_META: &str = "mcp-proxy/root";
+
+/// `_meta` keys a client may set to pick a request's root itself, bypassing the URI heuristics
+/// (stripped before forwarding)
+pub const CLIENT_ROOT_META: [&str; 2] = ["workspaceRoot", "targetRoot"];
+
+/// `_meta` key the root picked by the client is carried under once resolved
+pub const REQUESTED_ROOT_META: &str = "mcp-proxy/requested-root";
+
+/// Default project markers, in priority order
+pub const DEFAULT_WORKSPACE_MARKERS: &str = ".git,.hg,.jj,.svn,pnpm-workspace.yaml,Cargo.toml,package.json";
+
+/// Find the workspace root for a path by walking up looking for project markers
+///
+/// Markers are tried in order; the first marker found in any ancestor wins, so VCS
+/// roots take precedence over package manifests. `Cargo.toml` and `package.json`
+/// prefer the nearest ancestor declaring a workspace over a plain member manifest.
+pub fn find_workspace_root(path: &Path, markers: &[String]) -> Option<PathBuf> {
+    let start = if path.is_file() { path.parent()? } else { path };
+
+    markers.iter().find_map(|marker| {
+        let mut nearest = None;
+        for dir in start.ancestors() {
+            if !dir.join(marker).exists() {
+                continue;
+            }
+            let is_workspace = match marker.as_str() {
+                "Cargo.toml" => manifest_contains(&dir.join(marker), "[workspace]"),
+                "package.json" => manifest_contains(&dir.join(marker), "\"workspaces\""),
+                _ => true,
+            };
+            if is_workspace {
+                return Some(dir.to_path_buf());
+            }
+            nearest.get_or_insert_with(|| dir.to_path_buf());
+        }
+        nearest
+    })
+}
+
+/// Cheap check whether a manifest declares a workspace
+fn manifest_contains(manifest: &Path, needle: &str) -> bool {
+    std::fs::read_to_string(manifest)
+        .map(|content| content.contains(needle))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markers() -> Vec<String> {
+        DEFAULT_WORKSPACE_MARKERS.split(',').map(String::from).collect()
+    }
+
+    fn temp_tree(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-routing-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn rule(uri: Option<&str>, uri_regex: Option<&str>, method: Option<&str>, root: &str) -> RoutingRule {
+        RoutingRule {
+            uri: uri.map(String::from),
+            uri_regex: uri_regex.map(String::from),
+            method: method.map(String::from),
+            root: PathBuf::from(root),
+        }
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let rules = RoutingRules::new(&[
+            rule(Some("**/*.proto"), None, None, "/mono/proto"),
+            rule(None, None, Some("tools/*"), "/mono"),
+        ]);
+        let proto = Path::new("/mono/services/api/v1.proto");
+
+        assert_eq!(rules.route("tools/call", None, Some(proto)), Some(PathBuf::from("/mono/proto")));
+        assert_eq!(rules.route("tools/list", None, None), Some(PathBuf::from("/mono")));
+        assert_eq!(rules.route("resources/list", None, None), None);
+    }
+
+    #[test]
+    fn test_all_conditions_must_match() {
+        let rules = RoutingRules::new(&[rule(None, Some(r"^file:///work/"), Some("tools/call"), "/work")]);
+
+        assert!(rules.route("tools/call", Some("file:///work/a.rs"), None).is_some());
+        assert!(rules.route("tools/list", Some("file:///work/a.rs"), None).is_none());
+        assert!(rules.route("tools/call", Some("file:///home/a.rs"), None).is_none());
+        assert!(rules.route("tools/call", None, None).is_none());
+    }
+
+    #[test]
+    fn test_invalid_rules_are_skipped() {
+        let rules = RoutingRules::new(&[
+            rule(Some("[invalid"), None, None, "/a"),
+            rule(None, Some("(unclosed"), None, "/b"),
+            rule(None, None, None, "/catch-all"),
+        ]);
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules.route("anything", None, None), Some(PathBuf::from("/catch-all")));
+    }
+
+    #[test]
+    fn test_vcs_marker_takes_precedence() {
+        let root = temp_tree("vcs");
+        std::fs::create_dir_all(root.join(".hg")).unwrap();
+        std::fs::create_dir_all(root.join("crates/a/src")).unwrap();
+        std::fs::write(root.join("crates/a/Cargo.toml"), "[package]\nname = \"a\"\n").unwrap();
+
+        let found = find_workspace_root(&root.join("crates/a/src"), &markers());
+        assert_eq!(found, Some(root.clone()));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_cargo_workspace_preferred_over_member() {
+        let root = temp_tree("cargo");
+        std::fs::create_dir_all(root.join("crates/a/src")).unwrap();
+        std::fs::write(root.join("Cargo.toml"), "[workspace]\nmembers = [\"crates/*\"]\n").unwrap();
+        std::fs::write(root.join("crates/a/Cargo.toml"), "[package]\nname = \"a\"\n").unwrap();
+
+        let found = find_workspace_root(&root.join("crates/a/src"), &markers());
+        assert_eq!(found, Some(root.clone()));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_plain_package_json_falls_back_to_nearest() {
+        let root = temp_tree("npm");
+        std::fs::create_dir_all(root.join("app/src")).unwrap();
+        std::fs::write(root.join("app/package.json"), "{\"name\": \"app\"}").unwrap();
+
+        let only_npm = vec!["package.json".to_string()];
+        let found = find_workspace_root(&root.join("app/src"), &only_npm);
+        assert_eq!(found, Some(root.join("app")));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
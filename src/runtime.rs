@@ -0,0 +1,178 @@
+//! JavaScript runtime running the auggie entry (`runtime`): node, bun or deno
+
+use crate::version::compare;
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Runtime the backend entry is run with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, ValueEnum, Deserialize, JsonSchema, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Runtime {
+    #[default]
+    Node,
+    Bun,
+    Deno,
+}
+
+impl Runtime {
+    /// Executable name looked up on PATH
+    pub fn binary_name(self) -> &'static str {
+        match self {
+            Runtime::Node => "node",
+            Runtime::Bun => "bun",
+            Runtime::Deno => "deno",
+        }
+    }
+
+    /// Command running `entry` (deno needs `run` and full permissions, like node has by default)
+    pub fn command(self, executable: &Path, entry: &Path) -> Command {
+        let mut cmd = Command::new(executable);
+        if self == Runtime::Deno {
+            cmd.arg("run").arg("--allow-all");
+        }
+        cmd.arg(entry);
+        cmd
+    }
+
+    /// Per-user install locations checked before PATH, best first; for node, the highest
+    /// version installed by nvm/fnm/asdf that meets `min_node_version`, then version manager
+    /// shims and Homebrew
+    pub fn user_install_candidates(self, min_node_version: Option<&str>) -> Vec<PathBuf> {
+        let Some(home) = home_dir() else { return Vec::new() };
+        match self {
+            Runtime::Node => {
+                let mut candidates: Vec<PathBuf> =
+                    highest_installed(&node_version_dirs(&home), min_node_version).into_iter().collect();
+                candidates.extend(node_shims(&home));
+                candidates
+            }
+            Runtime::Bun => vec![home.join(".bun").join("bin").join(exe("bun"))],
+            Runtime::Deno => vec![home.join(".deno").join("bin").join(exe("deno"))],
+        }
+    }
+}
+
+impl std::fmt::Display for Runtime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.binary_name())
+    }
+}
+
+fn exe(name: &str) -> String {
+    if cfg!(windows) { format!("{}.exe", name) } else { name.to_string() }
+}
+
+fn env_dir(var: &str) -> Option<PathBuf> {
+    std::env::var_os(var).filter(|v| !v.is_empty()).map(PathBuf::from)
+}
+
+/// Directories holding one subdirectory per installed node version (named after the version),
+/// with the path of the node binary inside each
+fn node_version_dirs(home: &Path) -> Vec<(PathBuf, PathBuf)> {
+    let bin_node = Path::new("bin").join(exe("node"));
+    let mut dirs = Vec::new();
+    // nvm (nvm-windows keeps node.exe directly in the version directory)
+    let nvm = env_dir("NVM_DIR").unwrap_or_else(|| home.join(".nvm"));
+    dirs.push((nvm.join("versions").join("node"), bin_node.clone()));
+    if let Some(nvm_home) = env_dir("NVM_HOME") {
+        dirs.push((nvm_home, PathBuf::from(exe("node"))));
+    }
+    // fnm
+    let fnm_node = if cfg!(windows) {
+        Path::new("installation").join(exe("node"))
+    } else {
+        Path::new("installation").join(&bin_node)
+    };
+    let mut fnm_dirs: Vec<PathBuf> = env_dir("FNM_DIR").into_iter().collect();
+    fnm_dirs.push(home.join(".local").join("share").join("fnm"));
+    fnm_dirs.push(home.join(".fnm"));
+    fnm_dirs.push(home.join("Library").join("Application Support").join("fnm"));
+    fnm_dirs.extend(env_dir("APPDATA").map(|dir| dir.join("fnm")));
+    dirs.extend(fnm_dirs.into_iter().map(|dir| (dir.join("node-versions"), fnm_node.clone())));
+    // asdf
+    let asdf = env_dir("ASDF_DATA_DIR").unwrap_or_else(|| home.join(".asdf"));
+    dirs.push((asdf.join("installs").join("nodejs"), bin_node));
+    dirs
+}
+
+/// The highest installed version at least `min_version`
+fn highest_installed(dirs: &[(PathBuf, PathBuf)], min_version: Option<&str>) -> Option<PathBuf> {
+    dirs.iter()
+        .filter_map(|(dir, node)| std::fs::read_dir(dir).ok().map(|entries| (entries, node)))
+        .flat_map(|(entries, node)| {
+            entries.flatten().filter_map(move |entry| {
+                let version = entry.file_name().to_string_lossy().trim_start_matches('v').to_string();
+                let path = entry.path().join(node);
+                (version.starts_with(|c: char| c.is_ascii_digit()) && path.exists()).then_some((version, path))
+            })
+        })
+        .filter(|(version, _)| min_version.is_none_or(|min| compare(version, min) != Ordering::Less))
+        .max_by(|(a, _), (b, _)| compare(a, b))
+        .map(|(_, path)| path)
+}
+
+/// Shims of volta and asdf, and Homebrew's node
+fn node_shims(home: &Path) -> Vec<PathBuf> {
+    let volta = env_dir("VOLTA_HOME").unwrap_or_else(|| home.join(".volta"));
+    let asdf = env_dir("ASDF_DATA_DIR").unwrap_or_else(|| home.join(".asdf"));
+    let mut shims = vec![volta.join("bin").join(exe("node")), asdf.join("shims").join("node")];
+    if cfg!(windows) {
+        shims.extend(env_dir("LOCALAPPDATA").map(|dir| dir.join("Volta").join("bin").join(exe("node"))));
+    } else {
+        shims.extend(
+            ["/opt/homebrew/bin/node", "/usr/local/bin/node", "/home/linuxbrew/.linuxbrew/bin/node"]
+                .into_iter()
+                .map(PathBuf::from),
+        );
+    }
+    shims
+}
+
+/// The user's home directory (`HOME`, or `USERPROFILE` on Windows)
+pub fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_args() {
+        let args = |runtime: Runtime| -> Vec<String> {
+            runtime
+                .command(Path::new("rt"), Path::new("augment.mjs"))
+                .as_std()
+                .get_args()
+                .map(|a| a.to_string_lossy().into_owned())
+                .collect()
+        };
+        assert_eq!(args(Runtime::Node), vec!["augment.mjs"]);
+        assert_eq!(args(Runtime::Bun), vec!["augment.mjs"]);
+        assert_eq!(args(Runtime::Deno), vec!["run", "--allow-all", "augment.mjs"]);
+    }
+
+    #[test]
+    fn test_highest_installed() {
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-nvm-{}", std::process::id()));
+        let node = Path::new("bin").join(exe("node"));
+        for version in ["v18.19.0", "v20.9.0", "v20.11.1"] {
+            std::fs::create_dir_all(dir.join(version).join("bin")).unwrap();
+            std::fs::write(dir.join(version).join(&node), "").unwrap();
+        }
+        // Installed without a binary (e.g. interrupted download)
+        std::fs::create_dir_all(dir.join("v22.0.0")).unwrap();
+
+        let dirs = vec![(dir.clone(), node.clone())];
+        assert_eq!(highest_installed(&dirs, None), Some(dir.join("v20.11.1").join(&node)));
+        assert_eq!(highest_installed(&dirs, Some("21")), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
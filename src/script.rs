@@ -0,0 +1,248 @@
+//! Rhai scripting hooks (`scripting` feature)
+//! Site-specific request rewriting without recompiling
+//!
+//! Each hook is a script evaluated with these variables in scope:
+//! `method`, `id`, `params` and `root` (plus `result` and `error` for `post_response`).
+//! `pre_route` may change `params` and `root` (to pick another backend), `pre_forward` may
+//! change `params`, and `post_response` may change `result` and `error`. A `pre_*` script that
+//! evaluates to a map with a `result` or `error` key answers the request without forwarding it.
+
+use crate::config::ScriptsConfig;
+use crate::error::{ProxyError, ERROR_INTERNAL_ERROR};
+use crate::jsonrpc::{JsonRpcError, JsonRpcRequest, JsonRpcResponse};
+use rhai::{Dynamic, Engine, Scope, AST};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+/// Operation budget per script run, so a looping script can't stall the proxy
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// What a `pre_*` hook decided
+pub enum HookOutcome {
+    Continue,
+    Respond(JsonRpcResponse),
+}
+
+/// Compiled hook scripts
+pub struct ScriptHooks {
+    engine: Engine,
+    pre_route: Option<AST>,
+    pre_forward: Option<AST>,
+    post_response: Option<AST>,
+}
+
+impl ScriptHooks {
+    /// Compile the configured scripts (`None` when no hook is configured)
+    pub fn load(config: &ScriptsConfig) -> Result<Option<Self>, ProxyError> {
+        if config.pre_route.is_none() && config.pre_forward.is_none() && config.post_response.is_none() {
+            return Ok(None);
+        }
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.on_print(|s| info!("[script] {}", s));
+        engine.on_debug(|s, _, pos| debug!("[script {}] {}", pos, s));
+
+        let compile = |path: &Option<PathBuf>| -> Result<Option<AST>, ProxyError> {
+            let path = match path {
+                Some(path) => path,
+                None => return Ok(None),
+            };
+            let ast = engine
+                .compile_file(path.clone())
+                .map_err(|e| ProxyError::ConfigError(format!("Failed to compile script {}: {}", path.display(), e)))?;
+            info!("Loaded script hook {}", path.display());
+            Ok(Some(ast))
+        };
+        let (pre_route, pre_forward, post_response) =
+            (compile(&config.pre_route)?, compile(&config.pre_forward)?, compile(&config.post_response)?);
+
+        Ok(Some(Self {
+            engine,
+            pre_route,
+            pre_forward,
+            post_response,
+        }))
+    }
+
+    /// Before the backend is chosen: may rewrite params, change `root` or answer directly
+    pub fn pre_route(&self, request: &mut JsonRpcRequest, root: &mut Option<PathBuf>) -> HookOutcome {
+        let ast = match self.pre_route {
+            Some(ref ast) => ast,
+            None => return HookOutcome::Continue,
+        };
+        let mut scope = request_scope(request, root.as_deref());
+        let outcome = self.run_pre("pre_route", ast, &mut scope, request);
+        if let HookOutcome::Continue = outcome {
+            *root = match scope.get_value::<Dynamic>("root") {
+                Some(value) if value.is_string() => Some(PathBuf::from(value.into_string().unwrap_or_default())),
+                _ => None,
+            };
+        }
+        outcome
+    }
+
+    /// Right before the request is sent to the backend for `root`
+    pub fn pre_forward(&self, request: &mut JsonRpcRequest, root: &Path) -> HookOutcome {
+        match self.pre_forward {
+            Some(ref ast) => {
+                let mut scope = request_scope(request, Some(root));
+                self.run_pre("pre_forward", ast, &mut scope, request)
+            }
+            None => HookOutcome::Continue,
+        }
+    }
+
+    /// After the backend answered; may rewrite `result` and `error`
+    pub fn post_response(&self, request: &JsonRpcRequest, root: &Path, response: &mut JsonRpcResponse) {
+        let ast = match self.post_response {
+            Some(ref ast) => ast,
+            None => return,
+        };
+        let mut scope = request_scope(request, Some(root));
+        scope.push_dynamic("result", to_dynamic(response.result.as_ref()));
+        scope.push_dynamic("error", to_dynamic(serde_json::to_value(&response.error).ok().as_ref()));
+
+        if let Err(e) = self.engine.run_ast_with_scope(&mut scope, ast) {
+            warn!("post_response script failed for {}: {}", request.method, e);
+            return;
+        }
+        let result = from_scope(&scope, "result");
+        let error = from_scope(&scope, "error");
+        match error.map(serde_json::from_value::<JsonRpcError>) {
+            Some(Ok(error)) => {
+                response.result = None;
+                response.error = Some(error);
+            }
+            Some(Err(e)) => warn!("post_response script set an invalid error: {}", e),
+            None => {
+                response.error = None;
+                response.result = result;
+            }
+        }
+    }
+
+    /// Run a `pre_*` script and write back `params`; a returned map with `result`/`error` answers
+    fn run_pre(&self, hook: &str, ast: &AST, scope: &mut Scope, request: &mut JsonRpcRequest) -> HookOutcome {
+        let returned = match self.engine.eval_ast_with_scope::<Dynamic>(scope, ast) {
+            Ok(returned) => returned,
+            Err(e) => {
+                warn!("{} script failed for {}: {}", hook, request.method, e);
+                return HookOutcome::Respond(JsonRpcResponse::error(
+                    request.id.clone(),
+                    JsonRpcError::new(ERROR_INTERNAL_ERROR, format!("{} script failed: {}", hook, e)),
+                ));
+            }
+        };
+
+        if let Ok(Value::Object(map)) = rhai::serde::from_dynamic::<Value>(&returned) {
+            if let Some(error) = map.get("error") {
+                return HookOutcome::Respond(match serde_json::from_value::<JsonRpcError>(error.clone()) {
+                    Ok(error) => JsonRpcResponse::error(request.id.clone(), error),
+                    Err(e) => JsonRpcResponse::error(
+                        request.id.clone(),
+                        JsonRpcError::new(ERROR_INTERNAL_ERROR, format!("{} script returned an invalid error: {}", hook, e)),
+                    ),
+                });
+            }
+            if let Some(result) = map.get("result") {
+                return HookOutcome::Respond(JsonRpcResponse::success(request.id.clone(), result.clone()));
+            }
+        }
+        request.params = from_scope(scope, "params");
+        HookOutcome::Continue
+    }
+}
+
+fn request_scope(request: &JsonRpcRequest, root: Option<&Path>) -> Scope<'static> {
+    let mut scope = Scope::new();
+    scope.push("method", request.method.clone());
+    scope.push_dynamic("id", to_dynamic(serde_json::to_value(&request.id).ok().as_ref()));
+    scope.push_dynamic("params", to_dynamic(request.params.as_ref()));
+    scope.push_dynamic(
+        "root",
+        root.map(|r| Dynamic::from(r.display().to_string())).unwrap_or(Dynamic::UNIT),
+    );
+    scope
+}
+
+fn to_dynamic(value: Option<&Value>) -> Dynamic {
+    value
+        .and_then(|v| rhai::serde::to_dynamic(v).ok())
+        .unwrap_or(Dynamic::UNIT)
+}
+
+/// Read a variable back as JSON (`None` for unit/null)
+fn from_scope(scope: &Scope, name: &str) -> Option<Value> {
+    let value = scope.get_value::<Dynamic>(name)?;
+    match rhai::serde::from_dynamic::<Value>(&value) {
+        Ok(Value::Null) => None,
+        Ok(value) => Some(value),
+        Err(e) => {
+            warn!("Script set {} to a value that isn't JSON: {}", name, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jsonrpc::JsonRpcId;
+    use serde_json::json;
+
+    fn hooks(pre_route: &str, post_response: &str) -> ScriptHooks {
+        let engine = Engine::new();
+        let compile = |src: &str| (!src.is_empty()).then(|| engine.compile(src).unwrap());
+        let (pre_route, post_response) = (compile(pre_route), compile(post_response));
+        ScriptHooks { engine, pre_route, pre_forward: None, post_response }
+    }
+
+    fn request() -> JsonRpcRequest {
+        JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "tools/call".to_string(),
+            id: Some(JsonRpcId::Number(1)),
+            params: Some(json!({"name": "codebase-retrieval", "arguments": {"query": "auth"}})),
+        }
+    }
+
+    #[test]
+    fn test_pre_route_rewrites_params_and_root() {
+        let hooks = hooks(
+            r#"
+            if params.arguments.query.starts_with("auth") {
+                params.arguments.query += " middleware";
+                root = "/repos/backend";
+            }
+            "#,
+            "",
+        );
+        let mut req = request();
+        let mut root = Some(PathBuf::from("/repos/frontend"));
+        assert!(matches!(hooks.pre_route(&mut req, &mut root), HookOutcome::Continue));
+        assert_eq!(req.params.unwrap()["arguments"]["query"], "auth middleware");
+        assert_eq!(root, Some(PathBuf::from("/repos/backend")));
+    }
+
+    #[test]
+    fn test_pre_route_short_circuits() {
+        let maintenance = hooks(r#"if method == "tools/call" { #{ error: #{ code: -32004, message: "maintenance" } } }"#, "");
+        match maintenance.pre_route(&mut request(), &mut None) {
+            HookOutcome::Respond(response) => assert_eq!(response.error.unwrap().message, "maintenance"),
+            HookOutcome::Continue => panic!("expected a synthetic response"),
+        }
+
+        let mut looping = hooks("loop {}", "");
+        looping.engine.set_max_operations(1000);
+        assert!(matches!(looping.pre_route(&mut request(), &mut None), HookOutcome::Respond(_)));
+    }
+
+    #[test]
+    fn test_post_response_rewrites_result() {
+        let hooks = hooks("", r#"result.content[0].text = "[" + root + "] " + result.content[0].text;"#);
+        let mut response = JsonRpcResponse::success(Some(JsonRpcId::Number(1)), json!({"content": [{"type": "text", "text": "hit"}]}));
+        hooks.post_response(&request(), Path::new("/repo"), &mut response);
+        assert_eq!(response.result.unwrap()["content"][0]["text"], "[/repo] hit");
+    }
+}
@@ -0,0 +1,95 @@
+//! Serving clients on a socket (`--listen`) instead of stdio
+//! Each connection gets a proxy (and backends) of its own, shut down when the client disconnects
+
+use crate::admin::AdminHub;
+use crate::logging::LevelHandle;
+use crate::proxy::McpProxy;
+use crate::transport::{BackendAddress, Listener};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tracing::{error, info};
+
+/// Completes on Ctrl+C, or SIGTERM on Unix (how service managers stop the proxy)
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut term) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = term.recv() => {}
+            }
+            return;
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Bind the `--listen` address
+pub async fn bind(address: &str) -> anyhow::Result<Listener> {
+    let address = BackendAddress::parse(address).map_err(|e| anyhow::anyhow!("Invalid --listen address: {}", e))?;
+    let listener = Listener::bind(&address).await?;
+    info!("Listening for clients on {}", address);
+    Ok(listener)
+}
+
+/// Accept clients until `stop` completes, then shut every session down; sessions start with the
+/// hub's current configuration and answer its admin commands
+pub async fn serve(hub: Arc<AdminHub>, listener: Listener, log_handle: LevelHandle, stop: impl Future<Output = ()>) -> anyhow::Result<()> {
+    #[cfg(target_os = "linux")]
+    crate::systemd::notify("READY=1");
+
+    let (stop_tx, stop_rx) = watch::channel(false);
+    let mut sessions = JoinSet::new();
+    tokio::pin!(stop);
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (reader, writer, peer) = match accepted {
+                    Ok(connection) => connection,
+                    Err(e) => {
+                        error!("Failed to accept client: {}", e);
+                        continue;
+                    }
+                };
+                info!("Client connected: {}", peer);
+                let hub = hub.clone();
+                let log_handle = log_handle.clone();
+                let mut stop_rx = stop_rx.clone();
+                sessions.spawn(async move {
+                    let mut proxy = match McpProxy::new(hub.config()) {
+                        Ok(proxy) => proxy,
+                        Err(e) => {
+                            error!("Failed to start session for {}: {}", peer, e);
+                            return;
+                        }
+                    };
+                    proxy.set_log_handle(log_handle);
+                    proxy.set_inflight_limiter(hub.inflight_limiter());
+                    let (session, admin_rx) = hub.register();
+                    proxy.set_admin_channel(admin_rx);
+                    let stopped = async move {
+                        let _ = stop_rx.wait_for(|stopped| *stopped).await;
+                    };
+                    if let Err(e) = proxy.serve(reader, writer, stopped).await {
+                        error!("Session for {} failed: {}", peer, e);
+                    }
+                    hub.unregister(session);
+                    info!("Client disconnected: {}", peer);
+                });
+            }
+            Some(_) = sessions.join_next() => {}
+            _ = &mut stop => break,
+        }
+    }
+
+    info!("Stopping {} session(s)", sessions.len());
+    #[cfg(target_os = "linux")]
+    crate::systemd::notify("STOPPING=1");
+    drop(listener);
+    let _ = stop_tx.send(true);
+    while sessions.join_next().await.is_some() {}
+    Ok(())
+}
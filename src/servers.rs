@@ -0,0 +1,85 @@
+//! Extra MCP servers (`servers` config section)
+//! Arbitrary stdio MCP servers run alongside auggie; their tools and prompts are listed under
+//! the server's name (`github.create_issue`) and their resources as they are, and calls to
+//! those names and URIs go to the server
+
+use crate::tools;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::process::Command;
+
+/// Entry of the `servers` config section, keyed by the name its tools and prompts are listed under
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Program to run (looked up in `PATH`)
+    pub command: String,
+    pub args: Vec<String>,
+    /// Extra environment variables
+    pub env: HashMap<String, String>,
+    /// Working directory (default: the proxy's)
+    pub cwd: Option<PathBuf>,
+}
+
+impl ServerConfig {
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.command);
+        cmd.args(&self.args).envs(&self.env);
+        if let Some(ref cwd) = self.cwd {
+            cmd.current_dir(cwd);
+        }
+        cmd
+    }
+}
+
+/// Take the entries under `key` (`tools`, `prompts`, `resources`) out of a server's list result
+pub fn take_items(result: &mut Value, key: &str) -> Vec<Value> {
+    match result.get_mut(key).map(Value::take) {
+        Some(Value::Array(items)) => items,
+        _ => Vec::new(),
+    }
+}
+
+/// Prefix the `name` of listed tools or prompts with the server's name
+pub fn prefix_names(items: &mut [Value], server: &str) {
+    for item in items {
+        if let Some(name) = item.get("name").and_then(Value::as_str) {
+            item["name"] = format!("{}{}{}", server, tools::SEPARATOR, name).into();
+        }
+    }
+}
+
+/// Split a prefixed tool or prompt name into the server and the server's own name
+pub fn split_name<'a>(name: &str, servers: impl IntoIterator<Item = &'a String>) -> Option<(String, String)> {
+    tools::split_name(name, servers.into_iter().map(String::as_str))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_prefixed_items() {
+        let mut result = json!({ "tools": [{ "name": "create_issue" }, { "description": "nameless" }], "nextCursor": "2" });
+        let mut items = take_items(&mut result, "tools");
+        prefix_names(&mut items, "github");
+        assert_eq!(items, vec![json!({ "name": "github.create_issue" }), json!({ "description": "nameless" })]);
+        assert!(take_items(&mut result, "prompts").is_empty());
+
+        let servers: HashMap<String, ServerConfig> = serde_json::from_value(json!({
+            "github": { "command": "github-mcp", "args": ["stdio"] },
+            "fs": { "command": "fs-mcp", "env": { "ROOT": "/srv" } },
+        }))
+        .unwrap();
+        assert_eq!(servers["github"].args, vec!["stdio"]);
+        assert_eq!(
+            split_name("github.create_issue", servers.keys()),
+            Some(("github".to_string(), "create_issue".to_string()))
+        );
+        assert_eq!(split_name("codebase-retrieval", servers.keys()), None);
+    }
+}
@@ -0,0 +1,250 @@
+//! Windows service (`mcp-proxy [OPTIONS] service install|uninstall|run`)
+//! The service runs the proxy with `--listen`; stop and shutdown requests from the service
+//! control manager end every session and its backends before the service reports stopped
+
+use crate::admin::AdminHub;
+use crate::config::{Config, ServiceAction};
+use crate::logging::LevelHandle;
+use anyhow::{bail, Context, Result};
+use std::ffi::OsString;
+use std::os::windows::ffi::OsStrExt;
+use std::sync::Mutex;
+use tokio::sync::oneshot;
+use tracing::{error, info};
+use windows::core::{PCWSTR, PWSTR};
+use windows::Win32::System::Services::{
+    ChangeServiceConfig2W, CloseServiceHandle, ControlService, CreateServiceW, DeleteService, OpenSCManagerW,
+    OpenServiceW, RegisterServiceCtrlHandlerExW, SetServiceStatus, StartServiceCtrlDispatcherW, StartServiceW,
+    SC_HANDLE, SC_MANAGER_CONNECT, SC_MANAGER_CREATE_SERVICE, SERVICE_ACCEPT_SHUTDOWN, SERVICE_ACCEPT_STOP,
+    SERVICE_ALL_ACCESS, SERVICE_AUTO_START, SERVICE_CONFIG_DESCRIPTION, SERVICE_CONTROL_INTERROGATE,
+    SERVICE_CONTROL_SHUTDOWN, SERVICE_CONTROL_STOP, SERVICE_DESCRIPTIONW, SERVICE_ERROR_NORMAL, SERVICE_QUERY_STATUS,
+    SERVICE_RUNNING, SERVICE_STATUS, SERVICE_STATUS_CURRENT_STATE, SERVICE_STATUS_HANDLE, SERVICE_STOP,
+    SERVICE_STOPPED, SERVICE_STOP_PENDING, SERVICE_TABLE_ENTRYW, SERVICE_WIN32_OWN_PROCESS,
+};
+
+const SERVICE_NAME: &str = "mcp-proxy";
+const DISPLAY_NAME: &str = "MCP Proxy for Augment Context Engine";
+const DESCRIPTION: &str = "Serves MCP clients on a socket and manages auggie backends";
+/// Standard `DELETE` access right
+const DELETE: u32 = 0x0001_0000;
+const NO_ERROR: u32 = 0;
+const ERROR_CALL_NOT_IMPLEMENTED: u32 = 120;
+
+/// Handles passed from the service control manager's threads to the proxy
+struct Control {
+    /// Status handle once `service_main` has registered (as an address, to cross threads)
+    ready: Option<oneshot::Sender<usize>>,
+    stop: Option<oneshot::Sender<()>>,
+    status: usize,
+}
+
+static CONTROL: Mutex<Option<Control>> = Mutex::new(None);
+
+pub async fn run(action: ServiceAction, config: Config, log_handle: LevelHandle) -> Result<()> {
+    match action {
+        ServiceAction::Install => install(&config),
+        ServiceAction::Uninstall => uninstall(),
+        ServiceAction::Run => run_service(config, log_handle).await,
+    }
+}
+
+/// Register the service with the options given before `service install`, and start it
+fn install(config: &Config) -> Result<()> {
+    if config.listen.is_none() {
+        bail!("The service needs --listen, e.g. mcp-proxy --listen 127.0.0.1:7800 service install");
+    }
+    let exe = std::env::current_exe().context("Failed to locate the mcp-proxy executable")?;
+    let options = std::env::args_os().skip(1).take_while(|arg| arg != "service");
+    let mut command_line = quote(exe.as_os_str());
+    for arg in options {
+        command_line.push(" ");
+        command_line.push(quote(&arg));
+    }
+    command_line.push(" service run");
+
+    unsafe {
+        let manager = Handle(
+            OpenSCManagerW(None, None, SC_MANAGER_CREATE_SERVICE)
+                .context("Failed to open the service control manager (run as administrator)")?,
+        );
+        let service = Handle(
+            CreateServiceW(
+                manager.0,
+                PCWSTR(wide(SERVICE_NAME).as_ptr()),
+                PCWSTR(wide(DISPLAY_NAME).as_ptr()),
+                SERVICE_ALL_ACCESS,
+                SERVICE_WIN32_OWN_PROCESS,
+                SERVICE_AUTO_START,
+                SERVICE_ERROR_NORMAL,
+                PCWSTR(wide(&command_line).as_ptr()),
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .context("Failed to create the service")?,
+        );
+        let mut description = wide(DESCRIPTION);
+        let info = SERVICE_DESCRIPTIONW { lpDescription: PWSTR(description.as_mut_ptr()) };
+        if let Err(e) = ChangeServiceConfig2W(service.0, SERVICE_CONFIG_DESCRIPTION, Some(&info as *const _ as *const _)) {
+            error!("Failed to set the service description: {}", e);
+        }
+        println!("Installed service {}: {}", SERVICE_NAME, command_line.to_string_lossy());
+        StartServiceW(service.0, None).context("Service installed but failed to start")?;
+        println!("Service {} started", SERVICE_NAME);
+    }
+    Ok(())
+}
+
+/// Stop the service if it is running, and remove it
+fn uninstall() -> Result<()> {
+    unsafe {
+        let manager = Handle(
+            OpenSCManagerW(None, None, SC_MANAGER_CONNECT).context("Failed to open the service control manager")?,
+        );
+        let service = Handle(
+            OpenServiceW(manager.0, PCWSTR(wide(SERVICE_NAME).as_ptr()), SERVICE_STOP | SERVICE_QUERY_STATUS | DELETE)
+                .context("Failed to open the service (is it installed? run as administrator)")?,
+        );
+        let mut status = SERVICE_STATUS::default();
+        if ControlService(service.0, SERVICE_CONTROL_STOP, &mut status).is_ok() {
+            println!("Stopping service {}", SERVICE_NAME);
+        }
+        DeleteService(service.0).context("Failed to delete the service")?;
+    }
+    println!("Removed service {}", SERVICE_NAME);
+    Ok(())
+}
+
+/// Run under the service control manager until it asks the service to stop
+async fn run_service(config: Config, log_handle: LevelHandle) -> Result<()> {
+    let Some(listen) = config.listen.clone() else {
+        bail!("The service needs --listen");
+    };
+    let (ready_tx, ready_rx) = oneshot::channel();
+    let (stop_tx, stop_rx) = oneshot::channel();
+    *CONTROL.lock().unwrap() = Some(Control { ready: Some(ready_tx), stop: Some(stop_tx), status: 0 });
+
+    // Blocks until the service reports stopped
+    let dispatcher = tokio::task::spawn_blocking(|| unsafe {
+        let mut name = wide(SERVICE_NAME);
+        let table = [
+            SERVICE_TABLE_ENTRYW { lpServiceName: PWSTR(name.as_mut_ptr()), lpServiceProc: Some(service_main) },
+            SERVICE_TABLE_ENTRYW::default(),
+        ];
+        StartServiceCtrlDispatcherW(table.as_ptr())
+    });
+
+    let status = tokio::select! {
+        ready = ready_rx => ready?,
+        result = dispatcher => {
+            result?.context("Failed to connect to the service control manager (service run is started by Windows)")?;
+            bail!("The service control manager exited before the service started");
+        }
+    };
+    info!("Running as Windows service {}", SERVICE_NAME);
+    let hub = AdminHub::new(config.clone());
+    if let Some(address) = crate::admin::address(&config) {
+        crate::admin::spawn(hub.clone(), &address).await;
+    }
+    let stopped = async {
+        tokio::select! {
+            _ = stop_rx => {}
+            _ = hub.stopped() => {}
+        }
+    };
+    let result = match crate::server::bind(&listen).await {
+        Ok(listener) => crate::server::serve(hub.clone(), listener, log_handle, stopped).await,
+        Err(e) => Err(e),
+    };
+    if let Err(ref e) = result {
+        error!("Service failed: {}", e);
+    }
+    set_status(status, SERVICE_STOPPED, if result.is_ok() { NO_ERROR } else { 1 });
+    result
+}
+
+unsafe extern "system" fn service_main(_argc: u32, _argv: *mut PWSTR) {
+    let name = wide(SERVICE_NAME);
+    let status = match RegisterServiceCtrlHandlerExW(PCWSTR(name.as_ptr()), Some(control_handler), None) {
+        Ok(handle) => handle.0 as usize,
+        Err(e) => {
+            error!("Failed to register the service control handler: {}", e);
+            return;
+        }
+    };
+    set_status(status, SERVICE_RUNNING, NO_ERROR);
+    let mut control = CONTROL.lock().unwrap();
+    if let Some(control) = control.as_mut() {
+        control.status = status;
+        if let Some(ready) = control.ready.take() {
+            let _ = ready.send(status);
+        }
+    }
+}
+
+unsafe extern "system" fn control_handler(
+    control: u32,
+    _event_type: u32,
+    _event_data: *mut std::ffi::c_void,
+    _context: *mut std::ffi::c_void,
+) -> u32 {
+    match control {
+        SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+            let mut guard = CONTROL.lock().unwrap();
+            if let Some(control) = guard.as_mut() {
+                set_status(control.status, SERVICE_STOP_PENDING, NO_ERROR);
+                if let Some(stop) = control.stop.take() {
+                    let _ = stop.send(());
+                }
+            }
+            NO_ERROR
+        }
+        SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+        _ => ERROR_CALL_NOT_IMPLEMENTED,
+    }
+}
+
+fn set_status(handle: usize, state: SERVICE_STATUS_CURRENT_STATE, exit_code: u32) {
+    let status = SERVICE_STATUS {
+        dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+        dwCurrentState: state,
+        dwControlsAccepted: if state == SERVICE_RUNNING { SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN } else { 0 },
+        dwWin32ExitCode: exit_code,
+        // Sessions may take a while to shut their backends down
+        dwCheckPoint: u32::from(state == SERVICE_STOP_PENDING),
+        dwWaitHint: if state == SERVICE_STOP_PENDING { 30_000 } else { 0 },
+        ..Default::default()
+    };
+    unsafe {
+        if let Err(e) = SetServiceStatus(SERVICE_STATUS_HANDLE(handle as *mut _), &status) {
+            error!("Failed to report service status: {}", e);
+        }
+    }
+}
+
+/// Service control manager handle, closed on drop
+struct Handle(SC_HANDLE);
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CloseServiceHandle(self.0);
+        }
+    }
+}
+
+/// Null-terminated UTF-16 string
+fn wide(s: impl AsRef<std::ffi::OsStr>) -> Vec<u16> {
+    s.as_ref().encode_wide().chain(std::iter::once(0)).collect()
+}
+
+/// Quote an argument for the service command line when needed
+fn quote(arg: &std::ffi::OsStr) -> OsString {
+    let text = arg.to_string_lossy();
+    if !text.is_empty() && !text.contains([' ', '\t', '"']) {
+        return arg.to_owned();
+    }
+    OsString::from(format!("\"{}\"", text.replace('"', "\\\"")))
+}
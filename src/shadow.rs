@@ -0,0 +1,160 @@
+//! Request shadowing
+//! Mirrors a share of requests to a second backend per root (e.g. a new auggie version) and
+//! logs how its answers and latency differ from the primary's; the client only ever sees the
+//! primary's response
+
+use crate::jsonrpc::JsonRpcResponse;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::PathBuf;
+use tracing::warn;
+
+/// `shadow` section of the config file
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(default)]
+pub struct ShadowConfig {
+    /// auggie entry of the shadow backend (shadowing is off without it)
+    pub auggie_entry: Option<PathBuf>,
+    /// Node.js for the shadow backend (defaults to the primary's)
+    pub node: Option<PathBuf>,
+    /// Share of matching requests mirrored, 0-100
+    pub percent: u32,
+    /// Methods (globs) to mirror; tools/call is left out by default since tools may have side effects
+    pub methods: Vec<String>,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            auggie_entry: None,
+            node: None,
+            percent: 10,
+            methods: vec!["resources/*".to_string(), "prompts/*".to_string(), "tools/list".to_string()],
+        }
+    }
+}
+
+/// Picks the requests to mirror
+pub struct ShadowSampler {
+    percent: u32,
+    methods: Option<GlobSet>,
+    /// Accumulated share; a request is mirrored each time it passes 100
+    credit: u32,
+}
+
+impl ShadowSampler {
+    pub fn new(config: &ShadowConfig) -> Self {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &config.methods {
+            match Glob::new(pattern) {
+                Ok(glob) => {
+                    builder.add(glob);
+                }
+                Err(e) => warn!("Ignoring invalid shadow.methods pattern {:?}: {}", pattern, e),
+            }
+        }
+        Self {
+            percent: config.percent.min(100),
+            methods: builder.build().ok(),
+            credit: 0,
+        }
+    }
+
+    /// Whether to mirror this request; spreads mirrored requests evenly instead of at random
+    pub fn sample(&mut self, method: &str) -> bool {
+        if self.percent == 0 || !self.methods.as_ref().is_some_and(|m| m.is_match(method)) {
+            return false;
+        }
+        self.credit += self.percent;
+        if self.credit >= 100 {
+            self.credit -= 100;
+            return true;
+        }
+        false
+    }
+}
+
+/// Where the shadow's response differs from the primary's (`None` if they match)
+pub fn diff(primary: &JsonRpcResponse, shadow: &JsonRpcResponse) -> Option<String> {
+    let outcome = |r: &JsonRpcResponse| match (&r.result, &r.error) {
+        (Some(result), _) => Ok(result.clone()),
+        (None, Some(error)) => Err(error.code),
+        (None, None) => Ok(Value::Null),
+    };
+    match (outcome(primary), outcome(shadow)) {
+        (Ok(a), Ok(b)) => first_difference("result", &a, &b),
+        (Err(a), Err(b)) if a == b => None,
+        (Err(a), Err(b)) => Some(format!("error {} vs error {}", a, b)),
+        (Ok(_), Err(code)) => Some(format!("result vs error {}", code)),
+        (Err(code), Ok(_)) => Some(format!("error {} vs result", code)),
+    }
+}
+
+/// Path of the first difference between two values, with a short description
+fn first_difference(path: &str, a: &Value, b: &Value) -> Option<String> {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, value) in a {
+                match b.get(key) {
+                    Some(other) => {
+                        if let Some(diff) = first_difference(&format!("{}.{}", path, key), value, other) {
+                            return Some(diff);
+                        }
+                    }
+                    None => return Some(format!("{}.{} missing", path, key)),
+                }
+            }
+            b.keys()
+                .find(|key| !a.contains_key(*key))
+                .map(|key| format!("{}.{} added", path, key))
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            if a.len() != b.len() {
+                return Some(format!("{} has {} items vs {}", path, a.len(), b.len()));
+            }
+            a.iter()
+                .zip(b)
+                .enumerate()
+                .find_map(|(i, (a, b))| first_difference(&format!("{}[{}]", path, i), a, b))
+        }
+        (a, b) if a == b => None,
+        _ => Some(format!("{} differs", path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jsonrpc::{JsonRpcError, JsonRpcId};
+    use serde_json::json;
+
+    fn ok(result: Value) -> JsonRpcResponse {
+        JsonRpcResponse::success(Some(JsonRpcId::Number(1)), result)
+    }
+
+    #[test]
+    fn test_sampling() {
+        let config = ShadowConfig { percent: 25, ..ShadowConfig::default() };
+        let mut sampler = ShadowSampler::new(&config);
+        let mirrored = (0..8).filter(|_| sampler.sample("resources/read")).count();
+        assert_eq!(mirrored, 2);
+        assert!(!(0..8).any(|_| sampler.sample("tools/call")));
+
+        let mut off = ShadowSampler::new(&ShadowConfig { percent: 0, ..ShadowConfig::default() });
+        assert!(!off.sample("resources/read"));
+    }
+
+    #[test]
+    fn test_diff() {
+        let a = ok(json!({"content": [{"text": "one"}], "isError": false}));
+        assert_eq!(diff(&a, &a.clone()), None);
+        let b = ok(json!({"content": [{"text": "two"}], "isError": false}));
+        assert_eq!(diff(&a, &b).unwrap(), "result.content[0].text differs");
+        let c = ok(json!({"content": [], "isError": false}));
+        assert_eq!(diff(&a, &c).unwrap(), "result.content has 1 items vs 0");
+        let error = JsonRpcResponse::error(Some(JsonRpcId::Number(1)), JsonRpcError::new(-32603, "boom"));
+        assert_eq!(diff(&a, &error).unwrap(), "result vs error -32603");
+    }
+}
@@ -0,0 +1,148 @@
+//! Remote backends over SSH
+//! Runs auggie on a remote host and streams its stdio over the SSH connection
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Per-root `ssh` section of the config file
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+pub struct SshConfig {
+    /// Remote host (as understood by ssh, may be a `~/.ssh/config` alias)
+    pub host: String,
+    /// Remote user (defaults to ssh's own default)
+    pub user: Option<String>,
+    /// Remote port
+    pub port: Option<u16>,
+    /// Extra arguments passed to ssh before the host (e.g. `["-i", "~/.ssh/build"]`)
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Path to the ssh executable (defaults to `ssh` on PATH)
+    pub ssh_exe: Option<PathBuf>,
+    /// Node executable on the remote host (defaults to `node`)
+    pub node: Option<String>,
+    /// Auggie entry on the remote host; when unset the `auggie` CLI is run directly
+    pub auggie_entry: Option<String>,
+    /// Workspace root on the remote host (defaults to the local root path)
+    pub remote_root: Option<String>,
+}
+
+impl SshConfig {
+    /// Workspace root on the remote side for a local root
+    pub fn remote_root_for(&self, local_root: &Path) -> String {
+        self.remote_root
+            .clone()
+            .unwrap_or_else(|| local_root.display().to_string())
+    }
+
+    /// Translate a local path under `local_root` into the remote namespace
+    pub fn remote_path(&self, local_root: &Path, path: &Path) -> String {
+        let remote_root = self.remote_root_for(local_root);
+        match path.strip_prefix(local_root) {
+            Ok(relative) if relative.as_os_str().is_empty() => remote_root,
+            Ok(relative) => format!(
+                "{}/{}",
+                remote_root.trim_end_matches('/'),
+                relative.display().to_string().replace('\\', "/")
+            ),
+            Err(_) => path.display().to_string(),
+        }
+    }
+
+    /// Build the local `ssh` command that runs the backend remotely
+    /// (`env` is set inline on the remote command line, ssh doesn't forward it)
+    pub fn backend_command(&self, local_root: &Path, mode: &str, env: &[(String, String)]) -> Command {
+        let ssh_exe = self.ssh_exe.clone().unwrap_or_else(|| PathBuf::from("ssh"));
+        let mut cmd = Command::new(ssh_exe);
+
+        // No tty (stdio carries JSON-RPC) and never block on interactive prompts
+        cmd.arg("-T").arg("-o").arg("BatchMode=yes").arg("-o").arg("ServerAliveInterval=30");
+        if let Some(port) = self.port {
+            cmd.arg("-p").arg(port.to_string());
+        }
+        cmd.args(&self.args);
+        cmd.arg(match self.user {
+            Some(ref user) => format!("{}@{}", user, self.host),
+            None => self.host.clone(),
+        });
+        cmd.arg("--");
+        cmd.arg(self.remote_command(local_root, mode, env));
+        cmd
+    }
+
+    /// Shell command line executed on the remote host
+    fn remote_command(&self, local_root: &Path, mode: &str, env: &[(String, String)]) -> String {
+        let mut parts = vec!["AUGMENT_DISABLE_AUTO_UPDATE=1".to_string()];
+        parts.extend(env.iter().map(|(name, value)| format!("{}={}", name, shell_quote(value))));
+        match self.auggie_entry {
+            Some(ref entry) => {
+                parts.push(shell_quote(self.node.as_deref().unwrap_or("node")));
+                parts.push(shell_quote(entry));
+            }
+            None => parts.push("auggie".to_string()),
+        }
+        parts.extend(["--mcp".to_string(), "-m".to_string(), shell_quote(mode)]);
+        parts.push("--workspace-root".to_string());
+        parts.push(shell_quote(&self.remote_root_for(local_root)));
+        parts.join(" ")
+    }
+}
+
+/// Quote a string for a POSIX shell
+fn shell_quote(s: &str) -> String {
+    if !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || "/._-+=:@".contains(c)) {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> SshConfig {
+        SshConfig {
+            host: "build01".to_string(),
+            user: Some("me".to_string()),
+            port: Some(2222),
+            args: vec!["-i".to_string(), "/keys/build".to_string()],
+            ssh_exe: None,
+            node: None,
+            auggie_entry: Some("/opt/auggie/augment.mjs".to_string()),
+            remote_root: Some("/srv/my repo".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_ssh_backend_command() {
+        let env = [("ORG".to_string(), "acme corp".to_string())];
+        let cmd = config().backend_command(Path::new("/home/me/repo"), "default", &env);
+        let args: Vec<_> = cmd.as_std().get_args().map(|a| a.to_string_lossy().to_string()).collect();
+        assert_eq!(
+            args,
+            [
+                "-T", "-o", "BatchMode=yes", "-o", "ServerAliveInterval=30", "-p", "2222", "-i", "/keys/build",
+                "me@build01", "--",
+                "AUGMENT_DISABLE_AUTO_UPDATE=1 ORG='acme corp' node /opt/auggie/augment.mjs --mcp -m default --workspace-root '/srv/my repo'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_remote_path_translation() {
+        let ssh = config();
+        let local = Path::new("/home/me/repo");
+        assert_eq!(ssh.remote_path(local, Path::new("/home/me/repo/src/a.rs")), "/srv/my repo/src/a.rs");
+        assert_eq!(ssh.remote_path(local, local), "/srv/my repo");
+        assert_eq!(ssh.remote_path(local, Path::new("/elsewhere/b.rs")), "/elsewhere/b.rs");
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("/plain/path"), "/plain/path");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+}
@@ -0,0 +1,285 @@
+//! Files the proxy keeps between runs, in the state directory (`dirs::state_dir`)
+//! Session state (`persist_session`) carries routing context over a restart; on Unix each
+//! proxy also records the backends it spawned, so the next run can kill the ones an unclean
+//! exit left behind (on Windows the Job Object takes them down with the proxy)
+
+use crate::dirs::state_dir;
+use crate::jsonrpc::JsonRpcRequest;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+#[cfg(unix)]
+use tracing::debug;
+#[cfg(unix)]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(unix)]
+use std::time::Instant;
+
+/// Saved session state older than this is ignored
+const SESSION_STATE_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long an orphan gets to exit after SIGTERM before it is killed
+#[cfg(unix)]
+const ORPHAN_TERM_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Routing context of a stdio session, saved on exit
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SessionState {
+    /// Seconds since the epoch when saved
+    pub saved_at: u64,
+    /// Workspace roots the client reported
+    pub roots: Vec<PathBuf>,
+    /// Session -> root bindings, least recently used first
+    pub sessions: Vec<(String, PathBuf)>,
+    /// Change batches per root: held for roots without a backend, and the recent ones each
+    /// backend would replay after a restart
+    pub changes: HashMap<PathBuf, Vec<JsonRpcRequest>>,
+}
+
+impl SessionState {
+    /// Session state file for a proxy started with this instance id in this workspace (the
+    /// default root, or the current directory), so concurrent IDE windows keep their own
+    pub fn path(instance: Option<&str>, workspace: Option<&Path>) -> PathBuf {
+        let cwd = std::env::current_dir().ok();
+        let workspace = workspace.or(cwd.as_deref()).unwrap_or(Path::new(""));
+        let key = format!("{}\n{}", instance.unwrap_or_default(), workspace.display());
+        let hash: String = Sha256::digest(key.as_bytes()).iter().take(8).map(|b| format!("{:02x}", b)).collect();
+        state_dir().join("sessions").join(format!("{}.json", hash))
+    }
+
+    /// Load and remove a saved state, unless it is missing, unreadable or stale
+    pub fn take(path: &Path) -> Option<Self> {
+        let contents = std::fs::read(path).ok()?;
+        let _ = std::fs::remove_file(path);
+        let state: Self = match serde_json::from_slice(&contents) {
+            Ok(state) => state,
+            Err(e) => {
+                warn!("Ignoring unreadable session state {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        let age = Duration::from_secs(now().saturating_sub(state.saved_at));
+        if age > SESSION_STATE_MAX_AGE {
+            info!("Ignoring session state saved {}h ago", age.as_secs() / 3600);
+            return None;
+        }
+        Some(state)
+    }
+
+    pub fn save(mut self, path: &Path) {
+        self.saved_at = now();
+        let written = serde_json::to_vec(&self).map_err(std::io::Error::from).and_then(|contents| {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let tmp = path.with_extension("tmp");
+            std::fs::write(&tmp, contents)?;
+            std::fs::rename(&tmp, path)
+        });
+        match written {
+            Ok(()) => info!("Saved session state to {}", path.display()),
+            Err(e) => warn!("Failed to save session state to {}: {}", path.display(), e),
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+#[cfg(unix)]
+fn backends_dir() -> PathBuf {
+    state_dir().join("backends")
+}
+
+/// A backend process as recorded on disk
+#[cfg(unix)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedBackend {
+    pub pid: u32,
+    pub root: PathBuf,
+    /// Process name at spawn time, so a PID reused by another program is left alone
+    pub name: Option<String>,
+}
+
+/// The backends of one process group, kept in `backends/<proxy pid>-<n>.json` while any run;
+/// the file is removed when the group is dropped
+#[cfg(unix)]
+pub struct BackendRecord {
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl BackendRecord {
+    pub fn new() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let name = format!("{}-{}.json", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+        Self { path: backends_dir().join(name) }
+    }
+
+    /// Write the current set of backends (removing the file once there are none)
+    pub fn save(&self, backends: &HashMap<u32, RecordedBackend>) {
+        if backends.is_empty() {
+            let _ = std::fs::remove_file(&self.path);
+            return;
+        }
+        let mut list: Vec<&RecordedBackend> = backends.values().collect();
+        list.sort_by_key(|backend| backend.pid);
+        let written = serde_json::to_vec(&list).map_err(std::io::Error::from).and_then(|contents| {
+            std::fs::create_dir_all(backends_dir())?;
+            // Written aside and renamed, so a crash never leaves half a file
+            let tmp = self.path.with_extension("tmp");
+            std::fs::write(&tmp, contents)?;
+            std::fs::rename(&tmp, &self.path)
+        });
+        if let Err(e) = written {
+            debug!("Failed to record backends in {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for BackendRecord {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Kill backends recorded by proxies that are no longer running, and drop their records;
+/// returns how many were killed
+#[cfg(unix)]
+pub fn reap_orphans() -> usize {
+    let Ok(entries) = std::fs::read_dir(backends_dir()) else { return 0 };
+    let mut killed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(proxy_pid) = record_owner(&path) else { continue };
+        if proxy_pid == std::process::id() || crate::daemon::is_mcp_proxy(proxy_pid) {
+            continue;
+        }
+        let backends: Vec<RecordedBackend> = std::fs::read(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_slice(&contents).ok())
+            .unwrap_or_default();
+        for backend in backends.iter().filter(|backend| is_orphan(backend)) {
+            warn!(
+                "Killing backend PID {} for {} left behind by mcp-proxy PID {}",
+                backend.pid,
+                backend.root.display(),
+                proxy_pid
+            );
+            terminate(backend.pid);
+            killed += 1;
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+    if killed > 0 {
+        info!("Killed {} orphaned backend(s)", killed);
+    }
+    killed
+}
+
+/// Proxy PID from a record file name (`<pid>-<n>.json`)
+#[cfg(unix)]
+fn record_owner(path: &Path) -> Option<u32> {
+    if path.extension()? != "json" {
+        return None;
+    }
+    path.file_stem()?.to_str()?.split('-').next()?.parse().ok()
+}
+
+/// Still running, under the name it had when recorded
+#[cfg(unix)]
+fn is_orphan(backend: &RecordedBackend) -> bool {
+    crate::daemon::is_alive(backend.pid)
+        && match backend.name {
+            Some(ref name) => crate::daemon::process_name(backend.pid).as_ref() == Some(name),
+            None => false,
+        }
+}
+
+/// SIGTERM, then SIGKILL if it is still there after `ORPHAN_TERM_TIMEOUT`
+#[cfg(unix)]
+fn terminate(pid: u32) {
+    let Ok(pid) = libc::pid_t::try_from(pid) else { return };
+    unsafe {
+        libc::kill(pid, libc::SIGTERM);
+    }
+    let deadline = Instant::now() + ORPHAN_TERM_TIMEOUT;
+    while Instant::now() < deadline {
+        if !crate::daemon::is_alive(pid as u32) {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    unsafe {
+        libc::kill(pid, libc::SIGKILL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_state_round_trip() {
+        let path = std::env::temp_dir().join(format!("mcp-proxy-session-{}.json", std::process::id()));
+        let batch: JsonRpcRequest = serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/files/didChange",
+            "params": { "uris": ["file:///repo/a.rs"] }
+        }))
+        .unwrap();
+        SessionState {
+            saved_at: 0,
+            roots: vec![PathBuf::from("/repo")],
+            sessions: vec![("s1".to_string(), PathBuf::from("/repo"))],
+            changes: HashMap::from([(PathBuf::from("/repo"), vec![batch])]),
+        }
+        .save(&path);
+
+        let state = SessionState::take(&path).unwrap();
+        assert_eq!(state.roots, vec![PathBuf::from("/repo")]);
+        assert_eq!(state.sessions[0].0, "s1");
+        assert_eq!(state.changes[Path::new("/repo")].len(), 1);
+        // Taken once: a later crash doesn't replay it again
+        assert!(SessionState::take(&path).is_none());
+
+        // Stale state is dropped
+        std::fs::write(&path, r#"{"saved_at":1,"roots":["/repo"],"sessions":[],"changes":{}}"#).unwrap();
+        assert!(SessionState::take(&path).is_none());
+        assert_ne!(SessionState::path(None, Some(Path::new("/a"))), SessionState::path(Some("work"), Some(Path::new("/a"))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_record_owner() {
+        assert_eq!(record_owner(Path::new("/x/1234-0.json")), Some(1234));
+        assert_eq!(record_owner(Path::new("/x/1234-0.tmp")), None);
+        assert_eq!(record_owner(Path::new("/x/notes.json")), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_orphan() {
+        let mut child = std::process::Command::new("sleep").arg("5").spawn().unwrap();
+        let mut backend = RecordedBackend {
+            pid: child.id(),
+            root: PathBuf::from("/repo"),
+            name: crate::daemon::process_name(child.id()),
+        };
+        assert!(is_orphan(&backend));
+        // A reused PID (another program now) is not ours to kill
+        backend.name = Some("not-a-backend".to_string());
+        assert!(!is_orphan(&backend));
+
+        backend.name = crate::daemon::process_name(child.id());
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert!(!is_orphan(&backend));
+    }
+}
@@ -0,0 +1,195 @@
+//! systemd integration (Linux)
+//! `mcp-proxy [OPTIONS] systemd-install` writes a user service and socket unit; the proxy reports
+//! readiness to `Type=notify` units and accepts the socket passed by socket activation
+
+use crate::config::Config;
+use crate::transport::{BackendAddress, Listener};
+use anyhow::{bail, Context, Result};
+use std::ffi::OsStr;
+use std::os::fd::{FromRawFd, RawFd};
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+const UNIT_NAME: &str = "mcp-proxy";
+/// First file descriptor passed by socket activation
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Send a state change (`READY=1`, `STOPPING=1`) to the service manager; does nothing when
+/// not started by a `Type=notify` unit
+pub fn notify(state: &str) {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else { return };
+    let sent = UnixDatagram::unbound().and_then(|socket| {
+        let path = path.as_bytes();
+        let address = match path.strip_prefix(b"@") {
+            Some(name) => SocketAddr::from_abstract_name(name)?,
+            None => SocketAddr::from_pathname(OsStr::from_bytes(path))?,
+        };
+        socket.send_to_addr(state.as_bytes(), &address)
+    });
+    if let Err(e) = sent {
+        debug!("Failed to notify systemd ({}): {}", state, e);
+    }
+}
+
+/// The listening socket passed by socket activation, if any; the activation variables are
+/// cleared so backends don't see them
+pub fn take_listener() -> Result<Option<Listener>> {
+    let pid = std::env::var("LISTEN_PID").ok();
+    let fds = std::env::var("LISTEN_FDS").ok();
+    for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        std::env::remove_var(var);
+    }
+    let (Some(pid), Some(fds)) = (pid, fds) else { return Ok(None) };
+    let fds: u32 = fds.parse().unwrap_or(0);
+    if pid.parse::<u32>().ok() != Some(std::process::id()) || fds == 0 {
+        return Ok(None);
+    }
+    if fds > 1 {
+        warn!("systemd passed {} sockets, only the first one is used", fds);
+    }
+
+    let fd = LISTEN_FDS_START;
+    let mut address: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    unsafe {
+        if libc::getsockname(fd, &mut address as *mut _ as *mut libc::sockaddr, &mut len) != 0 {
+            return Err(std::io::Error::last_os_error()).context("Invalid socket passed by systemd");
+        }
+        // Keep the socket out of backend processes
+        libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+    }
+    let listener = if i32::from(address.ss_family) == libc::AF_UNIX {
+        let listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(fd) };
+        listener.set_nonblocking(true)?;
+        Listener::Unix(tokio::net::UnixListener::from_std(listener)?, None)
+    } else {
+        let listener = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+        listener.set_nonblocking(true)?;
+        Listener::Tcp(tokio::net::TcpListener::from_std(listener)?)
+    };
+    Ok(Some(listener))
+}
+
+/// Write the user units for the options given before `systemd-install`, then enable and start them
+pub fn install(config: &Config) -> Result<()> {
+    let Some(listen) = config.listen.as_deref() else {
+        bail!("The systemd unit needs --listen, e.g. mcp-proxy --listen unix:/run/user/1000/mcp-proxy.sock systemd-install");
+    };
+    let address = BackendAddress::parse(listen).map_err(|e| anyhow::anyhow!("Invalid --listen address: {}", e))?;
+    let exe = std::env::current_exe().context("Failed to locate the mcp-proxy executable")?;
+    let options: Vec<String> = std::env::args()
+        .skip(1)
+        .take_while(|arg| arg != "systemd-install")
+        .collect();
+
+    let unit_dir = user_unit_dir().context("Cannot locate the systemd user unit directory (HOME is not set)")?;
+    std::fs::create_dir_all(&unit_dir).with_context(|| format!("Failed to create {}", unit_dir.display()))?;
+    for (name, contents) in unit_files(&exe, &options, &address) {
+        let path = unit_dir.join(&name);
+        std::fs::write(&path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("Wrote {}", path.display());
+    }
+
+    let socket_unit = format!("{}.socket", UNIT_NAME);
+    for args in [vec!["daemon-reload"], vec!["enable", "--now", &socket_unit]] {
+        let status = std::process::Command::new("systemctl").arg("--user").args(&args).status();
+        if !status.is_ok_and(|s| s.success()) {
+            bail!(
+                "`systemctl --user {}` failed; once the user manager is available, run: \
+                 systemctl --user daemon-reload && systemctl --user enable --now {}",
+                args.join(" "),
+                socket_unit
+            );
+        }
+    }
+    println!("Enabled {} (the service starts on the first connection)", socket_unit);
+    Ok(())
+}
+
+/// `$XDG_CONFIG_HOME/systemd/user`
+fn user_unit_dir() -> Option<PathBuf> {
+    Some(crate::dirs::config_home()?.join("systemd").join("user"))
+}
+
+/// The service and socket units (file name, contents)
+fn unit_files(exe: &Path, options: &[String], address: &BackendAddress) -> Vec<(String, String)> {
+    let mut exec_start = quote(&exe.to_string_lossy());
+    for option in options {
+        exec_start.push(' ');
+        exec_start.push_str(&quote(option));
+    }
+    let listen_stream = match address {
+        BackendAddress::Tcp(host_port) => host_port.clone(),
+        BackendAddress::Unix(path) => path.display().to_string(),
+    };
+    let service = format!(
+        "[Unit]\n\
+         Description=MCP Proxy for Augment Context Engine\n\
+         Requires={name}.socket\n\
+         \n\
+         [Service]\n\
+         Type=notify\n\
+         ExecStart={exec_start}\n\
+         Restart=on-failure\n\
+         # The proxy stops its backends itself on SIGTERM\n\
+         KillMode=mixed\n\
+         TimeoutStopSec=30\n\
+         \n\
+         [Install]\n\
+         WantedBy=default.target\n",
+        name = UNIT_NAME,
+    );
+    let socket = format!(
+        "[Unit]\n\
+         Description=MCP Proxy socket\n\
+         \n\
+         [Socket]\n\
+         ListenStream={}\n\
+         \n\
+         [Install]\n\
+         WantedBy=sockets.target\n",
+        listen_stream
+    );
+    vec![(format!("{}.service", UNIT_NAME), service), (format!("{}.socket", UNIT_NAME), socket)]
+}
+
+/// Quote a word for `ExecStart=` (specifiers and variables are escaped, not expanded)
+fn quote(word: &str) -> String {
+    let escaped = word.replace('\\', "\\\\").replace('"', "\\\"").replace('%', "%%").replace('$', "$$");
+    if !escaped.is_empty() && !escaped.contains([' ', '\t', '\'']) && escaped == word {
+        return escaped;
+    }
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unit_files() {
+        let options: Vec<String> = ["--listen", "127.0.0.1:7800", "--default-root", "/home/me/my code"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let address = BackendAddress::parse("127.0.0.1:7800").unwrap();
+        let units = unit_files(Path::new("/usr/local/bin/mcp-proxy"), &options, &address);
+        assert_eq!(units[0].0, "mcp-proxy.service");
+        assert!(units[0].1.contains(
+            "ExecStart=/usr/local/bin/mcp-proxy --listen 127.0.0.1:7800 --default-root \"/home/me/my code\"\n"
+        ));
+        assert!(units[0].1.contains("Type=notify\n"));
+        assert!(units[1].1.contains("ListenStream=127.0.0.1:7800\n"));
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("plain"), "plain");
+        assert_eq!(quote("100%"), "\"100%%\"");
+        assert_eq!(quote("$HOME"), "\"$$HOME\"");
+        assert_eq!(quote(""), "\"\"");
+    }
+}
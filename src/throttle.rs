@@ -1,78 +1,232 @@
 //! Event throttling/debouncing for file change notifications
 //! Prevents event storms from overwhelming the backend
 
-use std::collections::HashSet;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 use tracing::{debug, info};
 
-/// Throttled event ready to be processed
+/// How often a root's event rate is sampled for adaptive debouncing
+const RATE_SAMPLE: Duration = Duration::from_secs(1);
+
+/// What happened to a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Created,
+    Changed,
+    Deleted,
+}
+
+impl ChangeKind {
+    /// Net effect of `self` followed by `next` within one window (`None` if they cancel out)
+    fn then(self, next: ChangeKind) -> Option<ChangeKind> {
+        match (self, next) {
+            (ChangeKind::Created, ChangeKind::Changed) => Some(ChangeKind::Created),
+            // Never existed as far as the backend knows
+            (ChangeKind::Created, ChangeKind::Deleted) => None,
+            (ChangeKind::Deleted, ChangeKind::Created | ChangeKind::Changed) => Some(ChangeKind::Changed),
+            (_, next) => Some(next),
+        }
+    }
+}
+
+/// Throttled batch for one root, ready to be processed
 #[derive(Debug, Clone)]
 pub struct ThrottledEvent {
-    pub paths: Vec<PathBuf>,
+    pub root: PathBuf,
+    pub changes: Vec<(PathBuf, ChangeKind)>,
+}
+
+/// Pending changes of one root
+struct RootBatch {
+    changes: HashMap<PathBuf, ChangeKind>,
+    last_flush: Instant,
+    /// Current debounce window (widened during event storms)
+    window: Duration,
+    /// Events seen since `sample_start`
+    sample_events: u64,
+    sample_start: Instant,
+}
+
+impl RootBatch {
+    /// Widen the window while the event rate is above `threshold` (events/s), shrink it back
+    /// towards `base` once the storm passes
+    fn adapt(&mut self, base: Duration, threshold: u64, max: Duration) {
+        let elapsed = self.sample_start.elapsed();
+        if threshold == 0 || elapsed < RATE_SAMPLE {
+            return;
+        }
+        let rate = self.sample_events as f64 / elapsed.as_secs_f64();
+        let previous = self.window;
+        if rate > threshold as f64 {
+            self.window = (self.window.max(Duration::from_millis(1)) * 2).min(max.max(base));
+        } else if rate < threshold as f64 / 2.0 {
+            self.window = (self.window / 2).max(base);
+        }
+        if self.window != previous {
+            info!("Throttler: {:.0} events/s, debounce window {:?} -> {:?}", rate, previous, self.window);
+        }
+        self.sample_events = 0;
+        self.sample_start = Instant::now();
+    }
 }
 
-/// Event throttler that batches and deduplicates file change events
-/// 
+/// Event throttler that batches and deduplicates file change events per root
+///
 /// This is a simple synchronous throttler that collects paths and flushes them
-/// when the debounce window expires. The caller is responsible for checking
-/// `should_flush()` periodically and calling `flush()` to get batched events.
+/// when a root's debounce window expires, or early once `max_batch_size` paths
+/// are pending. The caller is responsible for calling `flush()` periodically
+/// to get the batches that are due.
 pub struct EventThrottler {
-    /// Pending paths to be processed
-    pending_paths: HashSet<PathBuf>,
-    /// Last flush time
-    last_flush: Instant,
+    /// Pending changes by root
+    batches: HashMap<PathBuf, RootBatch>,
     /// Debounce window duration
     debounce_duration: Duration,
+    /// Per-root debounce windows (`root_overrides`)
+    root_debounce: HashMap<PathBuf, Duration>,
+    /// Paths per batch before it is flushed early (0 = unlimited)
+    max_batch_size: usize,
+    /// Events/s above which a root's window widens (0 = fixed windows)
+    storm_threshold: u64,
+    /// Upper bound for widened windows
+    max_debounce: Duration,
+    /// Events merged into an already pending change
+    suppressed: u64,
 }
 
 impl EventThrottler {
     /// Create a new event throttler with the specified debounce window
-    pub fn new(debounce_ms: u64) -> Self {
+    pub fn new(debounce_ms: u64, max_batch_size: usize) -> Self {
         Self {
-            pending_paths: HashSet::new(),
-            last_flush: Instant::now(),
+            batches: HashMap::new(),
             debounce_duration: Duration::from_millis(debounce_ms),
+            root_debounce: HashMap::new(),
+            max_batch_size,
+            storm_threshold: 0,
+            max_debounce: Duration::ZERO,
+            suppressed: 0,
         }
     }
 
-    /// Add a path to the pending set (duplicates are automatically deduplicated)
-    pub fn add_path(&mut self, path: PathBuf) {
-        self.pending_paths.insert(path);
-        debug!("Throttler: added path, pending count: {}", self.pending_paths.len());
+    /// Widen debounce windows (up to `max_debounce_ms`) while a root gets more than
+    /// `storm_threshold` events per second
+    pub fn set_adaptive(&mut self, storm_threshold: u64, max_debounce_ms: u64) {
+        self.storm_threshold = storm_threshold;
+        self.max_debounce = Duration::from_millis(max_debounce_ms);
+    }
+
+    /// Override the debounce window for one root
+    pub fn set_root_debounce(&mut self, root: PathBuf, debounce_ms: u64) {
+        self.root_debounce.insert(root, Duration::from_millis(debounce_ms));
     }
 
-    /// Check if we should flush (debounce window expired and have pending paths)
-    pub fn should_flush(&self) -> bool {
-        !self.pending_paths.is_empty() 
-            && self.last_flush.elapsed() >= self.debounce_duration
+    /// Shortest debounce window of any root (how often `flush()` needs to run)
+    pub fn min_debounce(&self) -> Duration {
+        self.root_debounce.values().copied().fold(self.debounce_duration, Duration::min)
     }
 
-    /// Flush pending events and return them
-    /// Returns None if there are no pending paths
-    pub fn flush(&mut self) -> Option<ThrottledEvent> {
-        if self.pending_paths.is_empty() {
-            return None;
+    fn debounce_for(&self, root: &Path) -> Duration {
+        self.root_debounce.get(root).copied().unwrap_or(self.debounce_duration)
+    }
+
+    /// Add a change to a root's pending set (repeated changes to a path are merged)
+    pub fn add_path(&mut self, root: &Path, path: PathBuf, kind: ChangeKind) {
+        let base = self.debounce_for(root);
+        let batch = self.batches.entry(root.to_path_buf()).or_insert_with(|| RootBatch {
+            changes: HashMap::new(),
+            last_flush: Instant::now(),
+            window: base,
+            sample_events: 0,
+            sample_start: Instant::now(),
+        });
+        batch.sample_events += 1;
+        batch.adapt(base, self.storm_threshold, self.max_debounce);
+        match batch.changes.get(&path).copied() {
+            Some(previous) => match previous.then(kind) {
+                Some(merged) => {
+                    self.suppressed += 1;
+                    batch.changes.insert(path, merged);
+                }
+                None => {
+                    batch.changes.remove(&path);
+                }
+            },
+            None => {
+                batch.changes.insert(path, kind);
+            }
         }
+        debug!("Throttler: added path, pending count: {}", batch.changes.len());
+    }
 
-        let paths: Vec<PathBuf> = self.pending_paths.drain().collect();
-        self.last_flush = Instant::now();
+    /// Whether a root has reached `max_batch_size` and should be flushed right away
+    pub fn is_full(&self, root: &Path) -> bool {
+        self.max_batch_size > 0
+            && self.batches.get(root).is_some_and(|b| b.changes.len() >= self.max_batch_size)
+    }
+
+    /// Flush the batches whose debounce window expired or that are full
+    /// Returns an empty list if nothing is due
+    pub fn flush(&mut self) -> Vec<ThrottledEvent> {
+        // Shrink windows of roots whose storm ended without further events
+        for (root, batch) in self.batches.iter_mut() {
+            let base = self.root_debounce.get(root).copied().unwrap_or(self.debounce_duration);
+            batch.adapt(base, self.storm_threshold, self.max_debounce);
+        }
+        let due: Vec<PathBuf> = self
+            .batches
+            .iter()
+            .filter(|(root, batch)| {
+                !batch.changes.is_empty() && (batch.last_flush.elapsed() >= batch.window || self.is_full(root))
+            })
+            .map(|(root, _)| root.clone())
+            .collect();
 
-        info!("Throttler: flushing {} paths", paths.len());
+        let mut events = Vec::new();
+        for root in due {
+            let limit = match self.max_batch_size {
+                0 => usize::MAX,
+                n => n,
+            };
+            let batch = match self.batches.get_mut(&root) {
+                Some(batch) => batch,
+                None => continue,
+            };
+            let paths: Vec<PathBuf> = batch.changes.keys().take(limit).cloned().collect();
+            let changes: Vec<(PathBuf, ChangeKind)> = paths
+                .into_iter()
+                .filter_map(|path| batch.changes.remove_entry(&path))
+                .collect();
+            batch.last_flush = Instant::now();
 
-        Some(ThrottledEvent { paths })
+            info!("Throttler: flushing {} paths for {}", changes.len(), root.display());
+            events.push(ThrottledEvent { root, changes });
+        }
+        events
     }
 
     /// Get the number of pending paths
     pub fn pending_count(&self) -> usize {
-        self.pending_paths.len()
+        self.batches.values().map(|b| b.changes.len()).sum()
     }
-    
+
+    /// Number of events merged into an already pending change
+    pub fn suppressed_count(&self) -> u64 {
+        self.suppressed
+    }
+
+    /// Roots whose debounce window is currently widened
+    pub fn storm_roots(&self) -> usize {
+        self.batches
+            .iter()
+            .filter(|(root, batch)| batch.window > self.debounce_for(root))
+            .count()
+    }
+
     /// Clear all pending paths without flushing
     #[cfg(test)]
     #[allow(dead_code)]
     pub fn clear(&mut self) {
-        self.pending_paths.clear();
+        self.batches.clear();
     }
 }
 
@@ -80,46 +234,121 @@ impl EventThrottler {
 mod tests {
     use super::*;
 
+    fn root() -> PathBuf {
+        PathBuf::from("/test")
+    }
+
     #[test]
     fn test_throttler_basic() {
-        let mut throttler = EventThrottler::new(100);
-        
-        throttler.add_path(PathBuf::from("/test/file1.rs"));
-        throttler.add_path(PathBuf::from("/test/file2.rs"));
-        throttler.add_path(PathBuf::from("/test/file1.rs")); // duplicate
-        
+        let mut throttler = EventThrottler::new(100, 0);
+
+        throttler.add_path(&root(), PathBuf::from("/test/file1.rs"), ChangeKind::Changed);
+        throttler.add_path(&root(), PathBuf::from("/test/file2.rs"), ChangeKind::Changed);
+        throttler.add_path(&root(), PathBuf::from("/test/file1.rs"), ChangeKind::Changed); // duplicate
+
         assert_eq!(throttler.pending_count(), 2);
+        assert_eq!(throttler.suppressed_count(), 1);
+    }
+
+    #[test]
+    fn test_throttler_adaptive_window() {
+        let mut batch = RootBatch {
+            changes: HashMap::new(),
+            last_flush: Instant::now(),
+            window: Duration::from_millis(100),
+            sample_events: 5000,
+            sample_start: Instant::now() - RATE_SAMPLE,
+        };
+        let (base, max) = (Duration::from_millis(100), Duration::from_millis(300));
+
+        batch.adapt(base, 1000, max);
+        assert_eq!(batch.window, Duration::from_millis(200));
+        batch.sample_events = 5000;
+        batch.sample_start = Instant::now() - RATE_SAMPLE;
+        batch.adapt(base, 1000, max);
+        assert_eq!(batch.window, max);
+
+        // Storm over: back towards the configured window
+        batch.sample_start = Instant::now() - RATE_SAMPLE;
+        batch.adapt(base, 1000, max);
+        assert_eq!(batch.window, Duration::from_millis(150));
+        batch.sample_start = Instant::now() - RATE_SAMPLE;
+        batch.adapt(base, 1000, max);
+        assert_eq!(batch.window, base);
+    }
+
+    #[test]
+    fn test_throttler_merges_kinds() {
+        let mut throttler = EventThrottler::new(0, 0);
+        let (created, recreated, removed, gone) =
+            ("/test/new.rs", "/test/recreated.rs", "/test/removed.rs", "/test/scratch.rs");
+
+        throttler.add_path(&root(), PathBuf::from(created), ChangeKind::Created);
+        throttler.add_path(&root(), PathBuf::from(created), ChangeKind::Changed);
+        throttler.add_path(&root(), PathBuf::from(recreated), ChangeKind::Deleted);
+        throttler.add_path(&root(), PathBuf::from(recreated), ChangeKind::Created);
+        throttler.add_path(&root(), PathBuf::from(removed), ChangeKind::Changed);
+        throttler.add_path(&root(), PathBuf::from(removed), ChangeKind::Deleted);
+        throttler.add_path(&root(), PathBuf::from(gone), ChangeKind::Created);
+        throttler.add_path(&root(), PathBuf::from(gone), ChangeKind::Deleted);
+
+        let changes: HashMap<_, _> = throttler.flush().remove(0).changes.into_iter().collect();
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[&PathBuf::from(created)], ChangeKind::Created);
+        assert_eq!(changes[&PathBuf::from(recreated)], ChangeKind::Changed);
+        assert_eq!(changes[&PathBuf::from(removed)], ChangeKind::Deleted);
     }
-    
+
     #[test]
     fn test_throttler_flush() {
-        let mut throttler = EventThrottler::new(0); // 0ms debounce for immediate flush
-        
-        throttler.add_path(PathBuf::from("/test/file1.rs"));
-        throttler.add_path(PathBuf::from("/test/file2.rs"));
-        
-        assert!(throttler.should_flush());
-        
-        let event = throttler.flush();
-        assert!(event.is_some());
-        assert_eq!(event.unwrap().paths.len(), 2);
+        let mut throttler = EventThrottler::new(0, 0); // 0ms debounce for immediate flush
+
+        throttler.add_path(&root(), PathBuf::from("/test/file1.rs"), ChangeKind::Changed);
+        throttler.add_path(&root(), PathBuf::from("/test/file2.rs"), ChangeKind::Deleted);
+
+        let events = throttler.flush();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].root, root());
+        assert_eq!(events[0].changes.len(), 2);
         assert_eq!(throttler.pending_count(), 0);
     }
-    
+
     #[test]
     fn test_throttler_empty_flush() {
-        let mut throttler = EventThrottler::new(0);
-        assert!(!throttler.should_flush());
-        assert!(throttler.flush().is_none());
+        let mut throttler = EventThrottler::new(0, 0);
+        assert!(throttler.flush().is_empty());
     }
-    
+
     #[test]
     fn test_throttler_debounce_window() {
-        let mut throttler = EventThrottler::new(10000); // 10 second debounce
-        
-        throttler.add_path(PathBuf::from("/test/file1.rs"));
-        
+        let mut throttler = EventThrottler::new(10000, 0); // 10 second debounce
+
+        throttler.add_path(&root(), PathBuf::from("/test/file1.rs"), ChangeKind::Changed);
+
         // Should not flush immediately due to debounce window
-        assert!(!throttler.should_flush());
+        assert!(throttler.flush().is_empty());
+    }
+
+    #[test]
+    fn test_throttler_per_root_window_and_max_batch() {
+        let mut throttler = EventThrottler::new(10000, 2);
+        let fast = PathBuf::from("/fast");
+        throttler.set_root_debounce(fast.clone(), 0);
+        assert_eq!(throttler.min_debounce(), Duration::ZERO);
+
+        throttler.add_path(&fast, PathBuf::from("/fast/a.rs"), ChangeKind::Changed);
+        for name in ["a.rs", "b.rs", "c.rs"] {
+            throttler.add_path(&root(), root().join(name), ChangeKind::Changed);
+        }
+        assert!(throttler.is_full(&root()));
+
+        // The fast root is due by its window; the slow one only because it's full, capped at 2
+        let mut events = throttler.flush();
+        events.sort_by(|a, b| a.root.cmp(&b.root));
+        assert_eq!(events.len(), 2);
+        assert_eq!((events[0].root.as_path(), events[0].changes.len()), (fast.as_path(), 1));
+        assert_eq!((events[1].root.as_path(), events[1].changes.len()), (root().as_path(), 2));
+        assert_eq!(throttler.pending_count(), 1);
+        assert!(throttler.flush().is_empty());
     }
 }
@@ -0,0 +1,347 @@
+//! Tool list aggregation across backends
+//! With namespacing on, `tools/list` merges the tools of every running backend and prefixes
+//! names with the backend's namespace (`frontend.codebase-retrieval`); `tools/call` maps the
+//! prefixed name back to the backend and its original tool name
+//! Also defines the virtual tools the proxy serves itself (`virtual_tools`) and the `tools`
+//! config section that hides, renames or redescribes tools
+
+use crate::error::ERROR_INVALID_PARAMS;
+use crate::jsonrpc::{JsonRpcError, JsonRpcRequest, JsonRpcResponse};
+use crate::middleware::{Middleware, RequestContext};
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::{debug, warn};
+
+/// Separates a namespace from the tool name
+pub const SEPARATOR: char = '.';
+
+/// Marks which backend a tool came from until names are finalized
+const NAMESPACE_TAG: &str = "x-mcp-proxy-namespace";
+
+/// How tool names from several backends are kept apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToolNamespacing {
+    /// Only the routed backend's tools are listed, names unchanged
+    #[default]
+    Off,
+    /// Merge all backends' tools, prefixing names that more than one backend provides
+    Collisions,
+    /// Merge all backends' tools, prefixing every name
+    Always,
+}
+
+/// Entry of the `tools` config section, keyed by the backend's tool name
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ToolOverride {
+    /// Leave the tool out of tools/list and reject calls to it
+    pub hidden: bool,
+    /// Name the client sees instead (calls to it are sent to the backend under the original name)
+    pub name: Option<String>,
+    /// Replacement description
+    pub description: Option<String>,
+    /// Replacement input schema
+    pub input_schema: Option<Value>,
+}
+
+/// Middleware applying the `tools` config section to tools/list and tools/call
+pub struct ToolOverrides {
+    overrides: HashMap<String, ToolOverride>,
+    /// Client-visible name -> backend name
+    aliases: HashMap<String, String>,
+}
+
+impl ToolOverrides {
+    pub fn new(overrides: &HashMap<String, ToolOverride>) -> Self {
+        let mut aliases = HashMap::new();
+        for (tool, entry) in overrides {
+            if let Some(ref alias) = entry.name {
+                if overrides.contains_key(alias) && alias != tool {
+                    warn!("Tool alias {:?} for {:?} shadows another configured tool", alias, tool);
+                }
+                aliases.insert(alias.clone(), tool.clone());
+            }
+        }
+        Self {
+            overrides: overrides.clone(),
+            aliases,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.overrides.is_empty()
+    }
+
+    /// Apply the overrides to a `tools/list` result
+    pub fn rewrite_tools_list(&self, result: &mut Value) {
+        let tools = match result.get_mut("tools").and_then(|t| t.as_array_mut()) {
+            Some(tools) => tools,
+            None => return,
+        };
+        tools.retain(|tool| {
+            let name = tool.get("name").and_then(|n| n.as_str()).unwrap_or_default();
+            !self.overrides.get(name).is_some_and(|o| o.hidden)
+        });
+        for tool in tools.iter_mut() {
+            let name = tool.get("name").and_then(|n| n.as_str()).unwrap_or_default();
+            let entry = match self.overrides.get(name) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            if let Some(ref alias) = entry.name {
+                tool["name"] = alias.clone().into();
+            }
+            if let Some(ref description) = entry.description {
+                tool["description"] = description.clone().into();
+            }
+            if let Some(ref schema) = entry.input_schema {
+                tool["inputSchema"] = schema.clone();
+            }
+        }
+    }
+}
+
+impl Middleware for ToolOverrides {
+    fn on_request(&self, _ctx: &RequestContext, request: &mut JsonRpcRequest) -> Option<JsonRpcResponse> {
+        if request.method != "tools/call" {
+            return None;
+        }
+        let name = request.get_param_path("name").and_then(|v| v.as_str())?.to_string();
+        let tool = match self.aliases.get(&name) {
+            Some(original) => {
+                debug!("Tool alias {} -> {}", name, original);
+                if let Some(params) = request.params.as_mut() {
+                    params["name"] = original.clone().into();
+                }
+                original.as_str()
+            }
+            None => name.as_str(),
+        };
+        if self.overrides.get(tool).is_some_and(|o| o.hidden) {
+            return Some(JsonRpcResponse::error(
+                request.id.clone(),
+                JsonRpcError::new(ERROR_INVALID_PARAMS, format!("Unknown tool: {}", name)),
+            ));
+        }
+        None
+    }
+
+    fn on_response(&self, request: &JsonRpcRequest, response: &mut JsonRpcResponse) {
+        if request.method == "tools/list" {
+            if let Some(ref mut result) = response.result {
+                self.rewrite_tools_list(result);
+            }
+        }
+    }
+}
+
+/// Tools answered by the proxy instead of a backend
+pub const VIRTUAL_TOOLS: &[&str] = &[
+    "proxy_status",
+    "proxy_restart_backend",
+    "proxy_upgrade_backend",
+    "proxy_switch_root",
+];
+
+/// `tools/list` entries for the virtual tools
+pub fn virtual_tool_definitions() -> Vec<Value> {
+    let root_schema = |description: &str, required: bool| {
+        json!({
+            "type": "object",
+            "properties": {
+                "root": { "type": "string", "description": description }
+            },
+            "required": if required { vec!["root"] } else { Vec::new() },
+        })
+    };
+    vec![
+        json!({
+            "name": "proxy_status",
+            "description": "Show the workspace roots known to the MCP proxy, its running backends and request metrics.",
+            "inputSchema": { "type": "object", "properties": {} },
+        }),
+        json!({
+            "name": "proxy_restart_backend",
+            "description": "Restart the backend serving a workspace root, e.g. when it stopped responding.",
+            "inputSchema": root_schema("Workspace root path or file URI (defaults to the current root)", false),
+        }),
+        json!({
+            "name": "proxy_upgrade_backend",
+            "description": "Replace the backend serving a workspace root without downtime (e.g. after upgrading auggie): a new backend starts, takes over once ready, and the old one finishes its requests before it stops.",
+            "inputSchema": root_schema("Workspace root path or file URI (defaults to the current root)", false),
+        }),
+        json!({
+            "name": "proxy_switch_root",
+            "description": "Route following requests without a file path to another workspace root.",
+            "inputSchema": root_schema("Workspace root path or file URI", true),
+        }),
+    ]
+}
+
+/// A `tools/call` result holding one text block
+pub fn text_result(text: impl Into<String>, is_error: bool) -> Value {
+    json!({
+        "content": [{ "type": "text", "text": text.into() }],
+        "isError": is_error,
+    })
+}
+
+/// Default namespace of a root: its directory name, restricted to characters valid in tool names
+pub fn default_namespace(root: &Path) -> String {
+    let name: String = root
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    if name.is_empty() { "root".to_string() } else { name }
+}
+
+/// Take the tools out of a backend's `tools/list` result, tagged with the backend's namespace
+pub fn take_tools(result: &mut Value, namespace: &str) -> Vec<Value> {
+    let tools = match result.get_mut("tools").map(Value::take) {
+        Some(Value::Array(tools)) => tools,
+        _ => return Vec::new(),
+    };
+    tools
+        .into_iter()
+        .map(|mut tool| {
+            if let Some(obj) = tool.as_object_mut() {
+                obj.insert(NAMESPACE_TAG.to_string(), namespace.into());
+            }
+            tool
+        })
+        .collect()
+}
+
+/// Prefix the names of tagged tools in a `tools/list` result as `mode` requires and drop the tags
+pub fn apply_namespacing(result: &mut Value, mode: ToolNamespacing) {
+    let tools = match result.get_mut("tools").and_then(|t| t.as_array_mut()) {
+        Some(tools) => tools,
+        None => return,
+    };
+    let name_of = |tool: &Value| tool.get("name").and_then(|n| n.as_str()).unwrap_or_default().to_string();
+    let mut counts = std::collections::HashMap::new();
+    for tool in tools.iter() {
+        *counts.entry(name_of(tool)).or_insert(0usize) += 1;
+    }
+    for tool in tools.iter_mut() {
+        let name = name_of(tool);
+        let obj = match tool.as_object_mut() {
+            Some(obj) => obj,
+            None => continue,
+        };
+        let namespace = match obj.remove(NAMESPACE_TAG) {
+            Some(Value::String(namespace)) => namespace,
+            _ => continue,
+        };
+        if mode == ToolNamespacing::Always || (mode == ToolNamespacing::Collisions && counts[&name] > 1) {
+            obj.insert("name".to_string(), format!("{}{}{}", namespace, SEPARATOR, name).into());
+        }
+    }
+}
+
+/// Split a called tool name into a known namespace and the backend's tool name
+/// (the longest matching namespace wins, so namespaces may contain the separator)
+pub fn split_name<'a>(name: &str, namespaces: impl IntoIterator<Item = &'a str>) -> Option<(String, String)> {
+    namespaces
+        .into_iter()
+        .filter(|ns| {
+            name.len() > ns.len() + 1
+                && name.starts_with(ns)
+                && name[ns.len()..].starts_with(SEPARATOR)
+        })
+        .max_by_key(|ns| ns.len())
+        .map(|ns| (ns.to_string(), name[ns.len() + 1..].to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merged(mode: ToolNamespacing) -> Vec<String> {
+        let mut tools = take_tools(&mut json!({"tools": [{"name": "search"}, {"name": "lint"}]}), "frontend");
+        tools.extend(take_tools(&mut json!({"tools": [{"name": "search"}]}), "backend"));
+        let mut result = json!({ "tools": tools });
+        apply_namespacing(&mut result, mode);
+        assert!(result.to_string().find(NAMESPACE_TAG).is_none());
+        result["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["name"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_namespacing_modes() {
+        assert_eq!(merged(ToolNamespacing::Collisions), vec!["frontend.search", "lint", "backend.search"]);
+        assert_eq!(merged(ToolNamespacing::Always), vec!["frontend.search", "frontend.lint", "backend.search"]);
+    }
+
+    #[test]
+    fn test_split_name() {
+        let namespaces = ["web", "web.v2", "api"];
+        assert_eq!(split_name("web.v2.search", namespaces), Some(("web.v2".to_string(), "search".to_string())));
+        assert_eq!(split_name("api.search", namespaces), Some(("api".to_string(), "search".to_string())));
+        assert_eq!(split_name("search", namespaces), None);
+        assert_eq!(split_name("api.", namespaces), None);
+    }
+
+    #[test]
+    fn test_tool_overrides() {
+        let config: HashMap<String, ToolOverride> = serde_json::from_value(json!({
+            "codebase-retrieval": {"name": "search_code", "description": "Search the code"},
+            "lint": {"hidden": true},
+        }))
+        .unwrap();
+        let overrides = ToolOverrides::new(&config);
+
+        let mut result = json!({"tools": [
+            {"name": "codebase-retrieval", "description": "long"},
+            {"name": "lint"},
+            {"name": "other"},
+        ]});
+        overrides.rewrite_tools_list(&mut result);
+        assert_eq!(result, json!({"tools": [
+            {"name": "search_code", "description": "Search the code"},
+            {"name": "other"},
+        ]}));
+
+        let ctx = RequestContext {
+            root: None,
+            roots: &[],
+            resolver: &crate::paths::PathResolver::new(false),
+        };
+        let call = |name: &str| -> JsonRpcRequest {
+            serde_json::from_value(json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": name}}))
+                .unwrap()
+        };
+        let mut request = call("search_code");
+        assert!(overrides.on_request(&ctx, &mut request).is_none());
+        assert_eq!(request.get_param_path("name"), Some(&json!("codebase-retrieval")));
+        let response = overrides.on_request(&ctx, &mut call("lint")).unwrap();
+        assert_eq!(response.error.unwrap().code, ERROR_INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_virtual_tools_defined() {
+        let names: Vec<_> = virtual_tool_definitions()
+            .iter()
+            .map(|t| t["name"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names, VIRTUAL_TOOLS);
+    }
+
+    #[test]
+    fn test_default_namespace() {
+        assert_eq!(default_namespace(Path::new("/work/my app")), "my_app");
+        assert_eq!(default_namespace(Path::new("/")), "root");
+    }
+}
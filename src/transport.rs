@@ -0,0 +1,184 @@
+//! Transports for attaching to already-running backends
+//! Used by the per-root `connect` option instead of spawning a child process, and by
+//! `--listen` to accept clients on a socket instead of stdio
+
+use std::fmt;
+use std::path::PathBuf;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Read half of a backend connection (child stdout or socket)
+pub type BackendReader = Box<dyn AsyncRead + Send + Unpin>;
+/// Write half of a backend connection (child stdin or socket)
+pub type BackendWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// Address of a running MCP server speaking newline-delimited JSON-RPC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackendAddress {
+    /// `host:port` or `tcp://host:port`
+    Tcp(String),
+    /// `unix:/path/to/socket` or `unix:///path/to/socket`
+    Unix(PathBuf),
+}
+
+impl BackendAddress {
+    pub fn parse(address: &str) -> Result<Self, String> {
+        if let Some(path) = address.strip_prefix("unix:") {
+            let path = path.strip_prefix("//").unwrap_or(path);
+            if path.is_empty() {
+                return Err(format!("missing socket path in {:?}", address));
+            }
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+
+        let host_port = address.strip_prefix("tcp://").unwrap_or(address);
+        match host_port.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                Ok(Self::Tcp(host_port.to_string()))
+            }
+            _ => Err(format!("expected host:port or unix:/path, got {:?}", address)),
+        }
+    }
+
+    /// Connect and split into a read/write pair
+    pub async fn connect(&self) -> std::io::Result<(BackendReader, BackendWriter)> {
+        match self {
+            Self::Tcp(addr) => {
+                let stream = tokio::net::TcpStream::connect(addr).await?;
+                stream.set_nodelay(true)?;
+                let (reader, writer) = stream.into_split();
+                Ok((Box::new(reader), Box::new(writer)))
+            }
+            #[cfg(unix)]
+            Self::Unix(path) => {
+                let stream = tokio::net::UnixStream::connect(path).await?;
+                let (reader, writer) = stream.into_split();
+                Ok((Box::new(reader), Box::new(writer)))
+            }
+            #[cfg(not(unix))]
+            Self::Unix(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "Unix sockets are not supported on this platform",
+            )),
+        }
+    }
+}
+
+/// Socket accepting clients (`--listen`)
+pub enum Listener {
+    Tcp(tokio::net::TcpListener),
+    /// With the socket path to remove on drop (`None` for a socket handed over by systemd)
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener, Option<PathBuf>),
+}
+
+impl Listener {
+    /// Bind to `address`; a stale unix socket file left by an earlier run is replaced
+    pub async fn bind(address: &BackendAddress) -> std::io::Result<Self> {
+        match address {
+            BackendAddress::Tcp(addr) => Ok(Self::Tcp(tokio::net::TcpListener::bind(addr).await?)),
+            #[cfg(unix)]
+            BackendAddress::Unix(path) => {
+                if path.exists() && std::os::unix::net::UnixStream::connect(path).is_err() {
+                    std::fs::remove_file(path)?;
+                }
+                Ok(Self::Unix(tokio::net::UnixListener::bind(path)?, Some(path.clone())))
+            }
+            #[cfg(not(unix))]
+            BackendAddress::Unix(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "Unix sockets are not supported on this platform",
+            )),
+        }
+    }
+
+    /// Wait for the next client, split into a read/write pair, with a description for logs
+    pub async fn accept(&self) -> std::io::Result<(BackendReader, BackendWriter, String)> {
+        match self {
+            Self::Tcp(listener) => {
+                let (stream, peer) = listener.accept().await?;
+                stream.set_nodelay(true)?;
+                let (reader, writer) = stream.into_split();
+                Ok((Box::new(reader), Box::new(writer), peer.to_string()))
+            }
+            #[cfg(unix)]
+            Self::Unix(listener, _) => {
+                let (stream, _) = listener.accept().await?;
+                let (reader, writer) = stream.into_split();
+                Ok((Box::new(reader), Box::new(writer), "unix socket client".to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Self::Unix(_, Some(path)) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+impl fmt::Display for BackendAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "tcp://{}", addr),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    #[test]
+    fn test_parse_addresses() {
+        assert_eq!(BackendAddress::parse("127.0.0.1:7000"), Ok(BackendAddress::Tcp("127.0.0.1:7000".to_string())));
+        assert_eq!(BackendAddress::parse("tcp://localhost:7000"), Ok(BackendAddress::Tcp("localhost:7000".to_string())));
+        assert_eq!(BackendAddress::parse("unix:///tmp/auggie.sock"), Ok(BackendAddress::Unix(PathBuf::from("/tmp/auggie.sock"))));
+        assert_eq!(BackendAddress::parse("unix:/tmp/auggie.sock"), Ok(BackendAddress::Unix(PathBuf::from("/tmp/auggie.sock"))));
+        assert!(BackendAddress::parse("localhost").is_err());
+        assert!(BackendAddress::parse("localhost:notaport").is_err());
+        assert!(BackendAddress::parse("unix:").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_connect_round_trip() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = BackendAddress::parse(&listener.local_addr().unwrap().to_string()).unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.split();
+            let mut line = String::new();
+            BufReader::new(reader).read_line(&mut line).await.unwrap();
+            writer.write_all(line.as_bytes()).await.unwrap();
+        });
+
+        let (reader, mut writer) = address.connect().await.unwrap();
+        writer.write_all(b"ping\n").await.unwrap();
+        let mut line = String::new();
+        BufReader::new(reader).read_line(&mut line).await.unwrap();
+        assert_eq!(line, "ping\n");
+    }
+
+    #[tokio::test]
+    async fn test_listener_accepts() {
+        let listener = Listener::bind(&BackendAddress::Tcp("127.0.0.1:0".to_string())).await.unwrap();
+        let Listener::Tcp(ref tcp) = listener else { unreachable!() };
+        let address = BackendAddress::Tcp(tcp.local_addr().unwrap().to_string());
+        tokio::spawn(async move {
+            let (reader, mut writer, _) = listener.accept().await.unwrap();
+            let mut line = String::new();
+            BufReader::new(reader).read_line(&mut line).await.unwrap();
+            writer.write_all(line.as_bytes()).await.unwrap();
+        });
+
+        let (reader, mut writer) = address.connect().await.unwrap();
+        writer.write_all(b"hello\n").await.unwrap();
+        let mut line = String::new();
+        BufReader::new(reader).read_line(&mut line).await.unwrap();
+        assert_eq!(line, "hello\n");
+    }
+}
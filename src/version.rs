@@ -0,0 +1,174 @@
+//! Runtime (node, bun or deno) and auggie version detection
+//! Versions are checked before each spawn so a backend below `min_node_version` /
+//! `min_auggie_version` is refused with a clear error instead of failing mid-handshake; they
+//! are detected again only once the runtime binary or auggie entry has changed
+
+use crate::error::ProxyError;
+use crate::runtime::Runtime;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// What detected versions are cached under: the runtime and both paths with their modification times
+type CacheKey = (Runtime, Option<(PathBuf, Option<SystemTime>)>, Option<(PathBuf, Option<SystemTime>)>);
+
+/// Versions found for a runtime binary and auggie entry
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Versions {
+    pub runtime: Runtime,
+    /// Version of the runtime binary (node's, or bun's/deno's own)
+    pub node: Option<String>,
+    pub auggie: Option<String>,
+}
+
+impl Versions {
+    /// Detect the versions of the runtime at `node` and the auggie package containing `auggie_entry`
+    pub fn detect(runtime: Runtime, node: Option<&Path>, auggie_entry: Option<&Path>) -> Self {
+        Self {
+            runtime,
+            node: node.and_then(runtime_version),
+            auggie: auggie_entry.and_then(auggie_version),
+        }
+    }
+
+    /// `detect`, reusing the versions found earlier for the same unchanged binary and entry
+    /// (detection runs the runtime, which would hold up a spawn every time)
+    pub fn cached(runtime: Runtime, node: Option<&Path>, auggie_entry: Option<&Path>) -> Self {
+        static CACHE: OnceLock<Mutex<HashMap<CacheKey, Versions>>> = OnceLock::new();
+        let stamp = |path: Option<&Path>| {
+            path.map(|path| (path.to_path_buf(), std::fs::metadata(path).and_then(|m| m.modified()).ok()))
+        };
+        let key = (runtime, stamp(node), stamp(auggie_entry));
+        let cache = CACHE.get_or_init(Mutex::default);
+        if let Some(versions) = cache.lock().unwrap_or_else(|e| e.into_inner()).get(&key) {
+            return versions.clone();
+        }
+        let versions = Self::detect(runtime, node, auggie_entry);
+        cache.lock().unwrap_or_else(|e| e.into_inner()).insert(key, versions.clone());
+        versions
+    }
+
+    /// Refuse versions below the configured minimums (an undetectable version is refused too);
+    /// the Node.js minimum doesn't apply to bun and deno, which have versions of their own
+    pub fn check(&self, min_node: Option<&str>, min_auggie: Option<&str>) -> Result<(), ProxyError> {
+        if self.runtime == Runtime::Node {
+            check_one("Node.js", self.node.as_deref(), min_node)?;
+        }
+        check_one("auggie", self.auggie.as_deref(), min_auggie)
+    }
+}
+
+fn check_one(name: &str, found: Option<&str>, minimum: Option<&str>) -> Result<(), ProxyError> {
+    let Some(minimum) = minimum else { return Ok(()) };
+    match found {
+        Some(found) if compare(found, minimum) != Ordering::Less => Ok(()),
+        Some(found) => Err(ProxyError::ConfigError(format!(
+            "{} {} is older than the required {}",
+            name, found, minimum
+        ))),
+        None => Err(ProxyError::ConfigError(format!(
+            "Could not determine the {} version (required: {})",
+            name, minimum
+        ))),
+    }
+}
+
+/// Version printed by `<runtime> --version`
+fn runtime_version(executable: &Path) -> Option<String> {
+    let output = Command::new(executable).arg("--version").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_version_output(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// First version-looking word of the first line (`v20.11.0`, `1.1.8`, `deno 2.0.0 (stable, ...)`)
+fn parse_version_output(output: &str) -> Option<String> {
+    output
+        .lines()
+        .next()?
+        .split_whitespace()
+        .map(|word| word.trim_start_matches('v'))
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+        .map(str::to_string)
+}
+
+/// `version` of the nearest package.json above the auggie entry
+fn auggie_version(entry: &Path) -> Option<String> {
+    entry.ancestors().skip(1).find_map(|dir| {
+        let text = std::fs::read_to_string(dir.join("package.json")).ok()?;
+        let package: serde_json::Value = serde_json::from_str(&text).ok()?;
+        package.get("version")?.as_str().map(str::to_string)
+    })
+}
+
+/// Compare dotted versions numerically (`20.9.0` < `20.11.0`); pre-release suffixes are ignored
+pub fn compare(a: &str, b: &str) -> Ordering {
+    let parts = |v: &str| -> Vec<u64> {
+        v.trim()
+            .trim_start_matches('v')
+            .split(['-', '+'])
+            .next()
+            .unwrap_or("")
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    let (a, b) = (parts(a), parts(b));
+    (0..a.len().max(b.len()))
+        .map(|i| a.get(i).copied().unwrap_or(0).cmp(&b.get(i).copied().unwrap_or(0)))
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        assert_eq!(compare("20.9.0", "20.11.0"), Ordering::Less);
+        assert_eq!(compare("v22.1.0", "22"), Ordering::Greater);
+        assert_eq!(compare("0.5.0-beta.1", "0.5"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_check() {
+        let versions = Versions { runtime: Runtime::Node, node: Some("18.19.0".to_string()), auggie: None };
+        assert!(versions.check(Some("18.0.0"), None).is_ok());
+        assert!(versions.check(Some("20"), None).is_err());
+        assert!(versions.check(None, Some("0.5.0")).is_err());
+        let bun = Versions { runtime: Runtime::Bun, node: Some("1.1.8".to_string()), auggie: None };
+        assert!(bun.check(Some("20"), None).is_ok());
+    }
+
+    #[test]
+    fn test_parse_version_output() {
+        assert_eq!(parse_version_output("v20.11.0\n").as_deref(), Some("20.11.0"));
+        assert_eq!(parse_version_output("1.1.8\n").as_deref(), Some("1.1.8"));
+        assert_eq!(parse_version_output("deno 2.0.0 (stable, release, x86_64)\nv8 12.9\n").as_deref(), Some("2.0.0"));
+    }
+
+    #[test]
+    fn test_auggie_version() {
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-version-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("dist")).unwrap();
+        std::fs::write(dir.join("package.json"), r#"{"name": "@augmentcode/auggie", "version": "0.5.2"}"#).unwrap();
+        assert_eq!(auggie_version(&dir.join("dist/index.js")).as_deref(), Some("0.5.2"));
+
+        // Cached until the entry changes
+        let entry = dir.join("dist/index.js");
+        std::fs::write(&entry, "").unwrap();
+        assert_eq!(Versions::cached(Runtime::Node, None, Some(&entry)).auggie.as_deref(), Some("0.5.2"));
+        std::fs::write(dir.join("package.json"), r#"{"version": "0.6.0"}"#).unwrap();
+        assert_eq!(Versions::cached(Runtime::Node, None, Some(&entry)).auggie.as_deref(), Some("0.5.2"));
+        let later = SystemTime::now() + std::time::Duration::from_secs(5);
+        std::fs::File::options().write(true).open(&entry).unwrap().set_modified(later).unwrap();
+        assert_eq!(Versions::cached(Runtime::Node, None, Some(&entry)).auggie.as_deref(), Some("0.6.0"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,139 @@
+//! Built-in filesystem watcher
+//! Generates change events for active roots so backends stay fresh even when the
+//! client never sends file-change notifications
+
+use crate::throttle::ChangeKind;
+use notify::event::{EventKind, ModifyKind, RenameMode};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+/// Recursive watcher over the roots that currently have a backend
+pub struct RootWatcher {
+    watcher: RecommendedWatcher,
+    watched: HashSet<PathBuf>,
+}
+
+impl RootWatcher {
+    /// Create a watcher that sends changed paths to `tx`
+    pub fn new(tx: mpsc::UnboundedSender<(PathBuf, ChangeKind)>) -> notify::Result<Self> {
+        let watcher = notify::recommended_watcher(move |result: notify::Result<Event>| match result {
+            Ok(event) => {
+                if !is_change(&event.kind) {
+                    return;
+                }
+                let renamed_both = matches!(event.kind, EventKind::Modify(ModifyKind::Name(RenameMode::Both)));
+                for (i, path) in event.paths.into_iter().enumerate() {
+                    if is_git_internal(&path) {
+                        continue;
+                    }
+                    let kind = match renamed_both {
+                        // Paths are [from, to]
+                        true if i == 0 => ChangeKind::Deleted,
+                        true => ChangeKind::Created,
+                        false => change_kind(&event.kind, &path),
+                    };
+                    // The receiver is gone once the proxy shuts down
+                    let _ = tx.send((path, kind));
+                }
+            }
+            Err(e) => warn!("File watcher error: {}", e),
+        })?;
+        Ok(Self {
+            watcher,
+            watched: HashSet::new(),
+        })
+    }
+
+    /// Start watching a root (no-op if already watched)
+    pub fn watch(&mut self, root: &Path) {
+        if self.watched.contains(root) {
+            return;
+        }
+        match self.watcher.watch(root, RecursiveMode::Recursive) {
+            Ok(()) => {
+                info!("Watching {} for changes", root.display());
+                self.watched.insert(root.to_path_buf());
+            }
+            Err(e) => warn!("Failed to watch {}: {}", root.display(), e),
+        }
+    }
+
+    /// Stop watching a root
+    pub fn unwatch(&mut self, root: &Path) {
+        if self.watched.remove(root) {
+            if let Err(e) = self.watcher.unwatch(root) {
+                debug!("Failed to unwatch {}: {}", root.display(), e);
+            }
+        }
+    }
+}
+
+/// Content changes only (metadata and access events would just churn the index)
+pub(crate) fn is_change(kind: &EventKind) -> bool {
+    match kind {
+        EventKind::Create(_) | EventKind::Remove(_) => true,
+        EventKind::Modify(ModifyKind::Metadata(_)) => false,
+        EventKind::Modify(_) => true,
+        _ => false,
+    }
+}
+
+/// Map a notify event to the change reported to backends
+fn change_kind(kind: &EventKind, path: &Path) -> ChangeKind {
+    match kind {
+        EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => ChangeKind::Created,
+        EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => ChangeKind::Deleted,
+        // Renames without a direction: whichever side this path is now on
+        EventKind::Modify(ModifyKind::Name(_)) if !path.exists() => ChangeKind::Deleted,
+        _ => ChangeKind::Changed,
+    }
+}
+
+/// Git's own bookkeeping (`.git/index`, objects, ...) is never interesting to a backend
+fn is_git_internal(path: &Path) -> bool {
+    path.components().any(|c| c == Component::Normal(".git".as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, CreateKind, DataChange, MetadataKind};
+    use std::time::Duration;
+
+    #[test]
+    fn test_event_filtering() {
+        assert!(is_change(&EventKind::Create(CreateKind::File)));
+        assert!(is_change(&EventKind::Modify(ModifyKind::Data(DataChange::Content))));
+        assert!(!is_change(&EventKind::Modify(ModifyKind::Metadata(MetadataKind::AccessTime))));
+        assert!(!is_change(&EventKind::Access(AccessKind::Read)));
+        let gone = Path::new("/nonexistent/file.rs");
+        assert_eq!(change_kind(&EventKind::Create(CreateKind::File), gone), ChangeKind::Created);
+        assert_eq!(change_kind(&EventKind::Modify(ModifyKind::Name(RenameMode::From)), gone), ChangeKind::Deleted);
+        assert_eq!(change_kind(&EventKind::Modify(ModifyKind::Name(RenameMode::Any)), gone), ChangeKind::Deleted);
+        assert_eq!(change_kind(&EventKind::Modify(ModifyKind::Data(DataChange::Content)), gone), ChangeKind::Changed);
+        assert!(is_git_internal(Path::new("/repo/.git/index")));
+        assert!(!is_git_internal(Path::new("/repo/src/.gitignore")));
+    }
+
+    #[tokio::test]
+    async fn test_watcher_reports_changes() {
+        let dir = std::env::temp_dir().join(format!("mcp-proxy-watch-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        let dir = dir.canonicalize().unwrap();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut watcher = RootWatcher::new(tx).unwrap();
+        watcher.watch(&dir);
+        std::fs::write(dir.join(".git").join("index"), b"ignored").unwrap();
+        std::fs::write(dir.join("main.rs"), b"fn main() {}").unwrap();
+
+        let (path, kind) = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.unwrap().unwrap();
+        assert_eq!((path, kind), (dir.join("main.rs"), ChangeKind::Created));
+        watcher.unwatch(&dir);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
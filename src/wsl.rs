@@ -0,0 +1,152 @@
+//! WSL (Windows Subsystem for Linux) path translation and backend spawning
+//! Handles workspaces under `\\wsl$\<distro>\...` / `\\wsl.localhost\<distro>\...`
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// `wsl` section of the config file
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(default)]
+pub struct WslConfig {
+    /// Spawn backends for WSL roots inside the distro via wsl.exe
+    pub enabled: bool,
+    /// Path to wsl.exe (defaults to `wsl.exe` on PATH)
+    pub wsl_exe: Option<PathBuf>,
+    /// Node executable inside the distro (defaults to `node`)
+    pub node: Option<String>,
+    /// Auggie entry inside the distro; when unset the `auggie` CLI is run directly
+    pub auggie_entry: Option<String>,
+}
+
+/// A path inside a WSL distro, as seen from Windows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WslPath {
+    /// Distro name (lowercased, distro names are case-insensitive)
+    pub distro: String,
+    /// Absolute Linux path inside the distro
+    pub linux_path: String,
+}
+
+impl WslPath {
+    /// Parse `\\wsl$\Distro\path` or `\\wsl.localhost\Distro\path` (either separator)
+    pub fn parse(path: &str) -> Option<Self> {
+        let path = path.replace('/', "\\");
+        let rest = path.strip_prefix(r"\\")?;
+        let (host, rest) = rest.split_once('\\')?;
+        if !host.eq_ignore_ascii_case("wsl$") && !host.eq_ignore_ascii_case("wsl.localhost") {
+            return None;
+        }
+
+        let (distro, linux) = rest.split_once('\\').unwrap_or((rest, ""));
+        if distro.is_empty() {
+            return None;
+        }
+        let linux_path = format!(
+            "/{}",
+            linux.split('\\').filter(|p| !p.is_empty()).collect::<Vec<_>>().join("/")
+        );
+
+        Some(Self {
+            distro: distro.to_lowercase(),
+            linux_path,
+        })
+    }
+
+    /// Parse from a `Path`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        Self::parse(path.to_str()?)
+    }
+
+    /// Canonical Windows UNC form (`\\wsl.localhost\distro\...`)
+    pub fn to_unc(&self) -> String {
+        let mut unc = format!(r"\\wsl.localhost\{}", self.distro);
+        for part in self.linux_path.split('/').filter(|p| !p.is_empty()) {
+            unc.push('\\');
+            unc.push_str(part);
+        }
+        unc
+    }
+
+    /// File URI as seen from inside the distro
+    pub fn to_linux_uri(&self) -> String {
+        format!("file://{}", self.linux_path)
+    }
+}
+
+/// Build a command running the backend inside the distro
+/// (`env` names are added to WSLENV so their values cross into the distro)
+#[cfg_attr(not(windows), allow(dead_code))]
+pub fn backend_command(wsl: &WslConfig, path: &WslPath, mode: &str, env: &[(String, String)]) -> Command {
+    let wsl_exe = wsl.wsl_exe.clone().unwrap_or_else(|| PathBuf::from("wsl.exe"));
+    let mut cmd = Command::new(wsl_exe);
+    cmd.arg("-d").arg(&path.distro).arg("--");
+
+    match wsl.auggie_entry {
+        Some(ref entry) => {
+            cmd.arg(wsl.node.as_deref().unwrap_or("node")).arg(entry);
+        }
+        None => {
+            cmd.arg("auggie");
+        }
+    }
+
+    cmd.arg("--mcp")
+        .arg("-m")
+        .arg(mode)
+        .arg("--workspace-root")
+        .arg(&path.linux_path);
+
+    // Environment variables only cross into WSL when listed in WSLENV
+    let mut forwarded = vec!["AUGMENT_DISABLE_AUTO_UPDATE/u".to_string()];
+    forwarded.extend(env.iter().map(|(name, _)| format!("{}/u", name)));
+    let wslenv = match std::env::var("WSLENV") {
+        Ok(existing) if !existing.is_empty() => format!("{}:{}", existing, forwarded.join(":")),
+        _ => forwarded.join(":"),
+    };
+    cmd.env("WSLENV", wslenv);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_wsl_paths() {
+        let expected = WslPath {
+            distro: "ubuntu".to_string(),
+            linux_path: "/home/me/repo".to_string(),
+        };
+        assert_eq!(WslPath::parse(r"\\wsl$\Ubuntu\home\me\repo"), Some(expected.clone()));
+        assert_eq!(WslPath::parse(r"\\wsl.localhost\Ubuntu\home\me\repo\"), Some(expected.clone()));
+        assert_eq!(WslPath::parse("//WSL.LOCALHOST/Ubuntu/home/me/repo"), Some(expected));
+        assert_eq!(WslPath::parse(r"\\wsl$\Ubuntu").unwrap().linux_path, "/");
+        assert_eq!(WslPath::parse(r"\\server\share\repo"), None);
+        assert_eq!(WslPath::parse(r"C:\repo"), None);
+    }
+
+    #[test]
+    fn test_wsl_path_translation() {
+        let path = WslPath::parse(r"\\wsl$\Ubuntu\home\Me\Repo").unwrap();
+        assert_eq!(path.to_unc(), r"\\wsl.localhost\ubuntu\home\Me\Repo");
+        assert_eq!(path.to_linux_uri(), "file:///home/Me/Repo");
+    }
+
+    #[test]
+    fn test_backend_command() {
+        let wsl = WslConfig {
+            enabled: true,
+            auggie_entry: Some("/opt/auggie/augment.mjs".to_string()),
+            ..Default::default()
+        };
+        let path = WslPath::parse(r"\\wsl$\Ubuntu\home\me\repo").unwrap();
+        let cmd = backend_command(&wsl, &path, "default", &[]);
+        let args: Vec<_> = cmd.as_std().get_args().map(|a| a.to_string_lossy().to_string()).collect();
+        assert_eq!(
+            args,
+            ["-d", "ubuntu", "--", "node", "/opt/auggie/augment.mjs", "--mcp", "-m", "default", "--workspace-root", "/home/me/repo"]
+        );
+    }
+}